@@ -35,7 +35,7 @@ const integer_types = [
   "usize",
 ];
 
-const float_types = ["f32", "f64"];
+const float_types = ["f16", "bf16", "f32", "f64"];
 
 const primitive_types = integer_types
   .concat(float_types)
@@ -167,6 +167,7 @@ module.exports = grammar({
         optional(field("type_arguments", $.generic_argument_list)),
         field("parameters", $.parameter_list),
         optional(seq("->", field("return_type", $._type))),
+        optional(field("where_clause", $.where_clause)),
         choice(field("body", $.block), ";")
       ),
 
@@ -267,6 +268,7 @@ module.exports = grammar({
         "impl",
         field("name", $.identifier),
         optional(field("type_arguments", $.generic_argument_list)),
+        optional(field("where_clause", $.where_clause)),
         "{",
         field("body", repeat($._impl_item)),
         "}"
@@ -314,6 +316,7 @@ module.exports = grammar({
     generic_argument: ($) =>
       seq(
         field("placeholder", $.identifier),
+        optional(field("variadic", "...")),
         optional(seq(":", choice(
           field("all_bounds", sepBy("+", field("bound", $.protocol_bound))),
           field("any_bounds", sepBy("|", field("bound", $.protocol_bound))),
@@ -321,6 +324,23 @@ module.exports = grammar({
         optional(seq("=", field("default", $._type)))
       ),
 
+    where_clause: ($) =>
+      seq(
+        "where",
+        sepBy1(",", field("predicate", $.where_predicate)),
+        optional(",")
+      ),
+
+    where_predicate: ($) =>
+      seq(
+        field("type", $._type),
+        ":",
+        choice(
+          field("all_bounds", sepBy1("+", field("bound", $.protocol_bound))),
+          field("any_bounds", sepBy1("|", field("bound", $.protocol_bound)))
+        )
+      ),
+
     generic_argument_list: ($) =>
       seq(
         "<",
@@ -486,7 +506,7 @@ module.exports = grammar({
         field("name", $.identifier),
         optional(field("type_arguments", $.generic_argument_list)),
         optional(seq(":", field("type", $._type))),
-        seq("=", field("init", $._expression)),
+        optional(seq("=", field("init", $._expression))),
         ";"
       ),
 
@@ -940,6 +960,7 @@ module.exports = grammar({
     for_expression: ($) =>
       seq(
         "for",
+        optional(field("const", "const")),
         choice(
           field("name", $.identifier),
           seq(
@@ -987,6 +1008,10 @@ module.exports = grammar({
             seq(
               /([0-9][0-9]*)(\.[0-9]+)?([Ee][+\\-]?([0-9][0-9]*))?/,
               choice(...float_types)
+            ),
+            seq(
+              /0x[0-9a-fA-F]+(\.[0-9a-fA-F]+)?[pP][+\\-]?[0-9]+/,
+              optional(choice(...float_types))
             )
           )
         )
@@ -1019,23 +1044,30 @@ module.exports = grammar({
 
     string_literal: ($) =>
       token(
-        seq(
-          '"',
-          repeat(
-            choice(
-              seq(
-                "\\",
-                choice(
-                  /[^xu]/,
-                  /u[0-9a-fA-F]{4}/,
-                  /u{[0-9a-fA-F]+}/,
-                  /x[0-9a-fA-F]{2}/
-                )
-              ),
-              /[^"\\\n]+/
-            )
+        choice(
+          seq(
+            '"',
+            repeat(
+              choice(
+                seq(
+                  "\\",
+                  choice(
+                    /[^xu]/,
+                    /u[0-9a-fA-F]{4}/,
+                    /u{[0-9a-fA-F]+}/,
+                    /x[0-9a-fA-F]{2}/
+                  )
+                ),
+                /[^"\\\n]+/
+              )
+            ),
+            '"'
           ),
-          '"'
+          // Raw strings (r"...", r#"..."# and r##"..."##). Each form runs until the first
+          // closing delimiter with the same number of hashes.
+          /r"[^"]*"/,
+          /r#"([^"]|"+[^"#])*"+#/,
+          /r##"([^"]|"("|#")*#?[^"#])*"("|#")*##/
         )
       ),
 
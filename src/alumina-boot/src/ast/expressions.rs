@@ -1,3 +1,4 @@
+use crate::ast::float::FloatLit;
 use crate::ast::macros::{MacroExpander, MacroMaker};
 use crate::ast::maker::AstItemMaker;
 use crate::ast::types::TypeVisitor;
@@ -9,12 +10,15 @@ use crate::ast::{
 use crate::common::{
     AluminaError, ArenaAllocatable, CodeErrorBuilder, CodeErrorKind, HashSet, WithSpanDuringParsing,
 };
+use crate::completion;
+use crate::features::Feature;
 use crate::global_ctx::GlobalCtx;
 use crate::name_resolution::pass1::FirstPassVisitor;
 use crate::name_resolution::path::{Path, PathSegment};
 use crate::name_resolution::resolver::{ItemResolution, NameResolver};
 use crate::name_resolution::scope::{BoundItemType, NamedItem, NamedItemKind, Scope, ScopeType};
 use crate::parser::{AluminaVisitor, FieldKind, NodeExt, NodeKind, ParseCtx};
+use crate::rename;
 use crate::visitors::{AttributeVisitor, ScopedPathVisitor};
 
 use crate::common::IndexMap;
@@ -114,13 +118,16 @@ impl<'ast, 'src> ExpressionVisitor<'ast, 'src> {
     fn visit_ref(&mut self, node: tree_sitter::Node<'src>) -> Result<ExprP<'ast>, AluminaError> {
         let mut visitor = ScopedPathVisitor::new(self.ast, self.scope.clone(), self.macro_ctx);
         let path = visitor.visit(node)?;
+        let span = Span::from_node(self.scope.file_id(), node);
+        completion::complete_path(&self.global_ctx, &self.scope, &path, span);
+        rename::check_path(self.ast, &self.global_ctx, &self.scope, &path, span);
 
         resolve_name(
             self.global_ctx.clone(),
             self.ast,
             &self.scope,
             path,
-            Some(Span::from_node(self.scope.file_id(), node)),
+            Some(span),
         )
     }
 
@@ -269,14 +276,21 @@ impl<'ast, 'src> ExpressionVisitor<'ast, 'src> {
         &mut self,
         path: Path<'ast>,
         args: Vec<ExprP<'ast>>,
+        name_span: Option<Span>,
         span: Span,
     ) -> Result<ExprP<'ast>, AluminaError> {
         let mut resolver = NameResolver::new();
 
-        let r#macro = match resolver
+        let resolved = resolver
             .resolve_item(self.scope.clone(), path.clone())
-            .with_span(Some(span))?
-        {
+            .with_span(Some(span))?;
+
+        if let ItemResolution::Item(named_item) = &resolved {
+            self.global_ctx
+                .record_name(named_item.name_ref(&self.scope, name_span));
+        }
+
+        let r#macro = match resolved {
             ItemResolution::Item(NamedItem {
                 kind: NamedItemKind::Macro(symbol, node, scope),
                 attributes,
@@ -308,6 +322,61 @@ impl<'ast, 'src> ExpressionVisitor<'ast, 'src> {
 
         Ok(result)
     }
+
+    /// `for const (i, elem) in tuple { ... }`. Unlike a regular `for` loop, this cannot be
+    /// desugared here, as the number of elements is only known after monomorphization.
+    fn visit_static_for(
+        &mut self,
+        node: tree_sitter::Node<'src>,
+    ) -> Result<ExprP<'ast>, AluminaError> {
+        let value = self.visit(node.child_by_field(FieldKind::Value).unwrap())?;
+
+        let mut cursor = node.walk();
+        let name_nodes: Vec<_> = match node.child_by_field(FieldKind::Name) {
+            Some(name_node) => vec![name_node],
+            None => node
+                .children_by_field(FieldKind::Element, &mut cursor)
+                .collect(),
+        };
+
+        let (index_node, elem_node) = match name_nodes[..] {
+            [elem] => (None, elem),
+            [index, elem] => (Some(index), elem),
+            _ => {
+                return Err(CodeErrorKind::InvalidStaticForBinding)
+                    .with_span_from(&self.scope, node)
+            }
+        };
+
+        let index = index_node.map(|n| (self.ast.make_id(), n));
+        let elem = (self.ast.make_id(), elem_node);
+
+        let body = with_block_scope!(self, {
+            for (id, name_node) in index.into_iter().chain(std::iter::once(elem)) {
+                let name = self.code.node_text(name_node).alloc_on(self.ast);
+                self.ast.add_local_name(id, name);
+                self.scope
+                    .add_item(
+                        Some(name),
+                        NamedItem::new_default(NamedItemKind::Local(
+                            id,
+                            Span::from_node(self.scope.file_id(), name_node),
+                        )),
+                    )
+                    .with_span_from(&self.scope, name_node)?;
+            }
+
+            self.visit(node.child_by_field(FieldKind::Body).unwrap())?
+        });
+
+        Ok(
+            ExprKind::StaticFor(value, index.map(|(id, _)| id), elem.0, body).alloc_with_span_from(
+                self.ast,
+                &self.scope,
+                node,
+            ),
+        )
+    }
 }
 
 impl<'ast, 'src> AluminaVisitor<'src> for ExpressionVisitor<'ast, 'src> {
@@ -534,12 +603,19 @@ impl<'ast, 'src> AluminaVisitor<'src> for ExpressionVisitor<'ast, 'src> {
 
     fn visit_float_literal(&mut self, node: tree_sitter::Node<'src>) -> Self::ReturnType {
         let (remainder, kind) = suffixed_literals!(self.code.node_text(node),
+            // `bf16` has to come before `f16`
+            "bf16" => BuiltinType::BF16,
+            "f16" => BuiltinType::F16,
             "f32" => BuiltinType::F32,
             "f64" => BuiltinType::F64,
         );
 
+        let value = FloatLit::parse(remainder)
+            .ok_or(CodeErrorKind::InvalidLiteral)
+            .with_span_from(&self.scope, node)?;
+
         Ok(
-            ExprKind::Lit(Lit::Float(remainder.alloc_on(self.ast), kind)).alloc_with_span_from(
+            ExprKind::Lit(Lit::Float(value, kind)).alloc_with_span_from(
                 self.ast,
                 &self.scope,
                 node,
@@ -556,18 +632,19 @@ impl<'ast, 'src> AluminaVisitor<'src> for ExpressionVisitor<'ast, 'src> {
     }
 
     fn visit_char_literal(&mut self, node: tree_sitter::Node<'src>) -> Self::ReturnType {
-        let val = match parse_string_literal(self.code.node_text(node))
-            .with_span_from(&self.scope, node)?
-            .as_slice()
-        {
-            [v] => *v,
+        let units =
+            parse_literal_units(self.code.node_text(node)).with_span_from(&self.scope, node)?;
+
+        // ASCII characters are `u8` unless a `char` is expected, `\xNN` escapes are always
+        // bytes and everything else is a `char`.
+        let lit = match units[..] {
+            [LiteralUnit::Char(ch)] if ch.is_ascii() => Lit::Char(ch, None),
+            [LiteralUnit::Char(ch)] => Lit::Char(ch, Some(BuiltinType::Char)),
+            [LiteralUnit::Byte(b)] => Lit::Int(false, b as u128, Some(BuiltinType::U8)),
             _ => return Err(CodeErrorKind::InvalidCharLiteral).with_span_from(&self.scope, node),
         };
 
-        Ok(
-            ExprKind::Lit(Lit::Int(false, val as u128, Some(BuiltinType::U8)))
-                .alloc_with_span_from(self.ast, &self.scope, node),
-        )
+        Ok(ExprKind::Lit(lit).alloc_with_span_from(self.ast, &self.scope, node))
     }
 
     fn visit_boolean_literal(&mut self, node: tree_sitter::Node<'src>) -> Self::ReturnType {
@@ -707,7 +784,7 @@ impl<'ast, 'src> AluminaVisitor<'src> for ExpressionVisitor<'ast, 'src> {
         let span = Span::from_node(self.scope.file_id(), node);
         let inner = self.visit(node.child_by_field(FieldKind::Inner).unwrap())?;
 
-        self.visit_macro_invocation_impl(path, vec![inner], span)
+        self.visit_macro_invocation_impl(path, vec![inner], None, span)
     }
 
     fn visit_array_expression(&mut self, node: tree_sitter::Node<'src>) -> Self::ReturnType {
@@ -843,16 +920,50 @@ impl<'ast, 'src> AluminaVisitor<'src> for ExpressionVisitor<'ast, 'src> {
     }
 
     fn visit_type_check_expression(&mut self, node: tree_sitter::Node<'src>) -> Self::ReturnType {
-        let lhs = self.visit(node.child_by_field(FieldKind::Value).unwrap())?;
-        let typ = TypeVisitor::new(
+        let value_node = node.child_by_field(FieldKind::Value).unwrap();
+        let mut type_visitor = TypeVisitor::new(
             self.global_ctx.clone(),
             self.ast,
             self.scope.clone(),
             self.macro_ctx,
-        )
-        .visit(node.child_by_field(FieldKind::Type).unwrap())?;
+        );
+        let typ = type_visitor.visit(node.child_by_field(FieldKind::Type).unwrap())?;
+
+        // `T is Integer` checks the type itself rather than the type of a value
+        let result = if self.names_type(value_node) {
+            ExprKind::TypePredicate(type_visitor.visit_typeref(value_node)?, typ)
+        } else {
+            ExprKind::TypeCheck(self.visit(value_node)?, typ)
+        };
 
-        Ok(ExprKind::TypeCheck(lhs, typ).alloc_with_span_from(self.ast, &self.scope, node))
+        Ok(result.alloc_with_span_from(self.ast, &self.scope, node))
+    }
+
+    /// Whether an identifier in expression position refers to a type (e.g. a generic
+    /// placeholder) rather than a value.
+    fn names_type(&mut self, node: tree_sitter::Node<'src>) -> bool {
+        if !matches!(
+            node.kind_typed(),
+            NodeKind::Identifier | NodeKind::ScopedIdentifier
+        ) {
+            return false;
+        }
+
+        let mut visitor = ScopedPathVisitor::new(self.ast, self.scope.clone(), self.macro_ctx);
+        let Ok(path) = visitor.visit(node) else {
+            return false;
+        };
+
+        matches!(
+            NameResolver::new().resolve_item(self.scope.clone(), path),
+            Ok(ItemResolution::Item(NamedItem {
+                kind: NamedItemKind::Type(..)
+                    | NamedItemKind::TypeDef(..)
+                    | NamedItemKind::Protocol(..)
+                    | NamedItemKind::Placeholder(..),
+                ..
+            }))
+        )
     }
 
     fn visit_turbofish(&mut self, node: tree_sitter::Node<'src>) -> Self::ReturnType {
@@ -942,6 +1053,16 @@ impl<'ast, 'src> AluminaVisitor<'src> for ExpressionVisitor<'ast, 'src> {
     }
 
     fn visit_for_expression(&mut self, node: tree_sitter::Node<'src>) -> Self::ReturnType {
+        if node.child_by_field(FieldKind::Const).is_some() {
+            if !self
+                .global_ctx
+                .feature_enabled(Feature::ForConst, self.scope.file_id())
+            {
+                return Err(Feature::ForConst.error()).with_span_from(&self.scope, node);
+            }
+            return self.visit_static_for(node);
+        }
+
         let iterable_node = node.child_by_field(FieldKind::Value).unwrap();
         let iterable = self.visit(iterable_node)?;
 
@@ -1302,8 +1423,11 @@ impl<'ast, 'src> AluminaVisitor<'src> for ExpressionVisitor<'ast, 'src> {
         node: tree_sitter::Node<'src>,
     ) -> Self::ReturnType {
         let mut visitor = ScopedPathVisitor::new(self.ast, self.scope.clone(), self.macro_ctx);
-        let path = visitor.visit(node.child_by_field(FieldKind::Macro).unwrap())?;
+        let macro_node = node.child_by_field(FieldKind::Macro).unwrap();
+        let path = visitor.visit(macro_node)?;
 
+        let name_span = Span::from_node(self.scope.file_id(), macro_node)
+            .tail(path.segments.last().unwrap().0.len());
         let span = Span::from_node(self.scope.file_id(), node);
 
         let mut arguments = Vec::new();
@@ -1315,129 +1439,118 @@ impl<'ast, 'src> AluminaVisitor<'src> for ExpressionVisitor<'ast, 'src> {
         for node in arguments_node.children_by_field(FieldKind::Inner, &mut cursor) {
             arguments.push(self.visit(node)?);
         }
-        self.visit_macro_invocation_impl(path, arguments, span)
+        self.visit_macro_invocation_impl(path, arguments, Some(name_span), span)
     }
 }
 
-pub fn parse_string_literal(lit: &str) -> Result<Vec<u8>, CodeErrorKind> {
-    let mut result = Vec::<u8>::with_capacity(lit.len());
+/// A single unit of a string or a character literal
+#[derive(Debug, Clone, Copy)]
+pub enum LiteralUnit {
+    /// A `\xNN` escape. These can produce bytes that are not valid UTF-8 on their own.
+    Byte(u8),
+    Char(char),
+}
 
-    enum State {
-        Normal,
-        Escape,
-        Hex,
-        UnicodeStart,
-        UnicodeShort,
-        UnicodeLong,
+/// Decodes the contents of a string or a character literal (including the quotes) and
+/// validates the escape sequences.
+pub fn parse_literal_units(lit: &str) -> Result<Vec<LiteralUnit>, CodeErrorKind> {
+    if let Some(raw) = lit.strip_prefix('r') {
+        // Raw strings have no escapes, the contents are taken verbatim (with line endings
+        // normalized to `\n`).
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let contents = &raw[hashes + 1..raw.len() - hashes - 1];
+
+        return Ok(contents
+            .replace("\r\n", "\n")
+            .chars()
+            .map(LiteralUnit::Char)
+            .collect());
     }
 
-    let mut state = State::Normal;
-    let mut buf = String::with_capacity(4);
+    let mut result = Vec::with_capacity(lit.len());
+    let mut chars = lit[1..lit.len() - 1].chars();
 
-    for ch in lit[1..lit.len() - 1].bytes() {
-        state = match state {
-            State::Normal => match ch {
-                b'\\' => State::Escape,
-                _ => {
-                    result.push(ch);
-                    State::Normal
-                }
-            },
-            State::Escape => match ch {
-                b'\\' => {
-                    result.push(b'\\');
-                    State::Normal
-                }
-                b'n' => {
-                    result.push(b'\n');
-                    State::Normal
-                }
-                b'r' => {
-                    result.push(b'\r');
-                    State::Normal
-                }
-                b't' => {
-                    result.push(b'\t');
-                    State::Normal
-                }
-                b'\'' => {
-                    result.push(b'\'');
-                    State::Normal
-                }
-                b'"' => {
-                    result.push(b'"');
-                    State::Normal
-                }
-                b'0' => {
-                    result.push(b'\0');
-                    State::Normal
-                }
-                b'x' => State::Hex,
-                b'u' => State::UnicodeStart,
-                _ => {
-                    return Err(CodeErrorKind::InvalidEscapeSequence);
-                }
-            },
-            State::Hex => {
-                if buf.len() == 1 {
-                    buf.push(ch as char);
-                    let ch = u8::from_str_radix(&buf, 16).unwrap();
-                    result.push(ch);
-                    buf.clear();
-                    State::Normal
-                } else {
-                    buf.push(ch as char);
-                    State::Hex
-                }
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(LiteralUnit::Char(ch));
+            continue;
+        }
+
+        let unit = match chars.next().ok_or(CodeErrorKind::InvalidEscapeSequence)? {
+            '\\' => LiteralUnit::Char('\\'),
+            'n' => LiteralUnit::Char('\n'),
+            'r' => LiteralUnit::Char('\r'),
+            't' => LiteralUnit::Char('\t'),
+            '\'' => LiteralUnit::Char('\''),
+            '"' => LiteralUnit::Char('"'),
+            '0' => LiteralUnit::Char('\0'),
+            '\n' | '\r' => {
+                // Line continuation, the line break and the indentation of the next line
+                // are skipped.
+                chars = chars.as_str().trim_start().chars();
+                continue;
             }
-            State::UnicodeStart => match ch {
-                b'{' => State::UnicodeLong,
-                _ => {
-                    buf.push(ch as char);
-                    State::UnicodeShort
-                }
-            },
-            State::UnicodeShort => {
-                if buf.len() == 3 {
-                    buf.push(ch as char);
-                    let ch = u32::from_str_radix(&buf, 16)
-                        .map_err(|_| CodeErrorKind::InvalidEscapeSequence)?;
-
-                    let utf8 = char::from_u32(ch)
-                        .ok_or(CodeErrorKind::InvalidEscapeSequence)?
-                        .to_string();
-
-                    result.extend(utf8.as_bytes());
-                    buf.clear();
-                    State::Normal
-                } else {
-                    buf.push(ch as char);
-                    State::UnicodeShort
-                }
+            'x' => {
+                let rest = chars.as_str();
+                let digits = rest.get(..2).filter(|d| is_hex_digits(d)).ok_or_else(|| {
+                    CodeErrorKind::MalformedEscapeSequence(format!(
+                        "\\x{}",
+                        rest.chars().take(2).collect::<String>()
+                    ))
+                })?;
+                chars = rest[2..].chars();
+
+                LiteralUnit::Byte(u8::from_str_radix(digits, 16).unwrap())
             }
-            State::UnicodeLong => match ch {
-                b'}' => {
-                    let ch = u32::from_str_radix(&buf, 16)
-                        .map_err(|_| CodeErrorKind::InvalidEscapeSequence)?;
-                    let utf8 = char::from_u32(ch)
-                        .ok_or(CodeErrorKind::InvalidEscapeSequence)?
-                        .to_string();
-                    result.extend(utf8.as_bytes());
-                    buf.clear();
-                    State::Normal
-                }
-                _ => {
-                    buf.push(ch as char);
-                    State::UnicodeLong
+            'u' => {
+                let rest = chars.as_str();
+                let (digits, remainder) = match rest.strip_prefix('{') {
+                    Some(braced) => match braced.split_once('}') {
+                        Some((digits, remainder)) if digits.len() <= 6 => (digits, remainder),
+                        _ => ("", rest),
+                    },
+                    None => match rest.get(..4) {
+                        Some(digits) => (digits, &rest[4..]),
+                        None => ("", rest),
+                    },
+                };
+
+                if !is_hex_digits(digits) {
+                    return Err(CodeErrorKind::MalformedEscapeSequence(format!(
+                        "\\u{}",
+                        rest.chars().take(4).collect::<String>()
+                    )));
                 }
-            },
+                chars = remainder.chars();
+
+                let value = u32::from_str_radix(digits, 16).unwrap();
+                LiteralUnit::Char(
+                    char::from_u32(value).ok_or(CodeErrorKind::InvalidUnicodeScalar(value))?,
+                )
+            }
+            other => return Err(CodeErrorKind::UnknownEscapeSequence(other)),
         };
+
+        result.push(unit);
     }
 
-    match state {
-        State::Normal => Ok(result),
-        _ => Err(CodeErrorKind::InvalidEscapeSequence),
+    Ok(result)
+}
+
+fn is_hex_digits(digits: &str) -> bool {
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+pub fn parse_string_literal(lit: &str) -> Result<Vec<u8>, CodeErrorKind> {
+    let mut result = Vec::<u8>::with_capacity(lit.len());
+    for unit in parse_literal_units(lit)? {
+        match unit {
+            LiteralUnit::Byte(b) => result.push(b),
+            LiteralUnit::Char(ch) => result.extend(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
     }
+
+    Ok(result)
 }
 
 pub struct ClosureVisitor<'ast, 'src> {
@@ -1498,6 +1611,7 @@ impl<'ast, 'src> ClosureVisitor<'ast, 'src> {
                 },
                 span: None,
                 default: None,
+                variadic: false,
             });
 
             let span = Span::from_node(self.scope.file_id(), node);
@@ -1520,6 +1634,7 @@ impl<'ast, 'src> ClosureVisitor<'ast, 'src> {
             name: None,
             attributes: [].alloc_on(self.ast),
             placeholders: self.placeholders.alloc_on(self.ast),
+            where_predicates: &[],
             args: self.parameters.alloc_on(self.ast),
             return_type: self.return_type.unwrap(),
             body: Some(self.body.unwrap()),
@@ -1688,36 +1803,52 @@ pub fn resolve_name<'ast, 'src>(
     span: Option<Span>,
 ) -> Result<ExprP<'ast>, AluminaError> {
     let mut resolver = NameResolver::new();
-    let expr = match resolver
+    let resolved = match resolver
         .resolve_item(scope.clone(), path.clone())
-        .with_span(span)?
+        .with_span(span)
     {
-        ItemResolution::Item(named_item) => match named_item.kind {
-            NamedItemKind::Function(fun, _, _) => ExprKind::Fn(FnKind::Normal(fun), None),
-            NamedItemKind::Method(fun, _, _) => ExprKind::Fn(FnKind::Normal(fun), None),
-            NamedItemKind::Local(var, _) => ExprKind::Local(var),
-            NamedItemKind::BoundValue(self_id, var, bound_type, _) => {
-                ExprKind::BoundParam(self_id, var, bound_type)
-            }
-            NamedItemKind::MacroParameter(var, _, _) => ExprKind::Local(var),
-            NamedItemKind::Parameter(var, _) => ExprKind::Local(var),
-            NamedItemKind::Static(var, _, _) => ExprKind::Static(var, None),
-            NamedItemKind::Const(var, _, _) => ExprKind::Const(var, None),
-            NamedItemKind::EnumMember(typ, var, _) => ExprKind::EnumValue(typ, var),
-            NamedItemKind::Macro(symbol, node, scope) => {
-                let mut macro_maker = MacroMaker::new(ast, global_ctx);
-                macro_maker.make(
-                    Some(path.segments.last().unwrap().0),
-                    symbol,
-                    node,
-                    scope.clone(),
-                    named_item.attributes,
-                )?;
+        Ok(resolved) => resolved,
+        Err(e) if global_ctx.error_tolerant() => {
+            // Unresolved names become a void placeholder, so the rest of the item is
+            // still visited.
+            global_ctx.diag().add_from_error(e)?;
+            return Ok(ExprKind::Void.alloc_with_span(ast, span));
+        }
+        Err(e) => return Err(e),
+    };
 
-                ExprKind::Macro(symbol, &[])
+    let expr = match resolved {
+        ItemResolution::Item(named_item) => {
+            let name_len = path.segments.last().map(|s| s.0.len()).unwrap_or_default();
+            global_ctx.record_name(named_item.name_ref(scope, span.map(|s| s.tail(name_len))));
+
+            match named_item.kind {
+                NamedItemKind::Function(fun, _, _) => ExprKind::Fn(FnKind::Normal(fun), None),
+                NamedItemKind::Method(fun, _, _) => ExprKind::Fn(FnKind::Normal(fun), None),
+                NamedItemKind::Local(var, _) => ExprKind::Local(var),
+                NamedItemKind::BoundValue(self_id, var, bound_type, _) => {
+                    ExprKind::BoundParam(self_id, var, bound_type)
+                }
+                NamedItemKind::MacroParameter(var, _, _) => ExprKind::Local(var),
+                NamedItemKind::Parameter(var, _) => ExprKind::Local(var),
+                NamedItemKind::Static(var, _, _) => ExprKind::Static(var, None),
+                NamedItemKind::Const(var, _, _) => ExprKind::Const(var, None),
+                NamedItemKind::EnumMember(typ, var, _) => ExprKind::EnumValue(typ, var),
+                NamedItemKind::Macro(symbol, node, scope) => {
+                    let mut macro_maker = MacroMaker::new(ast, global_ctx);
+                    macro_maker.make(
+                        Some(path.segments.last().unwrap().0),
+                        symbol,
+                        node,
+                        scope.clone(),
+                        named_item.attributes,
+                    )?;
+
+                    ExprKind::Macro(symbol, &[])
+                }
+                kind => return Err(CodeErrorKind::Unexpected(format!("{}", kind))).with_span(span),
             }
-            kind => return Err(CodeErrorKind::Unexpected(format!("{}", kind))).with_span(span),
-        },
+        }
         ItemResolution::Defered(ty, name) => {
             let name = name.0.alloc_on(ast);
             let typ = ast.intern_type(ty);
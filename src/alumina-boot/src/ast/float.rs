@@ -0,0 +1,351 @@
+//! Floating point literals and conversions between the floating point formats.
+//!
+//! Literals are parsed into the actual binary values when the AST is built, so that the
+//! constant evaluator works with the same values as the generated code. Codegen emits
+//! them as hexadecimal float literals, which the C compiler converts exactly.
+use crate::ast::BuiltinType;
+
+/// A floating point literal (without the type suffix), rounded to both `f64` and `f32`.
+///
+/// The type of a literal is often only known after type inference, and rounding the
+/// `f64` value to `f32` can give a different result than rounding the decimal value
+/// directly, so both are kept.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct FloatLit {
+    f64_bits: u64,
+    f32_bits: u32,
+}
+
+impl FloatLit {
+    /// Parses a decimal (`1.5e3`) or a hexadecimal (`0x1.8p+1`) literal.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+
+        let (f64_bits, f32_bits) = match digits.strip_prefix("0x") {
+            Some(hex) => {
+                let (mantissa, exponent, sticky) = parse_hex(hex)?;
+                (
+                    round_to_format(mantissa, exponent, sticky, 11, 52),
+                    round_to_format(mantissa, exponent, sticky, 8, 23) as u32,
+                )
+            }
+            None => (
+                digits.parse::<f64>().ok()?.to_bits(),
+                digits.parse::<f32>().ok()?.to_bits(),
+            ),
+        };
+
+        Some(Self {
+            f64_bits: f64_bits | ((negative as u64) << 63),
+            f32_bits: f32_bits | ((negative as u32) << 31),
+        })
+    }
+
+    pub fn to_f64(self) -> f64 {
+        f64::from_bits(self.f64_bits)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        f32::from_bits(self.f32_bits)
+    }
+}
+
+/// Mantissa and binary exponent of the digits of a hexadecimal literal (after `0x`).
+/// Digits that do not fit into the mantissa are collapsed into a sticky bit.
+fn parse_hex(hex: &str) -> Option<(u128, i32, bool)> {
+    let (digits, exponent) = hex.split_once(['p', 'P'])?;
+    let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+    if int_part.is_empty() && frac_part.is_empty() {
+        return None;
+    }
+
+    // Clamping keeps the arithmetic below from overflowing, such exponents are way out
+    // of range anyway.
+    let mut exponent = exponent.parse::<i64>().ok()?.clamp(-100_000, 100_000) as i32;
+    let mut mantissa = 0u128;
+    let mut sticky = false;
+
+    for (ch, fractional) in int_part
+        .chars()
+        .map(|ch| (ch, false))
+        .chain(frac_part.chars().map(|ch| (ch, true)))
+    {
+        let digit = ch.to_digit(16)? as u128;
+        if mantissa >> 120 == 0 {
+            mantissa = (mantissa << 4) | digit;
+            if fractional {
+                exponent -= 4;
+            }
+        } else {
+            sticky |= digit != 0;
+            if !fractional {
+                exponent += 4;
+            }
+        }
+    }
+
+    Some((mantissa, exponent, sticky))
+}
+
+/// Rounds `mantissa * 2^exponent` (plus less than one unit of the mantissa if `sticky`)
+/// to the nearest value (ties to even) in the binary floating point format with the
+/// given number of exponent and explicit mantissa bits. Returns the bit pattern without
+/// the sign bit.
+pub fn round_to_format(
+    mantissa: u128,
+    exponent: i32,
+    sticky: bool,
+    exp_bits: u32,
+    mant_bits: u32,
+) -> u64 {
+    let max_exp = (1u64 << exp_bits) - 1;
+    let bias = (max_exp >> 1) as i32;
+    let infinity = max_exp << mant_bits;
+
+    if mantissa == 0 {
+        return 0;
+    }
+
+    // Exponent of the leading bit
+    let lead = 127 - mantissa.leading_zeros() as i32 + exponent;
+    if lead > bias {
+        return infinity;
+    }
+
+    let subnormal = lead < 1 - bias;
+    let quantum = if subnormal { 1 - bias } else { lead } - mant_bits as i32;
+    let shift = quantum - exponent;
+
+    let rounded = if shift <= 0 {
+        (mantissa << -shift) as u64
+    } else if shift > 128 {
+        0
+    } else {
+        let truncated = mantissa.checked_shr(shift as u32).unwrap_or(0);
+        let remainder = mantissa - truncated.checked_shl(shift as u32).unwrap_or(0);
+        let half = 1u128 << (shift - 1);
+        let round_up = remainder > half || (remainder == half && (sticky || truncated & 1 == 1));
+        (truncated + round_up as u128) as u64
+    };
+
+    let bits = if subnormal {
+        // Rounding up to the smallest normal number is handled by the carry
+        rounded
+    } else {
+        (((lead + bias) as u64) << mant_bits) + rounded - (1 << mant_bits)
+    };
+
+    bits.min(infinity)
+}
+
+/// Rounds a value to the format (see `round_to_format`), including the sign, infinities
+/// and NaNs.
+fn round_f64(value: f64, exp_bits: u32, mant_bits: u32) -> u64 {
+    let sign_bit = 1 << (exp_bits + mant_bits);
+    let sign = if value.is_sign_negative() {
+        sign_bit
+    } else {
+        0
+    };
+    let infinity = ((1u64 << exp_bits) - 1) << mant_bits;
+
+    if value.is_nan() {
+        return sign | infinity | (1 << (mant_bits - 1));
+    }
+    if value.is_infinite() {
+        return sign | infinity;
+    }
+
+    let bits = value.to_bits();
+    let raw_exp = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = (bits & ((1 << 52) - 1)) as u128;
+    let (mantissa, exponent) = if raw_exp == 0 {
+        (fraction, -1074)
+    } else {
+        (fraction | (1 << 52), raw_exp - 1075)
+    };
+
+    sign | round_to_format(mantissa, exponent, false, exp_bits, mant_bits)
+}
+
+/// Number of exponent and explicit mantissa bits of a floating point type
+pub fn float_format(kind: BuiltinType) -> (u32, u32) {
+    match kind {
+        BuiltinType::F16 => (5, 10),
+        BuiltinType::BF16 => (8, 7),
+        BuiltinType::F32 => (8, 23),
+        BuiltinType::F64 => (11, 52),
+        _ => unreachable!(),
+    }
+}
+
+/// Bit pattern of the value rounded to the floating point type
+pub fn to_bits(kind: BuiltinType, value: f64) -> u64 {
+    match kind {
+        BuiltinType::F64 => value.to_bits(),
+        _ => {
+            let (exp_bits, mant_bits) = float_format(kind);
+            round_f64(value, exp_bits, mant_bits)
+        }
+    }
+}
+
+/// Bit pattern of the integer rounded to the floating point type
+pub fn int_to_bits(kind: BuiltinType, negative: bool, magnitude: u128) -> u64 {
+    let (exp_bits, mant_bits) = float_format(kind);
+    let sign = (negative as u64) << (exp_bits + mant_bits);
+
+    sign | round_to_format(magnitude, 0, false, exp_bits, mant_bits)
+}
+
+/// Value of the bit pattern (exact, since `f64` is the widest format)
+pub fn from_bits(kind: BuiltinType, bits: u64) -> f64 {
+    match kind {
+        BuiltinType::F64 => f64::from_bits(bits),
+        BuiltinType::F32 => f32::from_bits(bits as u32) as f64,
+        _ => {
+            let (exp_bits, mant_bits) = float_format(kind);
+            let max_exp = (1 << exp_bits) - 1;
+            let bias = max_exp >> 1;
+            let sign = if (bits >> (exp_bits + mant_bits)) & 1 == 1 {
+                -1.0
+            } else {
+                1.0
+            };
+            let exp = ((bits >> mant_bits) & max_exp) as i32;
+            let mant = (bits & ((1 << mant_bits) - 1)) as f64;
+
+            sign * match exp {
+                0 => mant * 2f64.powi(1 - bias as i32 - mant_bits as i32),
+                _ if exp == max_exp as i32 && mant == 0.0 => f64::INFINITY,
+                _ if exp == max_exp as i32 => f64::NAN,
+                _ => {
+                    (mant + (1u64 << mant_bits) as f64)
+                        * 2f64.powi(exp - bias as i32 - mant_bits as i32)
+                }
+            }
+        }
+    }
+}
+
+/// Formats a finite value as a C hexadecimal float literal (e.g. `0x1.8p+1`).
+pub fn format_hex(value: f64) -> String {
+    let bits = value.to_bits();
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    let raw_exp = ((bits >> 52) & 0x7ff) as i64;
+    let fraction = bits & ((1 << 52) - 1);
+
+    let (lead, exponent) = match (raw_exp, fraction) {
+        (0, 0) => (0, 0),
+        (0, _) => (0, -1022),
+        _ => (1, raw_exp - 1023),
+    };
+
+    if fraction == 0 {
+        format!("{}0x{}p{:+}", sign, lead, exponent)
+    } else {
+        let digits = format!("{:013x}", fraction);
+        format!(
+            "{}0x{}.{}p{:+}",
+            sign,
+            lead,
+            digits.trim_end_matches('0'),
+            exponent
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_f32(mantissa: u128, exponent: i32, sticky: bool) -> u32 {
+        round_to_format(mantissa, exponent, sticky, 8, 23) as u32
+    }
+
+    fn round_f16(mantissa: u128, exponent: i32, sticky: bool) -> u16 {
+        round_to_format(mantissa, exponent, sticky, 5, 10) as u16
+    }
+
+    #[test]
+    fn exact_values() {
+        assert_eq!(round_f32(3, -1, false), 1.5f32.to_bits());
+        assert_eq!(round_f32(1 << 24, 0, false), 16777216f32.to_bits());
+        assert_eq!(round_f16(2048, 0, false), 0x6800);
+        assert_eq!(round_to_format(1, 0, false, 11, 52), 1f64.to_bits());
+        assert_eq!(round_f32(0, 10, true), 0);
+    }
+
+    #[test]
+    fn ties_to_even() {
+        // Halfway between 2^24 and 2^24 + 2, rounds down to the even mantissa
+        assert_eq!(round_f32((1 << 24) + 1, 0, false), 16777216f32.to_bits());
+        // Halfway between 2^24 + 2 and 2^24 + 4, rounds up to the even mantissa
+        assert_eq!(round_f32((1 << 24) + 3, 0, false), 16777220f32.to_bits());
+        // Anything beyond the halfway point rounds up
+        assert_eq!(round_f32((1 << 24) + 1, 0, true), 16777218f32.to_bits());
+        assert_eq!(round_f32((1 << 25) + 3, -1, false), 16777218f32.to_bits());
+
+        assert_eq!(round_f16(2049, 0, false), 0x6800);
+        assert_eq!(round_f16(2051, 0, false), 0x6802);
+        assert_eq!(round_f16(2049, 0, true), 0x6801);
+    }
+
+    #[test]
+    fn subnormals() {
+        // Smallest subnormal
+        assert_eq!(round_f32(1, -149, false), 1);
+        assert_eq!(round_f16(1, -24, false), 1);
+        // Half of the smallest subnormal is a tie with zero, which is even
+        assert_eq!(round_f32(1, -150, false), 0);
+        assert_eq!(round_f32(1, -150, true), 1);
+        assert_eq!(round_f32(3, -151, false), 1);
+        assert_eq!(round_f32(1, -200, true), 0);
+        // Largest subnormal
+        assert_eq!(round_f32(0x7fffff, -149, false), 0x7fffff);
+        assert_eq!(round_f16(0x3ff, -24, false), 0x3ff);
+        // Rounding up from the largest subnormal gives the smallest normal number
+        assert_eq!(
+            round_f32(0xffffff, -150, false),
+            f32::MIN_POSITIVE.to_bits()
+        );
+        assert_eq!(round_f16(0x7ff, -25, false), 0x400);
+        // Just below the smallest normal number, but closer to it than to any subnormal
+        assert_eq!(
+            round_f32((1 << 30) - 1, -156, false),
+            f32::MIN_POSITIVE.to_bits()
+        );
+        assert_eq!(round_to_format(1, -1074, false, 11, 52), 1);
+    }
+
+    #[test]
+    fn overflow() {
+        assert_eq!(round_f32(0xffffff, 104, false), f32::MAX.to_bits());
+        // Halfway between the largest finite value and 2^128 rounds to infinity
+        assert_eq!(round_f32(0x1ffffff, 103, false), f32::INFINITY.to_bits());
+        assert_eq!(round_f32(1, 128, false), f32::INFINITY.to_bits());
+        assert_eq!(round_f16(65520, 0, false), 0x7c00);
+        assert_eq!(round_f16(65519, 0, false), 0x7bff);
+    }
+
+    #[test]
+    fn hex_literals() {
+        let lit = FloatLit::parse("0x1.8p+1").unwrap();
+        assert_eq!(lit.to_f64(), 3.0);
+        assert_eq!(lit.to_f32(), 3.0);
+
+        let lit = FloatLit::parse("-0x1p-1074").unwrap();
+        assert_eq!(lit.to_f64(), -f64::from_bits(1));
+        assert_eq!(lit.to_f32().to_bits(), (-0.0f32).to_bits());
+
+        // More digits than fit into the mantissa, the last one makes it round up
+        let lit = FloatLit::parse("0x1.000001000000000000000000000000001p0").unwrap();
+        assert_eq!(lit.to_f32(), 1.0 + f32::EPSILON);
+
+        assert_eq!(FloatLit::parse("0x.p1"), None);
+        assert_eq!(FloatLit::parse("0x1.8"), None);
+    }
+}
@@ -8,6 +8,7 @@ pub enum LangItemKind {
     SliceNew,
     SliceIndex,
     SliceRangeIndex,
+    IndexFailure,
     SliceConstCoerce,
     SliceConstCast,
     SliceSlicify,
@@ -49,6 +50,7 @@ pub enum LangItemKind {
     ProtoSameLayoutAs,
     ProtoAny,
     ProtoNone,
+    ProtoDeref,
 
     ImplBuiltin(BuiltinType),
     ImplTuple(usize),
@@ -64,6 +66,9 @@ pub enum LangItemKind {
     TypeopEnumTypeOf,
 
     EntrypointGlue,
+    InstrumentEnter,
+    InstrumentExit,
+    AllocSite,
     TestCaseMeta,
     TestCaseMetaNew,
 
@@ -79,6 +84,9 @@ pub enum LangItemKind {
 
     FormatArg,
     EnumVariantNew,
+
+    Option,
+    Result,
 }
 
 impl LangItemKind {
@@ -142,6 +150,7 @@ impl TryFrom<&str> for LangItemKind {
             "slice_const_cast" => Ok(LangItemKind::SliceConstCast),
             "slice_index" => Ok(LangItemKind::SliceIndex),
             "slice_range_index" => Ok(LangItemKind::SliceRangeIndex),
+            "index_failure" => Ok(LangItemKind::IndexFailure),
             "slice_slicify" => Ok(LangItemKind::SliceSlicify),
 
             "range_full" => Ok(LangItemKind::RangeFull),
@@ -182,10 +191,12 @@ impl TryFrom<&str> for LangItemKind {
             "proto_same_layout_as" => Ok(LangItemKind::ProtoSameLayoutAs),
             "proto_any" => Ok(LangItemKind::ProtoAny),
             "proto_none" => Ok(LangItemKind::ProtoNone),
+            "proto_deref" => Ok(LangItemKind::ProtoDeref),
 
             "builtin_never" => Ok(LangItemKind::ImplBuiltin(BuiltinType::Never)),
             "builtin_void" => Ok(LangItemKind::ImplTuple(0)),
             "builtin_bool" => Ok(LangItemKind::ImplBuiltin(BuiltinType::Bool)),
+            "builtin_char" => Ok(LangItemKind::ImplBuiltin(BuiltinType::Char)),
             "builtin_u8" => Ok(LangItemKind::ImplBuiltin(BuiltinType::U8)),
             "builtin_u16" => Ok(LangItemKind::ImplBuiltin(BuiltinType::U16)),
             "builtin_u32" => Ok(LangItemKind::ImplBuiltin(BuiltinType::U32)),
@@ -198,6 +209,8 @@ impl TryFrom<&str> for LangItemKind {
             "builtin_i64" => Ok(LangItemKind::ImplBuiltin(BuiltinType::I64)),
             "builtin_i128" => Ok(LangItemKind::ImplBuiltin(BuiltinType::I128)),
             "builtin_isize" => Ok(LangItemKind::ImplBuiltin(BuiltinType::ISize)),
+            "builtin_f16" => Ok(LangItemKind::ImplBuiltin(BuiltinType::F16)),
+            "builtin_bf16" => Ok(LangItemKind::ImplBuiltin(BuiltinType::BF16)),
             "builtin_f32" => Ok(LangItemKind::ImplBuiltin(BuiltinType::F32)),
             "builtin_f64" => Ok(LangItemKind::ImplBuiltin(BuiltinType::F64)),
 
@@ -220,6 +233,9 @@ impl TryFrom<&str> for LangItemKind {
             "typeop_enum_type_of" => Ok(LangItemKind::TypeopEnumTypeOf),
 
             "entrypoint_glue" => Ok(LangItemKind::EntrypointGlue),
+            "instrument_enter" => Ok(LangItemKind::InstrumentEnter),
+            "instrument_exit" => Ok(LangItemKind::InstrumentExit),
+            "alloc_site" => Ok(LangItemKind::AllocSite),
             "test_case_meta" => Ok(LangItemKind::TestCaseMeta),
             "test_case_meta_new" => Ok(LangItemKind::TestCaseMetaNew),
 
@@ -234,6 +250,9 @@ impl TryFrom<&str> for LangItemKind {
             "format_arg" => Ok(LangItemKind::FormatArg),
             "enum_variant_new" => Ok(LangItemKind::EnumVariantNew),
 
+            "option" => Ok(LangItemKind::Option),
+            "result" => Ok(LangItemKind::Result),
+
             t => {
                 if let Some(matches) = regex!(r"^builtin_tuple_(\d+)$").captures(t) {
                     let n = matches[1].parse::<usize>().unwrap();
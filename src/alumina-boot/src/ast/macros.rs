@@ -2,7 +2,7 @@ use crate::ast::expressions::ExpressionVisitor;
 use crate::ast::format::{format_args, Piece};
 use crate::ast::pretty::PrettyPrinter;
 use crate::ast::{
-    AstCtx, AstId, Attribute, BuiltinMacro, BuiltinMacroKind, Expr, ExprKind, ExprP,
+    AstCtx, AstId, Attribute, BuiltinMacro, BuiltinMacroKind, BuiltinType, Expr, ExprKind, ExprP,
     FieldInitializer, FnKind, Item, ItemP, Lit, Macro, MacroCtx, MacroParameter, Span, Statement,
 };
 use crate::common::{AluminaError, ArenaAllocatable, CodeErrorKind, HashMap};
@@ -101,6 +101,9 @@ impl<'ast> MacroMaker<'ast> {
                 "bind" => BuiltinMacroKind::Bind,
                 "reduce" => BuiltinMacroKind::Reduce,
                 "stringify" => BuiltinMacroKind::Stringify,
+                "compiler_version" => BuiltinMacroKind::CompilerVersion,
+                "build_timestamp" => BuiltinMacroKind::BuildTimestamp,
+                "cfg_values" => BuiltinMacroKind::CfgValues,
                 s => {
                     return Err(CodeErrorKind::UnknownBuiltinMacro(s.to_string()))
                         .with_span_from(&scope, node)
@@ -457,7 +460,11 @@ impl<'ast> MacroExpander<'ast> {
                 self.visit_expr(then)?,
                 self.visit_expr(els)?,
             ),
+            StaticFor(value, index, elem, body) => {
+                StaticFor(self.visit_expr(value)?, index, elem, self.visit_expr(body)?)
+            }
             TypeCheck(expr, ty) => TypeCheck(self.visit_expr(expr)?, self.visit_typ(ty)?),
+            TypePredicate(ty, bound) => TypePredicate(self.visit_typ(ty)?, self.visit_typ(bound)?),
             Cast(inner, ty) => Cast(self.visit_expr(inner)?, self.visit_typ(ty)?),
             Fn(ref kind, generic_args) => {
                 let kind = match kind {
@@ -576,8 +583,8 @@ impl<'ast> MacroExpander<'ast> {
                 assert_args!(self, 1);
                 let name = string_arg!(self, 0);
 
-                let value = match std::str::from_utf8(name).map(std::env::var) {
-                    Ok(Ok(v)) => self.ast.arena.alloc_slice_copy(v.as_bytes()),
+                let value = match std::str::from_utf8(name).map(|n| self.global_ctx.env_var(n)) {
+                    Ok(Some(v)) => self.ast.arena.alloc_slice_copy(v.as_bytes()),
                     _ => unreachable!(),
                 };
 
@@ -587,10 +594,50 @@ impl<'ast> MacroExpander<'ast> {
                 }
                 .alloc_on(self.ast))
             }
+            BuiltinMacroKind::CompilerVersion | BuiltinMacroKind::CfgValues => {
+                assert_args!(self, 0);
+
+                let value = if let BuiltinMacroKind::CompilerVersion = kind {
+                    format!("alumina-boot {}", env!("CARGO_PKG_VERSION"))
+                } else {
+                    // Same format as `--print-cfg`
+                    self.global_ctx
+                        .cfg_values()
+                        .into_iter()
+                        .map(|(key, value)| match value {
+                            Some(value) => format!("{}={:?}\n", key, value),
+                            None => format!("{}\n", key),
+                        })
+                        .collect()
+                };
+
+                Ok(Expr {
+                    kind: ExprKind::Lit(Lit::Str(
+                        self.ast.arena.alloc_slice_copy(value.as_bytes()),
+                    )),
+                    span: self.invocation_span,
+                }
+                .alloc_on(self.ast))
+            }
+            BuiltinMacroKind::BuildTimestamp => {
+                assert_args!(self, 0);
+
+                let timestamp = self
+                    .global_ctx
+                    .build_timestamp()
+                    .ok_or(CodeErrorKind::BuildTimestampNotEnabled)
+                    .with_span(self.invocation_span)?;
+
+                Ok(Expr {
+                    kind: ExprKind::Lit(Lit::Int(false, timestamp as u128, Some(BuiltinType::U64))),
+                    span: self.invocation_span,
+                }
+                .alloc_on(self.ast))
+            }
             BuiltinMacroKind::Line | BuiltinMacroKind::Column => {
                 let (line, column) = self
                     .invocation_span
-                    .map(|s| (s.line + 1, s.column + 1))
+                    .map(|s| (s.line + 1, self.global_ctx.diag().column(&s)))
                     .ok_or(CodeErrorKind::NoSpanInformation)
                     .with_span(self.invocation_span)?;
 
@@ -640,6 +687,7 @@ impl<'ast> MacroExpander<'ast> {
                 let data = std::fs::read(filename)
                     .map_err(|_| CodeErrorKind::CannotReadFile(filename.to_string()))
                     .with_span(self.invocation_span)?;
+                self.global_ctx.add_dependency(filename.into());
 
                 Ok(Expr {
                     kind: ExprKind::Lit(Lit::Str(self.ast.arena.alloc_slice_copy(&data[..]))),
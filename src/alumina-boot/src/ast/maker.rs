@@ -3,14 +3,16 @@ use crate::ast::lang::LangItemKind;
 use crate::ast::macros::MacroMaker;
 use crate::ast::types::TypeVisitor;
 use crate::ast::{
-    AssociatedFn, AstCtx, Attribute, Enum, EnumMember, Field, Function, Intrinsic, Item, ItemP,
-    Mixin, MixinCell, Parameter, Placeholder, Protocol, Span, StaticOrConst, StructLike, Ty, TyP,
-    TypeDef,
+    AssociatedFn, AstCtx, Attribute, BuiltinType, Enum, EnumMember, ExprKind, Field, Function,
+    Intrinsic, Item, ItemP, Lit, Mixin, MixinCell, OptimizationLevel, Parameter, Placeholder,
+    Protocol, ProtocolBounds, ProtocolBoundsKind, Span, StaticOrConst, StructLike, Ty, TyP,
+    TypeDef, UnOp, WherePredicate,
 };
 use crate::common::{
-    AluminaError, ArenaAllocatable, CodeError, CodeErrorKind, HashSet, Marker,
+    AluminaError, ArenaAllocatable, CodeError, CodeErrorKind, HashMap, HashSet, Marker,
     WithSpanDuringParsing,
 };
+use crate::features::Feature;
 use crate::global_ctx::GlobalCtx;
 use crate::intrinsics::intrinsic_kind;
 use crate::name_resolution::resolver::NameResolver;
@@ -26,6 +28,7 @@ pub struct AstItemMaker<'ast> {
     global_ctx: GlobalCtx,
     symbols: Vec<ItemP<'ast>>,
     ambient_placeholders: Vec<Placeholder<'ast>>,
+    ambient_where_predicates: Vec<WherePredicate<'ast>>,
     macro_ctx: MacroCtx,
     local: bool,
 }
@@ -37,6 +40,7 @@ impl<'ast> AstItemMaker<'ast> {
             global_ctx,
             symbols: Vec::new(),
             ambient_placeholders: Vec::new(),
+            ambient_where_predicates: Vec::new(),
             macro_ctx,
             local: false,
         }
@@ -48,6 +52,7 @@ impl<'ast> AstItemMaker<'ast> {
             global_ctx,
             symbols: Vec::new(),
             ambient_placeholders: Vec::new(),
+            ambient_where_predicates: Vec::new(),
             macro_ctx,
             local: true,
         }
@@ -62,11 +67,33 @@ impl<'ast> AstItemMaker<'ast> {
         scope: &Scope<'ast, 'src>,
     ) -> Result<&'ast [Placeholder<'ast>], AluminaError> {
         let mut placeholders = self.ambient_placeholders.clone();
+        let mut variadic_seen = false;
         for (_name, item) in scope.inner().all_items() {
             match item.kind {
                 NamedItemKind::Placeholder(id, node) => {
+                    let variadic = node.child_by_field(FieldKind::Variadic).is_some();
+                    if variadic
+                        && !self
+                            .global_ctx
+                            .feature_enabled(Feature::VariadicGenerics, scope.file_id())
+                    {
+                        return Err(Feature::VariadicGenerics.error()).with_span_from(scope, node);
+                    }
+                    if variadic && scope.typ() != ScopeType::Function {
+                        return Err(CodeErrorKind::VariadicNotAllowedHere)
+                            .with_span_from(scope, node);
+                    }
+                    if variadic_seen
+                        || (variadic && node.child_by_field(FieldKind::Default).is_some())
+                    {
+                        return Err(CodeErrorKind::VariadicPlaceholderNotLast)
+                            .with_span_from(scope, node);
+                    }
+                    variadic_seen = variadic;
+
                     placeholders.push(Placeholder {
                         id,
+                        variadic,
                         default: node
                             .child_by_field(FieldKind::Default)
                             .map(|node| {
@@ -105,13 +132,77 @@ impl<'ast> AstItemMaker<'ast> {
         Ok(placeholders.alloc_on(self.ast))
     }
 
+    /// Parses the `where` clause of a function or an impl block (if any). Predicates that
+    /// constrain one of the generic parameters directly are merged into its bounds, the
+    /// rest are returned to be checked separately during monomorphization.
+    fn apply_where_clause<'src>(
+        &self,
+        scope: &Scope<'ast, 'src>,
+        node: tree_sitter::Node<'src>,
+        placeholders: &mut [Placeholder<'ast>],
+    ) -> Result<Vec<WherePredicate<'ast>>, AluminaError> {
+        let mut where_predicates = Vec::new();
+        let where_clause = match node.child_by_field(FieldKind::WhereClause) {
+            Some(where_clause) => where_clause,
+            None => return Ok(where_predicates),
+        };
+
+        let mut cursor = where_clause.walk();
+        for predicate in where_clause.children_by_field(FieldKind::Predicate, &mut cursor) {
+            let mut visitor = TypeVisitor::new(
+                self.global_ctx.clone(),
+                self.ast,
+                scope.clone(),
+                self.macro_ctx,
+            );
+
+            let typ = visitor.visit(predicate.child_by_field(FieldKind::Type).unwrap())?;
+            let bounds = visitor.parse_protocol_bounds(predicate)?;
+
+            let placeholder = match typ {
+                Ty::Placeholder(id) => placeholders.iter_mut().find(|p| p.id == *id),
+                _ => None,
+            };
+
+            match placeholder {
+                // `T: A + B` where `T: C + D` merges into `T: A + B + C + D`. Disjunctive bounds
+                // cannot be merged with existing ones, so they are checked as a separate predicate.
+                Some(placeholder)
+                    if placeholder.bounds.bounds.is_empty()
+                        || (placeholder.bounds.kind == ProtocolBoundsKind::All
+                            && bounds.kind == ProtocolBoundsKind::All) =>
+                {
+                    let merged: Vec<_> = placeholder
+                        .bounds
+                        .bounds
+                        .iter()
+                        .chain(bounds.bounds.iter())
+                        .copied()
+                        .collect();
+
+                    placeholder.bounds = ProtocolBounds {
+                        kind: bounds.kind,
+                        bounds: merged.alloc_on(self.ast),
+                    };
+                }
+                _ => where_predicates.push(WherePredicate {
+                    typ,
+                    bounds,
+                    span: Some(Span::from_node(scope.file_id(), predicate)),
+                }),
+            }
+        }
+
+        Ok(where_predicates)
+    }
+
     fn resolve_associated_items<'src>(
         &self,
         impl_scopes: &[Scope<'ast, 'src>],
     ) -> Result<(&'ast [AssociatedFn<'ast>], &'ast [Mixin<'ast>]), AluminaError> {
         let mut associated_fns = Vec::new();
         let mut mixins = Vec::new();
-        let mut names = HashSet::default();
+        let mut names: HashMap<&'ast str, Span> = HashMap::default();
 
         for impl_scope in impl_scopes {
             for (name, item) in impl_scope.inner().all_items() {
@@ -119,11 +210,18 @@ impl<'ast> AstItemMaker<'ast> {
                     NamedItemKind::Function(symbol, node, _)
                     | NamedItemKind::Method(symbol, node, _) => {
                         if let Some(name) = name {
-                            if !names.insert(name) {
-                                self.global_ctx.diag().add_warning(CodeError::from_kind(
-                                    CodeErrorKind::DuplicateNameShadow(name.to_string()),
-                                    Some(Span::from_node(impl_scope.file_id(), *node)),
-                                ));
+                            let span = Span::from_node(impl_scope.file_id(), *node);
+                            if let Some(previous) = names.insert(name, span) {
+                                return Err(AluminaError::CodeErrors(vec![CodeError {
+                                    kind: CodeErrorKind::ConflictingAssociatedFn(name.to_string()),
+                                    backtrace: vec![
+                                        Marker::Span(span),
+                                        Marker::RelatedSpan(
+                                            "previously defined here".to_string(),
+                                            previous,
+                                        ),
+                                    ],
+                                }]));
                             }
                         }
                         associated_fns.push(AssociatedFn {
@@ -196,10 +294,25 @@ impl<'ast> AstItemMaker<'ast> {
 
                     let span = Span::from_node(code.file_id(), node);
 
+                    let has_width = item
+                        .attributes
+                        .iter()
+                        .any(|a| matches!(a, Attribute::Bits(_)));
+                    if has_width != attributes.contains(&Attribute::Bitfield) {
+                        let detail = if has_width {
+                            "`#[bits(...)]` is only valid on fields of `#[bitfield]` structs"
+                        } else {
+                            "fields of `#[bitfield]` structs must have a width (`#[bits(...)]`)"
+                        };
+                        return Err(CodeErrorKind::InvalidBitfield(detail.to_string()))
+                            .with_span_from(&scope, node);
+                    }
+
                     fields.push(Field {
                         id: self.ast.make_id(),
                         name: name.unwrap(),
                         typ: field_type,
+                        attributes: item.attributes,
                         span: Some(span),
                     });
                 }
@@ -218,6 +331,17 @@ impl<'ast> AstItemMaker<'ast> {
             return Err(CodeErrorKind::InvalidTransparent).with_span_from(&scope, node);
         }
 
+        if attributes.iter().any(|a| matches!(a, Attribute::Repr(_))) {
+            return Err(CodeErrorKind::IntegerReprOnStruct).with_span_from(&scope, node);
+        }
+
+        if attributes.contains(&Attribute::Bitfield) && is_union {
+            return Err(CodeErrorKind::InvalidBitfield(
+                "unions cannot be bitfields".to_string(),
+            ))
+            .with_span_from(&scope, node);
+        }
+
         let (associated_fns, mixins) = self.resolve_associated_items(impl_scopes)?;
 
         let span = Span::from_node(code.file_id(), node);
@@ -269,11 +393,18 @@ impl<'ast> AstItemMaker<'ast> {
         Ok(())
     }
 
-    fn make_impl<'src>(&mut self, scope: Scope<'ast, 'src>) -> Result<(), AluminaError> {
+    fn make_impl<'src>(
+        &mut self,
+        node: tree_sitter::Node<'src>,
+        scope: Scope<'ast, 'src>,
+    ) -> Result<(), AluminaError> {
         // Ambient placeholders on impl blocks
-        self.ambient_placeholders = self.get_placeholders(&scope)?.to_vec();
+        let mut placeholders = self.get_placeholders(&scope)?.to_vec();
+        self.ambient_where_predicates = self.apply_where_clause(&scope, node, &mut placeholders)?;
+        self.ambient_placeholders = placeholders;
         let res = self.make(scope.clone());
         self.ambient_placeholders.clear();
+        self.ambient_where_predicates.clear();
         res
     }
 
@@ -317,6 +448,15 @@ impl<'ast> AstItemMaker<'ast> {
             }
         }
 
+        let repr = attributes.iter().find_map(|a| match a {
+            Attribute::Repr(kind) => Some(*kind),
+            Attribute::ReprC => Some(BuiltinType::I32),
+            _ => None,
+        });
+        if let Some(repr) = repr {
+            self.check_discriminants(repr, &members)?;
+        }
+
         let (associated_fns, mixins) = self.resolve_associated_items(impl_scopes)?;
 
         let span = Span::from_node(scope.file_id(), node);
@@ -338,6 +478,98 @@ impl<'ast> AstItemMaker<'ast> {
         Ok(())
     }
 
+    /// Checks that the values of enum members fit into the representation of the enum given
+    /// with `#[repr(...)]`. Only literal values can be checked here, the rest (and the type of
+    /// the values) is checked during monomorphization.
+    fn check_discriminants(
+        &self,
+        repr: BuiltinType,
+        members: &[EnumMember<'ast>],
+    ) -> Result<(), AluminaError> {
+        let bits = match repr {
+            BuiltinType::U8 | BuiltinType::I8 => 8,
+            BuiltinType::U16 | BuiltinType::I16 => 16,
+            BuiltinType::U32 | BuiltinType::I32 => 32,
+            BuiltinType::U64 | BuiltinType::I64 => 64,
+            BuiltinType::U128 | BuiltinType::I128 => 128,
+            _ => self
+                .global_ctx
+                .cfg("target_pointer_width")
+                .flatten()
+                .and_then(|w| w.parse().ok())
+                .unwrap_or(64),
+        };
+        let fits = |negative: bool, magnitude: u128| {
+            let max = u128::MAX >> (128 - bits);
+            match (repr.is_signed(), negative) {
+                (true, false) => magnitude <= max >> 1,
+                (true, true) => magnitude <= (max >> 1) + 1,
+                (false, _) => magnitude <= max && (!negative || magnitude == 0),
+            }
+        };
+        let out_of_range = |negative: bool, magnitude: u128, span: Option<Span>| {
+            Err(AluminaError::CodeErrors(vec![CodeError {
+                kind: CodeErrorKind::EnumDiscriminantOutOfRange(
+                    format!("{}{}", if negative { "-" } else { "" }, magnitude),
+                    format!("{:?}", repr).to_lowercase(),
+                ),
+                backtrace: span.map(Marker::Span).into_iter().collect(),
+            }]))
+        };
+
+        // Members without a value take the smallest non-negative values that are not taken
+        // (see `monomorphize_enum`), which can only be predicted if all the values are literals.
+        let mut taken = HashSet::default();
+        let mut all_literals = true;
+
+        for member in members {
+            let Some(value) = member.value else {
+                continue;
+            };
+
+            let literal = match &value.kind {
+                ExprKind::Lit(Lit::Int(negative, magnitude, kind)) => {
+                    Some((*negative, *magnitude, *kind))
+                }
+                ExprKind::Unary(UnOp::Neg, inner) => match &inner.kind {
+                    ExprKind::Lit(Lit::Int(false, magnitude, kind)) => {
+                        Some((true, *magnitude, *kind))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            match literal {
+                // Literals of a different type are a type mismatch, reported later
+                Some((negative, magnitude, kind)) if kind.is_none() || kind == Some(repr) => {
+                    if !fits(negative, magnitude) {
+                        return out_of_range(negative, magnitude, member.span);
+                    }
+                    if !negative {
+                        taken.insert(magnitude);
+                    }
+                }
+                _ => all_literals = false,
+            }
+        }
+
+        if all_literals {
+            let mut counter = 0;
+            for member in members.iter().filter(|m| m.value.is_none()) {
+                while taken.contains(&counter) {
+                    counter += 1;
+                }
+                if !fits(false, counter) {
+                    return out_of_range(false, counter, member.span);
+                }
+                taken.insert(counter);
+            }
+        }
+
+        Ok(())
+    }
+
     fn make_typedef<'src>(
         &mut self,
         name: Option<&'ast str>,
@@ -421,7 +653,10 @@ impl<'ast> AstItemMaker<'ast> {
             .map(|n| code.node_text(n));
 
         let span = Span::from_node(scope.file_id(), node);
-        let placeholders = self.get_placeholders(&scope)?;
+        let mut placeholders = self.get_placeholders(&scope)?.to_vec();
+        let mut where_predicates = self.ambient_where_predicates.clone();
+        where_predicates.extend(self.apply_where_clause(&scope, node, &mut placeholders)?);
+        let placeholders = placeholders.alloc_on(self.ast);
 
         for (_name, item) in scope.inner().all_items() {
             match item.kind {
@@ -451,6 +686,27 @@ impl<'ast> AstItemMaker<'ast> {
             return Err(CodeErrorKind::ProtocolFnsCannotBeExtern).with_span_from(&scope, node);
         }
 
+        if is_extern && attributes.contains(&Attribute::ConstEval) {
+            return Err(CodeErrorKind::ExternConstEval).with_span_from(&scope, node);
+        }
+
+        if attributes.contains(&Attribute::Optimize(OptimizationLevel::None))
+            && attributes.contains(&Attribute::AlwaysInline)
+        {
+            return Err(CodeErrorKind::InvalidAttributeDetail(
+                "`optimize(none)` cannot be combined with `inline(always)`".to_string(),
+            ))
+            .with_span_from(&scope, node);
+        }
+
+        // The parameters depend on the target, they are checked during monomorphization
+        if attributes.contains(&Attribute::Interrupt) && (!placeholders.is_empty() || has_varargs) {
+            return Err(CodeErrorKind::InvalidInterruptHandlerSignature(
+                "interrupt handlers cannot be generic or variadic".to_string(),
+            ))
+            .with_span_from(&scope, node);
+        }
+
         match abi {
             None | Some("\"C\"") => {
                 if is_extern && !placeholders.is_empty() {
@@ -517,6 +773,7 @@ impl<'ast> AstItemMaker<'ast> {
             name,
             attributes,
             placeholders,
+            where_predicates: where_predicates.alloc_on(self.ast),
             args: parameters.alloc_on(self.ast),
             return_type,
             body: function_body,
@@ -556,7 +813,6 @@ impl<'ast> AstItemMaker<'ast> {
             .transpose()?;
 
         let is_extern = node.child_by_field(FieldKind::Extern).is_some();
-        assert!(!is_extern || !is_const);
 
         let init = node
             .child_by_field(FieldKind::Init)
@@ -584,6 +840,10 @@ impl<'ast> AstItemMaker<'ast> {
             return Err(CodeErrorKind::ExternStaticMustHaveType).with_span_from(&scope, node);
         }
 
+        if is_const && !is_extern && init.is_none() {
+            return Err(CodeErrorKind::ConstMustHaveValue).with_span_from(&scope, node);
+        }
+
         let span = Span::from_node(scope.file_id(), node);
 
         let result = Item::StaticOrConst(StaticOrConst {
@@ -666,13 +926,14 @@ impl<'ast> AstItemMaker<'ast> {
                 let mut impl_scopes = Vec::with_capacity(rest.len());
                 for impl_item in rest {
                     match &impl_item.kind {
-                        NamedItemKind::Impl(_, scope) => {
-                            self.make_impl(scope.clone())?;
+                        NamedItemKind::Impl(node, scope) => {
+                            self.make_impl(*node, scope.clone())?;
                             impl_scopes.push(scope.clone());
                         }
                         _ => unreachable!(),
                     }
                 }
+                self.ast.add_item_path(symbol, scope.path());
                 self.make_type(
                     name,
                     symbol,
@@ -686,6 +947,7 @@ impl<'ast> AstItemMaker<'ast> {
                 kind: TypeDef(symbol, node, scope),
                 attributes,
             }] => {
+                self.ast.add_item_path(symbol, scope.path());
                 self.make_typedef(name, symbol, *node, scope.clone(), attributes)?;
             }
             [NI {
@@ -693,6 +955,7 @@ impl<'ast> AstItemMaker<'ast> {
                 attributes,
             }] => {
                 self.make(scope.clone())?;
+                self.ast.add_item_path(symbol, scope.path());
                 self.make_protocol(name, symbol, *node, scope.clone(), attributes)?;
             }
             [NI {
@@ -732,11 +995,24 @@ impl<'ast> AstItemMaker<'ast> {
         Ok(())
     }
 
+    /// Makes all the items in the scope (and the modules within it). In error tolerant mode
+    /// (editor tooling), an item that fails does not prevent the others from being made and
+    /// all the errors are returned at the end.
     pub fn make<'src>(&mut self, scope: Scope<'ast, 'src>) -> Result<(), AluminaError> {
+        let mut errors = Vec::new();
         for (name, items) in scope.inner().grouped_items() {
-            self.make_item_group(scope.clone(), name, items)?;
+            match self.make_item_group(scope.clone(), name, items) {
+                Err(AluminaError::CodeErrors(e)) if self.global_ctx.error_tolerant() => {
+                    errors.extend(e)
+                }
+                result => result?,
+            }
         }
 
-        Ok(())
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AluminaError::CodeErrors(errors))
+        }
     }
 }
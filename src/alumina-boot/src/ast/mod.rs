@@ -1,4 +1,5 @@
 pub mod expressions;
+pub mod float;
 pub mod format;
 pub mod lang;
 pub mod macros;
@@ -7,6 +8,7 @@ pub mod pretty;
 pub mod rebind;
 pub mod types;
 
+use crate::ast::float::FloatLit;
 use crate::ast::lang::LangItemKind;
 use crate::common::{
     impl_allocatable, Allocatable, ArenaAllocatable, CodeErrorKind, FileId, HashMap, HashSet,
@@ -15,6 +17,7 @@ use crate::common::{
 use crate::intrinsics::IntrinsicKind;
 use crate::name_resolution::path::{Path, PathSegment};
 use crate::name_resolution::scope::BoundItemType;
+use crate::utils::DebugItemGuard;
 
 use bumpalo::Bump;
 use once_cell::unsync::OnceCell;
@@ -38,6 +41,7 @@ pub struct AstCtx<'ast> {
     lang_items: RefCell<HashMap<LangItemKind, ItemP<'ast>>>,
     local_names: RefCell<HashMap<AstId, &'ast str>>,
     test_metadata: RefCell<HashMap<ItemP<'ast>, TestMetadata<'ast>>>,
+    item_paths: RefCell<HashMap<ItemP<'ast>, Path<'ast>>>,
 }
 
 impl<'ast> AstCtx<'ast> {
@@ -50,6 +54,7 @@ impl<'ast> AstCtx<'ast> {
             lang_items: RefCell::new(HashMap::default()),
             local_names: RefCell::new(HashMap::default()),
             test_metadata: RefCell::new(HashMap::default()),
+            item_paths: RefCell::new(HashMap::default()),
         }
     }
 
@@ -107,6 +112,16 @@ impl<'ast> AstCtx<'ast> {
         self.test_metadata.borrow().get(&item).cloned()
     }
 
+    /// Records the full path of a named type, for telling apart types with the same name
+    /// in diagnostics.
+    pub fn add_item_path(&self, item: ItemP<'ast>, path: Path<'ast>) {
+        self.item_paths.borrow_mut().insert(item, path);
+    }
+
+    pub fn item_path(&self, item: ItemP<'ast>) -> Option<Path<'ast>> {
+        self.item_paths.borrow().get(&item).cloned()
+    }
+
     pub fn intern_type(&'ast self, ty: Ty<'ast>) -> TyP<'ast> {
         if let Some(key) = self.types.borrow().get(&ty) {
             return key;
@@ -209,6 +224,7 @@ impl Debug for AstId {
 pub enum BuiltinType {
     Never,
     Bool,
+    Char,
     U8,
     U16,
     U32,
@@ -221,6 +237,8 @@ pub enum BuiltinType {
     I32,
     I64,
     I128,
+    F16,
+    BF16,
     F32,
     F64,
 }
@@ -285,7 +303,10 @@ impl BuiltinType {
     }
 
     pub fn is_float(&self) -> bool {
-        matches!(self, BuiltinType::F32 | BuiltinType::F64)
+        matches!(
+            self,
+            BuiltinType::F16 | BuiltinType::BF16 | BuiltinType::F32 | BuiltinType::F64
+        )
     }
 
     pub fn is_numeric(&self) -> bool {
@@ -373,7 +394,9 @@ impl<'ast> Item<'ast> {
         self.can_compile()
             && match self {
                 Item::Function(Function { attributes, .. }) => {
-                    attributes.contains(&Attribute::Test) || attributes.contains(&Attribute::Export)
+                    attributes.contains(&Attribute::Test)
+                        || attributes.contains(&Attribute::Export)
+                        || attributes.contains(&Attribute::Interrupt)
                 }
                 _ => false,
             }
@@ -472,12 +495,13 @@ impl Eq for ItemCell<'_> {}
 
 impl Debug for ItemCell<'_> {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        if fmt.alternate() {
-            writeln!(fmt, "{} {{", self.id)?;
-            writeln!(fmt, "\t{:?}", self.contents.get())?;
-            writeln!(fmt, "}}")?;
-        } else {
-            write!(fmt, "{}", self.id)?
+        match DebugItemGuard::enter("ast", self.id.id) {
+            Some(_guard) if fmt.alternate() => {
+                writeln!(fmt, "{} {{", self.id)?;
+                writeln!(fmt, "\t{:?}", self.contents.get())?;
+                writeln!(fmt, "}}")?;
+            }
+            _ => write!(fmt, "{}", self.id)?,
         }
 
         Ok(())
@@ -489,6 +513,7 @@ pub struct Field<'ast> {
     pub id: AstId,
     pub name: &'ast str,
     pub typ: TyP<'ast>,
+    pub attributes: &'ast [Attribute],
     pub span: Option<Span>,
 }
 
@@ -544,6 +569,17 @@ pub struct Placeholder<'ast> {
     pub bounds: ProtocolBounds<'ast>,
     pub span: Option<Span>,
     pub default: Option<TyP<'ast>>,
+    /// `Args...` - binds to a tuple of any number of types, the bounds apply to each element
+    pub variadic: bool,
+}
+
+/// A `where` clause predicate that does not constrain a generic parameter directly,
+/// e.g. `where &T: Hashable<&T, H>`.
+#[derive(Debug, Clone, Copy)]
+pub struct WherePredicate<'ast> {
+    pub typ: TyP<'ast>,
+    pub bounds: ProtocolBounds<'ast>,
+    pub span: Option<Span>,
 }
 
 #[derive(Debug)]
@@ -633,6 +669,9 @@ pub enum BuiltinMacroKind {
     Bind,
     Reduce,
     Stringify,
+    CompilerVersion,
+    BuildTimestamp,
+    CfgValues,
 }
 
 #[derive(Debug)]
@@ -646,6 +685,7 @@ pub struct Function<'ast> {
     pub name: Option<&'ast str>,
     pub attributes: &'ast [Attribute],
     pub placeholders: &'ast [Placeholder<'ast>],
+    pub where_predicates: &'ast [WherePredicate<'ast>],
     pub args: &'ast [Parameter<'ast>],
     pub return_type: TyP<'ast>,
     pub body: Option<ExprP<'ast>>,
@@ -733,8 +773,18 @@ pub enum Attribute {
     Inline,
     Align(usize),
     Packed,
+    /// `#[repr(<integer type>)]`, the underlying type of an enum
+    Repr(BuiltinType),
+    /// `#[repr(C)]`
+    ReprC,
+    /// `#[bitfield]`, a struct whose fields are packed into a single integer
+    Bitfield,
+    /// `#[bits(n)]`, the width of a field of a bitfield struct
+    Bits(usize),
     MustUse,
     Transparent,
+    Sealed,
+    ConstEval,
     NoInline,
     ThreadLocal,
     Builtin,
@@ -743,6 +793,31 @@ pub enum Attribute {
     Intrinsic,
     StaticConstructor,
     LinkName(usize, [u8; 255]),
+    Interrupt,
+    InterruptVector(usize),
+    NoRecursion,
+    /// `#[no_instrument]`, excluded from `-Zinstrument-functions`
+    NoInstrument,
+    /// `#[no_builtins]`, no implicit calls to `memcpy` and friends (see `-Zno-builtins`)
+    NoBuiltins,
+    /// `#[allocator]`, call sites are recorded with `-Zinstrument-allocs`
+    Allocator,
+    /// `#[optimize(...)]`
+    Optimize(OptimizationLevel),
+    /// `#[target_feature("...")]` (as a C string literal)
+    TargetFeature(usize, [u8; 255]),
+    /// `#[multiversion("avx2", "sse4.2", "default")]`, the versions separated by NUL bytes
+    Multiversion(usize, [u8; 255]),
+    /// `#[c_attribute("...")]`, written verbatim into the generated C (UTF-8)
+    CAttribute(usize, [u8; 255]),
+}
+
+/// Per-function optimization setting (`#[optimize(...)]`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OptimizationLevel {
+    None,
+    Size,
+    Speed,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -756,7 +831,8 @@ pub enum UnOp {
 pub enum Lit<'ast> {
     Str(&'ast [u8]),
     Int(bool, u128, Option<BuiltinType>),
-    Float(&'ast str, Option<BuiltinType>),
+    Float(FloatLit, Option<BuiltinType>),
+    Char(char, Option<BuiltinType>),
     Bool(bool),
     Null,
 }
@@ -828,7 +904,11 @@ pub enum ExprKind<'ast> {
     Range(Option<ExprP<'ast>>, Option<ExprP<'ast>>, bool),
     If(ExprP<'ast>, ExprP<'ast>, ExprP<'ast>),
     TypeCheck(ExprP<'ast>, TyP<'ast>),
+    /// `T is Protocol` where the left-hand side names a type rather than a value
+    TypePredicate(TyP<'ast>, TyP<'ast>),
     StaticIf(ExprP<'ast>, ExprP<'ast>, ExprP<'ast>),
+    /// `for const (i, elem) in tuple { ... }`, unrolled during monomorphization
+    StaticFor(ExprP<'ast>, Option<AstId>, AstId, ExprP<'ast>),
     Cast(ExprP<'ast>, TyP<'ast>),
 
     Void,
@@ -861,6 +941,16 @@ impl Span {
     pub fn len(&self) -> usize {
         self.end - self.start
     }
+
+    /// Span of the last `len` bytes (e.g. the name in `foo::bar` or `foo.bar`).
+    ///
+    /// Line and column still refer to the start of the original span.
+    pub fn tail(&self, len: usize) -> Self {
+        Self {
+            start: self.end.saturating_sub(len).max(self.start),
+            ..*self
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
@@ -38,6 +38,7 @@ impl<'ast> PrettyPrinter<'ast> {
         match kind {
             BuiltinType::Never => "!".to_string(),
             BuiltinType::Bool => "bool".to_string(),
+            BuiltinType::Char => "char".to_string(),
             BuiltinType::U8 => "u8".to_string(),
             BuiltinType::U16 => "u16".to_string(),
             BuiltinType::U32 => "u32".to_string(),
@@ -50,6 +51,8 @@ impl<'ast> PrettyPrinter<'ast> {
             BuiltinType::I32 => "i32".to_string(),
             BuiltinType::I64 => "i64".to_string(),
             BuiltinType::I128 => "i128".to_string(),
+            BuiltinType::F16 => "f16".to_string(),
+            BuiltinType::BF16 => "bf16".to_string(),
             BuiltinType::F32 => "f32".to_string(),
             BuiltinType::F64 => "f64".to_string(),
         }
@@ -209,6 +212,35 @@ impl<'ast> PrettyPrinter<'ast> {
         }
     }
 
+    /// Signature of a function without the body, e.g. `fn get<T>(self: &Foo<T>) -> T`
+    pub fn print_signature(&mut self, func: &Function<'ast>) -> String {
+        let mut s = format!("fn {}", func.name.unwrap_or("_"));
+        if !func.placeholders.is_empty() {
+            let placeholders: Vec<_> = func
+                .placeholders
+                .iter()
+                .map(|p| self.id_to_name(p.id))
+                .collect();
+            write!(s, "<{}>", placeholders.join(", ")).unwrap();
+        }
+
+        let mut args: Vec<_> = func
+            .args
+            .iter()
+            .map(|arg| format!("{}: {}", self.id_to_name(arg.id), self.print_typ(arg.typ)))
+            .collect();
+        if func.varargs {
+            args.push("...".to_string());
+        }
+        write!(s, "({})", args.join(", ")).unwrap();
+
+        if !matches!(func.return_type, Ty::Tuple(&[])) {
+            write!(s, " -> {}", self.print_typ(func.return_type)).unwrap();
+        }
+
+        s
+    }
+
     pub fn print_expr(&mut self, expr: ExprP<'ast>) -> String {
         self.print_expr_full(expr, false, false)
     }
@@ -526,16 +558,35 @@ impl<'ast> PrettyPrinter<'ast> {
                 }
                 Lit::Float(val, kind) => {
                     let mut s = String::new();
-                    s.push_str(val.as_ref());
+                    if *kind == Some(BuiltinType::F32) {
+                        s.push_str(&format!("{:?}", val.to_f32()));
+                    } else {
+                        s.push_str(&format!("{:?}", val.to_f64()));
+                    }
                     if let Some(kind) = kind {
                         s.push_str(&self.print_builtin_type(*kind));
                     }
                     s
                 }
+                Lit::Char(ch, _) => format!("{:?}", ch),
                 Lit::Bool(v) => format!("{}", v),
                 Lit::Null => "null".to_string(),
             },
             ExprKind::Loop(body) => format!("loop {}", self.print_expr_full(body, true, false)),
+            ExprKind::StaticFor(value, index, elem, body) => {
+                let binding = match index {
+                    Some(index) => {
+                        format!("({}, {})", self.id_to_name(index), self.id_to_name(elem))
+                    }
+                    None => self.id_to_name(elem),
+                };
+                format!(
+                    "for const {} in {} {}",
+                    binding,
+                    self.print_expr(value),
+                    self.print_expr_full(body, true, false)
+                )
+            }
             ExprKind::Break(val) => {
                 if let Some(val) = val {
                     add_parens = true;
@@ -634,6 +685,10 @@ impl<'ast> PrettyPrinter<'ast> {
                     self.print_typ(typ)
                 )
             }
+            ExprKind::TypePredicate(typ, bound) => {
+                add_parens = true;
+                format!("{} is {}", self.print_typ(typ), self.print_typ(bound))
+            }
             ExprKind::If(cond, then, els) | ExprKind::StaticIf(cond, then, els) => {
                 add_parens = true;
 
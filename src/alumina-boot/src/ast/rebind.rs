@@ -1,6 +1,6 @@
 use crate::ast::{
     AstCtx, AstId, Bound, Expr, ExprP, FieldInitializer, FnKind, Placeholder, ProtocolBounds,
-    Statement, TyP,
+    Statement, TyP, WherePredicate,
 };
 use crate::common::{AluminaError, ArenaAllocatable, HashMap};
 
@@ -23,6 +23,18 @@ impl<'ast> Rebinder<'ast> {
             default: placeholder.default.map(|d| self.visit_typ(d)).transpose()?,
             span: placeholder.span,
             id: placeholder.id,
+            variadic: placeholder.variadic,
+        })
+    }
+
+    pub fn visit_where_predicate(
+        &mut self,
+        predicate: &WherePredicate<'ast>,
+    ) -> Result<WherePredicate<'ast>, AluminaError> {
+        Ok(WherePredicate {
+            typ: self.visit_typ(predicate.typ)?,
+            bounds: self.visit_bounds(&predicate.bounds)?,
+            span: predicate.span,
         })
     }
 
@@ -262,7 +274,11 @@ impl<'ast> Rebinder<'ast> {
                 self.visit_expr(then)?,
                 self.visit_expr(els)?,
             ),
+            StaticFor(value, index, elem, body) => {
+                StaticFor(self.visit_expr(value)?, index, elem, self.visit_expr(body)?)
+            }
             TypeCheck(lhs, rhs) => TypeCheck(self.visit_expr(lhs)?, self.visit_typ(rhs)?),
+            TypePredicate(lhs, rhs) => TypePredicate(self.visit_typ(lhs)?, self.visit_typ(rhs)?),
             Local(_) | BoundParam(_, _, _) | Continue | EnumValue(_, _) | Lit(_) | Void => {
                 expr.kind.clone()
             }
@@ -3,10 +3,12 @@ use crate::ast::{
     AstCtx, Bound, BuiltinType, Defered, ProtocolBounds, ProtocolBoundsKind, Span, Ty, TyP,
 };
 use crate::common::{AluminaError, ArenaAllocatable, CodeErrorKind, WithSpanDuringParsing};
+use crate::completion;
 use crate::global_ctx::GlobalCtx;
 use crate::name_resolution::resolver::{ItemResolution, NameResolver};
 use crate::name_resolution::scope::{NamedItemKind, Scope};
 use crate::parser::{AluminaVisitor, FieldKind, NodeExt, ParseCtx};
+use crate::rename;
 use crate::visitors::ScopedPathVisitor;
 
 use super::MacroCtx;
@@ -66,26 +68,49 @@ impl<'ast, 'src> TypeVisitor<'ast, 'src> {
         Ok(ProtocolBounds { bounds, kind })
     }
 
-    fn visit_typeref(&mut self, node: tree_sitter::Node<'src>) -> Result<TyP<'ast>, AluminaError> {
+    pub fn visit_typeref(
+        &mut self,
+        node: tree_sitter::Node<'src>,
+    ) -> Result<TyP<'ast>, AluminaError> {
         let mut visitor = ScopedPathVisitor::new(self.ast, self.scope.clone(), self.macro_ctx);
         let path = visitor.visit(node)?;
+        let name_len = path.segments.last().map(|s| s.0.len()).unwrap_or_default();
+        let span = Span::from_node(self.scope.file_id(), node);
+        completion::complete_path(&self.global_ctx, &self.scope, &path, span);
+        rename::check_path(self.ast, &self.global_ctx, &self.scope, &path, span);
         let mut resolver = NameResolver::new();
 
-        let res = match resolver
+        let resolved = match resolver
             .resolve_item(self.scope.clone(), path)
-            .with_span_from(&self.scope, node)?
+            .with_span_from(&self.scope, node)
         {
-            ItemResolution::Item(item) => match item.kind {
-                NamedItemKind::Type(ty, _, _) => self.ast.intern_type(Ty::Item(ty)),
-                NamedItemKind::TypeDef(ty, _, _) => self.ast.intern_type(Ty::Item(ty)),
-                NamedItemKind::Function(ty, _, _) => self.ast.intern_type(Ty::Item(ty)),
-                NamedItemKind::Protocol(ty, _, _) => self.ast.intern_type(Ty::Item(ty)),
-                NamedItemKind::Placeholder(ty, _) => self.ast.intern_type(Ty::Placeholder(ty)),
-                kind => {
-                    return Err(CodeErrorKind::Unexpected(format!("{}", kind)))
-                        .with_span_from(&self.scope, node)
+            Ok(resolved) => resolved,
+            Err(e) if self.global_ctx.error_tolerant() => {
+                // See `resolve_name`, unresolved types become `()`.
+                self.global_ctx.diag().add_from_error(e)?;
+                return Ok(self.ast.intern_type(Ty::Tuple(&[])));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let res = match resolved {
+            ItemResolution::Item(item) => {
+                let usage = span.tail(name_len);
+                self.global_ctx
+                    .record_name(item.name_ref(&self.scope, Some(usage)));
+
+                match item.kind {
+                    NamedItemKind::Type(ty, _, _) => self.ast.intern_type(Ty::Item(ty)),
+                    NamedItemKind::TypeDef(ty, _, _) => self.ast.intern_type(Ty::Item(ty)),
+                    NamedItemKind::Function(ty, _, _) => self.ast.intern_type(Ty::Item(ty)),
+                    NamedItemKind::Protocol(ty, _, _) => self.ast.intern_type(Ty::Item(ty)),
+                    NamedItemKind::Placeholder(ty, _) => self.ast.intern_type(Ty::Placeholder(ty)),
+                    kind => {
+                        return Err(CodeErrorKind::Unexpected(format!("{}", kind)))
+                            .with_span_from(&self.scope, node)
+                    }
                 }
-            },
+            }
             ItemResolution::Defered(typ, name) => self.ast.intern_type(Ty::Defered(Defered {
                 typ: self.ast.intern_type(typ),
                 name: name.0,
@@ -137,6 +162,8 @@ impl<'ast, 'src> AluminaVisitor<'src> for TypeVisitor<'ast, 'src> {
             "i32" => Ty::Builtin(BuiltinType::I32),
             "i64" => Ty::Builtin(BuiltinType::I64),
             "i128" => Ty::Builtin(BuiltinType::I128),
+            "f16" => Ty::Builtin(BuiltinType::F16),
+            "bf16" => Ty::Builtin(BuiltinType::BF16),
             "f32" => Ty::Builtin(BuiltinType::F32),
             "f64" => Ty::Builtin(BuiltinType::F64),
             _ => unreachable!(),
@@ -0,0 +1,199 @@
+//! Build plans (`--emit build-plan`)
+//!
+//! Describes in JSON everything the driver would do with the same command line (without
+//! `--emit build-plan`), but does none of it, so that external build systems can wrap the
+//! compiler hermetically:
+//!
+//! ```text
+//! {
+//!   "inputs": [{"module": "::std::mem", "filename": "sysroot/std/mem.alu", "sysroot": true, "extern": false}, ...],
+//!   "cfg": {"debug": null, "target_os": "linux", ...},
+//!   "options": ["deny-warnings"],
+//!   "env": {"CC": null, "CFLAGS": "-march=native", "LDFLAGS": null, "SOURCE_DATE_EPOCH": null},
+//!   "steps": [
+//!     {"kind": "compile", "inputs": [...], "outputs": ["/tmp/alumina-run-XXXXXXXXXXXXXXXX/lib.c"]},
+//!     {"kind": "c-compile", "program": "cc", "args": [...], "inputs": [...], "outputs": ["libfoo.so"]}
+//!   ]
+//! }
+//! ```
+//!
+//! The `compile` step produces the C code (no outputs means that it is written to stdout),
+//! `c-compile` steps invoke the C compiler, `version-script` writes the linker version
+//! script and `run` runs the built program. The scratch directory of `--run` and `--shared`
+//! gets a random name when it is created, so its path only has placeholders here. Native libraries requested with `#[link]` are
+//! only known after the program is compiled, so they are not a part of the C compiler
+//! command lines.
+
+use crate::compiler::SourceFile;
+use crate::global_ctx::GlobalCtx;
+use crate::runner;
+use crate::utils::json_string;
+
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variables that change the outcome of the build
+const ENV_VARS: [&str; 4] = ["CC", "CFLAGS", "LDFLAGS", "SOURCE_DATE_EPOCH"];
+
+/// What the driver was asked to produce
+#[derive(Clone, Copy)]
+pub enum Output<'a> {
+    /// C code, written to a file or to stdout
+    C(Option<&'a Path>),
+    /// A shared library built with the system C compiler
+    SharedLibrary(&'a Path),
+    /// An executable that is built in a scratch directory and run with the given arguments
+    Run(&'a [String]),
+}
+
+fn json_path(path: &Path) -> String {
+    json_string(&path.to_string_lossy())
+}
+
+fn json_array<T>(values: impl IntoIterator<Item = T>, f: impl Fn(T) -> String) -> String {
+    let values: Vec<_> = values.into_iter().map(f).collect();
+    format!("[{}]", values.join(", "))
+}
+
+fn json_optional(value: Option<&str>) -> String {
+    value.map(json_string).unwrap_or_else(|| "null".to_string())
+}
+
+fn c_compile_step(cc: &OsStr, command: &Command, source: &Path, output: &Path) -> String {
+    format!(
+        "{{\"kind\": \"c-compile\", \"program\": {}, \"args\": {}, \"inputs\": [{}], \
+         \"outputs\": [{}]}}",
+        json_string(&cc.to_string_lossy()),
+        json_array(command.get_args(), |arg| json_string(
+            &arg.to_string_lossy()
+        )),
+        json_path(source),
+        json_path(output)
+    )
+}
+
+/// Formats the build plan for the given source files and output
+pub fn format(
+    global_ctx: &GlobalCtx,
+    options: &[String],
+    files: &[SourceFile],
+    externs: &[(String, PathBuf)],
+    output: Output<'_>,
+    version_script: Option<&Path>,
+) -> String {
+    let source = match output {
+        Output::C(output) => output.map(Path::to_path_buf),
+        Output::SharedLibrary(_) => {
+            Some(runner::temp_dir_path().join(runner::SHARED_LIBRARY_SOURCE))
+        }
+        Output::Run(_) => Some(runner::temp_dir_path().join(runner::EXECUTABLE_SOURCE)),
+    };
+
+    // Modules of `--extern` libraries are not files of their own
+    let inputs: Vec<_> = files
+        .iter()
+        .filter(|f| f.contents.is_none())
+        .map(|f| json_path(&f.filename))
+        .chain(externs.iter().map(|(_, filename)| json_path(filename)))
+        .collect();
+
+    let mut steps = vec![format!(
+        "{{\"kind\": \"compile\", \"inputs\": [{}], \"outputs\": {}}}",
+        inputs.join(", "),
+        json_array(source.as_deref(), json_path)
+    )];
+
+    if let Some(version_script) = version_script {
+        steps.push(format!(
+            "{{\"kind\": \"version-script\", \"outputs\": [{}]}}",
+            json_path(version_script)
+        ));
+    }
+
+    match (output, source) {
+        (Output::SharedLibrary(output), Some(source)) => {
+            let (cc, command) =
+                runner::shared_library_command(global_ctx, &source, output, version_script);
+            steps.push(c_compile_step(&cc, &command, &source, output));
+        }
+        (Output::Run(args), Some(source)) => {
+            let executable = runner::temp_dir_path().join(runner::EXECUTABLE);
+            let (cc, command) = runner::executable_command(global_ctx, &source, &executable);
+            steps.push(c_compile_step(&cc, &command, &source, &executable));
+            steps.push(format!(
+                "{{\"kind\": \"run\", \"program\": {}, \"args\": {}}}",
+                json_path(&executable),
+                json_array(args, |arg| json_string(arg))
+            ));
+        }
+        _ => {}
+    }
+
+    let mut options = options.to_vec();
+    options.sort();
+    options.dedup();
+
+    let mut result = String::new();
+    result.push_str("{\n");
+    let _ = writeln!(
+        result,
+        "  \"inputs\": {},",
+        json_array(
+            files
+                .iter()
+                .filter(|f| f.contents.is_none())
+                .map(|f| (f.path.as_str(), f.filename.as_path(), f.sysroot, false))
+                .chain(externs.iter().map(|(name, filename)| (
+                    name.as_str(),
+                    filename.as_path(),
+                    false,
+                    true
+                ))),
+            |(module, filename, sysroot, is_extern)| format!(
+                "{{\"module\": {}, \"filename\": {}, \"sysroot\": {}, \"extern\": {}}}",
+                json_string(module),
+                json_path(filename),
+                sysroot,
+                is_extern
+            )
+        )
+    );
+    let _ = writeln!(
+        result,
+        "  \"cfg\": {{{}}},",
+        global_ctx
+            .cfg_values()
+            .iter()
+            .map(|(key, value)| format!(
+                "{}: {}",
+                json_string(key),
+                json_optional(value.as_deref())
+            ))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let _ = writeln!(
+        result,
+        "  \"options\": {},",
+        json_array(options, |o| json_string(&o))
+    );
+    let _ = writeln!(
+        result,
+        "  \"env\": {{{}}},",
+        ENV_VARS
+            .iter()
+            .map(|var| format!(
+                "{}: {}",
+                json_string(var),
+                json_optional(std::env::var(var).ok().as_deref())
+            ))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let _ = writeln!(result, "  \"steps\": [\n    {}\n  ]", steps.join(",\n    "));
+    result.push_str("}\n");
+
+    result
+}
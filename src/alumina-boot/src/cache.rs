@@ -0,0 +1,419 @@
+//! Compilation cache (`--cache-dir`)
+//!
+//! The generated C code is stored under a key that covers everything it depends on: the
+//! compiler (its version and a hash of the executable, so that a rebuilt compiler does not
+//! reuse the entries of the previous one), the `-Z` options, the cfg values (which include the `target_*` values
+//! from `--target`), the other code generation settings and the path and contents of every
+//! module, including the sysroot and `--extern` libraries. This is the first level.
+//!
+//! Files that the program reads while it is being compiled (`include_bytes!`) and the
+//! environment variables it reads (`env!`) are only known after the compilation, so they are
+//! recorded in the entry with a hash of their contents or value and checked again on every
+//! hit. This is the second level.
+//!
+//! An entry is the generated code (`<key>.c`) and a manifest (`<key>.manifest`), which is
+//! written last, so that an interrupted write is never used:
+//!
+//! ```text
+//! alumina-cache 2
+//! slot main.c
+//! part 9f1c2b3a4d5e6f70 compiler
+//! part 0c1d2e3f40516273 module ::std::mem
+//! dep 1a2b3c4d5e6f7081 assets/logo.png
+//! env 5e6f708192a3b4c5 GIT_REVISION
+//! ```
+//!
+//! Each part of the key is hashed separately, so that a miss can be explained by comparing
+//! the key with the most recent entry for the same output (the slot). Hits refresh the
+//! modification time of the manifest, and when the cache grows over its size limit, the
+//! least recently used entries are removed.
+//!
+//! Compilations with warnings are not stored, so the warnings are shown every time.
+
+use crate::common::CodeError;
+use crate::compiler::SourceFile;
+use crate::diagnostics::DiagnosticContext;
+use crate::global_ctx::GlobalCtx;
+use crate::utils::stable_hash;
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const MAGIC: &str = "alumina-cache 2";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Environment variables that change the generated code
+const ENV_VARS: [&str; 1] = ["SOURCE_DATE_EPOCH"];
+
+pub struct CacheKey {
+    slot: String,
+    parts: Vec<(String, u64)>,
+}
+
+impl CacheKey {
+    /// Key for compiling the given files into `slot` (the name of the output). Returns
+    /// `None` if a file cannot be read, the compilation reports the error in that case.
+    pub fn new(
+        global_ctx: &GlobalCtx,
+        options: &[String],
+        files: &[SourceFile],
+        slot: &str,
+    ) -> Option<Self> {
+        let mut result = Self {
+            slot: slot.to_string(),
+            parts: Vec::new(),
+        };
+
+        let mut options = options.to_vec();
+        options.sort();
+        options.dedup();
+
+        let mut compiler = VERSION.as_bytes().to_vec();
+        if let Some(exe) = std::env::current_exe().ok().and_then(|p| fs::read(p).ok()) {
+            compiler.push(0);
+            compiler.extend(exe);
+        }
+        result.add("compiler", compiler);
+        result.add("options", options.join("\n"));
+
+        let mut cfg = String::new();
+        for (key, value) in global_ctx.cfg_values() {
+            let _ = writeln!(cfg, "{}={:?}", key, value);
+        }
+        result.add("cfg", cfg);
+
+        for var in ENV_VARS {
+            result.add(var, std::env::var(var).unwrap_or_default());
+        }
+
+        for file in files {
+            let mut value = file.filename.to_string_lossy().into_owned().into_bytes();
+            value.push(0);
+            match &file.contents {
+                Some(contents) => value.extend_from_slice(contents.as_bytes()),
+                None => value.extend(fs::read(&file.filename).ok()?),
+            }
+            result.add(format!("module {}", file.path), value);
+        }
+
+        Some(result)
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, value: impl AsRef<[u8]>) {
+        self.parts.push((name.into(), stable_hash(value.as_ref())));
+    }
+
+    fn id(&self) -> String {
+        let mut key = String::new();
+        for (name, hash) in &self.parts {
+            let _ = writeln!(key, "{:016x} {}", hash, name);
+        }
+        format!("{:016x}", stable_hash(key.as_bytes()))
+    }
+}
+
+/// Hash of the value of an environment variable, which tells an unset variable apart from
+/// an empty one
+fn env_hash(value: Option<&str>) -> u64 {
+    match value {
+        Some(value) => stable_hash(format!("={}", value).as_bytes()),
+        None => stable_hash(b""),
+    }
+}
+
+struct Manifest {
+    slot: String,
+    parts: Vec<(String, u64)>,
+    dependencies: Vec<(PathBuf, u64)>,
+    env_vars: Vec<(String, u64)>,
+}
+
+impl Manifest {
+    fn parse(contents: &str) -> Option<Self> {
+        let mut lines = contents.lines();
+        if lines.next() != Some(MAGIC) {
+            return None;
+        }
+
+        let mut result = Self {
+            slot: lines.next()?.strip_prefix("slot ")?.to_string(),
+            parts: Vec::new(),
+            dependencies: Vec::new(),
+            env_vars: Vec::new(),
+        };
+
+        for line in lines {
+            let mut fields = line.splitn(3, ' ');
+            let (Some(kind), Some(hash), Some(name)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return None;
+            };
+            let hash = u64::from_str_radix(hash, 16).ok()?;
+            match kind {
+                "part" => result.parts.push((name.to_string(), hash)),
+                "dep" => result.dependencies.push((name.into(), hash)),
+                "env" => result.env_vars.push((name.to_string(), hash)),
+                _ => return None,
+            }
+        }
+
+        Some(result)
+    }
+
+    fn format(&self) -> String {
+        let mut result = String::new();
+        let _ = writeln!(result, "{}", MAGIC);
+        let _ = writeln!(result, "slot {}", self.slot);
+        for (name, hash) in &self.parts {
+            let _ = writeln!(result, "part {:016x} {}", hash, name);
+        }
+        for (path, hash) in &self.dependencies {
+            let _ = writeln!(result, "dep {:016x} {}", hash, path.display());
+        }
+        for (name, hash) in &self.env_vars {
+            let _ = writeln!(result, "env {:016x} {}", hash, name);
+        }
+        result
+    }
+}
+
+pub struct Cache {
+    dir: PathBuf,
+    max_size: u64,
+    verbose: bool,
+}
+
+impl Cache {
+    /// Cache in `dir` that is kept under `max_size` bytes. With `verbose`, the hits and
+    /// misses are explained in notes.
+    pub fn new(dir: PathBuf, max_size: u64, verbose: bool) -> Self {
+        Self {
+            dir,
+            max_size,
+            verbose,
+        }
+    }
+
+    fn note(&self, diag: &DiagnosticContext, message: String) {
+        if self.verbose {
+            diag.add_note(CodeError::freeform(format!("cache: {}", message)));
+        }
+    }
+
+    fn path(&self, id: &str, extension: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", id, extension))
+    }
+
+    /// Manifests of all the entries with the time they were last used
+    fn entries(&self) -> Vec<(PathBuf, SystemTime)> {
+        let Ok(dir) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        dir.filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "manifest" {
+                return None;
+            }
+            let modified = path.metadata().ok()?.modified().ok()?;
+            Some((path, modified))
+        })
+        .collect()
+    }
+
+    /// The generated code for the key, if it is in the cache and the files and environment
+    /// variables it depends on have not changed
+    pub fn lookup(&self, diag: &DiagnosticContext, key: &CacheKey) -> Option<String> {
+        let id = key.id();
+        let manifest_path = self.path(&id, "manifest");
+        let Some(manifest) = fs::read_to_string(&manifest_path)
+            .ok()
+            .and_then(|contents| Manifest::parse(&contents))
+        else {
+            self.explain_miss(diag, key);
+            return None;
+        };
+
+        for (path, hash) in &manifest.dependencies {
+            if fs::read(path).map(|data| stable_hash(&data)).ok() != Some(*hash) {
+                self.note(
+                    diag,
+                    format!(
+                        "miss for `{}`: `{}` has changed since it was included",
+                        key.slot,
+                        path.display()
+                    ),
+                );
+                return None;
+            }
+        }
+
+        for (name, hash) in &manifest.env_vars {
+            if env_hash(std::env::var(name).ok().as_deref()) != *hash {
+                self.note(
+                    diag,
+                    format!(
+                        "miss for `{}`: environment variable `{}` has changed",
+                        key.slot, name
+                    ),
+                );
+                return None;
+            }
+        }
+
+        let Ok(output) = fs::read_to_string(self.path(&id, "c")) else {
+            self.note(
+                diag,
+                format!("miss for `{}`: entry {} is damaged", key.slot, id),
+            );
+            return None;
+        };
+
+        // The modification time of the manifest is when the entry was last used
+        let _ = fs::File::options()
+            .append(true)
+            .open(&manifest_path)
+            .and_then(|f| f.set_modified(SystemTime::now()));
+
+        self.note(diag, format!("hit for `{}` (entry {})", key.slot, id));
+        Some(output)
+    }
+
+    /// Compares the key with the most recent entry for the same slot
+    fn explain_miss(&self, diag: &DiagnosticContext, key: &CacheKey) {
+        if !self.verbose {
+            return;
+        }
+
+        let mut entries = self.entries();
+        entries.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+        let previous = entries.iter().find_map(|(path, _)| {
+            fs::read_to_string(path)
+                .ok()
+                .and_then(|contents| Manifest::parse(&contents))
+                .filter(|manifest| manifest.slot == key.slot)
+        });
+
+        let Some(previous) = previous else {
+            self.note(
+                diag,
+                format!("miss for `{}`: not compiled before", key.slot),
+            );
+            return;
+        };
+
+        let mut changes = Vec::new();
+        for (name, hash) in &key.parts {
+            match previous.parts.iter().find(|(n, _)| n == name) {
+                Some((_, h)) if h == hash => {}
+                Some(_) => changes.push(format!("`{}` changed", name)),
+                None => changes.push(format!("`{}` added", name)),
+            }
+        }
+        for (name, _) in &previous.parts {
+            if !key.parts.iter().any(|(n, _)| n == name) {
+                changes.push(format!("`{}` removed", name));
+            }
+        }
+
+        self.note(
+            diag,
+            format!("miss for `{}`: {}", key.slot, changes.join(", ")),
+        );
+    }
+
+    /// Stores the generated code for the key, along with the files and environment variables
+    /// it depends on. Nothing is stored if the compilation had warnings or errors.
+    pub fn store(
+        &self,
+        diag: &DiagnosticContext,
+        key: &CacheKey,
+        dependencies: &[PathBuf],
+        env_vars: &[(String, Option<String>)],
+        output: &str,
+    ) {
+        if diag.has_errors() || diag.has_warnings() {
+            self.note(
+                diag,
+                format!("`{}` is not stored, as there were diagnostics", key.slot),
+            );
+            return;
+        }
+
+        let mut manifest = Manifest {
+            slot: key.slot.clone(),
+            parts: key.parts.clone(),
+            dependencies: Vec::new(),
+            env_vars: env_vars
+                .iter()
+                .map(|(name, value)| (name.clone(), env_hash(value.as_deref())))
+                .collect(),
+        };
+        for path in dependencies {
+            let Ok(data) = fs::read(path) else {
+                self.note(
+                    diag,
+                    format!(
+                        "`{}` is not stored, as `{}` cannot be read",
+                        key.slot,
+                        path.display()
+                    ),
+                );
+                return;
+            };
+            manifest
+                .dependencies
+                .push((path.clone(), stable_hash(&data)));
+        }
+
+        let id = key.id();
+        let result = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(self.path(&id, "c"), output))
+            .and_then(|_| fs::write(self.path(&id, "manifest"), manifest.format()));
+
+        match result {
+            Ok(()) => {
+                self.note(diag, format!("stored `{}` (entry {})", key.slot, id));
+                self.collect_garbage(diag);
+            }
+            Err(e) => self.note(diag, format!("could not store `{}`: {}", key.slot, e)),
+        }
+    }
+
+    /// Removes the least recently used entries until the cache fits in its size limit
+    fn collect_garbage(&self, diag: &DiagnosticContext) {
+        let entry_size = |manifest: &Path| -> u64 {
+            [manifest.to_path_buf(), manifest.with_extension("c")]
+                .iter()
+                .filter_map(|p| p.metadata().ok())
+                .map(|m| m.len())
+                .sum()
+        };
+
+        let mut entries = self.entries();
+        let mut total: u64 = entries.iter().map(|(path, _)| entry_size(path)).sum();
+        if total <= self.max_size {
+            return;
+        }
+
+        entries.sort_by_key(|(_, modified)| *modified);
+        let mut removed = 0;
+        for (path, _) in entries {
+            if total <= self.max_size {
+                break;
+            }
+            total = total.saturating_sub(entry_size(&path));
+            let _ = fs::remove_file(&path);
+            let _ = fs::remove_file(path.with_extension("c"));
+            removed += 1;
+        }
+
+        self.note(
+            diag,
+            format!("removed {} least recently used entries", removed),
+        );
+    }
+}
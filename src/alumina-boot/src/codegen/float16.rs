@@ -0,0 +1,109 @@
+//! Software implementation of `f16` and `bf16` for C compilers and targets that do not
+//! have `_Float16` and `__bf16`.
+//!
+//! Values are stored as raw bits in a struct (so that they keep their size and alignment
+//! and cannot be accidentally mixed with integers) and all arithmetic is done in `float`,
+//! rounding the result back to half precision after each operation. Since `float` has
+//! more than twice the precision of both formats, this gives the same results as native
+//! half precision arithmetic.
+use crate::ast::{BinOp, BuiltinType};
+use crate::ir::Ty;
+
+/// Whether the type is a half precision float and if so, which one.
+pub fn float16_kind(ty: &Ty<'_>) -> Option<BuiltinType> {
+    match ty {
+        Ty::Builtin(kind @ (BuiltinType::F16 | BuiltinType::BF16)) => Some(*kind),
+        _ => None,
+    }
+}
+
+/// Name of the storage type, which is also the prefix of the helpers.
+pub fn float16_type(kind: BuiltinType) -> &'static str {
+    match kind {
+        BuiltinType::F16 => "_AL_f16",
+        BuiltinType::BF16 => "_AL_bf16",
+        _ => unreachable!(),
+    }
+}
+
+/// Helper for a compound assignment operator
+pub fn compound_helper(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Plus => "_AL_float_add",
+        BinOp::Minus => "_AL_float_sub",
+        BinOp::Mul => "_AL_float_mul",
+        BinOp::Div => "_AL_float_div",
+        _ => unreachable!(),
+    }
+}
+
+pub const FLOAT16_PRELUDE: &str = r#"
+typedef struct _AL_f16 { uint16_t bits; } _AL_f16;
+typedef struct _AL_bf16 { uint16_t bits; } _AL_bf16;
+typedef float (*_AL_float_op)(float, float);
+static inline float _AL_float_add(float a, float b) { return a + b; }
+static inline float _AL_float_sub(float a, float b) { return a - b; }
+static inline float _AL_float_mul(float a, float b) { return a * b; }
+static inline float _AL_float_div(float a, float b) { return a / b; }
+static inline _AL_f16 _AL_f16_make(uint16_t bits) { _AL_f16 r; r.bits = bits; return r; }
+static inline _AL_bf16 _AL_bf16_make(uint16_t bits) { _AL_bf16 r; r.bits = bits; return r; }
+static inline float _AL_f16_to_f32(_AL_f16 a) {
+  union { uint32_t u; float f; } r;
+  uint32_t sign = (uint32_t)(a.bits & 0x8000) << 16;
+  uint32_t exp = (a.bits >> 10) & 0x1f;
+  uint32_t mant = a.bits & 0x3ff;
+  if (exp == 0x1f) {
+    r.u = sign | 0x7f800000 | (mant << 13);
+  } else if (exp != 0) {
+    r.u = sign | ((exp + 112) << 23) | (mant << 13);
+  } else {
+    r.f = (float)mant * 5.9604644775390625e-8f;
+    r.u |= sign;
+  }
+  return r.f;
+}
+static inline _AL_f16 _AL_f16_from_f32(float a) {
+  union { float f; uint32_t u; } v;
+  uint32_t sign, exp, mant, half, full, shift, rem, halfway;
+  v.f = a;
+  sign = (v.u >> 16) & 0x8000;
+  exp = (v.u >> 23) & 0xff;
+  mant = v.u & 0x7fffff;
+  if (exp == 0xff) {
+    half = 0x7c00 | (mant ? 0x200 : 0);
+  } else if (exp > 142) {
+    half = 0x7c00;
+  } else if (exp > 112) {
+    half = ((exp - 112) << 10) | (mant >> 13);
+    rem = mant & 0x1fff;
+    if (rem > 0x1000 || (rem == 0x1000 && (half & 1))) half++;
+  } else if (exp > 101) {
+    full = mant | 0x800000;
+    shift = 126 - exp;
+    rem = full & ((1u << shift) - 1);
+    halfway = 1u << (shift - 1);
+    half = full >> shift;
+    if (rem > halfway || (rem == halfway && (half & 1))) half++;
+  } else {
+    half = 0;
+  }
+  return _AL_f16_make((uint16_t)(sign | half));
+}
+static inline float _AL_bf16_to_f32(_AL_bf16 a) {
+  union { uint32_t u; float f; } r;
+  r.u = (uint32_t)a.bits << 16;
+  return r.f;
+}
+static inline _AL_bf16 _AL_bf16_from_f32(float a) {
+  union { float f; uint32_t u; } v;
+  v.f = a;
+  if ((v.u & 0x7fffffff) > 0x7f800000) return _AL_bf16_make((uint16_t)((v.u >> 16) | 0x40));
+  return _AL_bf16_make((uint16_t)((v.u + 0x7fff + ((v.u >> 16) & 1)) >> 16));
+}
+static inline _AL_f16 _AL_f16_compound(_AL_f16 *a, float b, _AL_float_op op) {
+  return *a = _AL_f16_from_f32(op(_AL_f16_to_f32(*a), b));
+}
+static inline _AL_bf16 _AL_bf16_compound(_AL_bf16 *a, float b, _AL_float_op op) {
+  return *a = _AL_bf16_from_f32(op(_AL_bf16_to_f32(*a), b));
+}
+"#;
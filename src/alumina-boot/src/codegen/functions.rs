@@ -1,37 +1,157 @@
-use crate::ast::{Attribute, BinOp, BuiltinType, Span, UnOp};
-use crate::codegen::types::TypeWriter;
-use crate::codegen::{w, CName, CodegenCtx};
-use crate::common::{AluminaError, CodeErrorBuilder};
-use crate::intrinsics::IntrinsicValueKind;
+use crate::ast::{float, Attribute, BinOp, BuiltinType, OptimizationLevel, Span, UnOp};
+use crate::codegen::float16::{compound_helper, float16_kind, float16_type};
+use crate::codegen::int128::{
+    binop_helper, builtin_type, int128_signedness, overflow_builtin_helper, unop_helper,
+};
+use crate::codegen::types::{struct_attributes, write_c_attributes, TypeWriter};
+use crate::codegen::{is_cxx_keyword, w, CName, CodegenCtx};
+use crate::common::{AluminaError, CodeErrorBuilder, CodeErrorKind, HashMap};
+use crate::global_ctx::OutputType;
+use crate::intrinsics::{expand_c_inline, IntrinsicValueKind};
 use crate::ir::const_eval::Value;
-use crate::ir::layout::Layouter;
+use crate::ir::layout::{Layout, Layouter};
 use crate::ir::{
-    Const, Expr, ExprKind, ExprP, Function, IrId, LocalDef, Statement, Static, Ty, ValueType,
+    Closure, Const, Expr, ExprKind, ExprP, Function, IRItem, IrId, LocalDef, Statement, Static,
+    StrId, StructInit, Ty, TyP, ValueType,
 };
 
-use std::borrow::Cow;
 use std::fmt::Write;
 
 pub struct FunctionWriter<'ir, 'gen> {
     ctx: &'gen CodegenCtx<'ir, 'gen>,
     type_writer: &'gen TypeWriter<'ir, 'gen>,
+    strings: HashMap<StrId, CName<'gen>>,
+    string_decls: String,
     fn_decls: String,
     fn_bodies: String,
     indent: usize,
     debug_info: bool,
     in_const_init: bool,
+    /// See `CodegenCtx::no_builtins`
+    no_builtins: bool,
     last_span: Option<Span>,
+    interrupt_vectors: Vec<(usize, IrId)>,
 }
 
-/// Prevent "1f32" from being interpreted as an int constant
-fn force_float(v: &str) -> Cow<'_, str> {
-    if v.chars().all(|ch| ch.is_ascii_digit() || ch == '-') {
-        Cow::Owned(format!("{}e0", v))
-    } else {
-        Cow::Borrowed(v)
+/// The symbol name from `#[link_name(...)]` or `#[export(name = ...)]` (as a C string literal)
+pub fn link_name<'ir>(item: &'ir Function<'ir>) -> Option<&'ir str> {
+    item.attributes
+        .iter()
+        .filter_map(|a| match a {
+            Attribute::LinkName(size, name) => {
+                Some(std::str::from_utf8(&name.as_slice()[..*size]).unwrap())
+            }
+            _ => None,
+        })
+        .next()
+}
+
+/// Writes the bytes as a C string literal
+fn write_c_string(buf: &mut String, bytes: &[u8]) {
+    w!(buf, "\"");
+
+    let mut did_we_just_write_a_hex_escape = false;
+
+    for c in bytes.iter().copied() {
+        match c {
+            b'\\' | b'\'' | b'"' | b' '..=b'~' => {
+                match c {
+                    b'\\' => w!(buf, "\\\\"),
+                    b'\'' => w!(buf, "\\'"),
+                    b'"' => w!(buf, "\\\""),
+                    _ => {
+                        // C's escape sequences are very dumb. There is no limit on the
+                        // length of a hexadecimal escape sequence. It would be easier to
+                        // just hex-escape everything, but that makes the generated C
+                        // less readable.
+                        if did_we_just_write_a_hex_escape
+                            && matches!(c, b'a'..=b'f' | b'A'..=b'F' | b'0'..=b'9')
+                        {
+                            w!(buf, "\"\"");
+                        }
+                        w!(buf, "{}", c as char);
+                    }
+                }
+                did_we_just_write_a_hex_escape = false;
+            }
+            _ => {
+                w!(buf, "\\x{:02x}", c);
+                did_we_just_write_a_hex_escape = true;
+            }
+        }
     }
+    w!(buf, "\"");
 }
 
+/// C++ only allows designated initializers in the order of declaration
+fn declaration_order<'a, 'ir>(
+    ty: TyP<'ir>,
+    inits: &'a [StructInit<'ir>],
+) -> Vec<&'a StructInit<'ir>> {
+    let mut inits: Vec<_> = inits.iter().collect();
+    if let Ty::Item(item) = ty {
+        if let Ok(IRItem::StructLike(s) | IRItem::Closure(Closure { data: s, .. })) = item.get() {
+            inits.sort_by_key(|init| s.fields.iter().position(|f| f.id == init.field));
+        }
+    }
+
+    inits
+}
+
+/// The features from `#[target_feature(...)]` (as a C string literal)
+fn target_feature<'ir>(item: &'ir Function<'ir>) -> Option<&'ir str> {
+    item.attributes.iter().find_map(|a| match a {
+        Attribute::TargetFeature(size, features) => {
+            Some(std::str::from_utf8(&features.as_slice()[..*size]).unwrap())
+        }
+        _ => None,
+    })
+}
+
+/// The versions from `#[multiversion(...)]`. Outside of x86, where the CPU features cannot
+/// be detected, only the `default` version is compiled (as the function itself).
+fn multiversions<'ir>(
+    ctx: &CodegenCtx<'ir, '_>,
+    item: &'ir Function<'ir>,
+) -> Option<Vec<&'ir str>> {
+    let versions = item.attributes.iter().find_map(|a| match a {
+        Attribute::Multiversion(size, versions) => {
+            Some(std::str::from_utf8(&versions.as_slice()[..*size]).unwrap())
+        }
+        _ => None,
+    })?;
+
+    let arch = ctx.global_ctx.cfg("target_arch").flatten();
+    if !matches!(arch.as_deref(), Some("x86" | "x86_64")) {
+        return None;
+    }
+
+    Some(versions.split('\0').collect())
+}
+
+/// Name of a version of a multiversioned function (`sse4.2` -> `name__sse4_2`)
+fn version_name(name: CName<'_>, version: &str) -> String {
+    let suffix: String = version
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{}__{}", name, suffix)
+}
+
+/// The condition under which a version of a multiversioned function can be used
+fn version_condition(version: &str) -> String {
+    version
+        .split(',')
+        .map(|feature| match feature.strip_prefix("arch=") {
+            Some(arch) => format!("__builtin_cpu_is(\"{}\")", arch),
+            None => format!("__builtin_cpu_supports(\"{}\")", feature),
+        })
+        .collect::<Vec<_>>()
+        .join(" && ")
+}
+
+/// Writes the signature of the function, or with `version`, of one of the versions of a
+/// multiversioned function (which are always static).
 pub fn write_function_signature<'ir, 'gen>(
     ctx: &'gen CodegenCtx<'ir, 'gen>,
     buf: &mut String,
@@ -39,8 +159,12 @@ pub fn write_function_signature<'ir, 'gen>(
     item: &'ir Function<'ir>,
     is_static: bool,
     is_body: bool,
+    version: Option<&str>,
 ) -> Result<(), AluminaError> {
-    let name = ctx.get_name(id);
+    let name = match version {
+        Some(version) => version_name(ctx.get_name(id), version),
+        None => ctx.get_name(id).to_string(),
+    };
     let mut is_inline = false;
 
     let mut attributes = if item.attributes.contains(&Attribute::AlwaysInline) {
@@ -61,8 +185,59 @@ pub fn write_function_signature<'ir, 'gen>(
         attributes = format!("__attribute__((cold)) {}", attributes);
     }
 
+    if item.attributes.contains(&Attribute::Interrupt) {
+        attributes = format!("__attribute__((interrupt, used)) {}", attributes);
+    }
+
+    // GCC and Clang spell these differently, see `OPTIMIZE_MACROS`
+    for attr in item.attributes {
+        if let Attribute::Optimize(level) = attr {
+            ctx.use_optimize_macros();
+            let level = match level {
+                OptimizationLevel::None => "NONE",
+                OptimizationLevel::Size => "SIZE",
+                OptimizationLevel::Speed => "SPEED",
+            };
+            attributes = format!("_AL_OPTIMIZE_{} {}", level, attributes);
+        }
+    }
+
+    if let Some(features) = target_feature(item) {
+        attributes = format!("__attribute__((target({}))) {}", features, attributes);
+    }
+
+    if let Some(features) = version.filter(|v| *v != "default") {
+        attributes = format!("__attribute__((target(\"{}\"))) {}", features, attributes);
+    }
+
+    attributes = format!("{}{}", write_c_attributes(buf, item.attributes), attributes);
+
+    // Clang only accepts `no_builtin` on definitions
+    if ctx.no_builtins(item) && is_body {
+        ctx.use_no_builtins_macros();
+        attributes = format!("_AL_NO_BUILTINS {}", attributes);
+    }
+
+    if ctx.lto_friendly() && item.body.get().is_some() {
+        attributes = format!("_AL_SECTION(\".text.{}\") {}", name, attributes);
+    }
+
     if item.return_type.is_never() {
-        attributes = format!("_Noreturn {}", attributes);
+        if ctx.cxx_compatible() {
+            attributes = format!("__attribute__((noreturn)) {}", attributes);
+        } else {
+            attributes = format!("_Noreturn {}", attributes);
+        }
+    }
+
+    // Shared libraries are built with hidden visibility by default, so the exported
+    // functions need to be marked explicitly.
+    if ctx.global_ctx.output_type() == OutputType::SharedLibrary
+        && version.is_none()
+        && item.body.get().is_some()
+        && (item.attributes.contains(&Attribute::Export) || link_name(item).is_some())
+    {
+        attributes = format!("__attribute__((visibility(\"default\"))) {}", attributes);
     }
 
     let return_type = if item.return_type.is_zero_sized() {
@@ -72,7 +247,7 @@ pub fn write_function_signature<'ir, 'gen>(
         ctx.get_type(item.return_type)
     };
 
-    if is_static || is_inline {
+    if is_static || is_inline || version.is_some() {
         w!(buf, "\n{}static {} {}(", attributes, return_type, name);
     } else {
         w!(buf, "\n{}{} {}(", attributes, return_type, name);
@@ -99,18 +274,7 @@ pub fn write_function_signature<'ir, 'gen>(
 
     w!(buf, ")");
 
-    let link_name = item
-        .attributes
-        .iter()
-        .filter_map(|a| match a {
-            Attribute::LinkName(size, name) => {
-                Some(std::str::from_utf8(&name.as_slice()[..*size]).unwrap())
-            }
-            _ => None,
-        })
-        .next();
-
-    if let Some(link_name) = link_name {
+    if let Some(link_name) = link_name(item) {
         if !is_body {
             w!(buf, " asm({})", link_name);
         }
@@ -128,12 +292,16 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
         Self {
             ctx,
             type_writer,
+            strings: HashMap::default(),
+            string_decls: String::new(),
             fn_decls: String::with_capacity(size_estimate / 3 * 2),
             fn_bodies: String::with_capacity(size_estimate),
             indent: 0,
             debug_info: ctx.global_ctx.has_flag("debug"),
             in_const_init: false,
+            no_builtins: false,
             last_span: None,
+            interrupt_vectors: Vec::new(),
         }
     }
 
@@ -176,45 +344,27 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
         }
     }
 
-    fn write_string_literal(&mut self, bytes: &[u8]) {
-        w!(self.fn_bodies, "(const uint8_t*)\"");
-
-        let mut did_we_just_write_a_hex_escape = false;
-
-        for c in bytes.iter().copied() {
-            match c {
-                b'\\' | b'\'' | b'"' | b' '..=b'~' => {
-                    match c {
-                        b'\\' => w!(self.fn_bodies, "\\\\"),
-                        b'\'' => w!(self.fn_bodies, "\\'"),
-                        b'"' => w!(self.fn_bodies, "\\\""),
-                        _ => {
-                            // C's escape sequences are very dumb. There is no limit on the
-                            // length of a hexadecimal escape sequence. It would be easier to
-                            // just hex-escape everything, but that makes the generated C
-                            // less readable.
-                            if did_we_just_write_a_hex_escape
-                                && matches!(c, b'a'..=b'f' | b'A'..=b'F' | b'0'..=b'9')
-                            {
-                                w!(self.fn_bodies, "\"\"");
-                            }
-                            w!(self.fn_bodies, "{}", c as char);
-                        }
-                    }
-                    did_we_just_write_a_hex_escape = false;
-                }
-                _ => {
-                    w!(self.fn_bodies, "\\x{:02x}", c);
-                    did_we_just_write_a_hex_escape = true;
-                }
-            }
+    /// Name of the backing array of an interned string, which is declared on first use.
+    /// All the string slices into the same string (e.g. const-evaluated substrings) point
+    /// into the same array.
+    fn string_name(&mut self, id: StrId) -> CName<'gen> {
+        if let Some(name) = self.strings.get(&id) {
+            return *name;
         }
-        w!(self.fn_bodies, "\"");
+
+        let name = CName::Mangled("str", self.ctx.make_id());
+        w!(self.string_decls, "static const uint8_t {}[] = ", name);
+        write_c_string(&mut self.string_decls, self.ctx.ir().get_str(id));
+        w!(self.string_decls, ";\n");
+
+        self.strings.insert(id, name);
+        name
     }
 
     fn write_const_val(&mut self, val: Value) {
         match val {
             Value::Bool(val) => w!(self.fn_bodies, "{}", val as u8),
+            Value::Char(val) => w!(self.fn_bodies, "{}U", val as u32),
             Value::U8(val) => w!(self.fn_bodies, "{}", val),
             Value::U16(val) => w!(self.fn_bodies, "{}", val),
             Value::U32(val) => w!(self.fn_bodies, "{}ULL", val),
@@ -243,13 +393,54 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
             }
             Value::USize(val) => w!(self.fn_bodies, "{}ULL", val),
             Value::ISize(val) => w!(self.fn_bodies, "{}LL", val),
-            Value::F32(val) => w!(self.fn_bodies, "{}f", force_float(val)),
-            Value::F64(val) => w!(self.fn_bodies, "{}", force_float(val)),
+            Value::F16(_) | Value::BF16(_) | Value::F32(_) | Value::F64(_) => {
+                self.write_float_literal(val)
+            }
             Value::Uninitialized => w!(self.fn_bodies, "{{0}}"),
             _ => unimplemented!(),
         }
     }
 
+    /// Floats are written as hexadecimal literals, which the C compiler converts exactly.
+    /// `f16` and `bf16` are written as `double`, which represents them exactly.
+    fn write_float_literal(&mut self, val: Value) {
+        let (kind, bits) = val.float_bits().unwrap();
+        let value = float::from_bits(kind, bits);
+        let suffix = if kind == BuiltinType::F32 { "f" } else { "" };
+
+        if value.is_finite() {
+            w!(self.fn_bodies, "{}{}", float::format_hex(value), suffix);
+            return;
+        }
+
+        let (exp_bits, mant_bits) = float::float_format(kind);
+        let sign = if (bits >> (exp_bits + mant_bits)) & 1 == 1 {
+            "-"
+        } else {
+            ""
+        };
+        let mantissa = bits & ((1 << mant_bits) - 1);
+        if value.is_infinite() {
+            w!(self.fn_bodies, "{}__builtin_inf{}()", sign, suffix);
+        } else {
+            // Keep the payload, widened to `double` for the types that do not have a builtin
+            let quiet = 1 << (mant_bits - 1);
+            let payload = if kind == BuiltinType::F32 {
+                mantissa & !quiet
+            } else {
+                (mantissa & !quiet) << (52 - mant_bits)
+            };
+            w!(
+                self.fn_bodies,
+                "{}__builtin_nan{}{}(\"{:#x}\")",
+                sign,
+                if mantissa & quiet == 0 { "s" } else { "" },
+                suffix,
+                payload
+            );
+        }
+    }
+
     fn indent(&mut self) {
         if !self.debug_info {
             w!(self.fn_bodies, "{}", " ".repeat(self.indent));
@@ -315,6 +506,51 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
         }
 
         match &expr.kind {
+            ExprKind::Binary(op, lhs, rhs) if self.is_int128_binary(*op, lhs, rhs) => {
+                self.write_int128_binary(expr, *op, lhs, rhs)?;
+            }
+            ExprKind::AssignOp(op, lhs, rhs) if self.is_int128_binary(*op, lhs, rhs) => {
+                self.write_int128_assign_op(*op, lhs, rhs)?;
+            }
+            ExprKind::Unary(op, inner)
+                if self.ctx.soft_int128() && int128_signedness(inner.ty).is_some() =>
+            {
+                w!(self.fn_bodies, "{}(", unop_helper(*op));
+                self.write_expr(inner, false)?;
+                w!(self.fn_bodies, ")");
+            }
+            ExprKind::Cast(inner)
+                if self.ctx.soft_int128()
+                    && (int128_signedness(inner.ty).is_some()
+                        || int128_signedness(expr.ty).is_some()) =>
+            {
+                self.write_int128_cast(expr, inner)?;
+            }
+            ExprKind::Binary(op, lhs, rhs) if self.soft_float16_kind(lhs.ty).is_some() => {
+                self.write_float16_binary(expr, *op, lhs, rhs)?;
+            }
+            ExprKind::AssignOp(op, lhs, rhs) if self.soft_float16_kind(lhs.ty).is_some() => {
+                let prefix = float16_type(self.soft_float16_kind(lhs.ty).unwrap());
+                // The place is only evaluated once
+                w!(self.fn_bodies, "{}_compound(&", prefix);
+                self.write_expr(lhs, false)?;
+                w!(self.fn_bodies, ", ");
+                self.write_float_operand(rhs)?;
+                w!(self.fn_bodies, ", {})", compound_helper(*op));
+            }
+            ExprKind::Unary(op, inner) if self.soft_float16_kind(inner.ty).is_some() => {
+                let prefix = float16_type(self.soft_float16_kind(inner.ty).unwrap());
+                w!(self.fn_bodies, "{}_from_f32(", prefix);
+                self.write_unop(*op);
+                self.write_float_operand(inner)?;
+                w!(self.fn_bodies, ")");
+            }
+            ExprKind::Cast(inner)
+                if self.soft_float16_kind(inner.ty).is_some()
+                    || self.soft_float16_kind(expr.ty).is_some() =>
+            {
+                self.write_float16_cast(expr, inner)?;
+            }
             ExprKind::Binary(op, lhs, rhs) => {
                 // Cast to C's automatic promotion of to int
                 w!(self.fn_bodies, "({})(", self.ctx.get_type(expr.ty));
@@ -329,8 +565,79 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
                 w!(self.fn_bodies, "=");
                 self.write_expr(rhs, false)?;
             }
+            ExprKind::Call(callee, args)
+                if matches!(
+                    callee.kind,
+                    ExprKind::Intrinsic(IntrinsicValueKind::CInline(_))
+                ) =>
+            {
+                let ExprKind::Intrinsic(IntrinsicValueKind::CInline(code)) = callee.kind else {
+                    unreachable!()
+                };
+                self.write_c_inline(code, args)?;
+            }
+            ExprKind::Call(callee, args)
+                if matches!(
+                    callee.kind,
+                    ExprKind::Intrinsic(IntrinsicValueKind::MemCopy(_))
+                ) =>
+            {
+                let ExprKind::Intrinsic(IntrinsicValueKind::MemCopy(overlapping)) = callee.kind
+                else {
+                    unreachable!()
+                };
+                let [dst, src, count] = args[..] else {
+                    unreachable!()
+                };
+                let Ty::Pointer(elem, _) = *dst.ty else {
+                    unreachable!()
+                };
+
+                self.type_writer.add_type(elem)?;
+                w!(
+                    self.fn_bodies,
+                    "{}(",
+                    if self.no_builtins {
+                        "_AL_copy_bytes"
+                    } else if overlapping {
+                        "__builtin_memmove"
+                    } else {
+                        "__builtin_memcpy"
+                    }
+                );
+                self.write_expr(dst, false)?;
+                w!(self.fn_bodies, ", ");
+                self.write_expr(src, false)?;
+                w!(self.fn_bodies, ", (");
+                self.write_expr(count, false)?;
+                w!(self.fn_bodies, ") * sizeof({}))", self.ctx.get_type(elem));
+            }
             ExprKind::Call(callee, args) => {
-                self.write_expr(callee, false)?;
+                let int128_overflow_helper = match (&callee.kind, args.first()) {
+                    (ExprKind::Intrinsic(IntrinsicValueKind::FunctionLike(name)), Some(arg))
+                        if self.ctx.soft_int128() =>
+                    {
+                        int128_signedness(arg.ty)
+                            .and_then(|signed| overflow_builtin_helper(name, signed))
+                    }
+                    _ => None,
+                };
+                // C++ does not convert `void*` (which is what most builtins return) to other
+                // pointer types implicitly.
+                let needs_cast = self.ctx.cxx_compatible()
+                    && matches!(
+                        callee.kind,
+                        ExprKind::Intrinsic(IntrinsicValueKind::FunctionLike(_))
+                    )
+                    && matches!(expr.ty, Ty::Pointer(_, _));
+                if needs_cast {
+                    w!(self.fn_bodies, "(({})", self.ctx.get_type(expr.ty));
+                }
+                if let Some(helper) = int128_overflow_helper {
+                    w!(self.fn_bodies, "{}", helper);
+                } else {
+                    self.write_expr(callee, false)?;
+                }
                 w!(self.fn_bodies, "(");
                 for (idx, arg) in args
                     .iter()
@@ -343,6 +650,9 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
                     self.write_expr(arg, false)?;
                 }
                 w!(self.fn_bodies, ")");
+                if needs_cast {
+                    w!(self.fn_bodies, ")");
+                }
             }
             ExprKind::Fn(fun) => {
                 w!(self.fn_bodies, "{}", self.ctx.get_name(fun.id));
@@ -381,7 +691,22 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
             ExprKind::Local(id) => {
                 w!(self.fn_bodies, "{}", self.ctx.get_name(*id));
             }
-            ExprKind::Static(item) | ExprKind::Const(item) => {
+            ExprKind::Static(item) => {
+                let s = item.get_static().with_no_span()?;
+                if s.r#extern && matches!(s.typ, Ty::Array(..)) {
+                    // Declared as a C array, see `write_static_decl`
+                    w!(
+                        self.fn_bodies,
+                        "(*({}{}*){})",
+                        if s.is_const { "const " } else { "" },
+                        self.ctx.get_type(s.typ),
+                        self.ctx.get_name(item.id)
+                    );
+                } else {
+                    w!(self.fn_bodies, "{}", self.ctx.get_name(item.id));
+                }
+            }
+            ExprKind::Const(item) => {
                 w!(self.fn_bodies, "{}", self.ctx.get_name(item.id));
             }
             ExprKind::Block(stmts, ret) => {
@@ -409,8 +734,13 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
                 }
             }
             ExprKind::Literal(v) => match v {
-                Value::Str(val, offset) => {
-                    self.write_string_literal(&val[*offset..]);
+                Value::Str(id, offset) => {
+                    let name = self.string_name(*id);
+                    if *offset == 0 {
+                        w!(self.fn_bodies, "{}", name);
+                    } else {
+                        w!(self.fn_bodies, "({} + {})", name, offset);
+                    }
                 }
                 Value::FunctionPointer(item) => {
                     w!(
@@ -420,6 +750,19 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
                         self.ctx.get_name(item.id)
                     );
                 }
+                Value::U128(val) if self.ctx.soft_int128() => self.write_int128_literal(*val),
+                Value::I128(val) if self.ctx.soft_int128() => {
+                    self.write_int128_literal(*val as u128)
+                }
+                Value::F16(bits) | Value::BF16(bits)
+                    if self.soft_float16_kind(expr.ty).is_some() =>
+                {
+                    let kind = self.soft_float16_kind(expr.ty).unwrap();
+                    self.write_float16_literal(kind, *bits);
+                }
+                Value::Uninitialized if self.in_const_init && !self.ctx.aggregate_literals() => {
+                    w!(self.fn_bodies, "{{0}}");
+                }
                 _ => {
                     self.type_writer.add_type(expr.ty)?;
                     w!(self.fn_bodies, "(({})", self.ctx.get_type(expr.ty));
@@ -481,7 +824,7 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
                 self.write_expr(inner, false)?;
             }
             ExprKind::Unreachable => {
-                w!(self.fn_bodies, "__builtin_unreachable()");
+                w!(self.fn_bodies, "{}", self.ctx.unreachable_expr());
             }
             ExprKind::Intrinsic(kind) => match kind {
                 IntrinsicValueKind::SizeOfLike(n, typ) => {
@@ -494,9 +837,22 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
                 IntrinsicValueKind::ConstLike(n) => {
                     w!(self.fn_bodies, "{}", n);
                 }
-                IntrinsicValueKind::Asm(n) => {
-                    w!(self.fn_bodies, "asm volatile({:?})", *n);
+                IntrinsicValueKind::Asm(n, clobbers) => {
+                    w!(self.fn_bodies, "asm volatile({:?}", *n);
+                    if !clobbers.is_empty() {
+                        w!(self.fn_bodies, " ::: ");
+                        for (idx, clobber) in clobbers.iter().enumerate() {
+                            if idx > 0 {
+                                w!(self.fn_bodies, ", ");
+                            }
+                            w!(self.fn_bodies, "{:?}", clobber);
+                        }
+                    }
+                    w!(self.fn_bodies, ")");
                 }
+                // Only valid as a callee, see `write_c_inline`
+                IntrinsicValueKind::CInline(_) => unreachable!(),
+                IntrinsicValueKind::MemCopy(_) => unreachable!(),
                 IntrinsicValueKind::Uninitialized => {
                     // I wish there was a prettier way to do this
                     w!(
@@ -532,7 +888,11 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
                 if !self.in_const_init {
                     w!(self.fn_bodies, "({})", self.ctx.get_type(expr.ty));
                 }
-                w!(self.fn_bodies, "{{.__data={{{}", self.endl());
+                if self.in_const_init && !self.ctx.aggregate_literals() {
+                    w!(self.fn_bodies, "{{{{{}", self.endl());
+                } else {
+                    w!(self.fn_bodies, "{{.__data={{{}", self.endl());
+                }
                 for elem in elems.iter() {
                     self.indent();
                     self.write_expr(elem, false)?;
@@ -541,38 +901,51 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
                 self.indent();
                 w!(self.fn_bodies, "}}}}");
             }
-            ExprKind::Tuple(inits) => {
+            ExprKind::Tuple(inits) if self.in_const_init && !self.ctx.aggregate_literals() => {
                 self.type_writer.add_type(expr.ty)?;
-                if !self.in_const_init {
-                    w!(self.fn_bodies, "({})", self.ctx.get_type(expr.ty));
-                }
-                w!(self.fn_bodies, "{{");
-                for init in inits.iter() {
-                    if init.value.ty.is_zero_sized() {
-                        continue;
-                    }
-                    w!(self.fn_bodies, "._{}=", init.index);
-                    self.write_expr(&init.value, false)?;
-                    w!(self.fn_bodies, ",");
-                }
-                w!(self.fn_bodies, "}}");
+                let Ty::Tuple(elems) = expr.ty else {
+                    unreachable!()
+                };
+
+                let (_, members) = self
+                    .ctx
+                    .layouter
+                    .field_layout_of_aggregate(
+                        None,
+                        false,
+                        false,
+                        elems.iter().copied().enumerate(),
+                    )
+                    .with_no_span()?;
+
+                self.write_positional_init(members, |idx| {
+                    inits.iter().find(|i| i.index == *idx).map(|i| i.value)
+                })?;
+            }
+            ExprKind::Struct(inits) if self.in_const_init && !self.ctx.aggregate_literals() => {
+                self.write_positional_struct_init(expr.ty, inits)?;
             }
-            ExprKind::Struct(inits) => {
+            ExprKind::Tuple(inits) => {
                 self.type_writer.add_type(expr.ty)?;
                 if !self.in_const_init {
                     w!(self.fn_bodies, "({})", self.ctx.get_type(expr.ty));
                 }
+                let mut inits: Vec<_> = inits.iter().collect();
+                if self.ctx.cxx_compatible() {
+                    inits.sort_by_key(|init| init.index);
+                }
                 w!(self.fn_bodies, "{{");
-                for init in inits.iter() {
+                for init in inits {
                     if init.value.ty.is_zero_sized() {
                         continue;
                     }
-                    w!(self.fn_bodies, ".{}=", self.ctx.get_name(init.field));
+                    w!(self.fn_bodies, "._{}=", init.index);
                     self.write_expr(&init.value, false)?;
                     w!(self.fn_bodies, ",");
                 }
                 w!(self.fn_bodies, "}}");
             }
+            ExprKind::Struct(inits) => self.write_struct_init(expr.ty, inits)?,
             ExprKind::Void => {}
         }
 
@@ -583,6 +956,341 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
         Ok(())
     }
 
+    fn is_int128_binary(&self, op: BinOp, lhs: ExprP<'ir>, rhs: ExprP<'ir>) -> bool {
+        self.ctx.soft_int128()
+            && (int128_signedness(lhs.ty).is_some()
+                || (matches!(op, BinOp::LShift | BinOp::RShift)
+                    && int128_signedness(rhs.ty).is_some()))
+    }
+
+    fn write_int128_literal(&mut self, val: u128) {
+        let (hi, lo) = ((val >> 64) as u64, val as u64);
+        if self.in_const_init {
+            w!(self.fn_bodies, "{{{}ULL,{}ULL}}", lo, hi);
+        } else {
+            w!(self.fn_bodies, "_AL_int128_make({}ULL,{}ULL)", hi, lo);
+        }
+    }
+
+    /// Operand of a 128-bit helper, other integer types are widened.
+    fn write_int128_operand(&mut self, expr: ExprP<'ir>) -> Result<(), AluminaError> {
+        if int128_signedness(expr.ty).is_some() {
+            return self.write_expr(&expr, false);
+        }
+
+        if builtin_type(expr.ty).map_or(false, |b| b.is_signed()) {
+            w!(self.fn_bodies, "_AL_int128_from_i64((int64_t)");
+        } else {
+            w!(self.fn_bodies, "_AL_int128_from_u64((uint64_t)");
+        }
+        self.write_expr(&expr, false)?;
+        w!(self.fn_bodies, ")");
+
+        Ok(())
+    }
+
+    fn write_int128_binary(
+        &mut self,
+        expr: ExprP<'ir>,
+        op: BinOp,
+        lhs: ExprP<'ir>,
+        rhs: ExprP<'ir>,
+    ) -> Result<(), AluminaError> {
+        let Some(signed) = int128_signedness(lhs.ty) else {
+            // Shift by a 128-bit amount
+            w!(self.fn_bodies, "({})(", self.ctx.get_type(expr.ty));
+            self.write_expr(&lhs, false)?;
+            self.write_binop(op);
+            w!(self.fn_bodies, "(");
+            self.write_expr(&rhs, false)?;
+            w!(self.fn_bodies, ").lo)");
+            return Ok(());
+        };
+
+        let (helper, swap, negate) = binop_helper(op, signed);
+        let (lhs, rhs) = if swap { (rhs, lhs) } else { (lhs, rhs) };
+        if negate {
+            w!(self.fn_bodies, "!");
+        }
+        w!(self.fn_bodies, "{}(", helper);
+        self.write_int128_operand(lhs)?;
+        w!(self.fn_bodies, ", ");
+        self.write_int128_operand(rhs)?;
+        w!(self.fn_bodies, ")");
+
+        Ok(())
+    }
+
+    fn write_int128_assign_op(
+        &mut self,
+        op: BinOp,
+        lhs: ExprP<'ir>,
+        rhs: ExprP<'ir>,
+    ) -> Result<(), AluminaError> {
+        let Some(signed) = int128_signedness(lhs.ty) else {
+            self.write_expr(&lhs, false)?;
+            self.write_binop(op);
+            w!(self.fn_bodies, "=(");
+            self.write_expr(&rhs, false)?;
+            w!(self.fn_bodies, ").lo");
+            return Ok(());
+        };
+
+        // The place is only evaluated once
+        let (helper, _, _) = binop_helper(op, signed);
+        w!(self.fn_bodies, "_AL_int128_compound(&");
+        self.write_expr(&lhs, false)?;
+        w!(self.fn_bodies, ", ");
+        self.write_int128_operand(rhs)?;
+        w!(self.fn_bodies, ", {})", helper);
+
+        Ok(())
+    }
+
+    fn write_int128_cast(
+        &mut self,
+        expr: ExprP<'ir>,
+        inner: ExprP<'ir>,
+    ) -> Result<(), AluminaError> {
+        let target = builtin_type(expr.ty);
+        match (int128_signedness(inner.ty), int128_signedness(expr.ty)) {
+            // u128 <-> i128 have the same representation
+            (Some(_), Some(_)) => self.write_expr(&inner, false)?,
+            (None, Some(signed)) if builtin_type(inner.ty).map_or(false, |b| b.is_float()) => {
+                w!(
+                    self.fn_bodies,
+                    "_AL_int128_from_f64{}((double)",
+                    if signed { "s" } else { "u" }
+                );
+                self.write_float_operand(inner)?;
+                w!(self.fn_bodies, ")");
+            }
+            (None, Some(_)) => self.write_int128_operand(inner)?,
+            (Some(signed), None) if self.soft_float16_kind(expr.ty).is_some() => {
+                w!(
+                    self.fn_bodies,
+                    "{}_from_f32((float)_AL_int128_to_f64{}(",
+                    float16_type(self.soft_float16_kind(expr.ty).unwrap()),
+                    if signed { "s" } else { "u" }
+                );
+                self.write_expr(&inner, false)?;
+                w!(self.fn_bodies, "))");
+            }
+            (Some(signed), None) if target.map_or(false, |b| b.is_float()) => {
+                w!(
+                    self.fn_bodies,
+                    "(({})_AL_int128_to_f64{}(",
+                    self.ctx.get_type(expr.ty),
+                    if signed { "s" } else { "u" }
+                );
+                self.write_expr(&inner, false)?;
+                w!(self.fn_bodies, "))");
+            }
+            (Some(_), None) => {
+                // Truncation
+                w!(self.fn_bodies, "(({})(", self.ctx.get_type(expr.ty));
+                self.write_expr(&inner, false)?;
+                w!(self.fn_bodies, ").lo)");
+            }
+            (None, None) => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    /// `f16` or `bf16` if they are implemented in software (see `float16.rs`)
+    fn soft_float16_kind(&self, ty: TyP<'ir>) -> Option<BuiltinType> {
+        float16_kind(ty).filter(|kind| self.ctx.soft_float16(*kind))
+    }
+
+    fn write_float16_literal(&mut self, kind: BuiltinType, bits: u16) {
+        if self.in_const_init {
+            w!(self.fn_bodies, "{{{:#06x}}}", bits);
+        } else {
+            w!(self.fn_bodies, "{}_make({:#06x})", float16_type(kind), bits);
+        }
+    }
+
+    /// Writes a floating point value, software half floats are widened to `float`.
+    fn write_float_operand(&mut self, expr: ExprP<'ir>) -> Result<(), AluminaError> {
+        match self.soft_float16_kind(expr.ty) {
+            Some(kind) => {
+                w!(self.fn_bodies, "{}_to_f32(", float16_type(kind));
+                self.write_expr(&expr, false)?;
+                w!(self.fn_bodies, ")");
+            }
+            None => self.write_expr(&expr, false)?,
+        }
+
+        Ok(())
+    }
+
+    fn write_float16_binary(
+        &mut self,
+        expr: ExprP<'ir>,
+        op: BinOp,
+        lhs: ExprP<'ir>,
+        rhs: ExprP<'ir>,
+    ) -> Result<(), AluminaError> {
+        let result = self.soft_float16_kind(expr.ty);
+        match result {
+            Some(kind) => w!(self.fn_bodies, "{}_from_f32(", float16_type(kind)),
+            None => w!(self.fn_bodies, "({})(", self.ctx.get_type(expr.ty)),
+        }
+        self.write_float_operand(lhs)?;
+        self.write_binop(op);
+        self.write_float_operand(rhs)?;
+        w!(self.fn_bodies, ")");
+
+        Ok(())
+    }
+
+    fn write_float16_cast(
+        &mut self,
+        expr: ExprP<'ir>,
+        inner: ExprP<'ir>,
+    ) -> Result<(), AluminaError> {
+        match self.soft_float16_kind(expr.ty) {
+            Some(kind) => {
+                w!(self.fn_bodies, "{}_from_f32((float)", float16_type(kind));
+                self.write_float_operand(inner)?;
+                w!(self.fn_bodies, ")");
+            }
+            None => {
+                self.type_writer.add_type(expr.ty)?;
+                w!(self.fn_bodies, "(({})", self.ctx.get_type(expr.ty));
+                self.write_float_operand(inner)?;
+                w!(self.fn_bodies, ")");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes an inline C snippet (`std::intrinsics::c_inline`) as a statement expression.
+    /// Each argument is evaluated once into a temporary, which the `$N` placeholders in the
+    /// snippet refer to.
+    fn write_c_inline(&mut self, code: &str, args: &[ExprP<'ir>]) -> Result<(), AluminaError> {
+        w!(self.fn_bodies, "({{ ");
+        let mut names = Vec::with_capacity(args.len());
+        for (idx, arg) in args.iter().enumerate() {
+            let name = format!("__c_inline_{}", idx);
+            self.type_writer.add_type(arg.ty)?;
+            w!(self.fn_bodies, "{} {} = ", self.ctx.get_type(arg.ty), name);
+            self.write_expr(arg, false)?;
+            w!(self.fn_bodies, "; ");
+            names.push(name);
+        }
+
+        // Placeholders are validated during monomorphization
+        let code = expand_c_inline(code, &names).unwrap();
+        w!(self.fn_bodies, "{}\n; }})", code);
+
+        Ok(())
+    }
+
+    fn write_struct_init(
+        &mut self,
+        ty: TyP<'ir>,
+        inits: &[StructInit<'ir>],
+    ) -> Result<(), AluminaError> {
+        self.type_writer.add_type(ty)?;
+        if !self.in_const_init {
+            w!(self.fn_bodies, "({})", self.ctx.get_type(ty));
+        }
+        let inits = if self.ctx.cxx_compatible() {
+            declaration_order(ty, inits)
+        } else {
+            inits.iter().collect()
+        };
+        w!(self.fn_bodies, "{{");
+        for init in inits {
+            if init.value.ty.is_zero_sized() {
+                continue;
+            }
+            w!(self.fn_bodies, ".{}=", self.ctx.get_name(init.field));
+            self.write_expr(&init.value, false)?;
+            w!(self.fn_bodies, ",");
+        }
+        w!(self.fn_bodies, "}}");
+
+        Ok(())
+    }
+
+    /// Writes the initializer of a constant struct or tuple without designators. The values
+    /// are listed in the order of the C members, padding included.
+    fn write_positional_init<K>(
+        &mut self,
+        members: Vec<(Option<K>, Layout)>,
+        value_of: impl Fn(&K) -> Option<ExprP<'ir>>,
+    ) -> Result<(), AluminaError> {
+        w!(self.fn_bodies, "{{");
+        for (member, layout) in members {
+            match member.map(|m| value_of(&m)) {
+                Some(_) if layout.is_zero_sized() => continue,
+                Some(Some(value)) => self.write_expr(&value, false)?,
+                _ => w!(self.fn_bodies, "{{0}}"),
+            }
+            w!(self.fn_bodies, ",");
+        }
+        w!(self.fn_bodies, "}}");
+
+        Ok(())
+    }
+
+    fn write_positional_struct_init(
+        &mut self,
+        ty: TyP<'ir>,
+        inits: &[StructInit<'ir>],
+    ) -> Result<(), AluminaError> {
+        let s = match ty {
+            Ty::Item(item) => match item.get().with_no_span()? {
+                IRItem::StructLike(s) | IRItem::Closure(Closure { data: s, .. }) => s,
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+
+        self.type_writer.add_type(ty)?;
+
+        let (alignment, is_packed, is_transparent) = struct_attributes(s.attributes);
+        if s.is_union || is_transparent {
+            let first = s.fields.iter().find(|f| !f.ty.is_zero_sized());
+            match inits.iter().find(|i| !i.value.ty.is_zero_sized()) {
+                // Only the first member of a union can be initialized positionally, there
+                // is no way around the designator for the others.
+                Some(init) if Some(init.field) != first.map(|f| f.id) => {
+                    self.write_struct_init(ty, inits)?;
+                }
+                init => {
+                    w!(self.fn_bodies, "{{");
+                    match init {
+                        Some(init) => self.write_expr(&init.value, false)?,
+                        None => w!(self.fn_bodies, "{{0}}"),
+                    }
+                    w!(self.fn_bodies, "}}");
+                }
+            }
+
+            return Ok(());
+        }
+
+        let (_, members) = self
+            .ctx
+            .layouter
+            .field_layout_of_aggregate(
+                alignment,
+                false,
+                is_packed,
+                s.fields.iter().map(|f| (f.id, f.ty)),
+            )
+            .with_no_span()?;
+
+        self.write_positional_init(members, |id| {
+            inits.iter().find(|i| i.field == *id).map(|i| i.value)
+        })
+    }
+
     pub fn write_function_decl(
         &mut self,
         id: IrId,
@@ -599,10 +1307,41 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
             self.type_writer.add_type(arg.ty)?;
         }
 
+        for attr in item.attributes {
+            if let Attribute::InterruptVector(vector) = attr {
+                self.interrupt_vectors.push((*vector, id));
+            }
+        }
+
         if !has_link_name && (item.body.get().is_none() || should_export) {
-            self.ctx
-                .register_name(id, CName::Native(item.name.unwrap()));
-            write_function_signature(self.ctx, &mut self.fn_decls, id, item, false, false)?;
+            let name = item.name.unwrap();
+            if self.ctx.cxx_compatible() && is_cxx_keyword(name) {
+                // C++ keywords cannot be used as identifiers, so the function gets a mangled
+                // name and the symbol is given with an asm label.
+                self.ctx
+                    .register_name(id, CName::Mangled(name, self.ctx.make_id()));
+                write_function_signature(
+                    self.ctx,
+                    &mut self.fn_decls,
+                    id,
+                    item,
+                    false,
+                    false,
+                    None,
+                )?;
+                w!(self.fn_decls, " asm(\"{}\")", name);
+            } else {
+                self.ctx.register_name(id, CName::Native(name));
+                write_function_signature(
+                    self.ctx,
+                    &mut self.fn_decls,
+                    id,
+                    item,
+                    false,
+                    false,
+                    None,
+                )?;
+            }
         } else {
             self.ctx.register_name(
                 id,
@@ -618,8 +1357,9 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
                 item,
                 item.body.get().is_some()
                     && !should_export
-                    && !self.ctx.global_ctx.has_flag("debug"),
+                    && (self.ctx.lto_friendly() || !self.ctx.global_ctx.has_flag("debug")),
                 false,
+                None,
             )?;
         }
 
@@ -635,33 +1375,59 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
     ) -> Result<(), AluminaError> {
         self.type_writer.add_type(item.typ)?;
 
-        let attributes = if item.attributes.contains(&Attribute::ThreadLocal) {
-            " __thread"
+        let mut attributes = if item.attributes.contains(&Attribute::ThreadLocal) {
+            " __thread".to_string()
         } else {
-            ""
+            String::new()
         };
 
+        let c_attributes = write_c_attributes(&mut self.fn_decls, item.attributes);
+        if !c_attributes.is_empty() {
+            w!(attributes, " {}", c_attributes.trim_end());
+        }
+
+        let mut asm_label = String::new();
         if item.r#extern {
-            self.ctx
-                .register_name(id, CName::Native(item.name.unwrap()));
+            let name = item.name.unwrap();
+            if self.ctx.cxx_compatible() && is_cxx_keyword(name) {
+                self.ctx.register_name(id, CName::Mangled(name, id.id));
+                w!(asm_label, " asm(\"{}\")", name);
+            } else {
+                self.ctx.register_name(id, CName::Native(name));
+            }
         } else if let Some(name) = item.name {
             self.ctx.register_name(id, CName::Mangled(name, id.id));
         }
 
         if !item.typ.is_zero_sized() {
             if item.r#extern {
+                // Arrays are wrapped in a union in the generated code, but the foreign
+                // symbol has to be declared as a plain C array so that the declaration is
+                // compatible with the one in the C headers.
+                let (element, dimensions) = array_declarator(item.typ);
                 w!(
                     self.fn_decls,
-                    "\nextern{} {} {};",
+                    "\nextern{} {}{} {}{}{};",
                     attributes,
-                    self.ctx.get_type(item.typ),
-                    self.ctx.get_name(id)
+                    if item.is_const { "const " } else { "" },
+                    self.ctx.get_type(element),
+                    self.ctx.get_name(id),
+                    dimensions,
+                    asm_label
                 );
             } else {
+                let section = if self.ctx.lto_friendly()
+                    && !item.attributes.contains(&Attribute::ThreadLocal)
+                {
+                    format!(" _AL_SECTION(\".bss.{}\")", self.ctx.get_name(id))
+                } else {
+                    String::new()
+                };
                 w!(
                     self.fn_decls,
-                    "\nstatic{} {} {};",
+                    "\nstatic{}{} {} {};",
                     attributes,
+                    section,
                     self.ctx.get_type(item.typ),
                     self.ctx.get_name(id)
                 );
@@ -681,20 +1447,48 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
         }
 
         self.type_writer.add_type(item.typ)?;
-        w!(
-            self.fn_decls,
-            "\nconst static {} {};",
-            self.ctx.get_type(item.typ),
-            self.ctx.get_name(id)
-        );
+        if self.ctx.cxx_compatible() {
+            // C++ has no tentative definitions, the constant is declared `extern` instead
+            // and the definition follows later.
+            w!(
+                self.fn_decls,
+                "\nextern const {} {};",
+                self.ctx.get_type(item.typ),
+                self.ctx.get_name(id)
+            );
+        } else {
+            w!(
+                self.fn_decls,
+                "\nconst static{} {} {};",
+                self.const_section(id),
+                self.ctx.get_type(item.typ),
+                self.ctx.get_name(id)
+            );
+        }
 
         Ok(())
     }
 
+    /// Section attribute of a constant for `-Zlto-friendly`
+    fn const_section(&self, id: IrId) -> String {
+        // C++ declares the constant `extern` first, see `write_const_decl`
+        if self.ctx.lto_friendly() && !self.ctx.cxx_compatible() {
+            format!(" _AL_SECTION(\".rodata.{}\")", self.ctx.get_name(id))
+        } else {
+            String::new()
+        }
+    }
+
     pub fn write_const(&mut self, id: IrId, item: &'ir Const<'ir>) -> Result<(), AluminaError> {
         w!(
             self.fn_bodies,
-            "\nconst static {} {} = ",
+            "\n{}{} {} {} = ",
+            if self.ctx.cxx_compatible() {
+                "const"
+            } else {
+                "const static"
+            },
+            self.const_section(id),
             self.ctx.get_type(item.typ),
             self.ctx.get_name(id)
         );
@@ -720,23 +1514,150 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
             return Ok(());
         }
 
+        let is_static =
+            !should_export && (self.ctx.lto_friendly() || !self.ctx.global_ctx.has_flag("debug"));
+
+        let Some(versions) = multiversions(self.ctx, item) else {
+            write_function_signature(
+                self.ctx,
+                &mut self.fn_bodies,
+                id,
+                item,
+                is_static,
+                true,
+                None,
+            )?;
+            return self.write_body(item);
+        };
+
+        if item.varargs {
+            return Err(CodeErrorKind::InvalidAttributeDetail(
+                "functions with varargs cannot be multiversioned".to_string(),
+            ))
+            .with_no_span();
+        }
+
+        for version in &versions {
+            write_function_signature(
+                self.ctx,
+                &mut self.fn_bodies,
+                id,
+                item,
+                true,
+                true,
+                Some(version),
+            )?;
+            self.write_body(item)?;
+        }
+
         write_function_signature(
             self.ctx,
             &mut self.fn_bodies,
             id,
             item,
-            !should_export && !self.ctx.global_ctx.has_flag("debug"),
+            is_static,
             true,
+            None,
         )?;
+        self.write_dispatcher(id, item, &versions);
 
+        Ok(())
+    }
+
+    /// Writes the function that calls the best version of a multiversioned function for the
+    /// CPU. The choice is made on the first call and remembered in a function pointer (this
+    /// is what an ifunc resolver would do, but it also works without ifunc support in the
+    /// loader). The versions are tried in the order they are listed.
+    fn write_dispatcher(&mut self, id: IrId, item: &'ir Function<'ir>, versions: &[&str]) {
+        let name = self.ctx.get_name(id);
+        let args: Vec<_> = item
+            .args
+            .iter()
+            .filter(|arg| !arg.ty.is_zero_sized())
+            .collect();
+
+        let returns_value = !item.return_type.is_zero_sized();
+        let return_type = if returns_value {
+            self.ctx.get_type(item.return_type)
+        } else {
+            self.ctx.get_type(&Ty::void())
+        };
+
+        let params = if args.is_empty() {
+            "void".to_string()
+        } else {
+            args.iter()
+                .map(|arg| self.ctx.get_type(arg.ty).to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        w!(self.fn_bodies, "{{\n");
+        w!(
+            self.fn_bodies,
+            "  static {} (*dispatch)({});\n",
+            return_type,
+            params
+        );
+        w!(
+            self.fn_bodies,
+            "  {} (*target)({}) = __atomic_load_n(&dispatch, __ATOMIC_RELAXED);\n",
+            return_type,
+            params
+        );
+        w!(
+            self.fn_bodies,
+            "  if (!target) {{\n    __builtin_cpu_init();\n"
+        );
+        for (idx, version) in versions.iter().filter(|v| **v != "default").enumerate() {
+            w!(
+                self.fn_bodies,
+                "    {}if ({}) target = {};\n",
+                if idx > 0 { "else " } else { "" },
+                version_condition(version),
+                version_name(name, version)
+            );
+        }
+        w!(
+            self.fn_bodies,
+            "    {}target = {};\n",
+            if versions.len() > 1 { "else " } else { "" },
+            version_name(name, "default")
+        );
+        w!(
+            self.fn_bodies,
+            "    __atomic_store_n(&dispatch, target, __ATOMIC_RELAXED);\n  }}\n"
+        );
+
+        let call_args: Vec<_> = args
+            .iter()
+            .map(|arg| self.ctx.get_name(arg.id).to_string())
+            .collect();
+        if returns_value {
+            w!(
+                self.fn_bodies,
+                "  return target({});\n",
+                call_args.join(", ")
+            );
+        } else {
+            w!(self.fn_bodies, "  target({});\n", call_args.join(", "));
+            if item.return_type.is_never() {
+                w!(self.fn_bodies, "  __builtin_unreachable();\n");
+            }
+        }
+        w!(self.fn_bodies, "}}\n");
+    }
+
+    fn write_body(&mut self, item: &'ir Function<'ir>) -> Result<(), AluminaError> {
         let body = item.body.get().unwrap();
+        self.no_builtins = self.ctx.no_builtins(item);
         w!(self.fn_bodies, "{{\n");
         self.indent += 2;
 
         if item.args.iter().any(|a| a.ty.is_never()) {
             // functions that accept a parameter that is of never type can never be legally called,
             // so we add this to keep C compiler from complaining. If someone called it, it's already
-            // UB.
+            // UB (or a trap with `-Ztrap-on-unreachable`, same as any other unreachable code).
             self.write_stmt(&Statement::Expression(&Expr {
                 ty: &Ty::Builtin(BuiltinType::Never),
                 kind: ExprKind::Unreachable,
@@ -760,9 +1681,106 @@ impl<'ir, 'gen> FunctionWriter<'ir, 'gen> {
         Ok(())
     }
 
+    pub fn write_module_banner(&mut self, module: &str) {
+        let line = "=".repeat(module.len() + 14);
+        w!(self.fn_bodies, "\n/* {} */\n", line);
+        w!(self.fn_bodies, "/* ===== {} ===== */\n", module);
+        w!(self.fn_bodies, "/* {} */\n\n", line);
+    }
+
+    fn write_alloc_site_table(&self, buf: &mut String, sites: &[Span]) {
+        let diag = self.ctx.global_ctx.diag();
+        // `extern` gives the constants external linkage in C++, which they have by default in C.
+        let linkage = if self.ctx.cxx_compatible() {
+            "extern "
+        } else {
+            ""
+        };
+
+        // Site ids start at 1, slot 0 is for allocations that were not made through
+        // an `#[allocator]` call site.
+        w!(
+            buf,
+            "\n{}const char *const __alumina_alloc_sites[{}] = {{\n  0,\n",
+            linkage,
+            sites.len() + 1
+        );
+        for span in sites {
+            let location = match diag.get_file_path(span.file) {
+                Some(path) => format!("{}:{}:{}", path.display(), span.line + 1, diag.column(span)),
+                None => "<unknown>".to_string(),
+            };
+            w!(buf, "  {:?},\n", location);
+        }
+        w!(buf, "}};\n");
+        w!(
+            buf,
+            "{}const uint32_t __alumina_alloc_site_count = {};\n",
+            linkage,
+            sites.len() + 1
+        );
+    }
+
+    fn write_interrupt_vector_table(&self, buf: &mut String) {
+        let mut vectors = self.interrupt_vectors.clone();
+        vectors.sort();
+
+        // Slots without a handler are left as null pointers, it is up to the startup code
+        // (or the linker script) to decide what to do with them. The handlers are cast, as
+        // on x86 they take the interrupt frame (and the error code) as parameters.
+        let len = vectors.last().unwrap().0 + 1;
+        w!(
+            buf,
+            "\n__attribute__((section(\".isr_vector\"), used)) void (*const _AL_isr_vector[{}])(void) = {{\n",
+            len
+        );
+        if self.ctx.cxx_compatible() || !self.ctx.aggregate_literals() {
+            // No array designators in C++ or in the conservative dialect
+            let mut vectors = vectors.into_iter().peekable();
+            for slot in 0..len {
+                match vectors.next_if(|(vector, _)| *vector == slot) {
+                    Some((_, id)) => w!(buf, "  (void (*)(void)){},\n", self.ctx.get_name(id)),
+                    None => w!(buf, "  0,\n"),
+                }
+            }
+        } else {
+            for (vector, id) in vectors {
+                w!(
+                    buf,
+                    "  [{}] = (void (*)(void)){},\n",
+                    vector,
+                    self.ctx.get_name(id)
+                );
+            }
+        }
+        w!(buf, "}};\n");
+    }
+
     pub fn write(&self, buf: &mut String) {
-        buf.reserve(self.fn_decls.len() + self.fn_bodies.len());
+        buf.reserve(self.string_decls.len() + self.fn_decls.len() + self.fn_bodies.len());
+        buf.push_str(&self.string_decls);
         buf.push_str(&self.fn_decls);
         buf.push_str(&self.fn_bodies);
+
+        if !self.interrupt_vectors.is_empty() {
+            self.write_interrupt_vector_table(buf);
+        }
+
+        let alloc_sites = self.ctx.global_ctx.alloc_sites();
+        if !alloc_sites.is_empty() {
+            self.write_alloc_site_table(buf, &alloc_sites);
+        }
+    }
+}
+
+/// Element type and dimensions (e.g. `[2][3]`) of a possibly nested array type, for
+/// writing a C array declarator.
+fn array_declarator(mut ty: TyP<'_>) -> (TyP<'_>, String) {
+    let mut dimensions = String::new();
+    while let Ty::Array(inner, len) = ty {
+        w!(dimensions, "[{}]", len);
+        ty = inner;
     }
+
+    (ty, dimensions)
 }
@@ -0,0 +1,238 @@
+//! Software implementation of 128-bit integers for C compilers that do not have
+//! `__int128` (MSVC, 32-bit targets).
+//!
+//! Both `u128` and `i128` are represented by the same struct, the signedness is
+//! determined by the helper that is used for the operation. The helpers follow the
+//! semantics of the native types (and thus of the constant evaluator): arithmetic
+//! wraps around, division truncates towards zero and right shifts of signed values
+//! are arithmetic.
+use crate::ast::{BinOp, BuiltinType, UnOp};
+use crate::ir::{IRItem, Ty};
+
+pub const INT128_TYPE: &str = "_AL_int128";
+
+/// The builtin type of a value (the underlying type for enums)
+pub fn builtin_type(ty: &Ty<'_>) -> Option<BuiltinType> {
+    match ty {
+        Ty::Builtin(builtin) => Some(*builtin),
+        Ty::Item(item) => match item.get() {
+            Ok(IRItem::Enum(e)) => builtin_type(e.underlying_type),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether the type is represented by `_AL_int128` and if so, whether it is signed.
+pub fn int128_signedness(ty: &Ty<'_>) -> Option<bool> {
+    match builtin_type(ty) {
+        Some(BuiltinType::U128) => Some(false),
+        Some(BuiltinType::I128) => Some(true),
+        _ => None,
+    }
+}
+
+/// Helper for a binary operator. The boolean indicates that the operands need to be
+/// swapped and the result negated (e.g. `a >= b` is `!(a < b)`), respectively.
+pub fn binop_helper(op: BinOp, signed: bool) -> (&'static str, bool, bool) {
+    let lt = if signed {
+        "_AL_int128_slt"
+    } else {
+        "_AL_int128_ult"
+    };
+
+    match op {
+        BinOp::Plus => ("_AL_int128_add", false, false),
+        BinOp::Minus => ("_AL_int128_sub", false, false),
+        BinOp::Mul => ("_AL_int128_mul", false, false),
+        BinOp::Div if signed => ("_AL_int128_sdiv", false, false),
+        BinOp::Div => ("_AL_int128_udiv", false, false),
+        BinOp::Mod if signed => ("_AL_int128_smod", false, false),
+        BinOp::Mod => ("_AL_int128_umod", false, false),
+        BinOp::BitAnd => ("_AL_int128_and", false, false),
+        BinOp::BitOr => ("_AL_int128_or", false, false),
+        BinOp::BitXor => ("_AL_int128_xor", false, false),
+        BinOp::LShift => ("_AL_int128_shl", false, false),
+        BinOp::RShift if signed => ("_AL_int128_ashr", false, false),
+        BinOp::RShift => ("_AL_int128_lshr", false, false),
+        BinOp::Eq => ("_AL_int128_eq", false, false),
+        BinOp::Neq => ("_AL_int128_eq", false, true),
+        BinOp::Lt => (lt, false, false),
+        BinOp::Gt => (lt, true, false),
+        BinOp::LEq => (lt, true, true),
+        BinOp::GEq => (lt, false, true),
+        BinOp::And | BinOp::Or => unreachable!(),
+    }
+}
+
+pub fn unop_helper(op: UnOp) -> &'static str {
+    match op {
+        UnOp::Neg => "_AL_int128_neg",
+        UnOp::BitNot => "_AL_int128_not",
+        UnOp::Not => unreachable!(),
+    }
+}
+
+/// Replacement for the `__builtin_*_overflow` family of builtins
+pub fn overflow_builtin_helper(name: &str, signed: bool) -> Option<&'static str> {
+    Some(match (name, signed) {
+        ("__builtin_add_overflow", false) => "_AL_int128_uadd_overflow",
+        ("__builtin_add_overflow", true) => "_AL_int128_sadd_overflow",
+        ("__builtin_sub_overflow", false) => "_AL_int128_usub_overflow",
+        ("__builtin_sub_overflow", true) => "_AL_int128_ssub_overflow",
+        ("__builtin_mul_overflow", false) => "_AL_int128_umul_overflow",
+        ("__builtin_mul_overflow", true) => "_AL_int128_smul_overflow",
+        _ => return None,
+    })
+}
+
+pub const INT128_PRELUDE: &str = r#"
+typedef struct _AL_int128 { uint64_t lo; uint64_t hi; } _AL_int128;
+typedef _AL_int128 (*_AL_int128_op)(_AL_int128, _AL_int128);
+static inline _AL_int128 _AL_int128_make(uint64_t hi, uint64_t lo) {
+  _AL_int128 r; r.lo = lo; r.hi = hi; return r;
+}
+static inline _AL_int128 _AL_int128_from_u64(uint64_t a) { return _AL_int128_make(0, a); }
+static inline _AL_int128 _AL_int128_from_i64(int64_t a) {
+  return _AL_int128_make(a < 0 ? ~(uint64_t)0 : 0, (uint64_t)a);
+}
+static inline int _AL_int128_is_neg(_AL_int128 a) { return (a.hi >> 63) != 0; }
+static inline int _AL_int128_eq(_AL_int128 a, _AL_int128 b) { return a.lo == b.lo && a.hi == b.hi; }
+static inline int _AL_int128_ult(_AL_int128 a, _AL_int128 b) {
+  return a.hi < b.hi || (a.hi == b.hi && a.lo < b.lo);
+}
+static inline int _AL_int128_slt(_AL_int128 a, _AL_int128 b) {
+  return (int64_t)a.hi < (int64_t)b.hi || (a.hi == b.hi && a.lo < b.lo);
+}
+static inline _AL_int128 _AL_int128_and(_AL_int128 a, _AL_int128 b) {
+  return _AL_int128_make(a.hi & b.hi, a.lo & b.lo);
+}
+static inline _AL_int128 _AL_int128_or(_AL_int128 a, _AL_int128 b) {
+  return _AL_int128_make(a.hi | b.hi, a.lo | b.lo);
+}
+static inline _AL_int128 _AL_int128_xor(_AL_int128 a, _AL_int128 b) {
+  return _AL_int128_make(a.hi ^ b.hi, a.lo ^ b.lo);
+}
+static inline _AL_int128 _AL_int128_not(_AL_int128 a) { return _AL_int128_make(~a.hi, ~a.lo); }
+static inline _AL_int128 _AL_int128_add(_AL_int128 a, _AL_int128 b) {
+  uint64_t lo = a.lo + b.lo;
+  return _AL_int128_make(a.hi + b.hi + (lo < a.lo), lo);
+}
+static inline _AL_int128 _AL_int128_sub(_AL_int128 a, _AL_int128 b) {
+  return _AL_int128_make(a.hi - b.hi - (a.lo < b.lo), a.lo - b.lo);
+}
+static inline _AL_int128 _AL_int128_neg(_AL_int128 a) {
+  return _AL_int128_add(_AL_int128_not(a), _AL_int128_from_u64(1));
+}
+static inline _AL_int128 _AL_int128_mul64(uint64_t a, uint64_t b) {
+  uint64_t a_lo = (uint32_t)a, a_hi = a >> 32, b_lo = (uint32_t)b, b_hi = b >> 32;
+  uint64_t p0 = a_lo * b_lo, p1 = a_lo * b_hi, p2 = a_hi * b_lo, p3 = a_hi * b_hi;
+  uint64_t mid = (p0 >> 32) + (uint32_t)p1 + (uint32_t)p2;
+  return _AL_int128_make(p3 + (p1 >> 32) + (p2 >> 32) + (mid >> 32), (mid << 32) | (uint32_t)p0);
+}
+static inline _AL_int128 _AL_int128_mul(_AL_int128 a, _AL_int128 b) {
+  _AL_int128 r = _AL_int128_mul64(a.lo, b.lo);
+  r.hi += a.lo * b.hi + a.hi * b.lo;
+  return r;
+}
+static inline _AL_int128 _AL_int128_shl(_AL_int128 a, _AL_int128 b) {
+  unsigned n = (unsigned)(b.lo & 127);
+  if (n == 0) return a;
+  if (n >= 64) return _AL_int128_make(a.lo << (n - 64), 0);
+  return _AL_int128_make((a.hi << n) | (a.lo >> (64 - n)), a.lo << n);
+}
+static inline _AL_int128 _AL_int128_lshr(_AL_int128 a, _AL_int128 b) {
+  unsigned n = (unsigned)(b.lo & 127);
+  if (n == 0) return a;
+  if (n >= 64) return _AL_int128_make(0, a.hi >> (n - 64));
+  return _AL_int128_make(a.hi >> n, (a.lo >> n) | (a.hi << (64 - n)));
+}
+static inline _AL_int128 _AL_int128_ashr(_AL_int128 a, _AL_int128 b) {
+  unsigned n = (unsigned)(b.lo & 127);
+  uint64_t sign = _AL_int128_is_neg(a) ? ~(uint64_t)0 : 0;
+  if (n == 0) return a;
+  if (n >= 64) return _AL_int128_make(sign, (uint64_t)((int64_t)a.hi >> (n - 64)));
+  return _AL_int128_make((uint64_t)((int64_t)a.hi >> n), (a.lo >> n) | (a.hi << (64 - n)));
+}
+static inline _AL_int128 _AL_int128_udivmod(_AL_int128 a, _AL_int128 b, _AL_int128 *rem) {
+  _AL_int128 q = _AL_int128_from_u64(0), r = _AL_int128_from_u64(0);
+  int i;
+  if ((b.lo | b.hi) == 0) {
+    /* Same behavior as the native division by zero */
+    q.lo = a.lo / (b.lo | b.hi);
+  }
+  for (i = 127; i >= 0; i--) {
+    r = _AL_int128_shl(r, _AL_int128_from_u64(1));
+    r.lo |= (i >= 64 ? a.hi >> (i - 64) : a.lo >> i) & 1;
+    if (!_AL_int128_ult(r, b)) {
+      r = _AL_int128_sub(r, b);
+      if (i >= 64) q.hi |= (uint64_t)1 << (i - 64); else q.lo |= (uint64_t)1 << i;
+    }
+  }
+  if (rem) *rem = r;
+  return q;
+}
+static inline _AL_int128 _AL_int128_udiv(_AL_int128 a, _AL_int128 b) {
+  return _AL_int128_udivmod(a, b, 0);
+}
+static inline _AL_int128 _AL_int128_umod(_AL_int128 a, _AL_int128 b) {
+  _AL_int128 r;
+  _AL_int128_udivmod(a, b, &r);
+  return r;
+}
+static inline _AL_int128 _AL_int128_abs(_AL_int128 a) {
+  return _AL_int128_is_neg(a) ? _AL_int128_neg(a) : a;
+}
+static inline _AL_int128 _AL_int128_sdiv(_AL_int128 a, _AL_int128 b) {
+  _AL_int128 q = _AL_int128_udiv(_AL_int128_abs(a), _AL_int128_abs(b));
+  return _AL_int128_is_neg(a) != _AL_int128_is_neg(b) ? _AL_int128_neg(q) : q;
+}
+static inline _AL_int128 _AL_int128_smod(_AL_int128 a, _AL_int128 b) {
+  _AL_int128 r = _AL_int128_umod(_AL_int128_abs(a), _AL_int128_abs(b));
+  return _AL_int128_is_neg(a) ? _AL_int128_neg(r) : r;
+}
+static inline double _AL_int128_to_f64u(_AL_int128 a) {
+  return (double)a.hi * 18446744073709551616.0 + (double)a.lo;
+}
+static inline double _AL_int128_to_f64s(_AL_int128 a) {
+  return _AL_int128_is_neg(a) ? -_AL_int128_to_f64u(_AL_int128_neg(a)) : _AL_int128_to_f64u(a);
+}
+static inline _AL_int128 _AL_int128_from_f64u(double a) {
+  uint64_t hi = (uint64_t)(a / 18446744073709551616.0);
+  return _AL_int128_make(hi, (uint64_t)(a - (double)hi * 18446744073709551616.0));
+}
+static inline _AL_int128 _AL_int128_from_f64s(double a) {
+  return a < 0 ? _AL_int128_neg(_AL_int128_from_f64u(-a)) : _AL_int128_from_f64u(a);
+}
+static inline void _AL_int128_compound(_AL_int128 *a, _AL_int128 b, _AL_int128_op op) {
+  *a = op(*a, b);
+}
+static inline int _AL_int128_uadd_overflow(_AL_int128 a, _AL_int128 b, _AL_int128 *r) {
+  *r = _AL_int128_add(a, b);
+  return _AL_int128_ult(*r, a);
+}
+static inline int _AL_int128_sadd_overflow(_AL_int128 a, _AL_int128 b, _AL_int128 *r) {
+  *r = _AL_int128_add(a, b);
+  return _AL_int128_is_neg(a) == _AL_int128_is_neg(b) && _AL_int128_is_neg(*r) != _AL_int128_is_neg(a);
+}
+static inline int _AL_int128_usub_overflow(_AL_int128 a, _AL_int128 b, _AL_int128 *r) {
+  *r = _AL_int128_sub(a, b);
+  return _AL_int128_ult(a, b);
+}
+static inline int _AL_int128_ssub_overflow(_AL_int128 a, _AL_int128 b, _AL_int128 *r) {
+  *r = _AL_int128_sub(a, b);
+  return _AL_int128_is_neg(a) != _AL_int128_is_neg(b) && _AL_int128_is_neg(*r) != _AL_int128_is_neg(a);
+}
+static inline int _AL_int128_umul_overflow(_AL_int128 a, _AL_int128 b, _AL_int128 *r) {
+  *r = _AL_int128_mul(a, b);
+  return (a.lo | a.hi) != 0 && !_AL_int128_eq(_AL_int128_udiv(*r, a), b);
+}
+static inline int _AL_int128_smul_overflow(_AL_int128 a, _AL_int128 b, _AL_int128 *r) {
+  _AL_int128 min = _AL_int128_make((uint64_t)1 << 63, 0), minus_one = _AL_int128_from_i64(-1);
+  *r = _AL_int128_mul(a, b);
+  if ((a.lo | a.hi) == 0 || (b.lo | b.hi) == 0) return 0;
+  if ((_AL_int128_eq(a, minus_one) && _AL_int128_eq(b, min))
+      || (_AL_int128_eq(b, minus_one) && _AL_int128_eq(a, min))) return 1;
+  return !_AL_int128_eq(_AL_int128_sdiv(*r, a), b);
+}
+"#;
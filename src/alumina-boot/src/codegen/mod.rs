@@ -1,12 +1,17 @@
+pub mod float16;
 pub mod functions;
+pub mod int128;
+pub mod snapshot;
 pub mod types;
 
-use crate::codegen::functions::FunctionWriter;
+use crate::ast::{Attribute, BuiltinType};
+use crate::codegen::functions::{link_name, write_function_signature, FunctionWriter};
 use crate::codegen::types::TypeWriter;
 use crate::common::{AluminaError, HashMap, Incrementable};
-use crate::global_ctx::GlobalCtx;
+use crate::global_ctx::{CDialect, GlobalCtx};
 use crate::ir::layout::Layouter;
-use crate::ir::{IRItem, IRItemP, IrId, Ty, TyP};
+use crate::ir::{Function, IRItem, IRItemP, IrCtx, IrId, Ty, TyP};
+use crate::utils::{bounded_debug, MAX_DEBUG_LEN};
 
 use bumpalo::Bump;
 
@@ -21,6 +26,118 @@ macro_rules! w {
 
 pub(crate) use w;
 
+/// Keywords of C++ that are valid identifiers in C
+const CXX_KEYWORDS: &[&str] = &[
+    "alignas",
+    "alignof",
+    "and",
+    "and_eq",
+    "asm",
+    "bitand",
+    "bitor",
+    "bool",
+    "catch",
+    "char8_t",
+    "char16_t",
+    "char32_t",
+    "class",
+    "compl",
+    "concept",
+    "consteval",
+    "constexpr",
+    "constinit",
+    "const_cast",
+    "co_await",
+    "co_return",
+    "co_yield",
+    "decltype",
+    "delete",
+    "dynamic_cast",
+    "explicit",
+    "export",
+    "false",
+    "friend",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "not_eq",
+    "nullptr",
+    "operator",
+    "or",
+    "or_eq",
+    "private",
+    "protected",
+    "public",
+    "reinterpret_cast",
+    "requires",
+    "static_assert",
+    "static_cast",
+    "template",
+    "this",
+    "thread_local",
+    "throw",
+    "true",
+    "try",
+    "typeid",
+    "typename",
+    "using",
+    "virtual",
+    "wchar_t",
+    "xor",
+    "xor_eq",
+];
+
+/// Per-function optimization attributes. Clang does not support `optimize`, but has
+/// its own attributes for the common cases.
+const OPTIMIZE_MACROS: &str = "#ifdef __clang__
+#define _AL_OPTIMIZE_NONE __attribute__((optnone))
+#define _AL_OPTIMIZE_SIZE __attribute__((minsize))
+#define _AL_OPTIMIZE_SPEED
+#else
+#define _AL_OPTIMIZE_NONE __attribute__((optimize(\"O0\")))
+#define _AL_OPTIMIZE_SIZE __attribute__((optimize(\"Os\")))
+#define _AL_OPTIMIZE_SPEED __attribute__((optimize(\"O3\")))
+#endif
+";
+
+/// Every function and global in its own section for `-Zlto-friendly`, so that the linker
+/// can drop the unused ones with `--gc-sections`. Only ELF has a naming convention for
+/// these sections.
+const SECTION_MACRO: &str = "#ifdef __ELF__
+#define _AL_SECTION(name) __attribute__((section(name)))
+#else
+#define _AL_SECTION(name)
+#endif
+";
+
+/// For functions compiled without builtins (`-Zno-builtins`, `#[no_builtins]`). Keeps the
+/// C compiler from recognizing loops as `memcpy`/`memset` and turning them into calls, and
+/// a byte-wise copy that the `MemCopy` intrinsic is lowered to instead of `__builtin_memcpy`
+/// (it handles overlapping ranges too, so it also replaces `__builtin_memmove`).
+const NO_BUILTINS_MACROS: &str = "#if defined(__clang__)
+#define _AL_NO_BUILTINS __attribute__((no_builtin))
+#elif defined(__GNUC__)
+#define _AL_NO_BUILTINS __attribute__((optimize(\"no-tree-loop-distribute-patterns\")))
+#else
+#define _AL_NO_BUILTINS
+#endif
+static _AL_NO_BUILTINS __attribute__((unused)) void _AL_copy_bytes(void *dst, const void *src, size_t len) {
+  unsigned char *d = (unsigned char *)dst;
+  const unsigned char *s = (const unsigned char *)src;
+  if (d < s) {
+    for (size_t i = 0; i < len; i++) d[i] = s[i];
+  } else {
+    for (size_t i = len; i > 0; i--) d[i - 1] = s[i - 1];
+  }
+}
+";
+
+pub fn is_cxx_keyword(name: &str) -> bool {
+    CXX_KEYWORDS.contains(&name)
+}
+
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum CName<'gen> {
     Native(&'gen str),
@@ -46,25 +163,43 @@ impl<'gen> CName<'gen> {
 
 pub struct CodegenCtx<'ir, 'gen> {
     global_ctx: GlobalCtx,
+    ir: &'ir IrCtx<'ir>,
     layouter: Layouter<'ir>,
     id_map: RefCell<HashMap<IrId, CName<'gen>>>,
     type_map: RefCell<HashMap<TyP<'ir>, CName<'gen>>>,
     counter: Cell<usize>,
     arena: Bump,
+    soft_int128: bool,
+    soft_float16: bool,
+    soft_bfloat16: bool,
+    optimize_macros: Cell<bool>,
+    no_builtins_macros: Cell<bool>,
+    lto_friendly: bool,
+    no_builtins: bool,
+    trap_on_unreachable: bool,
 }
 
 impl<'ir, 'gen> CodegenCtx<'ir, 'gen>
 where
     'ir: 'gen,
 {
-    pub fn new(global_ctx: GlobalCtx) -> Self {
+    pub fn new(global_ctx: GlobalCtx, ir: &'ir IrCtx<'ir>) -> Self {
         Self {
+            ir,
+            soft_int128: global_ctx.soft_int128(),
+            soft_float16: global_ctx.soft_float16(),
+            soft_bfloat16: global_ctx.soft_bfloat16(),
+            lto_friendly: global_ctx.has_option("lto-friendly"),
+            no_builtins: global_ctx.has_option("no-builtins"),
+            trap_on_unreachable: global_ctx.has_option("trap-on-unreachable"),
             layouter: Layouter::new(global_ctx.clone()),
             global_ctx,
             arena: Bump::new(),
             id_map: RefCell::new(HashMap::default()),
             type_map: RefCell::new(HashMap::default()),
             counter: Cell::new(0),
+            optimize_macros: Cell::new(false),
+            no_builtins_macros: Cell::new(false),
         }
     }
 
@@ -100,8 +235,12 @@ where
         }
 
         let map = self.type_map.borrow();
-        *map.get(typ)
-            .unwrap_or_else(|| panic!("type {:?} was not registered", typ))
+        *map.get(typ).unwrap_or_else(|| {
+            panic!(
+                "type {} was not registered",
+                bounded_debug(&typ, MAX_DEBUG_LEN)
+            )
+        })
     }
 
     pub fn get_type_maybe(&self, typ: TyP<'ir>) -> Option<CName<'gen>> {
@@ -109,9 +248,69 @@ where
         map.get(typ).copied()
     }
 
+    pub fn ir(&self) -> &'ir IrCtx<'ir> {
+        self.ir
+    }
+
     pub fn make_id(&self) -> usize {
         self.counter.increment()
     }
+
+    /// Whether compound literals and designated initializers can be used
+    pub fn aggregate_literals(&self) -> bool {
+        self.global_ctx.c_dialect().has_aggregate_literals()
+    }
+
+    /// See `int128.rs`
+    pub fn soft_int128(&self) -> bool {
+        self.soft_int128
+    }
+
+    /// See `float16.rs`
+    pub fn soft_float16(&self, kind: BuiltinType) -> bool {
+        match kind {
+            BuiltinType::F16 => self.soft_float16,
+            BuiltinType::BF16 => self.soft_bfloat16,
+            _ => false,
+        }
+    }
+
+    /// Marks `OPTIMIZE_MACROS` as needed in the generated code
+    pub fn use_optimize_macros(&self) {
+        self.optimize_macros.set(true);
+    }
+
+    /// Marks `NO_BUILTINS_MACROS` as needed in the generated code
+    pub fn use_no_builtins_macros(&self) {
+        self.no_builtins_macros.set(true);
+    }
+
+    /// Whether the function must not contain implicit calls to `memcpy` and friends, see
+    /// `NO_BUILTINS_MACROS`
+    pub fn no_builtins(&self, item: &Function<'ir>) -> bool {
+        self.no_builtins || item.attributes.contains(&Attribute::NoBuiltins)
+    }
+
+    /// The C expression that unreachable code is lowered to. With `-Ztrap-on-unreachable`,
+    /// reaching it aborts the program instead of being undefined behavior.
+    pub fn unreachable_expr(&self) -> &'static str {
+        if self.trap_on_unreachable {
+            "__builtin_trap()"
+        } else {
+            "__builtin_unreachable()"
+        }
+    }
+
+    /// Whether all items except the exported ones should have internal linkage (even in
+    /// debug builds) and be placed in their own sections, see `SECTION_MACRO`
+    pub fn lto_friendly(&self) -> bool {
+        self.lto_friendly
+    }
+
+    /// Whether the generated code needs to be valid C++ as well
+    pub fn cxx_compatible(&self) -> bool {
+        self.global_ctx.c_dialect() == CDialect::Cxx
+    }
 }
 
 impl Display for CName<'_> {
@@ -127,11 +326,19 @@ impl Display for CName<'_> {
     }
 }
 
-pub fn codegen(global_ctx: GlobalCtx, items: &[IRItemP<'_>]) -> Result<String, AluminaError> {
+/// Generates the C code for the items in the given order. `banners` are the positions in
+/// `items` where a new source module starts (with its path), each of them gets a banner
+/// comment before the function bodies of the module.
+pub fn codegen<'ir>(
+    global_ctx: GlobalCtx,
+    ir: &'ir IrCtx<'ir>,
+    items: &[IRItemP<'ir>],
+    banners: &[(usize, String)],
+) -> Result<String, AluminaError> {
     // Empirically, ~600 bytes per item, round it up to 1000 to minimize reallocations
     let size_estimate = 1000 * items.len();
 
-    let ctx = CodegenCtx::new(global_ctx);
+    let ctx = CodegenCtx::new(global_ctx, ir);
     let type_writer = TypeWriter::new(&ctx, size_estimate);
 
     let mut function_writer = FunctionWriter::new(&ctx, &type_writer, size_estimate);
@@ -145,7 +352,12 @@ pub fn codegen(global_ctx: GlobalCtx, items: &[IRItemP<'_>]) -> Result<String, A
         }
     }
 
-    for item in items {
+    let mut banners = banners.iter().peekable();
+    for (idx, item) in items.iter().enumerate() {
+        if let Some((_, module)) = banners.next_if(|(start, _)| *start == idx) {
+            function_writer.write_module_banner(module);
+        }
+
         match item.get().unwrap() {
             IRItem::Function(f) => function_writer.write_function_body(item.id, f)?,
             IRItem::Const(t) => function_writer.write_const(item.id, t)?,
@@ -156,6 +368,9 @@ pub fn codegen(global_ctx: GlobalCtx, items: &[IRItemP<'_>]) -> Result<String, A
     let mut buf = String::with_capacity(size_estimate);
     writeln!(buf, "#include <stdint.h>").unwrap();
     writeln!(buf, "#include <stddef.h>").unwrap();
+    if ctx.cxx_compatible() {
+        writeln!(buf, "#include <stdbool.h>").unwrap();
+    }
     writeln!(
         buf,
         "#pragma clang diagnostic ignored \"-Wunknown-warning-option\""
@@ -177,8 +392,87 @@ pub fn codegen(global_ctx: GlobalCtx, items: &[IRItemP<'_>]) -> Result<String, A
         "#pragma GCC diagnostic ignored \"-Wbuiltin-declaration-mismatch\""
     )
     .unwrap();
+    if ctx.optimize_macros.get() {
+        buf.push_str(OPTIMIZE_MACROS);
+    }
+    if ctx.lto_friendly() {
+        buf.push_str(SECTION_MACRO);
+    }
+    if ctx.no_builtins_macros.get() {
+        buf.push_str(NO_BUILTINS_MACROS);
+    }
+    if ctx.cxx_compatible() {
+        writeln!(buf, "#ifdef __cplusplus\nextern \"C\" {{\n#endif").unwrap();
+    }
     type_writer.write(&mut buf);
     function_writer.write(&mut buf);
+    if ctx.cxx_compatible() {
+        writeln!(buf, "\n#ifdef __cplusplus\n}}\n#endif").unwrap();
+    }
 
     Ok(buf)
 }
+
+/// An externally visible symbol of the generated code
+pub struct ExportedSymbol<'ir> {
+    pub item: IRItemP<'ir>,
+    pub symbol: String,
+    pub prototype: String,
+}
+
+/// Lists the functions that are visible outside of the generated C translation unit
+/// (`#[export]` and functions with an explicit link name) with their C prototypes.
+pub fn exported_symbols<'ir>(
+    global_ctx: GlobalCtx,
+    ir: &'ir IrCtx<'ir>,
+    items: &[IRItemP<'ir>],
+) -> Result<Vec<ExportedSymbol<'ir>>, AluminaError> {
+    let ctx = CodegenCtx::new(global_ctx, ir);
+    let type_writer = TypeWriter::new(&ctx, 0);
+    let mut function_writer = FunctionWriter::new(&ctx, &type_writer, 0);
+
+    // Declarations register the names of all the items and types
+    for item in items {
+        match item.get().unwrap() {
+            IRItem::Function(f) => function_writer.write_function_decl(item.id, f)?,
+            IRItem::Static(t) => function_writer.write_static_decl(item.id, t)?,
+            IRItem::Const(t) => function_writer.write_const_decl(item.id, t)?,
+            _ => {}
+        }
+    }
+
+    let mut symbols = Vec::new();
+    for item in items {
+        let IRItem::Function(f) = item.get().unwrap() else {
+            continue;
+        };
+
+        let link_name = link_name(f);
+        if f.body.get().is_none()
+            || !(f.attributes.contains(&Attribute::Export) || link_name.is_some())
+        {
+            continue;
+        }
+
+        let mut prototype = String::new();
+        write_function_signature(&ctx, &mut prototype, item.id, f, false, false, None)?;
+
+        let symbol = match (link_name, ctx.get_name(item.id)) {
+            (Some(name), _) => name.trim_matches('"').to_string(),
+            // C++ keyword, see `FunctionWriter::write_function_decl`
+            (None, CName::Mangled(name, _)) => {
+                write!(prototype, " asm(\"{}\")", name).unwrap();
+                name.to_string()
+            }
+            (None, name) => name.to_string(),
+        };
+
+        symbols.push(ExportedSymbol {
+            item,
+            symbol,
+            prototype: format!("{};", prototype.trim()),
+        });
+    }
+
+    Ok(symbols)
+}
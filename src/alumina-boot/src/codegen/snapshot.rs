@@ -0,0 +1,50 @@
+//! Normalized C output for codegen snapshot tests (`--emit c-snapshot`)
+//!
+//! The generated code is compared against checked-in snapshots, so anything that changes
+//! when unrelated code changes is normalized away:
+//!
+//! - the preamble (includes, pragmas and macro definitions) is left out,
+//! - the ids in mangled names (`_AL3foo1234`, `_AL01234`) are replaced by sequential numbers
+//!   in the order in which they first appear.
+
+use crate::common::HashMap;
+use crate::utils::regex;
+
+use std::fmt::Write;
+
+pub fn normalize(code: &str) -> String {
+    let mut ids: HashMap<String, usize> = HashMap::default();
+    let mut result = String::with_capacity(code.len());
+
+    let body = code.lines().skip_while(|line| {
+        let line = line.trim_start();
+        line.is_empty() || line.starts_with('#')
+    });
+
+    for line in body {
+        let mut last = 0;
+        for captures in regex!(r"_AL(\d+)(\w*)").captures_iter(line) {
+            let whole = captures.get(0).unwrap();
+            let rest = captures.get(2).unwrap().as_str();
+            let Some(name) = captures[1]
+                .parse::<usize>()
+                .ok()
+                .and_then(|len| rest.get(..len))
+            else {
+                continue;
+            };
+
+            let id = &rest[name.len()..];
+            let next = ids.len();
+            let id = *ids.entry(id.to_string()).or_insert(next);
+
+            result.push_str(&line[last..whole.start()]);
+            write!(result, "_AL{}{}{}", name.len(), name, id).unwrap();
+            last = whole.end();
+        }
+        result.push_str(&line[last..]);
+        result.push('\n');
+    }
+
+    result
+}
@@ -1,17 +1,65 @@
 use crate::ast::{Attribute, BuiltinType};
+use crate::codegen::float16::{float16_type, FLOAT16_PRELUDE};
+use crate::codegen::int128::{INT128_PRELUDE, INT128_TYPE};
 use crate::codegen::{w, CName, CodegenCtx};
 use crate::common::{AluminaError, CodeErrorBuilder, HashSet};
 use crate::ir::{Closure, IRItem, Ty, TyP};
+use crate::utils::{bounded_debug, MAX_DEBUG_LEN};
 
 use std::cell::RefCell;
 use std::fmt::Write;
 
+/// Custom alignment, whether the struct is packed and whether it is transparent
+pub(super) fn struct_attributes(attributes: &[Attribute]) -> (Option<usize>, bool, bool) {
+    let mut alignment = None;
+    let mut is_packed = false;
+    let mut is_transparent = false;
+
+    for attr in attributes {
+        match attr {
+            Attribute::Align(val) => alignment = Some(*val),
+            Attribute::Packed => is_packed = true,
+            Attribute::Transparent => is_transparent = true,
+            _ => {}
+        }
+    }
+
+    (alignment, is_packed, is_transparent)
+}
+
+/// Text of the `#[c_attribute(...)]` attributes, in the order they were given
+fn c_attributes(attributes: &[Attribute]) -> impl Iterator<Item = &str> {
+    attributes.iter().filter_map(|a| match a {
+        Attribute::CAttribute(size, value) => {
+            Some(std::str::from_utf8(&value.as_slice()[..*size]).unwrap())
+        }
+        _ => None,
+    })
+}
+
+/// Writes the pragmas among the `#[c_attribute(...)]` attributes on lines of their own
+/// and returns the rest, which are to be placed in the declaration itself.
+pub(super) fn write_c_attributes(buf: &mut String, attributes: &[Attribute]) -> String {
+    let mut inline = String::new();
+    for attr in c_attributes(attributes) {
+        if attr.starts_with('#') {
+            w!(buf, "\n{}\n", attr);
+        } else {
+            w!(inline, "{} ", attr);
+        }
+    }
+
+    inline
+}
+
 struct TypeWriterInner<'ir, 'gen> {
     ctx: &'gen CodegenCtx<'ir, 'gen>,
     type_decls: String,
     type_bodies: String,
     body_map: HashSet<TyP<'ir>>,
     needs_body: HashSet<TyP<'ir>>,
+    has_int128_prelude: bool,
+    has_float16_prelude: bool,
 }
 
 pub struct TypeWriter<'ir, 'gen> {
@@ -27,6 +75,8 @@ impl<'ir, 'gen> TypeWriter<'ir, 'gen> {
                 type_bodies: String::with_capacity(size_estimate / 2),
                 body_map: HashSet::default(),
                 needs_body: HashSet::default(),
+                has_int128_prelude: false,
+                has_float16_prelude: false,
             }),
         }
     }
@@ -59,6 +109,20 @@ impl<'ir, 'gen> TypeWriterInner<'ir, 'gen> {
         match ty {
             Ty::Builtin(a) if !body_only => {
                 let name = match a {
+                    BuiltinType::U128 | BuiltinType::I128 if self.ctx.soft_int128() => {
+                        if !self.has_int128_prelude {
+                            self.type_decls.push_str(INT128_PRELUDE);
+                            self.has_int128_prelude = true;
+                        }
+                        CName::Native(INT128_TYPE)
+                    }
+                    BuiltinType::F16 | BuiltinType::BF16 if self.ctx.soft_float16(*a) => {
+                        if !self.has_float16_prelude {
+                            self.type_decls.push_str(FLOAT16_PRELUDE);
+                            self.has_float16_prelude = true;
+                        }
+                        CName::Native(float16_type(*a))
+                    }
                     BuiltinType::U128 => {
                         let name = CName::Mangled("uint128", self.ctx.make_id());
                         w!(self.type_decls, "typedef unsigned __int128 {};\n", name);
@@ -69,15 +133,18 @@ impl<'ir, 'gen> TypeWriterInner<'ir, 'gen> {
                         w!(self.type_decls, "typedef signed __int128 {};\n", name);
                         name
                     }
+                    BuiltinType::Bool if self.ctx.cxx_compatible() => CName::from_native("bool"),
                     _ => CName::from_native(match a {
                         BuiltinType::U8 => "uint8_t",
                         BuiltinType::U16 => "uint16_t",
-                        BuiltinType::U32 => "uint32_t",
+                        BuiltinType::U32 | BuiltinType::Char => "uint32_t",
                         BuiltinType::U64 => "uint64_t",
                         BuiltinType::I8 => "int8_t",
                         BuiltinType::I16 => "int16_t",
                         BuiltinType::I32 => "int32_t",
                         BuiltinType::I64 => "int64_t",
+                        BuiltinType::F16 => "_Float16",
+                        BuiltinType::BF16 => "__bf16",
                         BuiltinType::F32 => "float",
                         BuiltinType::F64 => "double",
                         BuiltinType::USize => "size_t",
@@ -189,11 +256,17 @@ impl<'ir, 'gen> TypeWriterInner<'ir, 'gen> {
                     self.ctx.get_type(ret),
                     name
                 );
+                let mut has_args = false;
                 for (i, elem) in args.iter().filter(|f| !f.is_zero_sized()).enumerate() {
                     if i > 0 {
                         w!(self.type_decls, ", ");
                     }
                     w!(self.type_decls, "{0}", self.ctx.get_type(elem));
+                    has_args = true;
+                }
+                // An empty parameter list means unspecified parameters before C23
+                if !has_args {
+                    w!(self.type_decls, "void");
                 }
                 w!(self.type_decls, ");\n");
             }
@@ -236,21 +309,14 @@ impl<'ir, 'gen> TypeWriterInner<'ir, 'gen> {
                     }
 
                     let mut attributes = " ".to_string();
-                    let mut is_transparent = false;
-                    let mut is_packed = false;
-                    let mut alignment = None;
-
-                    for attr in s.attributes {
-                        if let Attribute::Align(val) = attr {
-                            alignment = Some(*val);
-                        }
-                        if let Attribute::Packed = attr {
-                            is_packed = true;
-                            w!(attributes, "__attribute__((packed)) ");
-                        }
-                        if let Attribute::Transparent = attr {
-                            is_transparent = true;
-                        }
+                    w!(
+                        attributes,
+                        "{}",
+                        write_c_attributes(&mut self.type_bodies, s.attributes)
+                    );
+                    let (alignment, is_packed, is_transparent) = struct_attributes(s.attributes);
+                    if is_packed {
+                        w!(attributes, "__attribute__((packed)) ");
                     }
 
                     let (agg_layout, field_layout) = self
@@ -311,8 +377,20 @@ impl<'ir, 'gen> TypeWriterInner<'ir, 'gen> {
                         }
                     }
                     w!(self.type_bodies, "}};\n");
+
+                    // Make the C compiler verify that `#[repr(C)]` types have the layout we
+                    // computed (without `_Static_assert`, which older compilers lack).
+                    if s.attributes.contains(&Attribute::ReprC) {
+                        w!(
+                            self.type_bodies,
+                            "typedef char {}[(sizeof({}) == {}) ? 1 : -1];\n",
+                            CName::Id(self.ctx.make_id()),
+                            name,
+                            agg_layout.size
+                        );
+                    }
                 }
-                _ => panic!("unimplemented: {:?}", ty),
+                _ => panic!("unimplemented: {}", bounded_debug(&ty, MAX_DEBUG_LEN)),
             },
             Ty::Tuple(items) => {
                 let name = self.ctx.get_type(ty);
@@ -21,11 +21,35 @@ macro_rules! ice {
 
 pub(crate) use ice;
 
-pub type HashMap<K, V> = rustc_hash::FxHashMap<K, V>;
-pub type HashSet<T> = rustc_hash::FxHashSet<T>;
-pub type IndexMap<K, V> =
-    indexmap::IndexMap<K, V, std::hash::BuildHasherDefault<rustc_hash::FxHasher>>;
-pub type IndexSet<K> = indexmap::IndexSet<K, std::hash::BuildHasherDefault<rustc_hash::FxHasher>>;
+#[cfg(all(feature = "ahash", feature = "randomized-hash"))]
+compile_error!("features `ahash` and `randomized-hash` are mutually exclusive");
+
+/// Hasher for all the hash maps and sets in the compiler.
+///
+/// FxHash is fast, but it is not keyed, so adversarial input (e.g. from a fuzzer) can
+/// make many names or types collide. The `randomized-hash` feature switches to SipHash
+/// with random keys (as in `std`) and the `ahash` feature to aHash with random keys.
+/// With either of them, the iteration order of maps and sets changes between runs.
+#[cfg(not(any(feature = "ahash", feature = "randomized-hash")))]
+pub type BuildHasher = std::hash::BuildHasherDefault<rustc_hash::FxHasher>;
+#[cfg(feature = "randomized-hash")]
+pub type BuildHasher = std::collections::hash_map::RandomState;
+#[cfg(feature = "ahash")]
+pub type BuildHasher = ahash::RandomState;
+
+/// Name of the hasher in use, reported with `--timings` to compare their performance
+pub const HASHER_NAME: &str = if cfg!(feature = "ahash") {
+    "aHash (randomized)"
+} else if cfg!(feature = "randomized-hash") {
+    "SipHash (randomized)"
+} else {
+    "FxHash"
+};
+
+pub type HashMap<K, V> = std::collections::HashMap<K, V, BuildHasher>;
+pub type HashSet<T> = std::collections::HashSet<T, BuildHasher>;
+pub type IndexMap<K, V> = indexmap::IndexMap<K, V, BuildHasher>;
+pub type IndexSet<K> = indexmap::IndexSet<K, BuildHasher>;
 
 /// A clonable (Rc) wrapper using pointer equality for `Hash` and `PartialEq`
 pub struct ByRef<T>(pub Rc<T>);
@@ -81,12 +105,16 @@ pub enum CodeErrorKind {
     ParseError(String),
     #[error("syntax error: missing `{}`", .0)]
     ParseErrorMissing(String),
+    #[error("unterminated raw string literal (missing `{}`)", .0)]
+    UnterminatedRawString(String),
     #[error("unexpected `{}` here", .0)]
     Unexpected(String),
     #[error("could not resolve the path `{}`", .0)]
     UnresolvedPath(String),
     #[error("cycle detected while resolving aliases")]
     CycleDetected,
+    #[error("type alias cycle: `{}`", .0)]
+    TypeAliasCycle(String),
     #[error("duplicate name `{}`", .0)]
     DuplicateName(String),
     #[error("duplicate name `{}` ({} cannot shadow a {})", .0, .1, .2)]
@@ -95,7 +123,7 @@ pub enum CodeErrorKind {
     NoAssociatedTypes,
     #[error("invalid literal")]
     InvalidLiteral,
-    #[error("character literals must be exactly one byte")]
+    #[error("character literals must contain exactly one character")]
     InvalidCharLiteral,
     #[error("{} generic parameters expected, {} found" , .0, .1)]
     GenericParamCountMismatch(usize, usize),
@@ -111,12 +139,24 @@ pub enum CodeErrorKind {
     // monomorphization
     #[error("type hint required (type inference)")]
     TypeInferenceFailed,
+    #[error("cannot infer generic parameter `{}` (specify it with `::<...>`)", .0)]
+    GenericParamInferenceFailed(String),
+    #[error("cannot infer the type of the numeric literal ({})", .0)]
+    LiteralTypeInferenceFailed(String),
     #[error("type mismatch: `{}` expected, `{}` found", .0, .1)]
     TypeMismatch(String, String),
+    #[error("type mismatch: `{}` expected, `{}` found (hint: {})", .0, .1, .2)]
+    TypeMismatchWithHint(String, String, String),
     #[error("branches have incompatible types (`{}`, `{}`)", .0, .1)]
     MismatchedBranchTypes(String, String),
     #[error("invalid escape sequence")]
     InvalidEscapeSequence,
+    #[error("unknown escape sequence `\\{}`", .0)]
+    UnknownEscapeSequence(char),
+    #[error("malformed escape sequence `{}`", .0)]
+    MalformedEscapeSequence(String),
+    #[error("`{:#x}` is not a valid Unicode scalar value", .0)]
+    InvalidUnicodeScalar(u32),
     #[error("invalid attribute")]
     InvalidAttribute,
     #[error("invalid attribute ({})", .0)]
@@ -151,6 +191,12 @@ pub enum CodeErrorKind {
     StructLikeExpectedHere,
     #[error("method `{}` not found on `{}`", .0, .1)]
     MethodNotFound(String, String),
+    #[error("method `{}` not found on `{}` (hint: {})", .0, .1, .2)]
+    MethodNotFoundWithHint(String, String, String),
+    #[error("cycle detected while dereferencing for method resolution (`{}`)", .0)]
+    DerefCycle(String),
+    #[error("too many dereferences while resolving a method (limit is {})", .0)]
+    DerefChainTooLong(usize),
     #[error("duplicate enum member")]
     DuplicateEnumMember,
     #[error("cannot be called as a method")]
@@ -175,6 +221,12 @@ pub enum CodeErrorKind {
     UnsupportedABI(String),
     #[error("unknown intrinsic `{}`", .0)]
     UnknownIntrinsic(String),
+    #[error("inline C code is not enabled (pass `-Z c-inline` to allow it)")]
+    CInlineNotEnabled,
+    #[error("invalid inline C code: {}", .0)]
+    InvalidCInline(String),
+    #[error("`{}` is not a register on `{}` (expected a register name, `memory` or `cc`)", .0, .1)]
+    InvalidAsmClobber(String, String),
     #[error("unknown lang item {:?}", .0)]
     UnknownLangItem(Option<String>),
     #[error("this cannot be a lang item")]
@@ -189,6 +241,10 @@ pub enum CodeErrorKind {
     MacroExpected,
     #[error("this expression is not evaluable at compile time ({})", .0)]
     CannotConstEvaluate(ConstEvalErrorKind),
+    #[error("condition of `when` is not evaluable at compile time ({})", .0)]
+    NonConstantWhenCondition(ConstEvalErrorKind),
+    #[error("array length is not evaluable at compile time ({})", .0)]
+    NonConstantArrayLength(ConstEvalErrorKind),
     #[error("values of enum variants can only be integers")]
     InvalidValueForEnumVariant,
     #[error("{}", .0)]
@@ -223,6 +279,8 @@ pub enum CodeErrorKind {
     NotInAFunctionScope,
     #[error("unknown builtin macro `{}`", .0)]
     UnknownBuiltinMacro(String),
+    #[error("`build_timestamp!()` makes the build non-reproducible (pass `-Z build-timestamp` or set `SOURCE_DATE_EPOCH` to enable it)")]
+    BuildTimestampNotEnabled,
     #[error("`{}` is not a protocol", .0)]
     NotAProtocol(String),
     #[error("protocol is not expected here")]
@@ -233,7 +291,23 @@ pub enum CodeErrorKind {
     ProtocolFnsCannotBeExtern,
     #[error("varargs functions can only be extern")]
     VarArgsCanOnlyBeExtern,
-    #[error("type `{}` matches `{}`, which it should not", .0, .1)]
+    #[error("extern functions cannot be `#[const_eval]`")]
+    ExternConstEval,
+    #[error("only functions can have variadic generic parameters")]
+    VariadicNotAllowedHere,
+    #[error("variadic generic parameter must be the last one and cannot have a default")]
+    VariadicPlaceholderNotLast,
+    #[error("variadic generic parameter must be a tuple, found `{}`", .0)]
+    VariadicArgsNotTuple(String),
+    #[error("`for const` binds either an element or an `(index, element)` pair")]
+    InvalidStaticForBinding,
+    #[error("`for const` can only iterate over tuples, found `{}`", .0)]
+    StaticForNotTuple(String),
+    #[error(
+        "closures in a `for const` loop must capture values of the same types on every iteration"
+    )]
+    ClosureCaptureInStaticFor,
+    #[error("type `{0}` matches `{1}`, but the bound `!{1}` requires that it does not")]
     ProtocolMatch(String, String),
     #[error("type `{}` does not match `{}`", .0, .1)]
     ProtocolMismatch(String, String),
@@ -245,6 +319,8 @@ pub enum CodeErrorKind {
     Unimplemented(String),
     #[error("multiple `main` functions found")]
     MultipleMainFunctions,
+    #[error("test harness not found (no `#[test_main]` function, is the sysroot missing?)")]
+    TestHarnessNotFound,
     #[error("type aliases cannot have their own impl block")]
     NoImplForTypedefs,
     #[error("unpopulated symbol")]
@@ -259,6 +335,14 @@ pub enum CodeErrorKind {
     InvalidTypeOperator,
     #[error("transparent structs and unions must have exactly one field")]
     InvalidTransparent,
+    #[error("integer representations (`#[repr(<integer type>)]`) are only valid on enums")]
+    IntegerReprOnStruct,
+    #[error("enum discriminant out of range ({} does not fit into `{}`)", .0, .1)]
+    EnumDiscriminantOutOfRange(String, String),
+    #[error("`#[repr(C)]` types cannot have zero-sized fields (`{}` is zero-sized)", .0)]
+    ReprCZeroSizedField(String),
+    #[error("invalid bitfield: {}", .0)]
+    InvalidBitfield(String),
 
     #[error("cannot determine source span")]
     NoSpanInformation,
@@ -268,13 +352,19 @@ pub enum CodeErrorKind {
     CannotBeATest,
     #[error("test cases must have 0 parameters and return void")]
     InvalidTestCaseSignature,
-    #[error("extern statics cannot have initializers")]
+    #[error("invalid interrupt handler signature ({})", .0)]
+    InvalidInterruptHandlerSignature(String),
+    #[error("interrupt vector {} is already assigned to a different handler", .0)]
+    DuplicateInterruptVector(usize),
+    #[error("extern statics and constants must have a type and cannot have initializers")]
     ExternStaticMustHaveType,
     #[error("extern statics cannot be generic")]
     ExternStaticCannotBeGeneric,
+    #[error("constants must have a value (unless they are `extern`)")]
+    ConstMustHaveValue,
     #[error("can only bind local variables")]
     CanOnlyCloseOverLocals,
-    #[error("anonymous functions that bind environment variables cannot be coerced to a function pointer")]
+    #[error("closures that capture state cannot be coerced to a function pointer")]
     ClosuresAreNotFns,
     #[error("thread local storage is not supported")]
     ThreadLocalNotSupported,
@@ -296,12 +386,47 @@ pub enum CodeErrorKind {
     InvalidFormatString(String),
     #[error("cannot read file `{}`", .0)]
     CannotReadFile(String),
+    #[error("cannot read `{}`: {}", .0, .1)]
+    CannotReadPath(String, String),
+    #[error("cannot infer the module name from `{}` (use `module::name=filename.alu`)", .0)]
+    InvalidModuleFilename(String),
+    #[error("source file is not valid UTF-8 (invalid byte 0x{:02x} at offset {})", .0, .1)]
+    InvalidUtf8(u8, usize),
+    #[error("{} unstable (enable the feature with `#![feature({})]` or `-Zunstable-features={}`)", .0, .1, .1)]
+    UnstableFeature(String, String),
+    #[error("unknown feature `{}`", .0)]
+    UnknownFeature(String),
+    #[error("`#![feature(...)]` can only be used at the top of a file")]
+    FeatureNotAtTopOfFile,
+    #[error("file `{}` is added both as module `{}` and as module `{}`", .0, .1, .2)]
+    DuplicateModuleFile(String, String, String),
+    #[error("module `{}` is added twice (from `{}` and from `{}`)", .0, .1, .2)]
+    DuplicateModulePath(String, String, String),
+    #[error("all the modules of a library have to be under the same root module (found `{}` and `{}`)", .0, .1)]
+    MetaMultipleRoots(String, String),
+    #[error("`{}` cannot be used as library metadata: {}", .0, .1)]
+    InvalidMeta(String, String),
     #[error("type alias must have a target")] // unless it is a blessed builtin :)
     TypedefWithoutTarget,
     #[error("type with infinite size (recursive type without indirection)")]
     TypeWithInfiniteSize,
-    #[error("integer literal out of range ({} does not fit into {})", .0, .1)]
-    IntegerOutOfRange(String, String),
+    #[error(
+        "integer literal out of range ({} does not fit into {}, which holds values from {} to {})",
+        .0, .1, .2, .3
+    )]
+    IntegerOutOfRange(String, String, String, String),
+    #[error("consider using `{}`, which can hold this value", .0)]
+    ConsiderWiderType(String),
+    #[error(
+        "cast from `{}` to `{}` can truncate the value (use `{1}::checked_from` to check that it fits)",
+        .0, .1
+    )]
+    TruncatingCast(String, String),
+    #[error(
+        "cast from `{}` to `{}` can change the sign of the value (use `{1}::checked_from` to check that it fits)",
+        .0, .1
+    )]
+    SignChangingCast(String, String),
     #[error(
         "`#[align(...)]` cannot be used together with `#[packed]` (alignment will always be 1)"
     )]
@@ -322,12 +447,28 @@ pub enum CodeErrorKind {
     ConstPanic(String),
     #[error("cannot generate code for a const-only intrinsic (guard with `std::runtime::in_const_context()`)")]
     ConstOnlyIntrinsic,
+    #[error("function `{}` is defined in multiple impl blocks", .0)]
+    ConflictingAssociatedFn(String),
+    #[error("method `{}` is provided by both `{}` and `{}` mixins", .0, .1, .2)]
+    ConflictingMixinFn(String, String, String),
+    #[error("protocol `{}` is sealed and cannot be mixed in outside of the module that defines it", .0)]
+    SealedProtocolMixin(String),
+    #[error("`{}` is not a valid identifier", .0)]
+    InvalidIdentifier(String),
+    #[error("there is nothing that can be renamed at this position")]
+    NothingToRename,
+    #[error("`{}` is defined in the sysroot and cannot be renamed", .0)]
+    CannotRenameSysroot(String),
+    #[error("renaming to `{}` would conflict with an existing name here", .0)]
+    RenameConflict(String),
 
     // Warnings
     #[error("defer inside a loop: this defered statement will only be executed once")]
     DeferInALoop,
-    #[error("duplicate function name {:?} (this function will shadow a previous one)", .0)]
-    DuplicateNameShadow(String),
+    #[error(
+        "stack allocation inside a loop: the memory is only released when the function returns"
+    )]
+    StackAllocInALoop,
     #[error("field `{}` is not initialized", .0)]
     UninitializedField(String),
     #[error("this is `std::typing::Self`, did you mean the enclosing type?")]
@@ -346,18 +487,75 @@ pub enum CodeErrorKind {
     UnusedImport(String),
     #[error("#[{}({})] refers to a lint that does not currently exist", .0, .1)]
     ImSoMetaEvenThisAcronym(String, String),
+    #[error("`#[{}]` has no effect, there are no such warnings in the code it applies to", .0)]
+    UnusedLintAttribute(String),
+    #[error("unknown attribute `{}` (attributes for external tools can use the `custom::` namespace)", .0)]
+    UnknownAttribute(String),
+    #[error("attribute `{}` {}", .0, .1)]
+    UnexpectedAttributeArguments(String, String),
     #[error("redundant top level block (no attributes)")]
     TopLevelBlockWithoutAttributes,
-    #[error("condition is always `{}`, did you mean to use `when`?", .0)]
-    ConstantCondition(bool),
+    #[error("condition is always `{}`{}, did you mean to use `when`?", .0, .1)]
+    ConstantCondition(bool, String),
     #[error("statement has no effect")]
     PureStatement,
+    #[error("variable may be used before it is initialized")]
+    PossiblyUninitialized,
+    #[error("value assigned here is never read")]
+    UnusedAssignment,
+    #[error("read of a union field other than the one that was last written")]
+    InactiveUnionFieldRead,
+    #[error("`{}` is marked as `#[no_recursion]`, but it can call itself ({})", .0, .1)]
+    UnexpectedRecursion(String, String),
+    #[error("dereference of a null pointer")]
+    NullPointerDereference,
+    #[error("dereference of a dangling pointer")]
+    DanglingPointerDereference,
+    #[error("pointer arithmetic goes out of bounds of the object (offset {} of {} elements)", .0, .1)]
+    PointerOutOfBounds(i128, i128),
+    #[error("{} by zero is undefined behavior", .0)]
+    ConstantDivisionByZero(String),
+    #[error("shift by {} is undefined behavior for `{}` (the amount must be between 0 and {})", .0, .1, .2)]
+    ConstantShiftOverflow(i128, String, u32),
+    #[error("index {} is out of bounds of an array of {} elements", .0, .1)]
+    ConstantIndexOutOfBounds(i128, usize),
+    #[error("`{}` is not a valid value of `{}`", .0, .1)]
+    InvalidEnumValue(String, String),
+    #[error("function body is larger than {} IR nodes", .0)]
+    FunctionTooLarge(usize),
+    #[error("function has conditionals nested more than {} levels deep", .0)]
+    FunctionTooDeeplyNested(usize),
+    #[error("function has more than {} branches", .0)]
+    FunctionTooComplex(usize),
+    #[error("{} `{}` should have a snake_case name (e.g. `{}`)", .0, .1, .2)]
+    NonSnakeCase(String, String, String),
+    #[error("{} `{}` should have a PascalCase name (e.g. `{}`)", .0, .1, .2)]
+    NonPascalCase(String, String, String),
+    #[error("{} `{}` should have an upper case name (e.g. `{}`)", .0, .1, .2)]
+    NonUpperCase(String, String, String),
+    #[error("`{}` is out of date, `{}` has changed since it was written", .0, .1)]
+    StaleMeta(String, String),
+    #[error("unresolved link to `{}` in doc comment", .0)]
+    UnresolvedDocLink(String),
+    #[error("`{}` in the sysroot is skipped: {}", .0, .1)]
+    SysrootEntrySkipped(String, String),
+    #[error("`{}` is the same file as `{}`, module `{}` is only added once", .0, .1, .2)]
+    AliasedModuleFile(String, String, String),
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum Marker {
     Span(Span),
     Monomorphization,
+    /// Instantiation of a generic item (with the concrete arguments)
+    Instantiation(String),
+    /// Instantiation whose name has not been formatted yet (by the id of the IR item). It is
+    /// replaced with [Marker::Instantiation] when an error leaves the instantiation.
+    PendingInstantiation(usize),
+    /// Explanation of why a particular branch of `when` was selected
+    StaticCondition(String),
+    /// Another location that is relevant to the error (e.g. a conflicting definition)
+    RelatedSpan(String, Span),
     ConstEval,
     Root,
 }
@@ -1,15 +1,35 @@
 use crate::ast::maker::AstItemMaker;
-use crate::ast::{AstCtx, MacroCtx};
+use crate::ast::{AstCtx, Attribute, ItemP, MacroCtx, Span};
 use crate::codegen;
-use crate::common::{AluminaError, ArenaAllocatable, CodeErrorBuilder, CodeErrorKind, HashSet};
-use crate::global_ctx::GlobalCtx;
+use crate::common::{
+    AluminaError, ArenaAllocatable, CodeError, CodeErrorBuilder, CodeErrorKind, FileId, HashMap,
+    HashSet,
+};
+use crate::diagnostics::DiagnosticsStack;
+use crate::global_ctx::{GlobalCtx, OutputType};
+use crate::ir::call_graph::CallGraph;
+use crate::ir::cfg::format_cfgs;
 use crate::ir::dce::DeadCodeEliminator;
-use crate::ir::mono::{MonoCtx, Monomorphizer};
-use crate::ir::IrCtx;
+use crate::ir::mono::{item_name, MonoCtx, Monomorphizer};
+#[cfg(feature = "plugins")]
+use crate::ir::plugins::{IrPass, PassCtx};
+use crate::ir::query::TypeAtQuery;
+use crate::ir::stats::mono_stats;
+use crate::ir::text::format_ir;
+use crate::ir::{IRItemP, IrCtx};
+use crate::lints::AstLintCtx;
+use crate::meta;
+use crate::name_index;
 use crate::name_resolution::pass1::FirstPassVisitor;
-use crate::name_resolution::scope::Scope;
-use crate::parser::{AluminaVisitor, ParseCtx};
+use crate::name_resolution::path::Path;
+use crate::name_resolution::scope::{NamedItemKind, Scope};
+use crate::parser::{read_source, AluminaVisitor, ParseCtx};
+use crate::rename;
+use crate::runner;
+use crate::symbols;
+use crate::visitors::cfg_exclusion;
 
+use std::fmt::Write;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
@@ -24,15 +44,108 @@ pub enum Stage {
     Codegen,
 }
 
+/// Kind of output the compiler should produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum EmitKind {
+    /// Generated C code
+    C,
+    /// Generated C code without the preamble and with the ids in mangled names renumbered,
+    /// for comparing against the snapshots in codegen tests
+    CSnapshot,
+    /// Index mapping every resolved name to its definition
+    DefsIndex,
+    /// Classification of every resolved name (for syntax highlighting)
+    SemanticTokens,
+    /// Externally visible symbols with their C prototypes
+    Exports,
+    /// Diagnostics in a stable format, checked against the `//~` comments in the source
+    DiagnosticsGolden,
+    /// All the items in the program with their paths, locations and signatures
+    Symbols,
+    /// The items in the program as a `tags` file for editors
+    Ctags,
+    /// The items in the program as an LSIF dump
+    Lsif,
+    /// Control-flow graphs of the functions selected with `--cfg-filter` in DOT format
+    Cfg,
+    /// The IR of the functions selected with `--cfg-filter` after optimizations, in the
+    /// text format that `--ir-test` reads
+    Ir,
+    /// All the AST items of the program (including the ones that are never used), one
+    /// line per item
+    Ast,
+    /// The steps of the build (inputs, cfg values, C compiler command lines and outputs)
+    /// in JSON, without running any of them
+    BuildPlan,
+    /// Library metadata for using the library with `--extern` (see `meta`)
+    Meta,
+}
+
+impl EmitKind {
+    /// Extension of the file the output is written to when several kinds are emitted at
+    /// once (`--emit c,ir,ast`). `None` for the kinds that can only be emitted on their own.
+    pub fn multi_extension(self) -> Option<&'static str> {
+        match self {
+            EmitKind::C => Some("c"),
+            EmitKind::CSnapshot => Some("c.snap"),
+            EmitKind::Exports => Some("exports"),
+            EmitKind::Cfg => Some("dot"),
+            EmitKind::Ir => Some("ir"),
+            EmitKind::Ast => Some("ast"),
+            EmitKind::Meta => Some("alumina-meta"),
+            _ => None,
+        }
+    }
+}
+
 pub struct Compiler {
     global_ctx: GlobalCtx,
     timings: Vec<(Stage, Duration)>,
+    emit: EmitKind,
+    also_emit: Vec<EmitKind>,
+    additional_outputs: Vec<(EmitKind, String)>,
+    type_query: Option<SourcePosition>,
+    type_query_results: Vec<String>,
+    completion_query: Option<SourcePosition>,
+    rename_query: Option<(SourcePosition, String)>,
+    rename_edits: Vec<Span>,
+    exported_symbols: Vec<String>,
+    modules: Vec<ModuleInfo>,
+    cfg_filters: Vec<String>,
+    doctests: bool,
+    #[cfg(feature = "plugins")]
+    ir_passes: Vec<Box<dyn IrPass>>,
+}
+
+/// A module that was given on the command line or found in the sysroot (`--print-modules`)
+#[derive(Debug, Clone)]
+pub struct ModuleInfo {
+    pub path: String,
+    pub filename: PathBuf,
+    /// The `cfg` predicate that does not hold if the module was excluded by a top-level
+    /// `#![cfg(...)]` attribute
+    pub excluded_by: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SourceFile {
     pub filename: PathBuf,
     pub path: String,
+    /// Source of the file if it does not come from the disk (modules of `--extern`
+    /// libraries)
+    pub contents: Option<String>,
+    /// Ignore the `#!` line at the start of the file (for scripts)
+    pub strip_shebang: bool,
+    /// Part of the sysroot or of an `--extern` library rather than the user's program
+    pub sysroot: bool,
+}
+
+/// A position in a source file (1-based line and column)
+#[derive(Debug, Clone)]
+pub struct SourcePosition {
+    pub filename: PathBuf,
+    pub line: usize,
+    pub column: usize,
 }
 
 macro_rules! timing {
@@ -53,13 +166,160 @@ impl Compiler {
         Self {
             global_ctx,
             timings: Vec::new(),
+            emit: EmitKind::C,
+            also_emit: Vec::new(),
+            additional_outputs: Vec::new(),
+            type_query: None,
+            type_query_results: Vec::new(),
+            completion_query: None,
+            rename_query: None,
+            rename_edits: Vec::new(),
+            exported_symbols: Vec::new(),
+            modules: Vec::new(),
+            cfg_filters: Vec::new(),
+            doctests: false,
+            #[cfg(feature = "plugins")]
+            ir_passes: Vec::new(),
         }
     }
 
+    /// Adds a custom pass that runs on the IR between monomorphization and codegen.
+    #[cfg(feature = "plugins")]
+    #[allow(dead_code)]
+    pub fn add_ir_pass(&mut self, pass: Box<dyn IrPass>) {
+        self.ir_passes.push(pass);
+    }
+
     pub fn timings(&self) -> impl Iterator<Item = (Stage, Duration)> + '_ {
         self.timings.iter().cloned()
     }
 
+    pub fn set_emit(&mut self, emit: EmitKind) {
+        if matches!(emit, EmitKind::DefsIndex | EmitKind::SemanticTokens) {
+            self.global_ctx.enable_name_index();
+        }
+        self.emit = emit;
+    }
+
+    /// Also produce the given kinds of output in the same compilation. Only the kinds with a
+    /// [EmitKind::multi_extension] are supported.
+    pub fn set_also_emit(&mut self, kinds: Vec<EmitKind>) {
+        self.also_emit = kinds;
+    }
+
+    fn emits(&self, kind: EmitKind) -> bool {
+        self.emit == kind || self.also_emit.contains(&kind)
+    }
+
+    /// The outputs requested with [Compiler::set_also_emit], in the same order (available
+    /// after compilation).
+    pub fn additional_outputs(&self) -> &[(EmitKind, String)] {
+        &self.additional_outputs
+    }
+
+    /// Restricts `--emit cfg` (and `ir`) to the functions whose path (e.g. `main::foo`) starts with one
+    /// of the given prefixes. By default, all the functions outside of the sysroot are included.
+    pub fn set_cfg_filters(&mut self, filters: Vec<String>) {
+        self.cfg_filters = filters;
+    }
+
+    /// Also compile the code blocks in the doc comments of the non-sysroot files as tests,
+    /// each in a child module of the file's module (see `doctest`).
+    pub fn set_doctests(&mut self, doctests: bool) {
+        self.doctests = doctests;
+    }
+
+    /// Request the types of the innermost expression at the given position to be collected
+    /// during compilation.
+    pub fn set_type_query(&mut self, position: SourcePosition) {
+        self.type_query = Some(position);
+    }
+
+    pub fn type_query_results(&self) -> &[String] {
+        &self.type_query_results
+    }
+
+    /// Request the names that can be completed at the given position to be collected
+    /// during compilation (they are available from [GlobalCtx::completions] afterwards).
+    /// Code generation is skipped.
+    pub fn set_completion_query(&mut self, position: SourcePosition) {
+        self.completion_query = Some(position);
+    }
+
+    /// Request the name at the given position to be renamed to `new_name`. The program is
+    /// only analyzed, the occurrences to replace are available from [Self::rename_edits]
+    /// after compilation.
+    pub fn set_rename_query(&mut self, position: SourcePosition, new_name: String) {
+        self.global_ctx.enable_name_index();
+        self.rename_query = Some((position, new_name));
+    }
+
+    /// Spans of the names that need to be replaced to perform the requested rename.
+    pub fn rename_edits(&self) -> &[Span] {
+        &self.rename_edits
+    }
+
+    /// Names of the symbols that are exported from a shared library (available after
+    /// compilation with the `SharedLibrary` output type).
+    pub fn exported_symbols(&self) -> &[String] {
+        &self.exported_symbols
+    }
+
+    /// All the modules of the program, including the ones that were excluded by `cfg`
+    /// (available after compilation).
+    pub fn modules(&self) -> &[ModuleInfo] {
+        &self.modules
+    }
+
+    fn resolve_position(
+        &self,
+        position: &SourcePosition,
+        source_files: &[(ParseCtx<'_>, Path<'_>)],
+    ) -> Option<Span> {
+        let wanted = std::fs::canonicalize(&position.filename).ok()?;
+        let (ctx, _) = source_files.iter().find(|(ctx, _)| {
+            self.global_ctx
+                .diag()
+                .get_file_path(ctx.file_id())
+                .and_then(|p| std::fs::canonicalize(p).ok())
+                .as_ref()
+                == Some(&wanted)
+        })?;
+
+        let offset = ctx
+            .source()
+            .split_inclusive('\n')
+            .take(position.line.checked_sub(1)?)
+            .map(|line| line.len())
+            .sum::<usize>()
+            + position.column.checked_sub(1)?;
+
+        Some(Span {
+            start: offset,
+            end: offset,
+            line: position.line - 1,
+            column: position.column - 1,
+            file: ctx.file_id(),
+        })
+    }
+
+    fn resolve_query_position(
+        &self,
+        position: &SourcePosition,
+        source_files: &[(ParseCtx<'_>, Path<'_>)],
+    ) -> Result<Span, AluminaError> {
+        self.resolve_position(position, source_files)
+            .ok_or_else(|| {
+                CodeErrorKind::UserDefined(format!(
+                    "{}:{}:{} is not a valid position in the program",
+                    position.filename.display(),
+                    position.line,
+                    position.column
+                ))
+            })
+            .with_no_span()
+    }
+
     pub fn compile(
         &mut self,
         source_files: Vec<SourceFile>,
@@ -68,32 +328,111 @@ impl Compiler {
         let mut cur_time = start_time;
         timing!(self, cur_time, Stage::Init);
 
+        let source_files = dedup_modules(&self.global_ctx, source_files)?;
+
         let ast = AstCtx::new();
         let root_scope = Scope::new_root();
+        let mut sysroot_files = HashSet::default();
+        let mut doctests = Vec::new();
 
-        let source_files: Vec<_> = source_files
+        let source_files: Vec<Result<_, AluminaError>> = source_files
             .iter()
             .map(|source_file| {
-                let file_id = self
-                    .global_ctx
-                    .diag()
-                    .add_file(source_file.filename.clone());
-                let source = std::fs::read_to_string(&source_file.filename)?;
+                let (file_id, mut source) = match &source_file.contents {
+                    Some(contents) => (
+                        self.global_ctx
+                            .diag()
+                            .add_file_with_source(source_file.filename.clone(), contents),
+                        contents.clone(),
+                    ),
+                    None => {
+                        let file_id = self
+                            .global_ctx
+                            .diag()
+                            .add_file(source_file.filename.clone());
+                        (file_id, read_source(file_id, &source_file.filename)?)
+                    }
+                };
+                if source_file.sysroot {
+                    sysroot_files.insert(file_id);
+                }
+                if source_file.strip_shebang
+                    && source.starts_with("#!")
+                    && !source.starts_with("#![")
+                {
+                    // Blank out the line rather than removing it, so that lines and byte
+                    // offsets in diagnostics still match the file. `#![...]` is an inner
+                    // attribute, not a shebang.
+                    let end = source.find('\n').unwrap_or(source.len());
+                    source.replace_range(..end, &" ".repeat(end));
+                }
+
+                if self.doctests && !source_file.sysroot {
+                    let module = source_file.path.trim_end_matches("::");
+                    for doctest in crate::doctest::extract(&source) {
+                        let path = format!("{}::doctest_{}", module, doctest.line);
+                        doctests.push((file_id, path, doctest.source));
+                    }
+                }
 
                 let parse_tree = ParseCtx::from_source(file_id, source);
                 parse_tree.check_syntax_errors(parse_tree.root_node())?;
 
                 Ok((parse_tree, ast.parse_path(&source_file.path)))
             })
-            .collect::<Result<_, AluminaError>>()?;
+            .collect();
+        let mut source_files = collect_all(source_files)?;
+
+        // Doctests reuse the file id of the file they come from, since their source has the
+        // same layout, so the diagnostics point into the doc comment.
+        for (file_id, path, source) in doctests {
+            let parse_tree = ParseCtx::from_source(file_id, source);
+            parse_tree.check_syntax_errors(parse_tree.root_node())?;
+            source_files.push((parse_tree, ast.parse_path(&path)));
+        }
 
         timing!(self, cur_time, Stage::Parse);
 
+        let type_query_span = match &self.type_query {
+            Some(position) => Some(self.resolve_query_position(position, &source_files)?),
+            None => None,
+        };
+
+        if let Some(position) = &self.completion_query {
+            let span = self.resolve_query_position(position, &source_files)?;
+            self.global_ctx.enable_completion(span);
+        }
+
+        let rename_span = match &self.rename_query {
+            Some((position, new_name)) => {
+                let span = self.resolve_query_position(position, &source_files)?;
+                self.global_ctx.enable_rename(new_name.clone());
+                Some(span)
+            }
+            None => None,
+        };
+
         let mut main_candidate = None;
         for (ctx, path) in source_files.iter() {
             let scope = root_scope.ensure_module(path.clone()).with_no_span()?;
             scope.set_code(ctx);
 
+            let excluded_by = cfg_exclusion(self.global_ctx.clone(), &scope, ctx.root_node())?;
+            let is_excluded = excluded_by.is_some();
+            self.modules.push(ModuleInfo {
+                path: path.to_string(),
+                filename: self
+                    .global_ctx
+                    .diag()
+                    .get_file_path(ctx.file_id())
+                    .unwrap_or_default(),
+                excluded_by,
+            });
+
+            if is_excluded {
+                continue;
+            }
+
             if self.global_ctx.should_generate_main_glue() {
                 let mut visitor = FirstPassVisitor::with_main(
                     self.global_ctx.clone(),
@@ -122,15 +461,80 @@ impl Compiler {
 
         timing!(self, cur_time, Stage::Pass1);
 
+        if matches!(
+            self.emit,
+            EmitKind::Symbols | EmitKind::Ctags | EmitKind::Lsif
+        ) {
+            let symbols = symbols::collect(&root_scope);
+            let diag = self.global_ctx.diag();
+            return Ok(match self.emit {
+                EmitKind::Ctags => symbols::format_ctags(&diag, &symbols),
+                EmitKind::Lsif => symbols::format_lsif(&diag, &symbols),
+                _ => symbols::format_symbols(&diag, &symbols),
+            });
+        }
+
         let mut item_maker = AstItemMaker::new(&ast, self.global_ctx.clone(), MacroCtx::default());
-        item_maker.make(root_scope)?;
+        if let Err(e) = item_maker.make(root_scope.clone()) {
+            if !self.global_ctx.error_tolerant() {
+                return Err(e);
+            }
+
+            // The program cannot be monomorphized, but the names that did resolve are
+            // still useful for editor tooling.
+            self.global_ctx.diag().add_from_error(e)?;
+            return Ok(self.format_name_index());
+        }
+
+        self.global_ctx.lints().check_scopes(
+            &AstLintCtx {
+                ast: &ast,
+                diag: &self.global_ctx.diag(),
+                sysroot_files: &sysroot_files,
+            },
+            &root_scope,
+        );
+        rename::check_scopes(&self.global_ctx, &root_scope);
 
         timing!(self, cur_time, Stage::Ast);
 
+        // The metadata is only written if the library compiles, but the sources are not
+        // around anymore by then.
+        let mut meta = if self.emits(EmitKind::Meta) {
+            let diag = self.global_ctx.diag();
+            let mut seen = HashSet::default();
+            let modules: Vec<_> = source_files
+                .iter()
+                .filter(|(ctx, _)| {
+                    !sysroot_files.contains(&ctx.file_id()) && seen.insert(ctx.file_id())
+                })
+                .map(|(ctx, path)| {
+                    let filename = diag.get_file_path(ctx.file_id()).unwrap_or_default();
+                    (path.to_string(), filename, ctx.source())
+                })
+                .collect();
+            Some(meta::write(&modules)?)
+        } else {
+            None
+        };
+
+        let mut module_paths: HashMap<FileId, String> = HashMap::default();
+        for (ctx, path) in source_files.iter() {
+            // The doctests share the file id, the file's own module comes first
+            module_paths
+                .entry(ctx.file_id())
+                .or_insert_with(|| path.to_string());
+        }
         drop(source_files);
 
         let ir_ctx = IrCtx::new();
         let items = item_maker.into_inner();
+
+        // Before monomorphization, so that the unused items are included as well
+        let mut ast_dump = self
+            .emits(EmitKind::Ast)
+            .then(|| format_ast(&module_paths, &items));
+
         let mut mono_ctx = MonoCtx::new(&ast, &ir_ctx, self.global_ctx.clone());
 
         let mut roots = HashSet::default();
@@ -142,7 +546,13 @@ impl Compiler {
             // The functions that are marked with export will always be emitted, otherwise
             // only the functions that are transitively called from the entry point will be
             // emitted. Can be forced to monomorphize all functions with "-Zmonomorphize-all"
-            let compile = if self.global_ctx.has_option("monomorphize-all") {
+            // (always the case for completion, renaming and type queries, which also need to
+            // see the functions that are not called from anywhere yet).
+            let compile = if self.global_ctx.has_option("monomorphize-all")
+                || self.completion_query.is_some()
+                || self.rename_query.is_some()
+                || self.type_query.is_some()
+            {
                 inner.can_compile()
             } else {
                 inner.should_compile()
@@ -150,12 +560,53 @@ impl Compiler {
 
             if compile {
                 let mut monomorphizer = Monomorphizer::new(&mut mono_ctx, false, None);
-                roots.insert(monomorphizer.monomorphize_item(item, &[])?);
+                match monomorphizer.monomorphize_item(item, &[]) {
+                    Ok(item) => {
+                        roots.insert(item);
+                    }
+                    Err(e) if self.global_ctx.error_tolerant() => {
+                        self.global_ctx.diag().add_from_error(e)?
+                    }
+                    Err(e) => return Err(e),
+                }
             }
         }
 
+        if self.global_ctx.error_tolerant() && self.global_ctx.diag().has_errors() {
+            return Ok(self.format_name_index());
+        }
+
+        if let Some(span) = rename_span {
+            self.rename_edits = rename::plan(&self.global_ctx, span, &sysroot_files)?;
+            return Ok(String::new());
+        }
+
+        if self.completion_query.is_some() {
+            return Ok(String::new());
+        }
+
+        if let Some(span) = type_query_span {
+            // Everything that was monomorphized from the roots, whether the program uses it
+            // or not
+            let mut reachable = DeadCodeEliminator::new();
+            for &item in &roots {
+                reachable.visit_item(item)?;
+            }
+
+            let mut query = TypeAtQuery::new(&mono_ctx, span);
+            for item in codegen_order(&mono_ctx, &module_paths, reachable.alive_items())? {
+                query.visit_item(item)?;
+            }
+            self.type_query_results = query.into_results();
+            return Ok(String::new());
+        }
+
         // Main glue code
         if self.global_ctx.should_generate_main_glue() {
+            if main_candidate.is_none() && self.global_ctx.test_mode() {
+                return Err(CodeErrorKind::TestHarnessNotFound).with_no_span();
+            }
+
             if let Some(main_candidate) = main_candidate {
                 let mut monomorphizer = Monomorphizer::new(&mut mono_ctx, false, None);
                 let user_main = monomorphizer.monomorphize_item(main_candidate, &[])?;
@@ -173,6 +624,14 @@ impl Compiler {
 
         timing!(self, cur_time, Stage::Mono);
 
+        if self.global_ctx.has_option("mono-stats") {
+            let lines = mono_stats(&mono_ctx)?;
+            let diag = self.global_ctx.diag();
+            for line in lines {
+                diag.add_note(CodeError::freeform(line));
+            }
+        }
+
         let mut dce = DeadCodeEliminator::new();
         for item in roots {
             dce.visit_item(item)?;
@@ -182,15 +641,580 @@ impl Compiler {
         let mut monomorphizer = Monomorphizer::new(&mut mono_ctx, false, None);
         dce.visit_item(monomorphizer.generate_static_constructor(dce.alive_items())?)?;
 
-        let items: Vec<_> = dce.alive_items().iter().copied().collect();
+        #[allow(unused_mut)]
+        let mut items = codegen_order(&mono_ctx, &module_paths, dce.alive_items())?;
+
+        #[cfg(feature = "plugins")]
+        {
+            let ctx = PassCtx {
+                global_ctx: &self.global_ctx,
+                ir: &ir_ctx,
+            };
+            for pass in &mut self.ir_passes {
+                pass.run(&ctx, &mut items)?;
+            }
+        }
+
+        let stack_depth = self.global_ctx.has_option("stack-depth");
+        let has_no_recursion = items.iter().any(|item| {
+            item.get_function()
+                .map(|f| f.attributes.contains(&Attribute::NoRecursion))
+                .unwrap_or(false)
+        });
+        if stack_depth || has_no_recursion {
+            let call_graph = CallGraph::new(self.global_ctx.clone(), &ir_ctx, &items)?;
+            let diag = DiagnosticsStack::new(self.global_ctx.diag().clone());
+            call_graph.check_no_recursion(&mono_ctx, &diag)?;
+
+            if stack_depth {
+                let lines = call_graph.stack_depth_report(&mono_ctx)?;
+                let diag = self.global_ctx.diag();
+                for line in lines {
+                    diag.add_note(CodeError::freeform(line));
+                }
+            }
+        }
         timing!(self, cur_time, Stage::Optimizations);
 
         // Dunno why the borrow checker is not letting me do that, it should be possible.
         // drop(ast);
 
-        let res = codegen::codegen(self.global_ctx.clone(), &items[..]);
+        let kinds: Vec<_> = std::iter::once(self.emit)
+            .chain(self.also_emit.iter().copied())
+            .collect();
+        let mut outputs = Vec::with_capacity(kinds.len());
+        for emit in kinds {
+            let output = match emit {
+                EmitKind::C | EmitKind::CSnapshot => {
+                    if self.global_ctx.output_type() == OutputType::SharedLibrary {
+                        self.exported_symbols = codegen::exported_symbols(
+                            self.global_ctx.clone(),
+                            &ir_ctx,
+                            &items[..],
+                        )?
+                        .into_iter()
+                        .map(|s| s.symbol)
+                        .collect();
+                    }
+
+                    let banners = if self.global_ctx.has_option("group-by-module") {
+                        module_banners(&mono_ctx, &module_paths, &items)
+                    } else {
+                        Vec::new()
+                    };
+
+                    codegen::codegen(self.global_ctx.clone(), &ir_ctx, &items[..], &banners).map(
+                        |code| match emit {
+                            EmitKind::CSnapshot => codegen::snapshot::normalize(&code),
+                            _ => code,
+                        },
+                    )
+                }
+                EmitKind::DefsIndex | EmitKind::SemanticTokens => Ok(self.format_name_index()),
+                // Only the diagnostics are printed, which happens in the driver.
+                EmitKind::DiagnosticsGolden => Ok(String::new()),
+                // Produced right after the first pass
+                EmitKind::Symbols | EmitKind::Ctags | EmitKind::Lsif => unreachable!(),
+                // Produced by the driver without compiling anything
+                EmitKind::BuildPlan => unreachable!(),
+                EmitKind::Meta => Ok(meta.take().unwrap()),
+                EmitKind::Exports => {
+                    codegen::exported_symbols(self.global_ctx.clone(), &ir_ctx, &items[..])
+                        .and_then(|symbols| format_exports(&self.global_ctx, &mono_ctx, symbols))
+                }
+                EmitKind::Ast => Ok(ast_dump.take().unwrap()),
+                EmitKind::Cfg | EmitKind::Ir => {
+                    let functions = cfg_functions(
+                        &mono_ctx,
+                        &module_paths,
+                        &sysroot_files,
+                        &self.cfg_filters,
+                        &items,
+                    )?;
+                    let diag = DiagnosticsStack::new(self.global_ctx.diag().clone());
+                    match emit {
+                        EmitKind::Cfg => format_cfgs(&mono_ctx, &diag, &functions),
+                        _ => format_ir(&diag, &functions),
+                    }
+                }
+            };
+            outputs.push((emit, output?));
+        }
         timing!(self, cur_time, Stage::Codegen);
 
-        res
+        // Warnings are only complete if the whole program was compiled
+        let diag = self.global_ctx.diag();
+        if matches!(
+            self.emit,
+            EmitKind::C | EmitKind::CSnapshot | EmitKind::DiagnosticsGolden | EmitKind::Meta
+        ) && !diag.has_errors()
+        {
+            diag.check_unused_overrides(&sysroot_files);
+        }
+
+        let (_, res) = outputs.remove(0);
+        self.additional_outputs = outputs;
+
+        Ok(res)
+    }
+
+    fn format_name_index(&self) -> String {
+        let diag = self.global_ctx.diag();
+        let names = self.global_ctx.name_index();
+        match self.emit {
+            EmitKind::SemanticTokens => name_index::format_semantic_tokens(&diag, names),
+            _ => name_index::format_defs_index(&diag, names),
+        }
     }
+
+    /// Evaluates the constant `name` in the module `module_path` and returns its value
+    /// formatted as text. Code generation is skipped entirely. The sysroot is taken from
+    /// the session, so it is not parsed again.
+    ///
+    /// Used by the REPL.
+    pub fn eval_const(
+        &mut self,
+        session: &Session<'_>,
+        source_files: Vec<SourceFile>,
+        module_path: &str,
+        name: &str,
+    ) -> Result<String, AluminaError> {
+        let ast = AstCtx::new();
+        let root_scope = Scope::new_root();
+
+        session.register_files(&self.global_ctx);
+        let parsed: Vec<Result<_, AluminaError>> = source_files
+            .iter()
+            .map(|source_file| {
+                let file_id = self
+                    .global_ctx
+                    .diag()
+                    .add_file(source_file.filename.clone());
+                let source = read_source(file_id, &source_file.filename)?;
+
+                let parse_tree = ParseCtx::from_source(file_id, source);
+                parse_tree.check_syntax_errors(parse_tree.root_node())?;
+
+                Ok((parse_tree, &source_file.path))
+            })
+            .collect();
+        let parsed = collect_all(parsed)?;
+
+        let source_files = session
+            .sysroot
+            .iter()
+            .map(|(parse_tree, source_file)| (parse_tree, &source_file.path))
+            .chain(parsed.iter().map(|(parse_tree, path)| (parse_tree, *path)))
+            .map(|(parse_tree, path)| (parse_tree, ast.parse_path(path)));
+
+        for (ctx, path) in source_files {
+            let scope = root_scope.ensure_module(path).with_no_span()?;
+            scope.set_code(ctx);
+
+            let mut visitor =
+                FirstPassVisitor::new(self.global_ctx.clone(), &ast, scope, MacroCtx::default());
+            visitor.visit(ctx.root_node())?;
+        }
+
+        let mut item_maker = AstItemMaker::new(&ast, self.global_ctx.clone(), MacroCtx::default());
+        item_maker.make(root_scope.clone())?;
+
+        let module = root_scope
+            .ensure_module(ast.parse_path(module_path))
+            .with_no_span()?;
+        let item = module
+            .inner()
+            .items_with_name(name)
+            .find_map(|item| match item.kind {
+                NamedItemKind::Const(item, _, _) => Some(item),
+                _ => None,
+            })
+            .ok_or_else(|| CodeErrorKind::UnresolvedItem(name.to_string()))
+            .with_no_span()?;
+
+        let ir_ctx = IrCtx::new();
+        let mut mono_ctx = MonoCtx::new(&ast, &ir_ctx, self.global_ctx.clone());
+        let mut monomorphizer = Monomorphizer::new(&mut mono_ctx, false, None);
+        let item = monomorphizer.monomorphize_item(item, &[])?;
+
+        let value = item.get_const().with_no_span()?.value;
+
+        Ok(value.to_string())
+    }
+}
+
+/// State that is kept between compilations of the same program with small changes (the
+/// REPL recompiles everything on every input).
+///
+/// Only the sysroot sources and their parse trees are retained. The AST arena and its
+/// interner tables are still created anew for every compilation, so the sysroot is lowered
+/// to AST items again each time. They cannot be shared yet: items are mutated while they
+/// are being monomorphized (e.g. mixin expansion), user modules are added to the same
+/// scope tree as the sysroot modules and everything allocated in the arena borrows it for
+/// the lifetime of a single compilation.
+pub struct Session<'src> {
+    sysroot: Vec<(ParseCtx<'src>, SourceFile)>,
+}
+
+impl<'src> Session<'src> {
+    pub fn new(sysroot: Vec<SourceFile>) -> Result<Self, AluminaError> {
+        let sysroot: Vec<Result<_, AluminaError>> = sysroot
+            .into_iter()
+            .enumerate()
+            .map(|(id, source_file)| {
+                let source = read_source(FileId { id }, &source_file.filename)?;
+                Ok((ParseCtx::from_source(FileId { id }, source), source_file))
+            })
+            .collect();
+        let sysroot = collect_all(sysroot)?;
+
+        for (parse_tree, _) in &sysroot {
+            parse_tree.check_syntax_errors(parse_tree.root_node())?;
+        }
+
+        Ok(Self { sysroot })
+    }
+
+    /// Adds the sysroot files to a fresh diagnostic context. They are added first, so they
+    /// get the same file ids that the parse trees were created with.
+    fn register_files(&self, global_ctx: &GlobalCtx) {
+        let diag = global_ctx.diag();
+        for (parse_tree, source_file) in &self.sysroot {
+            let file_id = diag.add_file(source_file.filename.clone());
+            assert_eq!(file_id, parse_tree.file_id());
+        }
+    }
+}
+
+/// Like collecting into a `Result`, but keeps going after the first error, so that all the
+/// files that cannot be read or parsed are reported at once
+fn collect_all<T>(results: Vec<Result<T, AluminaError>>) -> Result<Vec<T>, AluminaError> {
+    let mut values = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(value) => values.push(value),
+            Err(AluminaError::CodeErrors(e)) => errors.extend(e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(values)
+    } else {
+        Err(AluminaError::CodeErrors(errors))
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum FileIdentity {
+    Inode(u64, u64),
+    Path(PathBuf),
+}
+
+/// Identifies the file behind a path, so that a file that is reachable through several paths
+/// (symlinks, hard links or a different case on a case-insensitive filesystem) is recognized.
+fn file_identity(path: &std::path::Path) -> FileIdentity {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            return FileIdentity::Inode(metadata.dev(), metadata.ino());
+        }
+    }
+
+    FileIdentity::Path(std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()))
+}
+
+/// Makes sure that every file is added only once and that no two files are added as the
+/// same module, since either would otherwise show up as confusing duplicate definitions
+/// later on. A file that is added again under the same module path through another path
+/// (e.g. a symlink) is dropped with a warning. The same file under different module paths
+/// and different files under the same module path are errors.
+fn dedup_modules(
+    global_ctx: &GlobalCtx,
+    source_files: Vec<SourceFile>,
+) -> Result<Vec<SourceFile>, AluminaError> {
+    let mut by_file: HashMap<FileIdentity, usize> = HashMap::default();
+    let mut by_path: HashMap<String, usize> = HashMap::default();
+    let mut result: Vec<SourceFile> = Vec::with_capacity(source_files.len());
+    let mut errors = Vec::new();
+
+    for source_file in source_files {
+        // `foo::bar` and `::foo::bar` are the same module
+        let path = source_file
+            .path
+            .split("::")
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("::");
+        let identity = file_identity(&source_file.filename);
+
+        if let Some(&index) = by_file.get(&identity) {
+            let existing = &result[index];
+            if by_path.get(&path) == Some(&index) {
+                global_ctx.diag().add_warning(CodeError::from_kind(
+                    CodeErrorKind::AliasedModuleFile(
+                        source_file.filename.display().to_string(),
+                        existing.filename.display().to_string(),
+                        source_file.path.clone(),
+                    ),
+                    None,
+                ));
+            } else {
+                errors.push(CodeErrorKind::DuplicateModuleFile(
+                    source_file.filename.display().to_string(),
+                    existing.path.clone(),
+                    source_file.path.clone(),
+                ));
+            }
+            continue;
+        }
+
+        if let Some(&index) = by_path.get(&path) {
+            errors.push(CodeErrorKind::DuplicateModulePath(
+                source_file.path.clone(),
+                result[index].filename.display().to_string(),
+                source_file.filename.display().to_string(),
+            ));
+            continue;
+        }
+
+        by_file.insert(identity, result.len());
+        by_path.insert(path, result.len());
+        result.push(source_file);
+    }
+
+    if errors.is_empty() {
+        return Ok(result);
+    }
+
+    Err(AluminaError::CodeErrors(
+        errors
+            .into_iter()
+            .map(|kind| CodeError {
+                kind,
+                backtrace: vec![],
+            })
+            .collect(),
+    ))
+}
+
+/// Module an item was defined in, `None` for items generated by the compiler
+fn item_module<'a>(
+    mono_ctx: &MonoCtx<'_, '_>,
+    module_paths: &'a HashMap<FileId, String>,
+    item: IRItemP<'_>,
+) -> Option<&'a str> {
+    let span = match mono_ctx.source_item(item)?.0.get() {
+        crate::ast::Item::Function(f) => f.span,
+        crate::ast::Item::StaticOrConst(s) => s.span,
+        _ => None,
+    }?;
+
+    module_paths.get(&span.file).map(|path| path.as_str())
+}
+
+/// Functions to draw the control-flow graph of for `--emit cfg`, with their paths
+fn cfg_functions<'ir>(
+    mono_ctx: &MonoCtx<'_, 'ir>,
+    module_paths: &HashMap<FileId, String>,
+    sysroot_files: &HashSet<FileId>,
+    filters: &[String],
+    items: &[IRItemP<'ir>],
+) -> Result<Vec<(String, IRItemP<'ir>)>, AluminaError> {
+    let mut result = Vec::new();
+    for &item in items {
+        if item.get_function().is_err() {
+            continue;
+        }
+        let Some(key) = mono_ctx.source_item(item) else {
+            continue;
+        };
+        let Some(span) = (match key.0.get() {
+            crate::ast::Item::Function(f) => f.span,
+            _ => None,
+        }) else {
+            continue;
+        };
+        let Some(module) = module_paths.get(&span.file) else {
+            continue;
+        };
+
+        let name = if key.1.is_empty() {
+            item_name(key.0).to_string()
+        } else {
+            mono_ctx.instantiation_name(&key)?
+        };
+        let path = format!("{}::{}", module, name);
+
+        let selected = if filters.is_empty() {
+            !sysroot_files.contains(&span.file)
+        } else {
+            filters
+                .iter()
+                .any(|filter| path.starts_with(filter.as_str()))
+        };
+        if selected {
+            result.push((path, item));
+        }
+    }
+
+    Ok(result)
+}
+
+/// Formats all the AST items for `--emit ast`, one line per item in the order they were
+/// created: the id, the path and the item itself (in `Debug` format, untruncated). Other
+/// items are referred to by their ids, they have lines of their own.
+fn format_ast(module_paths: &HashMap<FileId, String>, items: &[ItemP<'_>]) -> String {
+    let mut result = String::new();
+    for &item in items {
+        let Some(contents) = item.try_get() else {
+            continue;
+        };
+
+        let span = match contents {
+            crate::ast::Item::Enum(e) => e.span,
+            crate::ast::Item::StructLike(s) => s.span,
+            crate::ast::Item::TypeDef(t) => t.span,
+            crate::ast::Item::Protocol(p) => p.span,
+            crate::ast::Item::Function(f) => f.span,
+            crate::ast::Item::StaticOrConst(s) => s.span,
+            crate::ast::Item::Macro(m) => m.span,
+            crate::ast::Item::BuiltinMacro(_) | crate::ast::Item::Intrinsic(_) => None,
+        };
+        let module = span
+            .and_then(|span| module_paths.get(&span.file))
+            .map(|path| path.as_str())
+            .unwrap_or_default();
+
+        let _ = writeln!(
+            result,
+            "{}\t{}::{}\t{:?}",
+            item.id,
+            module,
+            item_name(item),
+            contents
+        );
+    }
+
+    result
+}
+
+/// Sorts the items that are emitted by module and then by their position in the source, so
+/// that the generated code only changes where the program does, regardless of the order in
+/// which the items were monomorphized.
+fn codegen_order<'ir>(
+    mono_ctx: &MonoCtx<'_, 'ir>,
+    module_paths: &HashMap<FileId, String>,
+    items: &HashSet<IRItemP<'ir>>,
+) -> Result<Vec<IRItemP<'ir>>, AluminaError> {
+    let mut keyed = Vec::with_capacity(items.len());
+    for &item in items {
+        let (position, name) = match mono_ctx.source_item(item) {
+            Some(key) => {
+                let position = match key.0.get() {
+                    crate::ast::Item::Function(f) => f.span,
+                    crate::ast::Item::StaticOrConst(s) => s.span,
+                    _ => None,
+                }
+                .map(|span| span.start);
+
+                let name = if key.1.is_empty() {
+                    item_name(key.0).to_string()
+                } else {
+                    mono_ctx.instantiation_name(&key)?
+                };
+
+                (position, name)
+            }
+            None => (None, String::new()),
+        };
+
+        keyed.push((
+            (
+                item_module(mono_ctx, module_paths, item),
+                position,
+                name,
+                item.id,
+            ),
+            item,
+        ));
+    }
+
+    keyed.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(keyed.into_iter().map(|(_, item)| item).collect())
+}
+
+/// Positions in the (sorted) items where a new module starts, for `-Zgroup-by-module`
+fn module_banners(
+    mono_ctx: &MonoCtx<'_, '_>,
+    module_paths: &HashMap<FileId, String>,
+    items: &[IRItemP<'_>],
+) -> Vec<(usize, String)> {
+    let mut banners: Vec<(usize, String)> = Vec::new();
+    for (idx, &item) in items.iter().enumerate() {
+        let module = item_module(mono_ctx, module_paths, item).unwrap_or("<generated>");
+        if banners.last().map(|(_, m)| m.as_str()) != Some(module) {
+            banners.push((idx, module.to_string()));
+        }
+    }
+
+    banners
+}
+
+/// Formats the exported symbols as `symbol<TAB>prototype<TAB>origin` lines, where origin
+/// is the Alumina item (with generic arguments) and its location.
+fn format_exports(
+    global_ctx: &GlobalCtx,
+    mono_ctx: &MonoCtx<'_, '_>,
+    symbols: Vec<codegen::ExportedSymbol<'_>>,
+) -> Result<String, AluminaError> {
+    let diag = global_ctx.diag();
+    let mut result = String::new();
+
+    let link_flags = runner::link_flags(global_ctx);
+    if !link_flags.is_empty() {
+        result.push_str(&format!("# link: {}\n", link_flags.join(" ")));
+    }
+
+    for symbol in symbols {
+        let origin = match mono_ctx.source_item(symbol.item) {
+            Some(key) => {
+                let name = if key.1.is_empty() {
+                    item_name(key.0).to_string()
+                } else {
+                    mono_ctx.instantiation_name(&key)?
+                };
+
+                let location = match key.0.get() {
+                    crate::ast::Item::Function(f) => f.span,
+                    _ => None,
+                }
+                .and_then(|span| {
+                    diag.get_file_path(span.file).map(|path| {
+                        format!(
+                            "{}:{}:{}",
+                            path.display(),
+                            span.line + 1,
+                            diag.column(&span)
+                        )
+                    })
+                });
+
+                match location {
+                    Some(location) => format!("{} ({})", name, location),
+                    None => name,
+                }
+            }
+            None => "<generated>".to_string(),
+        };
+
+        result.push_str(&format!(
+            "{}\t{}\t{}\n",
+            symbol.symbol, symbol.prototype, origin
+        ));
+    }
+
+    Ok(result)
 }
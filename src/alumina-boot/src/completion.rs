@@ -0,0 +1,265 @@
+//! Completion candidates for a position in the source (`--complete`).
+//!
+//! Names are collected while the program is being compiled: the AST maker offers the
+//! names visible in the scope of an identifier at the position and the monomorphizer
+//! offers the fields and methods of the receiver of a `.` (only known after typing).
+use crate::ast::Span;
+use crate::common::HashSet;
+use crate::global_ctx::GlobalCtx;
+use crate::name_index::SymbolKind;
+use crate::name_resolution::path::Path;
+use crate::name_resolution::resolver::{ItemResolution, NameResolver, ScopeResolution};
+use crate::name_resolution::scope::{NamedItem, NamedItemKind, Scope};
+use crate::parser::{NodeExt, NodeKind, ParseCtx};
+
+use std::fmt::Write;
+
+use tree_sitter::Node;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub signature: String,
+}
+
+#[derive(Debug)]
+pub struct CompletionQuery {
+    pub position: Span,
+    pub items: Vec<CompletionItem>,
+    seen: HashSet<String>,
+}
+
+impl CompletionQuery {
+    pub fn new(position: Span) -> Self {
+        Self {
+            position,
+            items: Vec::new(),
+            seen: HashSet::default(),
+        }
+    }
+
+    pub fn add(&mut self, prefix: &str, items: impl IntoIterator<Item = CompletionItem>) {
+        for item in items {
+            if item.name.starts_with(prefix) && self.seen.insert(item.name.clone()) {
+                self.items.push(item);
+            }
+        }
+    }
+}
+
+/// Signature of a local variable. The type is only known if it is given explicitly.
+fn local_signature<'src>(code: &'src ParseCtx<'src>, span: Span) -> String {
+    let Some(node) = code
+        .root_node()
+        .descendant_for_byte_range(span.start, span.end)
+    else {
+        return String::new();
+    };
+
+    match node.parent() {
+        Some(parent) if matches!(parent.kind_typed(), NodeKind::LetDeclaration) => {
+            code.declaration_header(parent)
+        }
+        _ => code.node_text(node).to_string(),
+    }
+}
+
+fn describe<'ast, 'src>(
+    resolver: &mut NameResolver<'ast, 'src>,
+    scope: &Scope<'ast, 'src>,
+    name: &'ast str,
+    item: &NamedItem<'ast, 'src>,
+) -> Option<CompletionItem> {
+    let in_scope = |node: &Node<'src>| {
+        scope
+            .code()
+            .map(|code| code.declaration_header(*node))
+            .unwrap_or_default()
+    };
+
+    let (kind, signature) = match &item.kind {
+        NamedItemKind::Alias(target, _) => {
+            return match resolver.resolve_item(scope.clone(), target.clone()) {
+                Ok(ItemResolution::Item(target)) => {
+                    describe(resolver, scope, name, &target).map(|resolved| CompletionItem {
+                        name: name.to_string(),
+                        ..resolved
+                    })
+                }
+                _ => None,
+            };
+        }
+        NamedItemKind::Function(_, node, item_scope)
+        | NamedItemKind::Method(_, node, item_scope)
+        | NamedItemKind::TypeDef(_, node, item_scope)
+        | NamedItemKind::Type(_, node, item_scope)
+        | NamedItemKind::Protocol(_, node, item_scope)
+        | NamedItemKind::Static(_, node, item_scope)
+        | NamedItemKind::Const(_, node, item_scope)
+        | NamedItemKind::Macro(_, node, item_scope) => {
+            let kind = match item.kind {
+                NamedItemKind::Function(..) => SymbolKind::Function,
+                NamedItemKind::Method(..) => SymbolKind::Method,
+                NamedItemKind::Protocol(..) => SymbolKind::Protocol,
+                NamedItemKind::Static(..) => SymbolKind::Static,
+                NamedItemKind::Const(..) => SymbolKind::Const,
+                NamedItemKind::Macro(..) => SymbolKind::Macro,
+                _ => SymbolKind::Type,
+            };
+            let signature = item_scope
+                .code()
+                .map(|code| code.declaration_header(*node))
+                .unwrap_or_default();
+
+            (kind, signature)
+        }
+        NamedItemKind::Module(_) => (SymbolKind::Module, format!("mod {}", name)),
+        NamedItemKind::EnumMember(_, _, node) => (SymbolKind::EnumMember, in_scope(node)),
+        NamedItemKind::Placeholder(_, node) => (SymbolKind::TypeParameter, in_scope(node)),
+        NamedItemKind::Parameter(_, node) => (SymbolKind::Parameter, in_scope(node)),
+        NamedItemKind::MacroParameter(..) => (SymbolKind::Parameter, name.to_string()),
+        NamedItemKind::Local(_, span) | NamedItemKind::BoundValue(_, _, _, span) => {
+            let signature = scope
+                .code()
+                .map(|code| local_signature(code, *span))
+                .unwrap_or_default();
+
+            (SymbolKind::Variable, signature)
+        }
+        NamedItemKind::Mixin(..) | NamedItemKind::Impl(..) | NamedItemKind::Field(_) => {
+            return None
+        }
+    };
+
+    Some(CompletionItem {
+        name: name.to_string(),
+        kind,
+        signature,
+    })
+}
+
+/// Names defined directly in `scope`. Locals are skipped if the scope belongs to a
+/// different function than `function` (they cannot be referenced from nested functions).
+fn scope_names<'ast, 'src>(
+    resolver: &mut NameResolver<'ast, 'src>,
+    scope: &Scope<'ast, 'src>,
+    function: Option<&Scope<'ast, 'src>>,
+) -> Vec<CompletionItem> {
+    let locals_visible = match scope.find_containing_function() {
+        Some(containing) => Some(&containing) == function,
+        None => true,
+    };
+
+    let items: Vec<_> = scope
+        .inner()
+        .items
+        .iter()
+        .filter_map(|(name, items)| {
+            let item = items
+                .iter()
+                .find(|item| !matches!(item.kind, NamedItemKind::Impl(..)))?;
+            Some(((*name)?, item.clone()))
+        })
+        .collect();
+
+    items
+        .into_iter()
+        .filter(|(_, item)| {
+            locals_visible
+                || !matches!(
+                    item.kind,
+                    NamedItemKind::Local(..)
+                        | NamedItemKind::BoundValue(..)
+                        | NamedItemKind::Parameter(..)
+                        | NamedItemKind::MacroParameter(..)
+                        | NamedItemKind::Macro(..)
+                )
+        })
+        .filter_map(|(name, item)| describe(resolver, scope, name, &item))
+        .collect()
+}
+
+/// Names that can be referred to as `qualifier::name` from `scope`. With an empty
+/// qualifier, that is everything visible through the scope chain (in the same order the
+/// resolver looks them up, so shadowed names come after the ones that shadow them).
+fn visible_names<'ast, 'src>(
+    scope: &Scope<'ast, 'src>,
+    qualifier: Path<'ast>,
+) -> Vec<CompletionItem> {
+    let mut resolver = NameResolver::new();
+
+    if qualifier.absolute || !qualifier.segments.is_empty() {
+        return match resolver.resolve_scope(scope.clone(), qualifier) {
+            Ok(ScopeResolution::Scope(target)) => scope_names(&mut resolver, &target, None),
+            _ => Vec::new(),
+        };
+    }
+
+    let function = scope.find_containing_function();
+    let mut result = Vec::new();
+    let mut current = Some(scope.clone());
+    while let Some(current_scope) = current {
+        result.extend(scope_names(
+            &mut resolver,
+            &current_scope,
+            function.as_ref(),
+        ));
+
+        let star_imports: Vec<_> = current_scope.inner().star_imports().cloned().collect();
+        for import in star_imports {
+            if let Ok(ScopeResolution::Scope(imported)) =
+                resolver.resolve_scope(current_scope.clone(), import)
+            {
+                result.extend(scope_names(&mut resolver, &imported, None));
+            }
+        }
+
+        current = current_scope.parent();
+    }
+
+    result
+}
+
+/// Offers the names visible in `scope` as completions if the last segment of `path`
+/// (which is at the end of `span`) is at the completion position.
+pub fn complete_path<'ast, 'src>(
+    global_ctx: &GlobalCtx,
+    scope: &Scope<'ast, 'src>,
+    path: &Path<'ast>,
+    span: Span,
+) {
+    let (Some(position), Some(last)) = (global_ctx.completion_position(), path.segments.last())
+    else {
+        return;
+    };
+
+    let name_span = span.tail(last.0.len());
+    if !name_span.contains(&position) {
+        return;
+    }
+
+    let prefix = last
+        .0
+        .get(..position.start - name_span.start)
+        .unwrap_or_default();
+    global_ctx.add_completions(prefix, visible_names(scope, path.pop()));
+}
+
+/// Formats the completions as `name<TAB>kind<TAB>signature` lines, sorted by name.
+pub fn format_completions(mut items: Vec<CompletionItem>) -> String {
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut result = String::new();
+    for item in items {
+        let _ = writeln!(
+            result,
+            "{}\t{}\t{}",
+            item.name,
+            item.kind.as_ref(),
+            item.signature
+        );
+    }
+
+    result
+}
@@ -3,9 +3,11 @@ use crate::common::{
     AluminaError, CodeError, CodeErrorKind, FileId, HashMap, HashSet, IndexSet, Marker,
 };
 use crate::ir::const_eval::ConstEvalErrorKind;
+use crate::parser::read_source_lossy;
 use colored::Colorize;
 
 use std::cell::RefCell;
+use std::fmt::Write;
 use std::path::PathBuf;
 use std::rc::Rc;
 
@@ -17,6 +19,25 @@ enum Level {
     Note = 0,
 }
 
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Note => "note",
+        }
+    }
+}
+
+/// A diagnostic reduced to its primary location (`--emit diagnostics-golden`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PlainDiagnostic {
+    /// Filename, line and column (1-based)
+    pub location: Option<(PathBuf, usize, usize)>,
+    pub level: &'static str,
+    pub message: String,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Action {
     Keep,
@@ -29,12 +50,108 @@ pub struct Override {
     pub span: Option<Span>,
     pub kind: Option<&'static str>,
     pub action: Action,
+    /// The attribute the override comes from (e.g. `allow(unused_variable)`), if any
+    pub attribute: Option<(Span, String)>,
+}
+
+/// Width of a tab when showing source code
+const TAB_WIDTH: usize = 4;
+
+/// Maps the byte offsets in a source file (which is what spans store) to lines and columns.
+pub struct LineMap {
+    source: String,
+    line_starts: Vec<usize>,
+}
+
+impl LineMap {
+    pub fn new(source: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    /// 0-based line of the byte offset
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts
+            .partition_point(|&start| start <= offset)
+            .saturating_sub(1)
+    }
+
+    pub fn line_start(&self, line: usize) -> usize {
+        self.line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.source.len())
+    }
+
+    /// Text of the line, without the line terminator
+    pub fn line_text(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line)?;
+        let end = self.line_start(line + 1);
+
+        Some(self.source[start..end].trim_end_matches(['\n', '\r']))
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The part of the line before the offset
+    fn line_prefix(&self, offset: usize) -> &str {
+        let start = self.line_start(self.line(offset));
+        let mut end = offset.min(self.source.len());
+        while !self.source.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        &self.source[start..end]
+    }
+
+    /// 1-based column of the byte offset in characters
+    pub fn column(&self, offset: usize) -> usize {
+        self.line_prefix(offset).chars().count() + 1
+    }
+
+    /// 0-based column of the byte offset on screen, when the line is shown with
+    /// [`expand_tabs`](Self::expand_tabs)
+    pub fn display_column(&self, offset: usize) -> usize {
+        self.line_prefix(offset)
+            .chars()
+            .fold(0, |column, ch| match ch {
+                '\t' => (column / TAB_WIDTH + 1) * TAB_WIDTH,
+                _ => column + 1,
+            })
+    }
+
+    /// The line with tabs replaced by spaces, so that it lines up with
+    /// [`display_column`](Self::display_column) no matter what comes before it.
+    pub fn expand_tabs(&self, line: usize) -> String {
+        let mut result = String::new();
+        for ch in self.line_text(line).unwrap_or_default().chars() {
+            match ch {
+                '\t' => {
+                    let width = TAB_WIDTH - result.chars().count() % TAB_WIDTH;
+                    result.extend(std::iter::repeat(' ').take(width));
+                }
+                ch => result.push(ch),
+            }
+        }
+
+        result
+    }
 }
 
 struct DiagnosticContextInner {
     file_map: HashMap<FileId, PathBuf>,
     messages: IndexSet<(Level, CodeError)>,
     overrides: Vec<Override>,
+    /// Indices of the overrides that applied to at least one warning
+    matched_overrides: HashSet<usize>,
     counter: usize,
 }
 
@@ -108,6 +225,21 @@ impl DiagnosticsStack {
         markers
     }
 
+    /// Backtrace for warnings and notes. These do not show the instantiation chain (or the
+    /// reasons for `when` branches), so that the same warning in generic code is only reported
+    /// once and not for every instantiation.
+    fn materialize_without_instantiations(&self) -> Vec<Marker> {
+        self.materialize()
+            .into_iter()
+            .map(|marker| match marker {
+                Marker::Instantiation(_)
+                | Marker::PendingInstantiation(_)
+                | Marker::StaticCondition(_) => Marker::Monomorphization,
+                marker => marker,
+            })
+            .collect()
+    }
+
     pub fn err(&self, kind: CodeErrorKind) -> AluminaError {
         AluminaError::CodeErrors(vec![CodeError {
             kind,
@@ -118,14 +250,14 @@ impl DiagnosticsStack {
     pub fn warn(&self, kind: CodeErrorKind) {
         self.diag_ctx.add_warning(CodeError {
             kind,
-            backtrace: self.materialize(),
+            backtrace: self.materialize_without_instantiations(),
         });
     }
 
     pub fn note(&self, kind: CodeErrorKind) {
         self.diag_ctx.add_note(CodeError {
             kind,
-            backtrace: self.materialize(),
+            backtrace: self.materialize_without_instantiations(),
         });
     }
 
@@ -148,6 +280,8 @@ impl DiagnosticsStack {
 #[derive(Clone)]
 pub struct DiagnosticContext {
     inner: Rc<RefCell<DiagnosticContextInner>>,
+    /// Sources of the files, only read when a diagnostic needs to show them
+    line_maps: Rc<RefCell<HashMap<FileId, Option<Rc<LineMap>>>>>,
 }
 
 impl Default for DiagnosticContext {
@@ -163,8 +297,10 @@ impl DiagnosticContext {
                 file_map: HashMap::default(),
                 messages: Default::default(),
                 overrides: Default::default(),
+                matched_overrides: Default::default(),
                 counter: 0,
             })),
+            line_maps: Default::default(),
         }
     }
 
@@ -172,6 +308,25 @@ impl DiagnosticContext {
         self.inner.borrow().file_map.get(&file_id).cloned()
     }
 
+    pub fn line_map(&self, file_id: FileId) -> Option<Rc<LineMap>> {
+        self.line_maps
+            .borrow_mut()
+            .entry(file_id)
+            .or_insert_with(|| {
+                let path = self.get_file_path(file_id)?;
+                read_source_lossy(&path).map(|source| Rc::new(LineMap::new(source)))
+            })
+            .clone()
+    }
+
+    /// 1-based column of the start of the span in characters (spans store it in bytes)
+    pub fn column(&self, span: &Span) -> usize {
+        match self.line_map(span.file) {
+            Some(line_map) => line_map.column(span.start),
+            None => span.column + 1,
+        }
+    }
+
     pub fn add_file(&self, source_file: PathBuf) -> FileId {
         let mut inner = self.inner.borrow_mut();
         let file_id = FileId { id: inner.counter };
@@ -180,6 +335,16 @@ impl DiagnosticContext {
         file_id
     }
 
+    /// Adds a file that does not exist on the disk (e.g. a module of an `--extern` library),
+    /// so its source is given for showing it in diagnostics
+    pub fn add_file_with_source(&self, source_file: PathBuf, source: &str) -> FileId {
+        let file_id = self.add_file(source_file);
+        self.line_maps
+            .borrow_mut()
+            .insert(file_id, Some(Rc::new(LineMap::new(source.to_string()))));
+        file_id
+    }
+
     pub fn add_override(&self, r#override: Override) {
         self.inner.borrow_mut().overrides.push(r#override);
     }
@@ -216,7 +381,8 @@ impl DiagnosticContext {
             .copied()
             .next();
 
-        for r#override in &inner.overrides {
+        let inner = &mut *inner;
+        for (index, r#override) in inner.overrides.iter().enumerate() {
             // Lint name has to match
             if r#override.kind.is_some() && r#override.kind != Some(err.kind.as_ref()) {
                 continue;
@@ -233,6 +399,7 @@ impl DiagnosticContext {
                     continue;
                 }
 
+                inner.matched_overrides.insert(index);
                 if override_span.len() < min_span_length.unwrap_or(usize::MAX) {
                     min_span_length = Some(override_span.len());
                 } else {
@@ -256,6 +423,41 @@ impl DiagnosticContext {
         }
     }
 
+    /// Warns about the lint attributes that did not apply to any warning (e.g. an `allow`
+    /// on an item that never produces that warning). Only meaningful after the whole
+    /// program has been compiled.
+    pub fn check_unused_overrides(&self, ignored_files: &HashSet<FileId>) {
+        let inner = self.inner.borrow();
+
+        // An attribute for a lint group adds an override for each lint in the group, it is
+        // used if any of them is.
+        let mut used = HashSet::default();
+        let mut attributes = IndexSet::default();
+        for (index, r#override) in inner.overrides.iter().enumerate() {
+            let Some(attribute) = &r#override.attribute else {
+                continue;
+            };
+            if ignored_files.contains(&attribute.0.file) {
+                continue;
+            }
+
+            if inner.matched_overrides.contains(&index) {
+                used.insert(attribute.clone());
+            }
+            attributes.insert(attribute.clone());
+        }
+        drop(inner);
+
+        for (span, attribute) in attributes {
+            if !used.contains(&(span, attribute.clone())) {
+                self.add_warning(CodeError {
+                    kind: CodeErrorKind::UnusedLintAttribute(attribute),
+                    backtrace: vec![Marker::Span(span)],
+                });
+            }
+        }
+    }
+
     pub fn add_note(&self, err: CodeError) {
         self.inner.borrow_mut().messages.insert((Level::Note, err));
     }
@@ -268,6 +470,46 @@ impl DiagnosticContext {
             .any(|(level, _)| *level == Level::Error)
     }
 
+    pub fn has_warnings(&self) -> bool {
+        self.inner
+            .borrow()
+            .messages
+            .iter()
+            .any(|(level, _)| *level == Level::Warning)
+    }
+
+    /// All the diagnostics without colors, notes or backtraces, sorted by location.
+    pub fn plain_diagnostics(&self) -> Vec<PlainDiagnostic> {
+        let inner = self.inner.borrow();
+        let mut result: Vec<_> = inner
+            .messages
+            .iter()
+            .map(|(level, err)| {
+                let location = err
+                    .backtrace
+                    .iter()
+                    .find_map(|m| match m {
+                        Marker::Span(span) => Some(span),
+                        _ => None,
+                    })
+                    .and_then(|span| {
+                        let file_name = inner.file_map.get(&span.file)?;
+                        Some((file_name.clone(), span.line + 1, self.column(span)))
+                    });
+
+                PlainDiagnostic {
+                    location,
+                    level: level.as_str(),
+                    message: err.kind.to_string(),
+                }
+            })
+            .collect();
+
+        result.sort();
+        result.dedup();
+        result
+    }
+
     pub fn print_error_report(&self) -> Result<(), AluminaError> {
         let inner = self.inner.borrow();
         let mut all_errors: Vec<_> = inner.messages.iter().collect();
@@ -332,17 +574,41 @@ impl DiagnosticContext {
             }
 
             for marker in filtered_frames {
-                let Marker::Span(span) = marker else { continue };
-
-                if let Some(file_name) = inner.file_map.get(&span.file) {
-                    eprintln!(
-                        "  --> {}:{}:{}",
-                        file_name.display(),
-                        span.line + 1,
-                        span.column + 1
-                    );
-                } else {
-                    eprintln!("  --> {{ unknown location }}");
+                match marker {
+                    Marker::Span(span) => {
+                        if let Some(file_name) = inner.file_map.get(&span.file) {
+                            eprintln!(
+                                "  --> {}:{}:{}",
+                                file_name.display(),
+                                span.line + 1,
+                                self.column(span)
+                            );
+                        } else {
+                            eprintln!("  --> {{ unknown location }}");
+                        }
+                    }
+                    Marker::Instantiation(name) => {
+                        eprintln!(
+                            "  {} required by instantiation of `{}`",
+                            "note:".bold(),
+                            name
+                        );
+                    }
+                    Marker::StaticCondition(reason) => {
+                        eprintln!("  {} {}", "note:".bold(), reason);
+                    }
+                    Marker::RelatedSpan(label, span) => {
+                        eprintln!("  {} {}", "note:".bold(), label);
+                        if let Some(file_name) = inner.file_map.get(&span.file) {
+                            eprintln!(
+                                "  --> {}:{}:{}",
+                                file_name.display(),
+                                span.line + 1,
+                                self.column(span)
+                            );
+                        }
+                    }
+                    _ => continue,
                 }
                 needs_padding = true;
             }
@@ -364,6 +630,21 @@ impl DiagnosticContext {
                     eprintln!();
                     eprintln!("Compiler backtrace:");
                     eprintln!("{}", backtrace);
+                    eprintln!();
+                    match write_ice_report(self, error, &backtrace.to_string()) {
+                        Ok(path) => eprintln!(
+                            "  {} this is a bug in the compiler, sorry! A report with the details \
+                             was written to {}, please attach it when reporting the issue.",
+                            "note:".bold(),
+                            path.display()
+                        ),
+                        Err(e) => eprintln!(
+                            "  {} this is a bug in the compiler, sorry! The report could not be \
+                             written ({}).",
+                            "note:".bold(),
+                            e
+                        ),
+                    }
                     needs_padding = true;
                 }
                 _ => {}
@@ -377,3 +658,125 @@ impl DiagnosticContext {
         Ok(())
     }
 }
+
+/// Source lines around the span, with the line of the span marked
+fn source_snippet(diag: &DiagnosticContext, span: &Span) -> Option<String> {
+    const CONTEXT_LINES: usize = 2;
+
+    let line_map = diag.line_map(span.file)?;
+    let line = line_map.line(span.start);
+    let mut snippet = String::new();
+    for index in line.saturating_sub(CONTEXT_LINES)..(line + CONTEXT_LINES + 1) {
+        if index >= line_map.line_count() {
+            break;
+        }
+
+        let marker = if index == line { '>' } else { ' ' };
+        let text = line_map.expand_tabs(index);
+        writeln!(snippet, "{} {:>5} | {}", marker, index + 1, text).unwrap();
+
+        if index == line {
+            // Underline the span (up to the end of the line if it spans multiple lines)
+            let start = line_map.display_column(span.start);
+            let end = line_map.display_column(span.end.min(line_map.line_start(line + 1)));
+            let end = end.min(text.chars().count()).max(start + 1);
+            writeln!(
+                snippet,
+                "  {:>5} | {}{}",
+                "",
+                " ".repeat(start),
+                "^".repeat(end - start)
+            )
+            .unwrap();
+        }
+    }
+
+    Some(snippet)
+}
+
+/// Writes the details of an internal compiler error (compiler version, the chain of
+/// instantiations that led to it and the relevant source code) to a file in the temporary
+/// directory, so that it can be attached to a bug report.
+fn write_ice_report(
+    diag: &DiagnosticContext,
+    error: &CodeError,
+    backtrace: &str,
+) -> std::io::Result<PathBuf> {
+    let mut report = String::new();
+    writeln!(report, "alumina-boot {}", env!("CARGO_PKG_VERSION")).unwrap();
+    writeln!(
+        report,
+        "command line: {}",
+        std::env::args().collect::<Vec<_>>().join(" ")
+    )
+    .unwrap();
+    writeln!(report, "\nerror: {}", error.kind).unwrap();
+
+    let spans: Vec<_> = error
+        .backtrace
+        .iter()
+        .filter_map(|m| match m {
+            Marker::Span(span) => Some(span),
+            _ => None,
+        })
+        .collect();
+
+    // The innermost span is where the error happened and the outermost one is usually the
+    // item that was being processed.
+    for (title, span) in [("location", spans.first()), ("item", spans.last())] {
+        let Some(span) = span else {
+            continue;
+        };
+        let file_name = diag
+            .get_file_path(span.file)
+            .map(|f| f.display().to_string())
+            .unwrap_or_else(|| "{ unknown location }".to_string());
+
+        writeln!(
+            report,
+            "\n{}: {}:{}:{}",
+            title,
+            file_name,
+            span.line + 1,
+            diag.column(span)
+        )
+        .unwrap();
+        if let Some(snippet) = source_snippet(diag, span) {
+            report.push_str(&snippet);
+        }
+
+        if spans.len() == 1 {
+            break;
+        }
+    }
+
+    let instantiations: Vec<_> = error
+        .backtrace
+        .iter()
+        .filter_map(|m| match m {
+            Marker::Instantiation(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+    if !instantiations.is_empty() {
+        writeln!(report, "\ninstantiations (innermost first):").unwrap();
+        for name in instantiations {
+            writeln!(report, "  {}", name).unwrap();
+        }
+    }
+
+    writeln!(report, "\ncompiler backtrace:\n{}", backtrace).unwrap();
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let path = std::env::temp_dir().join(format!(
+        "alumina-ice-{}-{}.txt",
+        timestamp,
+        std::process::id()
+    ));
+    std::fs::write(&path, report)?;
+
+    Ok(path)
+}
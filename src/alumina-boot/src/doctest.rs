@@ -0,0 +1,172 @@
+//! Doctests (`--test --doctests`)
+//!
+//! The fenced code blocks in the doc comments (`///` and `//!`) of a source file are
+//! compiled as tests, each of them in a module of its own as the body of a `#[test]`
+//! function. The info string of the fence decides what happens with the block:
+//!
+//! - none or `alumina`, `dubious`: compiled and run
+//! - `no_run`, `nasaldemons`: compiled, but the test is ignored
+//! - `ignore`: neither compiled nor run, but still reported as an ignored test
+//! - `panics`: compiled and expected to panic
+//! - anything else (`no_test`, `compile_fail`, `bare`, `text`, ...): skipped
+//!
+//! Lines starting with `# ` are hidden lines: they are compiled (without the `# `), but
+//! alumina-doc does not show them, which is useful for setup code that would distract from
+//! the example.
+//!
+//! The generated source has the same layout as the original file: everything except the
+//! code is blanked out with spaces and the code stays at its original offset, so that the
+//! errors, panics and `line!()` point into the doc comment.
+
+pub struct Doctest {
+    /// Line of the opening fence (1-based)
+    pub line: usize,
+    pub source: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Run,
+    NoRun,
+    Ignore,
+    Panics,
+}
+
+impl Mode {
+    fn from_info(info: &str) -> Option<Self> {
+        match info {
+            "" | "alumina" | "dubious" => Some(Mode::Run),
+            "no_run" | "nasaldemons" => Some(Mode::NoRun),
+            "ignore" => Some(Mode::Ignore),
+            "panics" => Some(Mode::Panics),
+            _ => None,
+        }
+    }
+
+    fn attribute(self) -> &'static str {
+        match self {
+            Mode::Run => "#[test]",
+            Mode::NoRun | Mode::Ignore => "#[test(ignore)]",
+            Mode::Panics => "#[test(should_fail)]",
+        }
+    }
+}
+
+struct Block {
+    line: usize,
+    prefix: &'static str,
+    mode: Option<Mode>,
+    /// Byte ranges of the code lines (without the comment prefix)
+    code: Vec<(usize, usize)>,
+}
+
+fn doc_prefix(line: &str) -> Option<&'static str> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with("///") && !trimmed.starts_with("////") {
+        Some("///")
+    } else if trimmed.starts_with("//!") {
+        Some("//!")
+    } else {
+        None
+    }
+}
+
+fn generate(source: &str, mode: Mode, code: &[(usize, usize)]) -> String {
+    let mut result: Vec<u8> = source
+        .bytes()
+        .map(|b| if b == b'\n' || b == b'\r' { b } else { b' ' })
+        .collect();
+
+    // Ignored blocks become empty tests, so that they show up in the results
+    if mode != Mode::Ignore {
+        for &(start, end) in code {
+            result[start..end].copy_from_slice(&source.as_bytes()[start..end]);
+        }
+    }
+
+    // The header goes on a line before the code that is long enough (all of them are blank
+    // by now). If there is none, it is prepended, which only shifts the columns on the first
+    // line.
+    let header = format!("{} fn run() {{", mode.attribute());
+    let mut line_start = 0;
+    let mut slot = None;
+    for line in source[..code[0].0].split_inclusive('\n') {
+        if line.trim_end_matches(['\n', '\r']).len() >= header.len() {
+            slot = Some(line_start);
+        }
+        line_start += line.len();
+    }
+
+    match slot {
+        Some(start) => result[start..start + header.len()].copy_from_slice(header.as_bytes()),
+        None => {
+            result.splice(0..0, format!("{} ", header).bytes());
+        }
+    }
+
+    result.extend_from_slice(b"\n}\n");
+
+    // Only ASCII was written over whole characters, so this cannot fail
+    String::from_utf8(result).unwrap()
+}
+
+/// Extracts the doctests from the source of a file
+pub fn extract(source: &str) -> Vec<Doctest> {
+    let mut result = Vec::new();
+    let mut block: Option<Block> = None;
+    let mut offset = 0;
+
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        let line_start = offset;
+        offset += line.len();
+        let line = line.trim_end_matches(['\n', '\r']);
+
+        let Some(prefix) = doc_prefix(line) else {
+            // The doc comment ended without closing the fence
+            block = None;
+            continue;
+        };
+
+        let content_start = line.find(prefix).unwrap() + prefix.len();
+        let content = &line[content_start..];
+        let content_start = content_start + usize::from(content.starts_with(' '));
+        let content = content.strip_prefix(' ').unwrap_or(content);
+
+        match block.take() {
+            Some(b) if b.prefix != prefix => {}
+            Some(b) if content.trim().starts_with("```") => {
+                if let (Some(mode), false) = (b.mode, b.code.is_empty()) {
+                    result.push(Doctest {
+                        line: b.line,
+                        source: generate(source, mode, &b.code),
+                    });
+                }
+            }
+            Some(mut b) => {
+                // Hidden lines are compiled without the marker
+                let hidden = if content == "#" {
+                    1
+                } else if content.starts_with("# ") {
+                    2
+                } else {
+                    0
+                };
+                b.code
+                    .push((line_start + content_start + hidden, line_start + line.len()));
+                block = Some(b);
+            }
+            None => {
+                if let Some(info) = content.trim().strip_prefix("```") {
+                    block = Some(Block {
+                        line: index + 1,
+                        prefix,
+                        mode: Mode::from_info(info.trim()),
+                        code: Vec::new(),
+                    });
+                }
+            }
+        }
+    }
+
+    result
+}
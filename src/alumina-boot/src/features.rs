@@ -0,0 +1,57 @@
+//! Feature gates for unstable language features
+//!
+//! Experimental syntax is rejected unless its feature is enabled, either for a single file
+//! with a `#![feature(...)]` attribute at the top of it or for the whole program with
+//! `-Z unstable-features=name,...`. The error names the gate, so that code that uses a
+//! feature before it is stabilized opts into it explicitly.
+//!
+//! When a feature is stabilized, it is removed from [Feature] and the gate is dropped from
+//! the code that checks it.
+
+use crate::common::{CodeErrorKind, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// `fn foo<Args...>(args: Args)`
+    VariadicGenerics,
+    /// `for const x in tuple { ... }`
+    ForConst,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 2] = [Feature::VariadicGenerics, Feature::ForConst];
+
+    /// Name of the gate in `#![feature(...)]` and `-Z unstable-features=`
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::VariadicGenerics => "variadic_generics",
+            Feature::ForConst => "for_const",
+        }
+    }
+
+    /// What the feature is, for the error message
+    pub fn description(self) -> &'static str {
+        match self {
+            Feature::VariadicGenerics => "variadic generic parameters are",
+            Feature::ForConst => "`for const` loops are",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    /// Error for using the feature without enabling it
+    pub fn error(self) -> CodeErrorKind {
+        CodeErrorKind::UnstableFeature(self.description().to_string(), self.name().to_string())
+    }
+}
+
+/// Names of the features enabled for the whole program with `-Z unstable-features=`
+pub fn option_names(options: &HashSet<String>) -> impl Iterator<Item = &str> {
+    options
+        .iter()
+        .filter_map(|option| option.strip_prefix("unstable-features="))
+        .flat_map(|value| value.split(','))
+        .filter(|name| !name.is_empty())
+}
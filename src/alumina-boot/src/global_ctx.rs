@@ -1,54 +1,142 @@
-use crate::common::{HashMap, HashSet};
+use crate::ast::Span;
+use crate::common::{CodeError, CodeErrorKind, FileId, HashMap, HashSet, IndexMap, IndexSet};
+use crate::completion::{CompletionItem, CompletionQuery};
 use crate::diagnostics::{self, DiagnosticContext};
+use crate::features::{self, Feature};
+use crate::lints::{self, LintRegistry};
+use crate::name_index::NameRef;
+use crate::rename::RenameQuery;
+use crate::target::Target;
 
 use std::cell::{Ref, RefCell};
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum OutputType {
     Library,
+    /// Library that is linked as a shared object (`.so`/`.dylib`/`.dll`). Only the
+    /// exported functions are visible to the users of the library.
+    SharedLibrary,
     Executable,
 }
 
+/// Dialect of the generated C code
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum CDialect {
+    /// C11 with GNU extensions
+    #[default]
+    Gnu,
+    /// Code that also compiles as C++ (with GNU extensions), e.g. for including it in
+    /// C++ builds
+    Cxx,
+    /// No compound literals or designated initializers, for older or non-GNU compilers
+    Conservative,
+}
+
+impl CDialect {
+    /// Whether aggregates can be constructed with compound literals and designated
+    /// initializers (`(T){.a = 1, .b = 2}`)
+    pub fn has_aggregate_literals(self) -> bool {
+        !matches!(self, CDialect::Conservative)
+    }
+
+    /// Whether the compiler has a native 128-bit integer type (`__int128`)
+    pub fn has_int128(self) -> bool {
+        !matches!(self, CDialect::Conservative)
+    }
+
+    /// Whether the compiler has the half precision float types (`_Float16` and `__bf16`)
+    pub fn has_float16(self) -> bool {
+        matches!(self, CDialect::Gnu)
+    }
+}
+
+/// What happens when a slice is indexed out of bounds (`--index-checks`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum IndexChecks {
+    /// Panic with a message (the checks are only compiled in debug builds, unless the
+    /// mode is given explicitly)
+    #[default]
+    Panic,
+    /// Abort the program with a trap instruction, without formatting a message
+    Trap,
+    /// Assume that indices are always in bounds
+    Unchecked,
+}
+
+/// How a native library requested with `#[link]` is linked
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    Dylib,
+    Static,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LinkLibrary {
+    pub name: String,
+    pub kind: LinkKind,
+}
+
 struct GlobalCtxInner {
     pub diag: DiagnosticContext,
     pub cfg: HashMap<String, Option<String>>,
     pub options: HashSet<String>,
     pub output_type: OutputType,
+    pub c_dialect: CDialect,
+    pub index_checks: IndexChecks,
+    pub name_index: Option<RefCell<Vec<NameRef>>>,
+    pub completion: Option<RefCell<CompletionQuery>>,
+    pub rename: Option<RefCell<RenameQuery>>,
+    pub link_libraries: Vec<LinkLibrary>,
+    pub alloc_sites: IndexSet<Span>,
+    pub build_timestamp: Option<u64>,
+    pub dependencies: IndexSet<PathBuf>,
+    pub env_vars: IndexMap<String, Option<String>>,
+    /// Enabled unstable features, for a single file or for the whole program (`None`)
+    pub features: HashSet<(Option<FileId>, Feature)>,
 }
 
 #[derive(Clone)]
 pub struct GlobalCtx {
     inner: Rc<RefCell<GlobalCtxInner>>,
+    // Separate from the rest, as lint passes need to access the diagnostic context
+    // while they run.
+    lints: Rc<LintRegistry>,
 }
 
 impl GlobalCtx {
     pub fn new(output_type: OutputType, options: Vec<String>) -> Self {
+        let options: HashSet<String> = options.into_iter().collect();
+        let lints = LintRegistry::new(&options);
+
         let mut result = Self {
             inner: Rc::new(RefCell::new(GlobalCtxInner {
                 diag: DiagnosticContext::new(),
                 cfg: HashMap::default(),
-                options: options.into_iter().collect(),
+                options,
                 output_type,
+                c_dialect: CDialect::default(),
+                index_checks: IndexChecks::default(),
+                name_index: None,
+                completion: None,
+                rename: None,
+                link_libraries: Vec::new(),
+                alloc_sites: IndexSet::default(),
+                build_timestamp: None,
+                dependencies: IndexSet::default(),
+                env_vars: IndexMap::default(),
+                features: HashSet::default(),
             })),
+            lints: Rc::new(lints),
         };
 
         // We are the alumina-boot compiler
         result.add_flag("boot");
 
-        // No cross-compilation, so we just use whatever the compiler was compiled with
-        result.add_cfg("target_os", std::env::consts::OS);
-        result.add_cfg("target_family", std::env::consts::FAMILY);
-        result.add_cfg("target_arch", std::env::consts::ARCH);
-        result.add_cfg(
-            "target_pointer_width",
-            (std::mem::size_of::<usize>() * 8).to_string(),
-        );
-
-        #[cfg(target_endian = "big")]
-        result.add_cfg("target_endian", "big".to_string());
-        #[cfg(target_endian = "little")]
-        result.add_cfg("target_endian", "little".to_string());
+        // Whatever the compiler was compiled with, unless overridden with `--target`
+        result.set_target(&Target::host());
 
         match output_type {
             OutputType::Executable => {
@@ -57,13 +145,29 @@ impl GlobalCtx {
             OutputType::Library => {
                 result.add_cfg("output_type", "library");
             }
+            OutputType::SharedLibrary => {
+                result.add_cfg("output_type", "shared_library");
+            }
         };
 
+        // The sysroot only defines the default hooks when they are needed
+        if result.has_option("instrument-functions") {
+            result.add_flag("instrument_functions");
+        }
+        if result.has_option("instrument-allocs") {
+            result.add_flag("instrument_allocs");
+        }
+        // So that the sysroot does not call `memset` and friends itself either
+        if result.has_option("no-builtins") {
+            result.add_flag("no_builtins");
+        }
+
         if result.has_option("deny-warnings") {
             result.diag().add_override(diagnostics::Override {
                 span: None,
                 kind: None,
                 action: diagnostics::Action::Deny,
+                attribute: None,
             });
         }
 
@@ -72,16 +176,177 @@ impl GlobalCtx {
                 span: None,
                 kind: None,
                 action: diagnostics::Action::Allow,
+                attribute: None,
             });
         }
 
+        let names: Vec<_> = features::option_names(&result.inner.borrow().options)
+            .map(str::to_string)
+            .collect();
+        for name in names {
+            match Feature::from_name(&name) {
+                Some(feature) => {
+                    result.inner.borrow_mut().features.insert((None, feature));
+                }
+                None => result.diag().add_error(CodeError::from_kind(
+                    CodeErrorKind::UnknownFeature(name),
+                    None,
+                )),
+            }
+        }
+
+        // After the global settings, so these stay off even with `-Zdeny-warnings`
+        for lint in lints::ALLOWED_BY_DEFAULT {
+            result.diag().add_override(diagnostics::Override {
+                span: None,
+                kind: Some(lint),
+                action: diagnostics::Action::Allow,
+                attribute: None,
+            });
+        }
+
+        result
+    }
+
+    /// Creates a new context with the same options and `cfg` values, but with
+    /// a fresh diagnostic context.
+    pub fn fork(&self) -> Self {
+        let inner = self.inner.borrow();
+        let result = Self::new(inner.output_type, inner.options.iter().cloned().collect());
+        result.inner.borrow_mut().cfg = inner.cfg.clone();
+        result.inner.borrow_mut().c_dialect = inner.c_dialect;
+        result.inner.borrow_mut().index_checks = inner.index_checks;
+
         result
     }
 
+    /// Requests a native library to be linked (`#[link]`). Libraries are linked in the
+    /// order in which they were first requested.
+    pub fn add_link_library(&self, library: LinkLibrary) {
+        let mut inner = self.inner.borrow_mut();
+        if !inner.link_libraries.contains(&library) {
+            inner.link_libraries.push(library);
+        }
+    }
+
+    pub fn link_libraries(&self) -> Vec<LinkLibrary> {
+        self.inner.borrow().link_libraries.clone()
+    }
+
+    /// Id of a call site of an `#[allocator]` function (`-Zinstrument-allocs`). Ids start
+    /// at 1, so that 0 can mean an unknown site.
+    pub fn alloc_site_id(&self, span: Span) -> u32 {
+        let (index, _) = self.inner.borrow_mut().alloc_sites.insert_full(span);
+        index as u32 + 1
+    }
+
+    /// Call sites of `#[allocator]` functions, in the order of their ids
+    pub fn alloc_sites(&self) -> Vec<Span> {
+        self.inner.borrow().alloc_sites.iter().copied().collect()
+    }
+
+    /// Enables an unstable feature for a file (`#![feature(...)]`)
+    pub fn enable_feature(&self, file: FileId, feature: Feature) {
+        self.inner
+            .borrow_mut()
+            .features
+            .insert((Some(file), feature));
+    }
+
+    /// Whether an unstable feature can be used in the given file
+    pub fn feature_enabled(&self, feature: Feature, file: FileId) -> bool {
+        let inner = self.inner.borrow();
+        inner.features.contains(&(None, feature)) || inner.features.contains(&(Some(file), feature))
+    }
+
+    /// Records a file that the program depends on besides its modules (`include_bytes!`)
+    pub fn add_dependency(&self, path: PathBuf) {
+        self.inner.borrow_mut().dependencies.insert(path);
+    }
+
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        self.inner.borrow().dependencies.iter().cloned().collect()
+    }
+
+    /// Reads an environment variable for the program (`env!`) and records it, along with
+    /// its value (`None` if it is not set)
+    pub fn env_var(&self, name: &str) -> Option<String> {
+        let value = std::env::var(name).ok();
+        self.inner
+            .borrow_mut()
+            .env_vars
+            .insert(name.to_string(), value.clone());
+        value
+    }
+
+    pub fn env_vars(&self) -> Vec<(String, Option<String>)> {
+        self.inner
+            .borrow()
+            .env_vars
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Whether the test harness is built instead of the program (`--test` or `--cfg test`)
+    pub fn test_mode(&self) -> bool {
+        self.cfg("test").is_some()
+    }
+
     pub fn should_generate_main_glue(&self) -> bool {
         matches!(self.inner.borrow().output_type, OutputType::Executable)
     }
 
+    pub fn output_type(&self) -> OutputType {
+        self.inner.borrow().output_type
+    }
+
+    pub fn c_dialect(&self) -> CDialect {
+        self.inner.borrow().c_dialect
+    }
+
+    pub fn set_c_dialect(&mut self, dialect: CDialect) {
+        self.inner.borrow_mut().c_dialect = dialect;
+    }
+
+    pub fn index_checks(&self) -> IndexChecks {
+        self.inner.borrow().index_checks
+    }
+
+    /// Sets the behavior of out of bounds slice indexing. Unless it is `unchecked`, the
+    /// checks are also compiled into release builds (with the `bounds_checks` flag).
+    pub fn set_index_checks(&mut self, mode: IndexChecks) {
+        self.inner.borrow_mut().index_checks = mode;
+        if mode != IndexChecks::Unchecked {
+            self.add_flag("bounds_checks");
+        }
+    }
+
+    /// Whether 128-bit integers are implemented in software (pair of `uint64_t`) rather
+    /// than with `__int128`, which is not available on MSVC and on 32-bit targets.
+    pub fn soft_int128(&self) -> bool {
+        !self.c_dialect().has_int128()
+            || self.cfg("target_pointer_width") == Some(Some("32".to_string()))
+            || self.has_option("soft-int128")
+    }
+
+    /// Whether `f16` and `bf16` are stored as raw bits and computed on as `float`, for
+    /// compilers and targets that do not have `_Float16` and `__bf16`.
+    pub fn soft_float16(&self) -> bool {
+        let native_arch = matches!(
+            self.cfg("target_arch"),
+            Some(Some(arch)) if matches!(arch.as_str(), "x86_64" | "aarch64" | "arm" | "riscv64")
+        );
+
+        !self.c_dialect().has_float16() || !native_arch || self.has_option("soft-float16")
+    }
+
+    /// Same as `soft_float16` for `bf16`. Arithmetic on `__bf16` needs GCC 13 or Clang 17,
+    /// so it is opt-in with `-Znative-bf16`.
+    pub fn soft_bfloat16(&self) -> bool {
+        self.soft_float16() || !self.has_option("native-bf16")
+    }
+
     pub fn has_option(&self, name: &str) -> bool {
         self.inner.borrow().options.contains(name)
     }
@@ -90,6 +355,10 @@ impl GlobalCtx {
         Ref::map(self.inner.borrow(), |inner| &inner.diag)
     }
 
+    pub fn lints(&self) -> &LintRegistry {
+        &self.lints
+    }
+
     pub fn add_flag(&mut self, value: impl ToString) {
         let mut borrowed = self.inner.borrow_mut();
         borrowed.cfg.insert(value.to_string(), None);
@@ -99,6 +368,57 @@ impl GlobalCtx {
         self.inner.borrow().cfg.contains_key(name)
     }
 
+    /// Replaces the `target_*` cfg values with the ones for the given target
+    pub fn set_target(&mut self, target: &Target) {
+        let mut borrowed = self.inner.borrow_mut();
+        borrowed.cfg.retain(|key, _| {
+            !matches!(
+                key.as_str(),
+                "target_os"
+                    | "target_family"
+                    | "target_arch"
+                    | "target_pointer_width"
+                    | "target_endian"
+            )
+        });
+        for (key, value) in target.cfg() {
+            borrowed.cfg.insert(key.to_string(), Some(value));
+        }
+    }
+
+    /// Seconds since the Unix epoch for `build_timestamp!()`, the same for the whole
+    /// compilation. `SOURCE_DATE_EPOCH` is used if it is set, so that the build stays
+    /// reproducible. Otherwise the current time is only used with `-Zbuild-timestamp`.
+    pub fn build_timestamp(&self) -> Option<u64> {
+        if let Some(timestamp) = self.inner.borrow().build_timestamp {
+            return Some(timestamp);
+        }
+
+        let timestamp = match std::env::var("SOURCE_DATE_EPOCH") {
+            Ok(value) => value.trim().parse().ok()?,
+            Err(_) if self.has_option("build-timestamp") => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            Err(_) => return None,
+        };
+
+        self.inner.borrow_mut().build_timestamp = Some(timestamp);
+        Some(timestamp)
+    }
+
+    /// All the cfg values and flags, sorted by name (`--print-cfg`)
+    pub fn cfg_values(&self) -> Vec<(String, Option<String>)> {
+        let mut result: Vec<_> = self
+            .inner
+            .borrow()
+            .cfg
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        result.sort();
+        result
+    }
+
     pub fn add_cfg(&mut self, value: impl ToString, value_str: impl ToString) {
         let mut borrowed = self.inner.borrow_mut();
         borrowed
@@ -106,6 +426,95 @@ impl GlobalCtx {
             .insert(value.to_string(), Some(value_str.to_string()));
     }
 
+    /// Start collecting resolved names (for editor tooling outputs)
+    pub fn enable_name_index(&mut self) {
+        self.inner.borrow_mut().name_index = Some(RefCell::default());
+    }
+
+    /// Whether resolution errors should be recorded and recovered from instead of aborting
+    /// the compilation, so that editor tooling outputs still cover the parts of the program
+    /// that do resolve.
+    pub fn error_tolerant(&self) -> bool {
+        let inner = self.inner.borrow();
+        inner.name_index.is_some() || inner.completion.is_some()
+    }
+
+    pub fn record_name(&self, name: Option<NameRef>) {
+        if let (Some(index), Some(name)) = (&self.inner.borrow().name_index, name) {
+            index.borrow_mut().push(name);
+        }
+    }
+
+    pub fn name_index(&self) -> Vec<NameRef> {
+        self.inner
+            .borrow()
+            .name_index
+            .as_ref()
+            .map(|index| index.borrow().clone())
+            .unwrap_or_default()
+    }
+
+    /// Start collecting completions for the given position (`--complete`)
+    pub fn enable_completion(&mut self, position: Span) {
+        self.inner.borrow_mut().completion = Some(RefCell::new(CompletionQuery::new(position)));
+    }
+
+    pub fn completion_position(&self) -> Option<Span> {
+        self.inner
+            .borrow()
+            .completion
+            .as_ref()
+            .map(|query| query.borrow().position)
+    }
+
+    /// Adds the candidates that start with `prefix`. Names that were already offered take
+    /// precedence, so candidates from inner scopes should be added first.
+    pub fn add_completions(&self, prefix: &str, items: impl IntoIterator<Item = CompletionItem>) {
+        if let Some(query) = &self.inner.borrow().completion {
+            query.borrow_mut().add(prefix, items);
+        }
+    }
+
+    pub fn completions(&self) -> Vec<CompletionItem> {
+        self.inner
+            .borrow()
+            .completion
+            .as_ref()
+            .map(|query| query.borrow().items.clone())
+            .unwrap_or_default()
+    }
+
+    /// Start collecting the places where `new_name` would conflict with an existing
+    /// name (`--rename`)
+    pub fn enable_rename(&mut self, new_name: String) {
+        self.inner.borrow_mut().rename = Some(RefCell::new(RenameQuery::new(new_name)));
+    }
+
+    pub fn rename_to(&self) -> Option<String> {
+        self.inner
+            .borrow()
+            .rename
+            .as_ref()
+            .map(|query| query.borrow().new_name.clone())
+    }
+
+    /// Marks a name occurrence or definition that cannot be renamed, because the new name
+    /// already refers to something else there.
+    pub fn record_rename_conflict(&self, span: Span) {
+        if let Some(query) = &self.inner.borrow().rename {
+            query.borrow_mut().conflicts.insert(span);
+        }
+    }
+
+    pub fn rename_conflicts(&self) -> HashSet<Span> {
+        self.inner
+            .borrow()
+            .rename
+            .as_ref()
+            .map(|query| query.borrow().conflicts.clone())
+            .unwrap_or_default()
+    }
+
     pub fn cfg(&self, key: impl ToString) -> Option<Option<String>> {
         let borrowed = self.inner.borrow();
         borrowed.cfg.get(&key.to_string()).cloned()
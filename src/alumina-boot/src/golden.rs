@@ -0,0 +1,176 @@
+//! Diagnostics tests (`--emit diagnostics-golden`)
+//!
+//! Instead of the usual report, the diagnostics are printed to stdout one per line in
+//! a stable format (`path:line:column: level: message`) with paths relative to the
+//! working directory (or to the sysroot, as `$SYSROOT/...`), so that the output can be
+//! compared against a golden file.
+//!
+//! Source files can also contain expectation comments, which are checked against the
+//! diagnostics that were reported:
+//!
+//! ```text
+//! let a = 1; //~ WARNING unused variable
+//! let b: i32 = "hello";
+//! //~^ ERROR type mismatch
+//! ```
+//!
+//! The text after the level has to be a substring of the message (it can be omitted).
+//! Each `^` moves the expectation one line up. Every error and warning in a non-sysroot file
+//! has to be expected, so a file without expectations must compile cleanly. The only
+//! exception are files with a `.golden` file next to them and no expectations, whose output
+//! is compared against the golden file instead.
+
+use crate::compiler::SourceFile;
+use crate::diagnostics::{DiagnosticContext, PlainDiagnostic};
+use crate::parser::read_source_lossy;
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+struct Expectation {
+    filename: PathBuf,
+    line: usize,
+    level: &'static str,
+    message: String,
+}
+
+fn parse_expectations(source_file: &SourceFile, source: &str) -> Vec<Expectation> {
+    let mut result = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let Some((_, annotation)) = line.split_once("//~") else {
+            continue;
+        };
+
+        let carets = annotation.len() - annotation.trim_start_matches('^').len();
+        let annotation = annotation[carets..].trim_start();
+        let (level, message) = annotation
+            .split_once(char::is_whitespace)
+            .unwrap_or((annotation, ""));
+
+        let level = match level {
+            "ERROR" => "error",
+            "WARNING" => "warning",
+            "NOTE" => "note",
+            _ => continue,
+        };
+
+        result.push(Expectation {
+            filename: source_file.filename.clone(),
+            line: (index + 1).saturating_sub(carets),
+            level,
+            message: message.trim().to_string(),
+        });
+    }
+
+    result
+}
+
+fn display_path(path: &Path, sysroot: Option<&Path>) -> String {
+    if let Some(relative) = sysroot.and_then(|s| path.strip_prefix(s).ok()) {
+        return format!("$SYSROOT/{}", relative.display());
+    }
+
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+fn display_location(diagnostic: &PlainDiagnostic, sysroot: Option<&Path>) -> String {
+    match &diagnostic.location {
+        Some((path, line, column)) => {
+            format!("{}:{}:{}", display_path(path, sysroot), line, column)
+        }
+        None => "<unknown>".to_string(),
+    }
+}
+
+/// Prints the diagnostics in the golden format and checks them against the expectations
+/// in the source files. Returns `false` if they do not match.
+pub fn report(
+    diag: &DiagnosticContext,
+    source_files: &[SourceFile],
+    sysroot: Option<&Path>,
+) -> bool {
+    let diagnostics = diag.plain_diagnostics();
+    for diagnostic in &diagnostics {
+        println!(
+            "{}: {}: {}",
+            display_location(diagnostic, sysroot),
+            diagnostic.level,
+            diagnostic.message
+        );
+    }
+
+    let user_files: Vec<_> = source_files.iter().filter(|f| !f.sysroot).collect();
+    let mut expectations = Vec::new();
+    for source_file in &user_files {
+        if let Some(source) = read_source_lossy(&source_file.filename) {
+            expectations.extend(parse_expectations(source_file, &source));
+        }
+    }
+
+    let has_golden_file = user_files
+        .iter()
+        .any(|f| f.filename.with_extension("golden").exists());
+    if expectations.is_empty() && has_golden_file {
+        return true;
+    }
+
+    let mut matched = vec![false; diagnostics.len()];
+    let mut success = true;
+
+    for expectation in &expectations {
+        let found = diagnostics.iter().zip(&matched).position(|(d, matched)| {
+            let on_line = match &d.location {
+                Some((path, line, _)) => *path == expectation.filename && *line == expectation.line,
+                None => false,
+            };
+
+            !matched
+                && on_line
+                && d.level == expectation.level
+                && d.message.contains(&expectation.message)
+        });
+
+        match found {
+            Some(index) => matched[index] = true,
+            None => {
+                success = false;
+                eprintln!(
+                    "expected {} `{}` at {}:{} was not reported",
+                    expectation.level,
+                    expectation.message,
+                    display_path(&expectation.filename, sysroot),
+                    expectation.line
+                );
+            }
+        }
+    }
+
+    for (diagnostic, matched) in diagnostics.iter().zip(matched) {
+        if matched || diagnostic.level == "note" {
+            continue;
+        }
+
+        let in_user_file = match &diagnostic.location {
+            Some((path, _, _)) => user_files.iter().any(|f| f.filename == *path),
+            None => true,
+        };
+
+        if in_user_file {
+            success = false;
+            eprintln!(
+                "unexpected {} at {}: {}",
+                diagnostic.level,
+                display_location(diagnostic, sysroot),
+                diagnostic.message
+            );
+        }
+    }
+
+    success
+}
@@ -18,10 +18,15 @@ pub enum IntrinsicKind {
     CodegenTypeFunc,
     MakeVtable,
     EnumVariants,
+    IsEnumValue,
     Uninitialized,
     Dangling,
     Zeroed,
+    StackAlloc,
+    CopyNonoverlapping,
+    Copy,
     Asm,
+    CInline,
     InConstContext,
     IsConstEvaluable,
     ConstEval,
@@ -50,10 +55,15 @@ pub fn intrinsic_kind(name: &str) -> Option<IntrinsicKind> {
         "codegen_type_func" => IntrinsicKind::CodegenTypeFunc,
         "vtable" => IntrinsicKind::MakeVtable,
         "enum_variants" => IntrinsicKind::EnumVariants,
+        "is_enum_value" => IntrinsicKind::IsEnumValue,
         "asm" => IntrinsicKind::Asm,
+        "c_inline" => IntrinsicKind::CInline,
         "uninitialized" => IntrinsicKind::Uninitialized,
         "dangling" => IntrinsicKind::Dangling,
         "zeroed" => IntrinsicKind::Zeroed,
+        "stack_alloc" => IntrinsicKind::StackAlloc,
+        "copy_nonoverlapping" => IntrinsicKind::CopyNonoverlapping,
+        "copy" => IntrinsicKind::Copy,
         "in_const_context" => IntrinsicKind::InConstContext,
         "is_const_evaluable" => IntrinsicKind::IsConstEvaluable,
         "const_eval" => IntrinsicKind::ConstEval,
@@ -72,7 +82,12 @@ pub fn intrinsic_kind(name: &str) -> Option<IntrinsicKind> {
 pub enum IntrinsicValueKind<'ir> {
     SizeOfLike(&'ir str, TyP<'ir>),
     Dangling(TyP<'ir>),
-    Asm(&'ir str),
+    /// Basic (or with clobbers, extended) inline assembly
+    Asm(&'ir str, &'ir [&'ir str]),
+    CInline(&'ir str),
+    /// `memcpy` (or `memmove` if the ranges may overlap) of `count` elements, only valid
+    /// as a callee with `(dst, src, count)` arguments.
+    MemCopy(bool),
     FunctionLike(&'ir str),
     ConstLike(&'ir str),
     ConstPanic(ExprP<'ir>),
@@ -82,3 +97,43 @@ pub enum IntrinsicValueKind<'ir> {
     Uninitialized,
     InConstContext,
 }
+
+/// Replaces the `$N` placeholders in an inline C snippet with `args[N]` (`$$` is a literal
+/// `$`). Returns the offending placeholder if it is malformed or refers to a missing argument.
+pub fn expand_c_inline(code: &str, args: &[String]) -> Result<String, String> {
+    let mut result = String::with_capacity(code.len());
+    let mut chars = code.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+
+        if let Some((_, '$')) = chars.peek() {
+            chars.next();
+            result.push('$');
+            continue;
+        }
+
+        let mut end = start + 1;
+        while let Some((idx, d)) = chars.peek().copied() {
+            if !d.is_ascii_digit() {
+                break;
+            }
+            end = idx + 1;
+            chars.next();
+        }
+
+        match code[start + 1..end]
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| args.get(i))
+        {
+            Some(arg) => result.push_str(arg),
+            None => return Err(code[start..end].to_string()),
+        }
+    }
+
+    Ok(result)
+}
@@ -2,6 +2,7 @@ use crate::ast::BuiltinType;
 use crate::common::ArenaAllocatable;
 use crate::ir::const_eval::Value;
 use crate::ir::*;
+use crate::utils::{bounded_debug, MAX_DEBUG_LEN};
 
 #[derive(Clone)]
 pub struct ExpressionBuilder<'ir> {
@@ -21,6 +22,16 @@ impl<'ir> ExpressionBuilder<'ir> {
         Expr::lvalue(ExprKind::Static(item), typ, span).alloc_on(self.ir)
     }
 
+    /// Read-only static (`extern const`)
+    pub fn const_static_var(
+        &self,
+        item: IRItemP<'ir>,
+        typ: TyP<'ir>,
+        span: Option<Span>,
+    ) -> ExprP<'ir> {
+        Expr::const_lvalue(ExprKind::Static(item), typ, span).alloc_on(self.ir)
+    }
+
     pub fn const_var(&self, item: IRItemP<'ir>, typ: TyP<'ir>, span: Option<Span>) -> ExprP<'ir> {
         Expr::const_lvalue(ExprKind::Const(item), typ, span).alloc_on(self.ir)
     }
@@ -396,7 +407,7 @@ impl<'ir> ExpressionBuilder<'ir> {
         let result = match inner.ty {
             Ty::Array(ty, _) if !inner.is_const => Expr::lvalue(kind, ty, span),
             Ty::Array(ty, _) if inner.is_const => Expr::const_lvalue(kind, ty, span),
-            _ => panic!("cannot index {:?}", inner.ty),
+            _ => panic!("cannot index {}", bounded_debug(&inner.ty, MAX_DEBUG_LEN)),
         };
 
         result.alloc_on(self.ir)
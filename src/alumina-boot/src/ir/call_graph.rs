@@ -0,0 +1,435 @@
+//! Static call graph of the program after dead code elimination.
+//!
+//! Used to find recursion in functions marked with `#[no_recursion]` and to estimate the
+//! maximum stack depth of the program (`-Zstack-depth`).
+//!
+//! Indirect calls (through function pointers and protocol vtables) can call any function
+//! whose address is taken somewhere in the program and that takes the same number of
+//! arguments. This can only overestimate the possible callees.
+use crate::ast::{Attribute, BuiltinType, Span};
+use crate::common::{AluminaError, CodeErrorBuilder, CodeErrorKind, HashMap};
+use crate::diagnostics::DiagnosticsStack;
+use crate::global_ctx::GlobalCtx;
+use crate::intrinsics::IntrinsicValueKind;
+use crate::ir::layout::Layouter;
+use crate::ir::mono::{item_name, MonoCtx};
+use crate::ir::{
+    default_visit_expr, ExprKind, ExprP, ExpressionVisitor, IRItem, IRItemP, IrCtx, Ty,
+};
+
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy)]
+struct Call {
+    callee: usize,
+    indirect: bool,
+    span: Option<Span>,
+}
+
+struct Node<'ir> {
+    item: IRItemP<'ir>,
+    calls: Vec<Call>,
+    /// Estimated size of the stack frame (locals, parameters and the return address)
+    frame_size: usize,
+    /// Whether the function allocates on the stack dynamically (`stack_alloc`)
+    dynamic_frame: bool,
+    has_callers: bool,
+}
+
+/// Calls made by a single function body
+#[derive(Default)]
+struct CallCollector<'ir> {
+    direct: Vec<(IRItemP<'ir>, Option<Span>)>,
+    /// Number of arguments of each indirect call
+    indirect: Vec<(usize, Option<Span>)>,
+    address_taken: Vec<IRItemP<'ir>>,
+    dynamic_frame: bool,
+}
+
+/// Whether the expression allocates on the stack dynamically (`stack_alloc`)
+pub fn allocates_on_stack(expr: ExprP<'_>) -> Result<bool, AluminaError> {
+    let mut collector = CallCollector::default();
+    collector.visit_expr(expr)?;
+
+    Ok(collector.dynamic_frame)
+}
+
+impl<'ir> ExpressionVisitor<'ir> for CallCollector<'ir> {
+    fn visit_call(
+        &mut self,
+        callee: ExprP<'ir>,
+        args: &'ir [ExprP<'ir>],
+    ) -> Result<(), AluminaError> {
+        match callee.kind {
+            ExprKind::Fn(item) => self.direct.push((item, callee.span)),
+            ExprKind::Intrinsic(IntrinsicValueKind::FunctionLike("__builtin_alloca")) => {
+                self.dynamic_frame = true
+            }
+            ExprKind::Intrinsic(_) => {}
+            _ => {
+                self.indirect.push((args.len(), callee.span));
+                self.visit_expr(callee)?;
+            }
+        }
+
+        for arg in args {
+            self.visit_expr(arg)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_fn(&mut self, item: IRItemP<'ir>) -> Result<(), AluminaError> {
+        self.address_taken.push(item);
+        Ok(())
+    }
+
+    fn visit_expr(&mut self, expr: ExprP<'ir>) -> Result<(), AluminaError> {
+        default_visit_expr(self, expr)
+    }
+}
+
+/// Deepest call chain starting at a function
+#[derive(Clone, Copy)]
+struct Depth {
+    bytes: usize,
+    /// Callee on the deepest chain
+    next: Option<usize>,
+    dynamic: bool,
+}
+
+pub struct CallGraph<'ir> {
+    nodes: Vec<Node<'ir>>,
+}
+
+impl<'ir> CallGraph<'ir> {
+    pub fn new(
+        global_ctx: GlobalCtx,
+        ir: &'ir IrCtx<'ir>,
+        items: &[IRItemP<'ir>],
+    ) -> Result<Self, AluminaError> {
+        let layouter = Layouter::new(global_ctx);
+        let pointer_size = layouter
+            .layout_of(ir.intern_type(Ty::Builtin(BuiltinType::USize)))
+            .with_no_span()?
+            .size;
+
+        let mut nodes = Vec::new();
+        let mut arities = Vec::new();
+        let mut index = HashMap::default();
+        let mut collectors = Vec::new();
+        let mut statics = CallCollector::default();
+
+        for item in items {
+            match item.get().with_no_span()? {
+                IRItem::Function(func) => {
+                    let mut collector = CallCollector::default();
+                    let mut frame_size = 0;
+
+                    if let Some(body) = func.body.get() {
+                        frame_size += pointer_size;
+                        for param in func.args {
+                            frame_size += layouter.layout_of(param.ty).with_no_span()?.size;
+                        }
+                        for def in body.local_defs {
+                            frame_size += layouter.layout_of(def.typ).with_no_span()?.size;
+                        }
+                        for stmt in body.statements {
+                            collector.visit_statement(stmt)?;
+                        }
+                    }
+
+                    index.insert(*item, nodes.len());
+                    nodes.push(Node {
+                        item: *item,
+                        calls: Vec::new(),
+                        frame_size,
+                        dynamic_frame: collector.dynamic_frame,
+                        has_callers: false,
+                    });
+                    arities.push(func.args.len());
+                    collectors.push(collector);
+                }
+                // Function pointers in static initializers (e.g. handler tables)
+                IRItem::Static(s) => {
+                    if let Some(init) = s.init {
+                        statics.visit_expr(init)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut address_taken: Vec<usize> = statics
+            .address_taken
+            .iter()
+            .chain(collectors.iter().flat_map(|c| c.address_taken.iter()))
+            .filter_map(|item| index.get(item).copied())
+            .collect();
+        address_taken.sort_unstable();
+        address_taken.dedup();
+
+        for (node, collector) in nodes.iter_mut().zip(collectors) {
+            for (item, span) in collector.direct {
+                if let Some(&callee) = index.get(&item) {
+                    node.calls.push(Call {
+                        callee,
+                        indirect: false,
+                        span,
+                    });
+                }
+            }
+
+            for (arg_count, span) in collector.indirect {
+                for &callee in &address_taken {
+                    if arities[callee] == arg_count {
+                        node.calls.push(Call {
+                            callee,
+                            indirect: true,
+                            span,
+                        });
+                    }
+                }
+            }
+        }
+
+        let called: Vec<_> = nodes
+            .iter()
+            .flat_map(|node| node.calls.iter().map(|call| call.callee))
+            .collect();
+        for callee in called {
+            nodes[callee].has_callers = true;
+        }
+
+        Ok(Self { nodes })
+    }
+
+    /// Warns about functions marked with `#[no_recursion]` that can (directly or indirectly)
+    /// call themselves.
+    pub fn check_no_recursion(
+        &self,
+        mono_ctx: &MonoCtx<'_, 'ir>,
+        diag: &DiagnosticsStack,
+    ) -> Result<(), AluminaError> {
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let func = node.item.get_function().with_no_span()?;
+            if !func.attributes.contains(&Attribute::NoRecursion) {
+                continue;
+            }
+
+            if let Some(cycle) = self.path_to(idx, idx) {
+                let _guard = cycle[0].span.map(|span| diag.push_span(span));
+                diag.warn(CodeErrorKind::UnexpectedRecursion(
+                    self.function_name(mono_ctx, idx)?,
+                    self.format_path(mono_ctx, idx, &cycle)?,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shortest sequence of calls that leads from one function to another (breadth-first)
+    fn path_to(&self, from: usize, to: usize) -> Option<Vec<Call>> {
+        let mut parent: HashMap<usize, (usize, Call)> = HashMap::default();
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(idx) = queue.pop_front() {
+            for call in &self.nodes[idx].calls {
+                if parent.contains_key(&call.callee) {
+                    continue;
+                }
+                parent.insert(call.callee, (idx, *call));
+
+                if call.callee == to {
+                    let mut path = vec![*call];
+                    let mut current = idx;
+                    while current != from {
+                        let (prev, call) = parent[&current];
+                        path.push(call);
+                        current = prev;
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+
+                queue.push_back(call.callee);
+            }
+        }
+
+        None
+    }
+
+    /// Finds a cycle in the graph, if there is one (depth-first).
+    fn find_cycle(&self) -> Option<(usize, Vec<Call>)> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            New,
+            Active,
+            Done,
+        }
+
+        let mut state = vec![State::New; self.nodes.len()];
+        for root in 0..self.nodes.len() {
+            if state[root] != State::New {
+                continue;
+            }
+
+            // (node, index of the next call to visit)
+            let mut stack = vec![(root, 0)];
+            state[root] = State::Active;
+
+            while let Some((idx, next)) = stack.last_mut() {
+                let idx = *idx;
+                let Some(call) = self.nodes[idx].calls.get(*next).copied() else {
+                    state[idx] = State::Done;
+                    stack.pop();
+                    continue;
+                };
+                *next += 1;
+
+                match state[call.callee] {
+                    State::New => {
+                        state[call.callee] = State::Active;
+                        stack.push((call.callee, 0));
+                    }
+                    State::Active => {
+                        return self
+                            .path_to(call.callee, call.callee)
+                            .map(|path| (call.callee, path));
+                    }
+                    State::Done => {}
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Stack depth of the deepest call chain starting at each function. The graph must
+    /// be acyclic.
+    fn depths(&self) -> Vec<Depth> {
+        let mut depths: Vec<Option<Depth>> = vec![None; self.nodes.len()];
+
+        for root in 0..self.nodes.len() {
+            let mut stack = vec![(root, false)];
+            while let Some((idx, expanded)) = stack.pop() {
+                if depths[idx].is_some() {
+                    continue;
+                }
+
+                let node = &self.nodes[idx];
+                if !expanded {
+                    stack.push((idx, true));
+                    stack.extend(
+                        node.calls
+                            .iter()
+                            .filter(|call| depths[call.callee].is_none())
+                            .map(|call| (call.callee, false)),
+                    );
+                    continue;
+                }
+
+                let deepest = node
+                    .calls
+                    .iter()
+                    .map(|call| (call.callee, depths[call.callee].unwrap()))
+                    .max_by_key(|(_, depth)| depth.bytes);
+
+                depths[idx] = Some(Depth {
+                    bytes: node.frame_size + deepest.map(|(_, d)| d.bytes).unwrap_or(0),
+                    next: deepest.map(|(callee, _)| callee),
+                    dynamic: node.dynamic_frame
+                        || node
+                            .calls
+                            .iter()
+                            .any(|call| depths[call.callee].unwrap().dynamic),
+                });
+            }
+        }
+
+        depths.into_iter().map(Option::unwrap).collect()
+    }
+
+    /// Estimated maximum stack depth for each entry point of the program (functions that
+    /// are not called from anywhere, such as `main` and interrupt handlers), one line each.
+    pub fn stack_depth_report(
+        &self,
+        mono_ctx: &MonoCtx<'_, 'ir>,
+    ) -> Result<Vec<String>, AluminaError> {
+        if let Some((start, cycle)) = self.find_cycle() {
+            return Ok(vec![format!(
+                "stack depth: cannot be estimated, the program is recursive ({})",
+                self.format_path(mono_ctx, start, &cycle)?
+            )]);
+        }
+
+        let depths = self.depths();
+        let mut roots: Vec<_> = (0..self.nodes.len())
+            .filter(|idx| !self.nodes[*idx].has_callers && self.nodes[*idx].frame_size > 0)
+            .collect();
+        roots.sort_by_key(|idx| std::cmp::Reverse(depths[*idx].bytes));
+
+        let mut lines = Vec::new();
+        for root in roots {
+            let depth = depths[root];
+            let mut chain = vec![self.function_name(mono_ctx, root)?];
+            let mut current = depth.next;
+            while let Some(idx) = current {
+                chain.push(self.function_name(mono_ctx, idx)?);
+                current = depths[idx].next;
+            }
+
+            lines.push(format!(
+                "stack depth: {} bytes{} from `{}` ({})",
+                depth.bytes,
+                if depth.dynamic {
+                    " plus dynamic stack allocations"
+                } else {
+                    ""
+                },
+                chain[0],
+                chain.join(" -> ")
+            ));
+        }
+
+        Ok(lines)
+    }
+
+    fn function_name(
+        &self,
+        mono_ctx: &MonoCtx<'_, 'ir>,
+        idx: usize,
+    ) -> Result<String, AluminaError> {
+        let item = self.nodes[idx].item;
+        match mono_ctx.source_item(item) {
+            Some(key) if !key.1.is_empty() => mono_ctx.instantiation_name(&key),
+            Some(key) => Ok(item_name(key.0).to_string()),
+            None => Ok(item
+                .get_function()
+                .with_no_span()?
+                .name
+                .unwrap_or("{anonymous}")
+                .to_string()),
+        }
+    }
+
+    /// Formats a sequence of calls as `a -> b -> c`
+    fn format_path(
+        &self,
+        mono_ctx: &MonoCtx<'_, 'ir>,
+        start: usize,
+        path: &[Call],
+    ) -> Result<String, AluminaError> {
+        let mut result = self.function_name(mono_ctx, start)?;
+        for call in path {
+            result.push_str(" -> ");
+            result.push_str(&self.function_name(mono_ctx, call.callee)?);
+            if call.indirect {
+                result.push_str(" (indirect)");
+            }
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,346 @@
+//! Control-flow graphs of function bodies in Graphviz DOT format (`--emit cfg`).
+//!
+//! The graph is built from the IR after monomorphization and optimizations, so loops,
+//! `break`, `continue` and `defer` have already been lowered to labels and gotos. Basic
+//! blocks list the calls, assignments and returns they contain (with their source lines),
+//! other expressions only show up through the edges they create.
+use crate::ast::{BinOp, Span};
+use crate::common::{AluminaError, CodeErrorBuilder, HashMap};
+use crate::diagnostics::DiagnosticsStack;
+use crate::intrinsics::IntrinsicValueKind;
+use crate::ir::mono::{item_name, MonoCtx};
+use crate::ir::{ExprKind, ExprP, IRItemP, IrId, Statement};
+
+use std::fmt::Write;
+
+#[derive(Default)]
+struct Block {
+    title: String,
+    lines: Vec<String>,
+    /// Successors with an optional edge label (`true`/`false` for conditional branches)
+    edges: Vec<(usize, Option<&'static str>)>,
+}
+
+struct CfgBuilder<'a, 'ast, 'ir> {
+    mono_ctx: &'a MonoCtx<'ast, 'ir>,
+    diag: &'a DiagnosticsStack,
+    blocks: Vec<Block>,
+    labels: HashMap<IrId, usize>,
+    /// Block that is being appended to, `None` if the current point is unreachable
+    current: Option<usize>,
+    exit: usize,
+}
+
+impl<'a, 'ast, 'ir> CfgBuilder<'a, 'ast, 'ir> {
+    fn new(mono_ctx: &'a MonoCtx<'ast, 'ir>, diag: &'a DiagnosticsStack) -> Self {
+        let blocks = vec![
+            Block {
+                title: "entry".to_string(),
+                ..Default::default()
+            },
+            Block {
+                title: "exit".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        Self {
+            mono_ctx,
+            diag,
+            blocks,
+            labels: HashMap::default(),
+            current: Some(0),
+            exit: 1,
+        }
+    }
+
+    fn new_block(&mut self, title: String) -> usize {
+        self.blocks.push(Block {
+            title,
+            ..Default::default()
+        });
+        self.blocks.len() - 1
+    }
+
+    fn label_block(&mut self, id: IrId) -> usize {
+        if let Some(&block) = self.labels.get(&id) {
+            return block;
+        }
+        let block = self.new_block(format!("label {}", id));
+        self.labels.insert(id, block);
+        block
+    }
+
+    fn edge(&mut self, to: usize, label: Option<&'static str>) {
+        if let Some(from) = self.current {
+            self.blocks[from].edges.push((to, label));
+        }
+    }
+
+    fn line(&mut self, text: String, span: Option<Span>) {
+        if let Some(current) = self.current {
+            let text = match span {
+                Some(span) => format!("{}  (line {})", text, span.line + 1),
+                None => text,
+            };
+            self.blocks[current].lines.push(text);
+        }
+    }
+
+    fn callee_name(&self, callee: ExprP<'ir>) -> Result<String, AluminaError> {
+        Ok(match callee.kind {
+            ExprKind::Fn(item) => match self.mono_ctx.source_item(item) {
+                Some(key) if !key.1.is_empty() => self.mono_ctx.instantiation_name(&key)?,
+                Some(key) => item_name(key.0).to_string(),
+                None => item
+                    .get_function()
+                    .with_backtrace(self.diag)?
+                    .name
+                    .unwrap_or("{anonymous}")
+                    .to_string(),
+            },
+            ExprKind::Intrinsic(IntrinsicValueKind::FunctionLike(name)) => name.to_string(),
+            ExprKind::Intrinsic(_) => "{intrinsic}".to_string(),
+            _ => "{indirect}".to_string(),
+        })
+    }
+
+    /// Splits the control flow at the end of the current block into two arms (with the
+    /// given edge labels) that join afterwards. An arm without an expression goes straight
+    /// to the join block.
+    fn branch(
+        &mut self,
+        arms: [(Option<ExprP<'ir>>, &'static str); 2],
+    ) -> Result<(), AluminaError> {
+        let Some(start) = self.current else {
+            return Ok(());
+        };
+
+        let join = self.new_block(String::new());
+        let mut join_reachable = false;
+        for (arm, label) in arms {
+            match arm {
+                Some(expr) => {
+                    let block = self.new_block(String::new());
+                    self.blocks[start].edges.push((block, Some(label)));
+                    self.current = Some(block);
+                    self.visit_expr(expr)?;
+                    join_reachable |= self.current.is_some();
+                    self.edge(join, None);
+                }
+                None => {
+                    self.blocks[start].edges.push((join, Some(label)));
+                    join_reachable = true;
+                }
+            }
+        }
+        self.current = join_reachable.then_some(join);
+
+        Ok(())
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement<'ir>) -> Result<(), AluminaError> {
+        match stmt {
+            Statement::Expression(expr) => self.visit_expr(expr),
+            Statement::Label(id) => {
+                let block = self.label_block(*id);
+                self.edge(block, None);
+                self.current = Some(block);
+                Ok(())
+            }
+        }
+    }
+
+    fn visit_expr(&mut self, expr: ExprP<'ir>) -> Result<(), AluminaError> {
+        match expr.kind {
+            ExprKind::Block(statements, ret) => {
+                for stmt in statements {
+                    self.visit_statement(stmt)?;
+                }
+                self.visit_expr(ret)?;
+            }
+            ExprKind::Binary(op @ (BinOp::And | BinOp::Or), lhs, rhs) => {
+                // Short-circuiting, the right-hand side is only evaluated on one of the branches
+                self.visit_expr(lhs)?;
+                if op == BinOp::And {
+                    self.line("if &&".to_string(), expr.span);
+                    self.branch([(Some(rhs), "true"), (None, "false")])?;
+                } else {
+                    self.line("if ||".to_string(), expr.span);
+                    self.branch([(None, "true"), (Some(rhs), "false")])?;
+                }
+            }
+            ExprKind::Binary(_, lhs, rhs)
+            | ExprKind::Index(lhs, rhs)
+            | ExprKind::AssignOp(_, lhs, rhs) => {
+                self.visit_expr(lhs)?;
+                self.visit_expr(rhs)?;
+                if matches!(expr.kind, ExprKind::AssignOp(..)) {
+                    self.line(describe_assign(lhs), expr.span);
+                }
+            }
+            ExprKind::Assign(lhs, rhs) => {
+                self.visit_expr(rhs)?;
+                self.visit_expr(lhs)?;
+                self.line(describe_assign(lhs), expr.span);
+            }
+            ExprKind::Call(callee, args) => {
+                self.visit_expr(callee)?;
+                for arg in args {
+                    self.visit_expr(arg)?;
+                }
+                let name = self.callee_name(callee)?;
+                self.line(format!("call {}", name), expr.span);
+            }
+            ExprKind::Ref(inner)
+            | ExprKind::Deref(inner)
+            | ExprKind::Unary(_, inner)
+            | ExprKind::Field(inner, _)
+            | ExprKind::TupleIndex(inner, _)
+            | ExprKind::Cast(inner) => self.visit_expr(inner)?,
+            ExprKind::Return(inner) => {
+                self.visit_expr(inner)?;
+                self.line("return".to_string(), expr.span);
+                self.edge(self.exit, None);
+                self.current = None;
+            }
+            ExprKind::Goto(label) => {
+                let block = self.label_block(label);
+                self.edge(block, None);
+                self.current = None;
+            }
+            ExprKind::If(cond, then, els, const_cond) => match const_cond {
+                Some(true) => self.visit_expr(then)?,
+                Some(false) => self.visit_expr(els)?,
+                None => {
+                    self.visit_expr(cond)?;
+                    self.line("if".to_string(), cond.span);
+                    self.branch([(Some(then), "true"), (Some(els), "false")])?;
+                }
+            },
+            ExprKind::Intrinsic(ref kind) => match kind {
+                IntrinsicValueKind::ConstPanic(inner)
+                | IntrinsicValueKind::ConstWrite(inner, _)
+                | IntrinsicValueKind::ConstAlloc(_, inner)
+                | IntrinsicValueKind::ConstFree(inner) => self.visit_expr(inner)?,
+                IntrinsicValueKind::Asm(..) => self.line("asm".to_string(), expr.span),
+                _ => {}
+            },
+            ExprKind::Array(elems) => {
+                for elem in elems {
+                    self.visit_expr(elem)?;
+                }
+            }
+            ExprKind::Tuple(inits) => {
+                for init in inits {
+                    self.visit_expr(init.value)?;
+                }
+            }
+            ExprKind::Struct(inits) => {
+                for init in inits {
+                    self.visit_expr(init.value)?;
+                }
+            }
+            ExprKind::Unreachable => {
+                self.line("unreachable".to_string(), expr.span);
+                self.current = None;
+            }
+            ExprKind::Fn(_)
+            | ExprKind::Local(_)
+            | ExprKind::Static(_)
+            | ExprKind::Const(_)
+            | ExprKind::Literal(_)
+            | ExprKind::Void => {}
+        }
+
+        // Calls to functions that never return
+        if expr.diverges() {
+            self.current = None;
+        }
+
+        Ok(())
+    }
+}
+
+/// Target of an assignment, e.g. `$3.field` (locals are named by their IR id)
+fn describe_assign(lhs: ExprP<'_>) -> String {
+    fn place(expr: ExprP<'_>) -> String {
+        match expr.kind {
+            ExprKind::Local(id) => id.to_string(),
+            ExprKind::Field(inner, id) => format!("{}.{}", place(inner), id),
+            ExprKind::TupleIndex(inner, idx) => format!("{}.{}", place(inner), idx),
+            ExprKind::Index(inner, _) => format!("{}[_]", place(inner)),
+            ExprKind::Deref(inner) => format!("*{}", place(inner)),
+            ExprKind::Static(item) => match item.get_static() {
+                Ok(s) => s.name.unwrap_or("{static}").to_string(),
+                Err(_) => "{static}".to_string(),
+            },
+            _ => "_".to_string(),
+        }
+    }
+
+    format!("assign {}", place(lhs))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Formats the control-flow graphs of the given functions (with the names to show for them)
+/// as a single DOT graph with a cluster for each function.
+pub fn format_cfgs<'ir>(
+    mono_ctx: &MonoCtx<'_, 'ir>,
+    diag: &DiagnosticsStack,
+    functions: &[(String, IRItemP<'ir>)],
+) -> Result<String, AluminaError> {
+    let mut result = String::new();
+    writeln!(result, "digraph cfg {{").unwrap();
+    writeln!(result, "  node [shape=box, fontname=\"monospace\"];").unwrap();
+
+    for (idx, (name, item)) in functions.iter().enumerate() {
+        let func = item.get_function().with_backtrace(diag)?;
+        let Some(body) = func.body.get() else {
+            continue;
+        };
+
+        let mut builder = CfgBuilder::new(mono_ctx, diag);
+        for stmt in body.statements {
+            builder.visit_statement(stmt)?;
+        }
+        // Falling off the end of the body
+        builder.edge(builder.exit, None);
+
+        writeln!(result, "  subgraph cluster_{} {{", idx).unwrap();
+        writeln!(result, "    label=\"{}\";", escape(name)).unwrap();
+        for (block_idx, block) in builder.blocks.iter().enumerate() {
+            let mut label = if block.title.is_empty() {
+                format!("bb{}", block_idx)
+            } else {
+                block.title.clone()
+            };
+            label.push_str("\\l");
+            for line in &block.lines {
+                label.push_str(&escape(line));
+                label.push_str("\\l");
+            }
+            writeln!(result, "    f{}_{} [label=\"{}\"];", idx, block_idx, label).unwrap();
+        }
+        for (block_idx, block) in builder.blocks.iter().enumerate() {
+            for (target, label) in &block.edges {
+                match label {
+                    Some(label) => writeln!(
+                        result,
+                        "    f{}_{} -> f{}_{} [label=\"{}\"];",
+                        idx, block_idx, idx, target, label
+                    ),
+                    None => writeln!(result, "    f{}_{} -> f{}_{};", idx, block_idx, idx, target),
+                }
+                .unwrap();
+            }
+        }
+        writeln!(result, "  }}").unwrap();
+    }
+
+    writeln!(result, "}}").unwrap();
+    Ok(result)
+}
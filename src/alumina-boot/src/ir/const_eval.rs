@@ -1,14 +1,18 @@
 /// An interpreter for constant expressions. It's quite slow.
+use crate::ast::float;
 use crate::ast::BinOp;
 use crate::common::{
     AluminaError, ArenaAllocatable, ByRef, CodeError, CodeErrorBuilder, CodeErrorKind, HashMap,
 };
 use crate::diagnostics::DiagnosticsStack;
 use crate::intrinsics::IntrinsicValueKind;
-use crate::ir::{BuiltinType, ExprKind, ExprP, IRItem, IrCtx, IrId, Statement, Ty, TyP, UnOp};
+use crate::ir::{
+    BuiltinType, ExprKind, ExprP, IRItem, IrCtx, IrId, Statement, StrId, Ty, TyP, UnOp,
+};
 use std::backtrace::Backtrace;
 use std::cell::RefCell;
 use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
 use std::num::TryFromIntError;
 use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Rem, Shl, Shr, Sub};
 use std::rc::Rc;
@@ -23,6 +27,7 @@ pub enum Value<'ir> {
     Void,
     Uninitialized,
     Bool(bool),
+    Char(char),
     U8(u8),
     U16(u16),
     U32(u32),
@@ -35,9 +40,13 @@ pub enum Value<'ir> {
     I128(i128),
     USize(usize),
     ISize(isize),
-    F32(&'ir str),
-    F64(&'ir str),
-    Str(&'ir [u8], usize),
+    // Floats are stored as bit patterns, so that values can be compared and hashed
+    F16(u16),
+    BF16(u16),
+    F32(u32),
+    F64(u64),
+    // Pointer into a string in the string table of `IrCtx` (byte offset)
+    Str(StrId, usize),
     Tuple(&'ir [Value<'ir>]),
     Array(&'ir [Value<'ir>]),
     Struct(&'ir [(IrId, Value<'ir>)]),
@@ -86,6 +95,8 @@ pub enum ConstEvalErrorKind {
     UseAfterFree,
     #[error("invalid pointer used to free memory")]
     InvalidFree,
+    #[error("value is not a valid Unicode scalar value")]
+    InvalidCharValue,
 
     // These are not errors, but they are used to signal that the evaluation should stop.
     // They are bugs if they leak to the caller
@@ -140,9 +151,60 @@ use super::builder::TypeBuilder;
 use super::IRItemP;
 
 impl<'ir> Value<'ir> {
+    pub fn float(kind: BuiltinType, bits: u64) -> Value<'ir> {
+        match kind {
+            BuiltinType::F16 => Value::F16(bits as u16),
+            BuiltinType::BF16 => Value::BF16(bits as u16),
+            BuiltinType::F32 => Value::F32(bits as u32),
+            BuiltinType::F64 => Value::F64(bits),
+            _ => unreachable!(),
+        }
+    }
+
+    /// The type and the bit pattern of a floating point value
+    pub fn float_bits(self) -> Option<(BuiltinType, u64)> {
+        match self {
+            Value::F16(bits) => Some((BuiltinType::F16, bits as u64)),
+            Value::BF16(bits) => Some((BuiltinType::BF16, bits as u64)),
+            Value::F32(bits) => Some((BuiltinType::F32, bits as u64)),
+            Value::F64(bits) => Some((BuiltinType::F64, bits)),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(self) -> Option<f64> {
+        self.float_bits()
+            .map(|(kind, bits)| float::from_bits(kind, bits))
+    }
+
+    /// Sign and magnitude of an integer value, regardless of its type
+    pub fn as_int(self) -> Option<(bool, u128)> {
+        let signed = |v: i128| Some((v < 0, v.unsigned_abs()));
+        match self {
+            Value::U8(v) => Some((false, v as u128)),
+            Value::U16(v) => Some((false, v as u128)),
+            Value::U32(v) => Some((false, v as u128)),
+            Value::U64(v) => Some((false, v as u128)),
+            Value::U128(v) => Some((false, v)),
+            Value::USize(v) => Some((false, v as u128)),
+            Value::I8(v) => signed(v as i128),
+            Value::I16(v) => signed(v as i128),
+            Value::I32(v) => signed(v as i128),
+            Value::I64(v) => signed(v as i128),
+            Value::I128(v) => signed(v),
+            Value::ISize(v) => signed(v as i128),
+            _ => None,
+        }
+    }
+
     fn equal(self, other: Value) -> Result<Value<'ir>, ConstEvalErrorKind> {
+        if let (Some(a), Some(b)) = (self.as_float(), other.as_float()) {
+            return Ok(Value::Bool(a == b));
+        }
+
         match (self, other) {
             (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(a == b)),
+            (Value::Char(a), Value::Char(b)) => Ok(Value::Bool(a == b)),
             (Value::U8(a), Value::U8(b)) => Ok(Value::Bool(a == b)),
             (Value::U16(a), Value::U16(b)) => Ok(Value::Bool(a == b)),
             (Value::U32(a), Value::U32(b)) => Ok(Value::Bool(a == b)),
@@ -174,11 +236,69 @@ impl<'ir> Value<'ir> {
             (Value::USize(a), Value::USize(b)) => Ok(a.cmp(&b)),
             (Value::ISize(a), Value::ISize(b)) => Ok(a.cmp(&b)),
             (Value::Bool(a), Value::Bool(b)) => Ok(a.cmp(&b)),
+            (Value::Char(a), Value::Char(b)) => Ok(a.cmp(&b)),
             _ => Err(ConstEvalErrorKind::Unsupported),
         }
     }
 }
 
+impl Display for Value<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fn write_list(f: &mut Formatter<'_>, values: &[Value<'_>]) -> std::fmt::Result {
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", value)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Value::Void => write!(f, "()"),
+            Value::Uninitialized => write!(f, "<uninitialized>"),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Char(v) => write!(f, "{:?}", v),
+            Value::U8(v) => write!(f, "{}", v),
+            Value::U16(v) => write!(f, "{}", v),
+            Value::U32(v) => write!(f, "{}", v),
+            Value::U64(v) => write!(f, "{}", v),
+            Value::U128(v) => write!(f, "{}", v),
+            Value::I8(v) => write!(f, "{}", v),
+            Value::I16(v) => write!(f, "{}", v),
+            Value::I32(v) => write!(f, "{}", v),
+            Value::I64(v) => write!(f, "{}", v),
+            Value::I128(v) => write!(f, "{}", v),
+            Value::USize(v) => write!(f, "{}", v),
+            Value::ISize(v) => write!(f, "{}", v),
+            Value::F16(_) | Value::BF16(_) | Value::F32(_) | Value::F64(_) => {
+                write!(f, "{}", self.as_float().unwrap())
+            }
+            Value::Tuple(values) => {
+                write!(f, "(")?;
+                write_list(f, values)?;
+                write!(f, ")")
+            }
+            Value::Array(values) => {
+                write!(f, "[")?;
+                write_list(f, values)?;
+                write!(f, "]")
+            }
+            Value::Struct(fields) => {
+                write!(f, "{{ ")?;
+                let values: Vec<_> = fields.iter().map(|(_, v)| *v).collect();
+                write_list(f, &values)?;
+                write!(f, " }}")
+            }
+            Value::FunctionPointer(item) => match item.get_function() {
+                Ok(fun) => write!(f, "<function {}>", fun.name.unwrap_or("<anonymous>")),
+                Err(_) => write!(f, "<function>"),
+            },
+            Value::Str(..) | Value::Pointer(_) | Value::LValue(_) => write!(f, "<pointer>"),
+        }
+    }
+}
+
 impl<'ir> Add for Value<'ir> {
     type Output = Result<Value<'ir>, ConstEvalErrorKind>;
     fn add(self, other: Value) -> Result<Value<'ir>, ConstEvalErrorKind> {
@@ -362,6 +482,10 @@ impl<'ir> Neg for Value<'ir> {
             I64(a) => Ok(I64(-a)),
             I128(a) => Ok(I128(-a)),
             ISize(a) => Ok(ISize(-a)),
+            F16(a) => Ok(F16(a ^ 0x8000)),
+            BF16(a) => Ok(BF16(a ^ 0x8000)),
+            F32(a) => Ok(F32(a ^ 0x8000_0000)),
+            F64(a) => Ok(F64(a ^ 0x8000_0000_0000_0000)),
             _ => Err(ConstEvalErrorKind::Unsupported),
         }
     }
@@ -733,6 +857,14 @@ impl<'ir> ConstEvaluator<'ir> {
             }
         }
 
+        if let (Some(value), Ty::Builtin(kind)) = (val.as_float(), target) {
+            if kind.is_float() {
+                return Ok(Value::float(*kind, float::to_bits(*kind, value)));
+            } else if kind.is_integer() {
+                return self.float_to_int(value, *kind);
+            }
+        }
+
         let promoted = match val {
             Value::U8(a) => Value::U128(a as u128),
             Value::U16(a) => Value::U128(a as u128),
@@ -747,10 +879,17 @@ impl<'ir> ConstEvaluator<'ir> {
             Value::I128(a) => Value::I128(a),
             Value::ISize(a) => Value::I128(a as i128),
             Value::Bool(a) => Value::U128(a as u128),
+            Value::Char(a) => Value::U128(a as u128),
             _ => val,
         };
 
         match (promoted, target) {
+            (Value::U128(a), Ty::Builtin(BuiltinType::Char)) => u32::try_from(a)
+                .ok()
+                .and_then(char::from_u32)
+                .map(Value::Char)
+                .ok_or(ConstEvalErrorKind::InvalidCharValue)
+                .with_backtrace(&self.diag),
             (Value::U128(a), Ty::Builtin(BuiltinType::U8)) => Ok(Value::U8(a as u8)),
             (Value::U128(a), Ty::Builtin(BuiltinType::U16)) => Ok(Value::U16(a as u16)),
             (Value::U128(a), Ty::Builtin(BuiltinType::U32)) => Ok(Value::U32(a as u32)),
@@ -775,8 +914,13 @@ impl<'ir> ConstEvaluator<'ir> {
             (Value::I128(a), Ty::Builtin(BuiltinType::I64)) => Ok(Value::I64(a as i64)),
             (Value::I128(a), Ty::Builtin(BuiltinType::I128)) => Ok(Value::I128(a)),
             (Value::I128(a), Ty::Builtin(BuiltinType::ISize)) => Ok(Value::ISize(a as isize)),
-            (Value::F64(a), Ty::Builtin(BuiltinType::F32)) => Ok(Value::F32(a)),
-            (Value::F32(a), Ty::Builtin(BuiltinType::F64)) => Ok(Value::F64(a)),
+            (Value::U128(a), Ty::Builtin(kind)) if kind.is_float() => {
+                Ok(Value::float(*kind, float::int_to_bits(*kind, false, a)))
+            }
+            (Value::I128(a), Ty::Builtin(kind)) if kind.is_float() => Ok(Value::float(
+                *kind,
+                float::int_to_bits(*kind, a < 0, a.unsigned_abs()),
+            )),
             (Value::FunctionPointer(id), Ty::FunctionPointer(..)) => Ok(Value::FunctionPointer(id)),
             (Value::Pointer(value), Ty::Pointer(_underlying, _is_const)) => {
                 Ok(Value::Pointer(value))
@@ -855,8 +999,17 @@ impl<'ir> ConstEvaluator<'ir> {
                     match self.const_eval_rvalue(expr) {
                         Ok(_) => {}
                         Err(e) => {
-                            let AluminaError::CodeErrors(ref v) = e else { return Err(e); };
-                            let [CodeError { kind: CodeErrorKind::CannotConstEvaluate(ConstEvalErrorKind::Jump(label)), .. }] = v[..] else { return Err(e) };
+                            let AluminaError::CodeErrors(ref v) = e else {
+                                return Err(e);
+                            };
+                            let [CodeError {
+                                kind:
+                                    CodeErrorKind::CannotConstEvaluate(ConstEvalErrorKind::Jump(label)),
+                                ..
+                            }] = v[..]
+                            else {
+                                return Err(e);
+                            };
 
                             if let Some(new_ip) = label_indexes.get(&label) {
                                 ip = *new_ip;
@@ -879,7 +1032,9 @@ impl<'ir> ConstEvaluator<'ir> {
     fn materialize_lvalue(&mut self, value: LValue<'ir>) -> Result<Value<'ir>, AluminaError> {
         match value {
             LValue::Const(item) => {
-                let Ok(item) = item.get_const() else { bug!(self) };
+                let Ok(item) = item.get_const() else {
+                    bug!(self)
+                };
                 Ok(item.value)
             }
             LValue::Variable(id) => Ok(self.ctx.load_var(id)),
@@ -1050,7 +1205,8 @@ impl<'ir> ConstEvaluator<'ir> {
                 let value = self.const_eval_rvalue(value)?;
                 match value {
                     Value::Pointer(lvalue) => Ok(Value::LValue(lvalue)),
-                    Value::Str(arr, off) => {
+                    Value::Str(id, off) => {
+                        let arr = self.ir.get_str(id);
                         if off >= arr.len() {
                             return Err(ConstEvalErrorKind::IndexOutOfBounds)
                                 .with_backtrace(&self.diag);
@@ -1156,7 +1312,9 @@ impl<'ir> ConstEvaluator<'ir> {
                 IntrinsicValueKind::Uninitialized => Ok(Value::Uninitialized),
                 IntrinsicValueKind::Dangling(..) => Ok(Value::Uninitialized),
                 IntrinsicValueKind::SizeOfLike(_, _) => unsupported!(self),
-                IntrinsicValueKind::Asm(_) => unsupported!(self),
+                IntrinsicValueKind::Asm(..) => unsupported!(self),
+                IntrinsicValueKind::CInline(_) => unsupported!(self),
+                IntrinsicValueKind::MemCopy(_) => unsupported!(self),
                 IntrinsicValueKind::FunctionLike(_) => unsupported!(self),
                 IntrinsicValueKind::ConstLike(_) => unsupported!(self),
                 IntrinsicValueKind::InConstContext => Ok(Value::Bool(!self.codegen)),
@@ -1228,6 +1386,17 @@ impl<'ir> ConstEvaluator<'ir> {
                 self.return_slot = Some(self.const_eval_rvalue(value)?);
                 Err(ConstEvalErrorKind::Return).with_backtrace(&self.diag)
             }
+            ExprKind::Call(callee, args)
+                if matches!(
+                    callee.kind,
+                    ExprKind::Intrinsic(IntrinsicValueKind::MemCopy(_))
+                ) =>
+            {
+                let dst = self.const_eval_rvalue(args[0])?;
+                let src = self.const_eval_rvalue(args[1])?;
+                let count = self.const_eval_rvalue(args[2])?;
+                self.mem_copy(dst, src, count)
+            }
             ExprKind::Call(callee, args) => {
                 let callee = self.const_eval_rvalue(callee)?;
                 let (arg_spec, expr, local_defs) = match callee {
@@ -1274,7 +1443,13 @@ impl<'ir> ConstEvaluator<'ir> {
                         let AluminaError::CodeErrors(ref v) = e else {
                             return Err(e);
                         };
-                        let [CodeError { kind: CodeErrorKind::CannotConstEvaluate(ConstEvalErrorKind::Return), .. }] = v[..] else { return Err(e) };
+                        let [CodeError {
+                            kind: CodeErrorKind::CannotConstEvaluate(ConstEvalErrorKind::Return),
+                            ..
+                        }] = v[..]
+                        else {
+                            return Err(e);
+                        };
                         let value = child.return_slot.take().unwrap();
                         Ok(value)
                     }
@@ -1289,6 +1464,53 @@ impl<'ir> ConstEvaluator<'ir> {
         }
     }
 
+    /// Element-wise copy for `copy` and `copy_nonoverlapping`. All the elements are read
+    /// before any of them is written, so overlapping ranges behave as with `memmove`.
+    fn mem_copy(
+        &mut self,
+        dst: Value<'ir>,
+        src: Value<'ir>,
+        count: Value<'ir>,
+    ) -> Result<Value<'ir>, AluminaError> {
+        let Value::USize(count) = count else {
+            bug!(self)
+        };
+
+        let mut values = Vec::with_capacity(count);
+        for i in 0..count {
+            let ptr = if i == 0 {
+                src
+            } else {
+                self.plus_minus(src, BinOp::Plus, Value::USize(i))?
+            };
+            let value = match ptr {
+                Value::Pointer(lvalue) => self.materialize_lvalue(lvalue)?,
+                Value::Str(id, off) => match self.ir.get_str(id).get(off) {
+                    Some(byte) => Value::U8(*byte),
+                    None => {
+                        return Err(ConstEvalErrorKind::IndexOutOfBounds).with_backtrace(&self.diag)
+                    }
+                },
+                _ => unsupported!(self),
+            };
+            values.push(value);
+        }
+
+        for (i, value) in values.into_iter().enumerate() {
+            let ptr = if i == 0 {
+                dst
+            } else {
+                self.plus_minus(dst, BinOp::Plus, Value::USize(i))?
+            };
+            match ptr {
+                Value::Pointer(lvalue) => self.assign(lvalue, value)?,
+                _ => unsupported!(self),
+            }
+        }
+
+        Ok(Value::Void)
+    }
+
     // Handle pointer arithmetic
     fn plus_minus(
         &mut self,
@@ -1319,7 +1541,8 @@ impl<'ir> ConstEvaluator<'ir> {
                 let diff = (a_offset as isize) - (b_offset as isize);
                 return Ok(Value::ISize(diff));
             }
-            (Value::Str(buf, offset), _) => {
+            (Value::Str(id, offset), _) => {
+                let buf = self.ir.get_str(id);
                 let new_offset = match op {
                     BinOp::Plus => (offset as isize) + offset!(),
                     BinOp::Minus => (offset as isize) + offset!(),
@@ -1328,7 +1551,7 @@ impl<'ir> ConstEvaluator<'ir> {
                 if new_offset < 0 || new_offset > (buf.len() as isize) {
                     return Err(ConstEvalErrorKind::IndexOutOfBounds).with_backtrace(&self.diag);
                 }
-                return Ok(Value::Str(buf, new_offset as usize));
+                return Ok(Value::Str(id, new_offset as usize));
             }
             (Value::Pointer(LValue::Index(inner, offset)), _) => {
                 let arr = match self.materialize_lvalue(*inner)? {
@@ -1359,12 +1582,78 @@ impl<'ir> ConstEvaluator<'ir> {
         ret.with_backtrace(&self.diag)
     }
 
+    /// Truncating conversion. Values that do not fit into the target type are rejected, as
+    /// the conversion is undefined behavior in C.
+    fn float_to_int(&mut self, value: f64, kind: BuiltinType) -> Result<Value<'ir>, AluminaError> {
+        let truncated = value.trunc();
+        let converted = if truncated >= -(2f64.powi(127)) && truncated < 2f64.powi(127) {
+            let wide = truncated as i128;
+            match kind {
+                BuiltinType::U8 => wide.try_into().ok().map(Value::U8),
+                BuiltinType::U16 => wide.try_into().ok().map(Value::U16),
+                BuiltinType::U32 => wide.try_into().ok().map(Value::U32),
+                BuiltinType::U64 => wide.try_into().ok().map(Value::U64),
+                BuiltinType::U128 => wide.try_into().ok().map(Value::U128),
+                BuiltinType::USize => wide.try_into().ok().map(Value::USize),
+                BuiltinType::I8 => wide.try_into().ok().map(Value::I8),
+                BuiltinType::I16 => wide.try_into().ok().map(Value::I16),
+                BuiltinType::I32 => wide.try_into().ok().map(Value::I32),
+                BuiltinType::I64 => wide.try_into().ok().map(Value::I64),
+                BuiltinType::I128 => Some(Value::I128(wide)),
+                BuiltinType::ISize => wide.try_into().ok().map(Value::ISize),
+                _ => bug!(self),
+            }
+        } else if kind == BuiltinType::U128 && truncated >= 0.0 && truncated < 2f64.powi(128) {
+            Some(Value::U128(truncated as u128))
+        } else {
+            // Also NaN
+            None
+        };
+
+        converted
+            .ok_or(ConstEvalErrorKind::ArithmeticOverflow)
+            .with_backtrace(&self.diag)
+    }
+
+    /// Arithmetic is done in `f64` and rounded to the type of the operands. This gives
+    /// the same results as doing it in the narrower type, since `f64` has more than twice
+    /// the precision of `f32`.
+    fn float_bin_op(
+        &mut self,
+        kind: BuiltinType,
+        lhs: f64,
+        op: BinOp,
+        rhs: f64,
+    ) -> Result<Value<'ir>, AluminaError> {
+        let result = match op {
+            BinOp::Eq => return Ok(Value::Bool(lhs == rhs)),
+            BinOp::Neq => return Ok(Value::Bool(lhs != rhs)),
+            BinOp::Lt => return Ok(Value::Bool(lhs < rhs)),
+            BinOp::LEq => return Ok(Value::Bool(lhs <= rhs)),
+            BinOp::Gt => return Ok(Value::Bool(lhs > rhs)),
+            BinOp::GEq => return Ok(Value::Bool(lhs >= rhs)),
+            BinOp::Plus => lhs + rhs,
+            BinOp::Minus => lhs - rhs,
+            BinOp::Mul => lhs * rhs,
+            BinOp::Div => lhs / rhs,
+            _ => bug!(self),
+        };
+
+        Ok(Value::float(kind, float::to_bits(kind, result)))
+    }
+
     fn bin_op(
         &mut self,
         lhs: Value<'ir>,
         op: BinOp,
         rhs: Value<'ir>,
     ) -> Result<Value<'ir>, AluminaError> {
+        if let (Some((kind, _)), Some(a), Some(b)) =
+            (lhs.float_bits(), lhs.as_float(), rhs.as_float())
+        {
+            return self.float_bin_op(kind, a, op, b);
+        }
+
         let ret = match op {
             BinOp::BitAnd => lhs & rhs,
             BinOp::BitOr => lhs | rhs,
@@ -1410,8 +1699,8 @@ impl<'ir> ConstEvaluator<'ir> {
                         Value::USize(len_) => {
                             len = Some(*len_);
                         }
-                        Value::Str(r, offset) => {
-                            buf = r.get(*offset..);
+                        Value::Str(id, offset) => {
+                            buf = self.ir.get_str(*id).get(*offset..);
                         }
                         Value::Pointer(LValue::Index(r, offset)) => {
                             let r = self.materialize_lvalue(**r).unwrap();
@@ -1529,6 +1818,7 @@ pub fn make_zeroed<'ir>(ir: &'ir IrCtx<'ir>, typ: TyP<'ir>) -> Value<'ir> {
         Ty::Builtin(kind) => match kind {
             BuiltinType::Never => Value::Uninitialized,
             BuiltinType::Bool => Value::Bool(false),
+            BuiltinType::Char => Value::Char('\0'),
             BuiltinType::U8 => Value::U8(0),
             BuiltinType::U16 => Value::U16(0),
             BuiltinType::U32 => Value::U32(0),
@@ -1541,8 +1831,10 @@ pub fn make_zeroed<'ir>(ir: &'ir IrCtx<'ir>, typ: TyP<'ir>) -> Value<'ir> {
             BuiltinType::I32 => Value::I32(0),
             BuiltinType::I64 => Value::I64(0),
             BuiltinType::I128 => Value::I128(0),
-            BuiltinType::F32 => Value::F32("0"),
-            BuiltinType::F64 => Value::F64("0"),
+            BuiltinType::F16 => Value::F16(0),
+            BuiltinType::BF16 => Value::BF16(0),
+            BuiltinType::F32 => Value::F32(0),
+            BuiltinType::F64 => Value::F64(0),
         },
     }
 }
@@ -0,0 +1,146 @@
+use crate::ast::{BinOp, BuiltinType};
+use crate::common::{AluminaError, CodeErrorKind};
+use crate::diagnostics::DiagnosticsStack;
+use crate::ir::const_eval::Value;
+use crate::ir::{default_visit_expr, ExprKind, ExprP, ExpressionVisitor, IRItem, Ty};
+
+fn int_value(expr: ExprP<'_>) -> Option<i128> {
+    let value = match expr.kind {
+        ExprKind::Literal(ref value) => value,
+        ExprKind::Const(item) => match item.get() {
+            Ok(IRItem::Const(c)) => &c.value,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    match *value {
+        Value::U8(v) => Some(v as i128),
+        Value::U16(v) => Some(v as i128),
+        Value::U32(v) => Some(v as i128),
+        Value::U64(v) => Some(v as i128),
+        Value::U128(v) => i128::try_from(v).ok(),
+        Value::USize(v) => Some(v as i128),
+        Value::I8(v) => Some(v as i128),
+        Value::I16(v) => Some(v as i128),
+        Value::I32(v) => Some(v as i128),
+        Value::I64(v) => Some(v as i128),
+        Value::I128(v) => Some(v),
+        Value::ISize(v) => Some(v as i128),
+        _ => None,
+    }
+}
+
+/// Width of an integer type in bits. `usize` and `isize` are assumed to be 64 bits wide,
+/// so shifts between 32 and 63 bits are not caught on 32-bit targets.
+fn integer_bits(ty: &Ty<'_>) -> Option<u32> {
+    match ty {
+        Ty::Builtin(BuiltinType::U8 | BuiltinType::I8) => Some(8),
+        Ty::Builtin(BuiltinType::U16 | BuiltinType::I16) => Some(16),
+        Ty::Builtin(BuiltinType::U32 | BuiltinType::I32) => Some(32),
+        Ty::Builtin(
+            BuiltinType::U64 | BuiltinType::I64 | BuiltinType::USize | BuiltinType::ISize,
+        ) => Some(64),
+        Ty::Builtin(BuiltinType::U128 | BuiltinType::I128) => Some(128),
+        _ => None,
+    }
+}
+
+/// Finds operations with constant operands that are undefined behavior no matter what
+/// the other operand is: integer division or remainder by zero, shifts by a negative
+/// amount or by at least the width of the type, and constant indices that are out of
+/// bounds of an array.
+///
+/// Branches of `if`s with a constant condition that are never taken are not checked.
+pub struct ConstantUbChecker {
+    diag: DiagnosticsStack,
+}
+
+impl ConstantUbChecker {
+    pub fn new(diag: DiagnosticsStack) -> Self {
+        Self { diag }
+    }
+
+    pub fn check(mut self, body: ExprP<'_>) -> Result<(), AluminaError> {
+        self.visit_expr(body)
+    }
+
+    fn warn(&self, expr: ExprP<'_>, kind: CodeErrorKind) {
+        let _guard = self.diag.push_span(expr.span);
+        self.diag.warn(kind);
+    }
+
+    fn check_binary(&self, expr: ExprP<'_>, op: BinOp, lhs: ExprP<'_>, rhs: ExprP<'_>) {
+        let Some(bits) = integer_bits(lhs.ty) else {
+            return;
+        };
+        let Some(value) = int_value(rhs) else {
+            return;
+        };
+
+        match op {
+            BinOp::Div | BinOp::Mod if value == 0 => self.warn(
+                expr,
+                CodeErrorKind::ConstantDivisionByZero(
+                    if op == BinOp::Div {
+                        "division"
+                    } else {
+                        "remainder"
+                    }
+                    .to_string(),
+                ),
+            ),
+            BinOp::LShift | BinOp::RShift if value < 0 || value >= bits as i128 => {
+                if let Ty::Builtin(kind) = lhs.ty {
+                    self.warn(
+                        expr,
+                        CodeErrorKind::ConstantShiftOverflow(
+                            value,
+                            format!("{:?}", kind).to_lowercase(),
+                            bits - 1,
+                        ),
+                    )
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'ir> ExpressionVisitor<'ir> for ConstantUbChecker {
+    fn visit_if(
+        &mut self,
+        cond: ExprP<'ir>,
+        then: ExprP<'ir>,
+        els: ExprP<'ir>,
+        const_cond: Option<bool>,
+    ) -> Result<(), AluminaError> {
+        match const_cond {
+            Some(true) => self.visit_expr(then),
+            Some(false) => self.visit_expr(els),
+            None => {
+                self.visit_expr(cond)?;
+                self.visit_expr(then)?;
+                self.visit_expr(els)
+            }
+        }
+    }
+
+    fn visit_expr(&mut self, expr: ExprP<'ir>) -> Result<(), AluminaError> {
+        match expr.kind {
+            ExprKind::Binary(op, lhs, rhs) | ExprKind::AssignOp(op, lhs, rhs) => {
+                self.check_binary(expr, op, lhs, rhs)
+            }
+            ExprKind::Index(base, index) => {
+                if let (Ty::Array(_, len), Some(value)) = (base.ty, int_value(index)) {
+                    if value < 0 || value >= *len as i128 {
+                        self.warn(expr, CodeErrorKind::ConstantIndexOutOfBounds(value, *len));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        default_visit_expr(self, expr)
+    }
+}
@@ -0,0 +1,235 @@
+use crate::ast::{BinOp, Span};
+use crate::common::{CodeErrorKind, HashMap, HashSet};
+use crate::diagnostics::DiagnosticsStack;
+use crate::intrinsics::IntrinsicValueKind;
+use crate::ir::{ExprKind, ExprP, IrId, Statement};
+
+/// Stores to each local that may not have been read yet at a point in the function.
+/// `None` means that the point is unreachable.
+type State = Option<HashMap<IrId, HashSet<usize>>>;
+
+fn join(a: State, b: State) -> State {
+    match (a, b) {
+        (None, b) => b,
+        (a, None) => a,
+        (Some(mut a), Some(b)) => {
+            for (id, stores) in b {
+                a.entry(id).or_default().extend(stores);
+            }
+            Some(a)
+        }
+    }
+}
+
+/// Finds assignments to local variables whose value is never read, because the variable
+/// is overwritten or goes out of scope first.
+///
+/// Only whole assignments to locals declared with `let` are considered. Assignments to a
+/// field or an element count as a read of the variable, and variables that have their
+/// address taken are ignored altogether, since the value can be read through the pointer.
+/// Variables that are never read at all are left to the unused variable lint.
+pub struct DeadStores<'a> {
+    diag: DiagnosticsStack,
+    tracked: &'a HashSet<IrId>,
+    pending: State,
+    labels: HashMap<IrId, State>,
+    /// Span of each store in the order they are visited (stable across iterations)
+    stores: Vec<(IrId, Option<Span>)>,
+    live: HashSet<usize>,
+    read: HashSet<IrId>,
+    escaped: HashSet<IrId>,
+}
+
+impl<'a> DeadStores<'a> {
+    pub fn new(diag: DiagnosticsStack, tracked: &'a HashSet<IrId>) -> Self {
+        Self {
+            diag,
+            tracked,
+            pending: Some(HashMap::default()),
+            labels: HashMap::default(),
+            stores: Vec::new(),
+            live: HashSet::default(),
+            read: HashSet::default(),
+            escaped: HashSet::default(),
+        }
+    }
+
+    pub fn check(mut self, body: ExprP<'_>) {
+        if self.tracked.is_empty() {
+            return;
+        }
+
+        // Stores that reach a backward jump can be read in the loop body, so iterate until
+        // the state at the labels stops changing.
+        loop {
+            let labels = self.labels.clone();
+            self.pending = Some(HashMap::default());
+            self.stores.clear();
+            self.visit_expr(body);
+
+            if self.labels == labels {
+                break;
+            }
+        }
+
+        for (idx, (id, span)) in self.stores.iter().enumerate() {
+            if self.live.contains(&idx) || !self.read.contains(id) || self.escaped.contains(id) {
+                continue;
+            }
+
+            let _guard = self.diag.push_span(*span);
+            self.diag.warn(CodeErrorKind::UnusedAssignment);
+        }
+    }
+
+    fn read(&mut self, id: IrId) {
+        self.read.insert(id);
+        if let Some(stores) = self.pending.as_ref().and_then(|p| p.get(&id)) {
+            self.live.extend(stores.iter().copied());
+        }
+    }
+
+    fn store(&mut self, id: IrId, expr: ExprP<'_>) {
+        if !self.tracked.contains(&id) {
+            return;
+        }
+
+        let idx = self.stores.len();
+        self.stores.push((id, expr.span));
+        if let Some(pending) = &mut self.pending {
+            pending.insert(id, [idx].into_iter().collect());
+        }
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement<'_>) {
+        match stmt {
+            Statement::Expression(expr) => self.visit_expr(expr),
+            Statement::Label(id) => {
+                if let Some(incoming) = self.labels.get(id) {
+                    self.pending = join(self.pending.take(), incoming.clone());
+                }
+            }
+        }
+    }
+
+    /// Visits the target of a partial assignment or a reference.
+    fn visit_place(&mut self, expr: ExprP<'_>, escapes: bool) {
+        match expr.kind {
+            ExprKind::Local(id) => {
+                if escapes {
+                    self.escaped.insert(id);
+                }
+                self.read(id);
+            }
+            ExprKind::Field(inner, _) | ExprKind::TupleIndex(inner, _) => {
+                self.visit_place(inner, escapes)
+            }
+            ExprKind::Index(inner, index) => {
+                self.visit_expr(index);
+                self.visit_place(inner, escapes);
+            }
+            _ => self.visit_expr(expr),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: ExprP<'_>) {
+        match expr.kind {
+            ExprKind::Block(statements, ret) => {
+                for stmt in statements {
+                    self.visit_statement(stmt);
+                }
+                self.visit_expr(ret);
+            }
+            ExprKind::Binary(BinOp::And | BinOp::Or, lhs, rhs) => {
+                self.visit_expr(lhs);
+                let state = self.pending.clone();
+                self.visit_expr(rhs);
+                self.pending = join(state, self.pending.take());
+            }
+            ExprKind::Binary(_, lhs, rhs) | ExprKind::Index(lhs, rhs) => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            ExprKind::AssignOp(_, lhs, rhs) => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            ExprKind::Assign(lhs, rhs) => {
+                self.visit_expr(rhs);
+                match lhs.kind {
+                    ExprKind::Local(id) => self.store(id, expr),
+                    _ => self.visit_place(lhs, false),
+                }
+            }
+            ExprKind::Call(callee, args) => {
+                self.visit_expr(callee);
+                for arg in args {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprKind::Ref(inner) => self.visit_place(inner, true),
+            ExprKind::Deref(inner)
+            | ExprKind::Unary(_, inner)
+            | ExprKind::Field(inner, _)
+            | ExprKind::TupleIndex(inner, _)
+            | ExprKind::Cast(inner) => self.visit_expr(inner),
+            ExprKind::Return(inner) => {
+                self.visit_expr(inner);
+                self.pending = None;
+            }
+            ExprKind::Goto(label) => {
+                let state = self.pending.take();
+                let incoming = match self.labels.remove(&label) {
+                    Some(existing) => join(existing, state),
+                    None => state,
+                };
+                self.labels.insert(label, incoming);
+            }
+            ExprKind::Local(id) => self.read(id),
+            ExprKind::If(cond, then, els, const_cond) => match const_cond {
+                Some(true) => self.visit_expr(then),
+                Some(false) => self.visit_expr(els),
+                None => {
+                    self.visit_expr(cond);
+                    let state = self.pending.clone();
+                    self.visit_expr(then);
+                    let then_state = std::mem::replace(&mut self.pending, state);
+                    self.visit_expr(els);
+                    self.pending = join(then_state, self.pending.take());
+                }
+            },
+            ExprKind::Intrinsic(ref kind) => match kind {
+                IntrinsicValueKind::ConstPanic(inner)
+                | IntrinsicValueKind::ConstWrite(inner, _)
+                | IntrinsicValueKind::ConstAlloc(_, inner)
+                | IntrinsicValueKind::ConstFree(inner) => self.visit_expr(inner),
+                _ => {}
+            },
+            ExprKind::Array(elems) => {
+                for elem in elems {
+                    self.visit_expr(elem);
+                }
+            }
+            ExprKind::Tuple(inits) => {
+                for init in inits {
+                    self.visit_expr(init.value);
+                }
+            }
+            ExprKind::Struct(inits) => {
+                for init in inits {
+                    self.visit_expr(init.value);
+                }
+            }
+            ExprKind::Unreachable => self.pending = None,
+            ExprKind::Fn(_)
+            | ExprKind::Static(_)
+            | ExprKind::Const(_)
+            | ExprKind::Literal(_)
+            | ExprKind::Void => {}
+        }
+
+        if expr.diverges() {
+            self.pending = None;
+        }
+    }
+}
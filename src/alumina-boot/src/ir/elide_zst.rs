@@ -6,7 +6,9 @@ use crate::diagnostics::DiagnosticsStack;
 use crate::intrinsics::IntrinsicValueKind;
 use crate::ir::builder::{ExpressionBuilder, TypeBuilder};
 use crate::ir::const_eval::Value;
-use crate::ir::{Expr, ExprKind, ExprP, FuncBody, IrCtx, IrId, LocalDef, Statement, Ty, ValueType};
+use crate::ir::{
+    Expr, ExprKind, ExprP, FuncBody, IrCtx, IrId, LocalDef, Statement, Ty, TyP, ValueType,
+};
 
 use super::const_eval::LValue;
 use super::IRItem;
@@ -20,6 +22,7 @@ pub struct ZstElider<'ir> {
     diag: DiagnosticsStack,
     additional_locals: Vec<LocalDef<'ir>>,
     used_ids: HashSet<IrId>,
+    lower_aggregates: bool,
 }
 
 impl<'ir> ZstElider<'ir> {
@@ -29,7 +32,73 @@ impl<'ir> ZstElider<'ir> {
             diag,
             additional_locals: Vec::new(),
             used_ids: HashSet::default(),
+            lower_aggregates: false,
+        }
+    }
+
+    /// Decompose the construction of aggregates (structs, tuples and arrays) into assignments
+    /// to a temporary, for C compilers without compound literals and designated initializers.
+    ///
+    /// This introduces new local variables, so it cannot be used for constant initializers.
+    pub fn with_aggregate_lowering(mut self, enabled: bool) -> Self {
+        self.lower_aggregates = enabled;
+        self
+    }
+
+    fn temporary(&mut self, typ: TyP<'ir>, span: Option<Span>) -> ExprP<'ir> {
+        let id = self.ir.make_id();
+        self.additional_locals.push(LocalDef { id, typ });
+        self.used_ids.insert(id);
+
+        ExpressionBuilder::new(self.ir).local(id, typ, span)
+    }
+
+    fn lower_aggregate(&mut self, expr: ExprP<'ir>) -> ExprP<'ir> {
+        if !self.lower_aggregates {
+            return expr;
         }
+
+        let builder = ExpressionBuilder::new(self.ir);
+        let temporary = self.temporary(expr.ty, expr.span);
+
+        let statements: Vec<_> = match expr.kind {
+            ExprKind::Tuple(inits) => inits
+                .iter()
+                .map(|init| {
+                    if init.value.ty.is_zero_sized() {
+                        return init.value;
+                    }
+                    let lhs =
+                        builder.tuple_index(temporary, init.index, init.value.ty, init.value.span);
+                    builder.assign(lhs, init.value, init.value.span)
+                })
+                .collect(),
+            ExprKind::Struct(inits) => inits
+                .iter()
+                .map(|init| {
+                    if init.value.ty.is_zero_sized() {
+                        return init.value;
+                    }
+                    let lhs = builder.field(temporary, init.field, init.value.ty, init.value.span);
+                    builder.assign(lhs, init.value, init.value.span)
+                })
+                .collect(),
+            ExprKind::Array(elems) => elems
+                .iter()
+                .enumerate()
+                .map(|(idx, elem)| {
+                    let lhs = builder.const_index(temporary, idx, elem.span);
+                    builder.assign(lhs, elem, elem.span)
+                })
+                .collect(),
+            _ => unreachable!(),
+        };
+
+        builder.block(
+            statements.into_iter().map(Statement::Expression),
+            temporary,
+            expr.span,
+        )
     }
 
     pub fn elide_zst_func_body(
@@ -97,7 +166,7 @@ impl<'ir> ZstElider<'ir> {
                     Statement::Label(id) => self.used_ids.contains(id),
                     _ => true,
                 });
-                builder.block(statements, ret, expr.span)
+                builder.block(fuse_field_copies(self.ir, statements), ret, expr.span)
             }
             ExprKind::Binary(op, lhs, rhs) => builder.binary(
                 op,
@@ -122,7 +191,7 @@ impl<'ir> ZstElider<'ir> {
                 let ret = if expr.ty.is_zero_sized() {
                     builder.void(expr.ty, ValueType::RValue, expr.span)
                 } else {
-                    builder.tuple(inits, expr.ty, expr.span)
+                    self.lower_aggregate(builder.tuple(inits, expr.ty, expr.span))
                 };
 
                 builder.block(statements, ret, expr.span)
@@ -143,8 +212,10 @@ impl<'ir> ZstElider<'ir> {
 
                 let ret = if expr.ty.is_zero_sized() {
                     builder.void(expr.ty, ValueType::RValue, expr.span)
+                } else if let Some(source) = fieldwise_copy_source(expr.ty, &inits) {
+                    source
                 } else {
-                    builder.r#struct(inits, expr.ty, expr.span)
+                    self.lower_aggregate(builder.r#struct(inits, expr.ty, expr.span))
                 };
 
                 builder.block(statements, ret, expr.span)
@@ -160,14 +231,11 @@ impl<'ir> ZstElider<'ir> {
                         expr.span,
                     )
                 } else {
-                    builder.array(
-                        elems
-                            .iter()
-                            .map(|e| self.elide_zst_expr(e))
-                            .collect::<Result<Vec<_>, _>>()?,
-                        expr.ty,
-                        expr.span,
-                    )
+                    let elems = elems
+                        .iter()
+                        .map(|e| self.elide_zst_expr(e))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.lower_aggregate(builder.array(elems, expr.ty, expr.span))
                 }
             }
             ExprKind::AssignOp(op, lhs, rhs) => builder.assign_op(
@@ -353,16 +421,13 @@ impl<'ir> ZstElider<'ir> {
                         Ty::Array(ty, _) => ty,
                         _ => unreachable!(),
                     };
-                    builder.array(
-                        elems
-                            .iter()
-                            .map(|val| {
-                                self.elide_zst_expr(builder.literal(*val, element_type, expr.span))
-                            })
-                            .collect::<Result<Vec<_>, _>>()?,
-                        expr.ty,
-                        expr.span,
-                    )
+                    let elems = elems
+                        .iter()
+                        .map(|val| {
+                            self.elide_zst_expr(builder.literal(*val, element_type, expr.span))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.lower_aggregate(builder.array(elems, expr.ty, expr.span))
                 }
                 Value::Tuple(elems) => {
                     let element_types = match expr.ty {
@@ -370,19 +435,16 @@ impl<'ir> ZstElider<'ir> {
                         _ => unreachable!(),
                     };
 
-                    builder.tuple(
-                        elems
-                            .iter()
-                            .zip(element_types.iter())
-                            .enumerate()
-                            .map(|(idx, (val, ty))| {
-                                self.elide_zst_expr(builder.literal(*val, ty, expr.span))
-                                    .map(|e| (idx, e))
-                            })
-                            .collect::<Result<Vec<_>, _>>()?,
-                        expr.ty,
-                        expr.span,
-                    )
+                    let elems = elems
+                        .iter()
+                        .zip(element_types.iter())
+                        .enumerate()
+                        .map(|(idx, (val, ty))| {
+                            self.elide_zst_expr(builder.literal(*val, ty, expr.span))
+                                .map(|e| (idx, e))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.lower_aggregate(builder.tuple(elems, expr.ty, expr.span))
                 }
                 Value::Struct(fields) => {
                     let struct_like = match expr.ty {
@@ -397,25 +459,20 @@ impl<'ir> ZstElider<'ir> {
                     let element_types: HashMap<_, _> =
                         struct_like.fields.iter().map(|f| (f.id, f.ty)).collect();
 
-                    builder.r#struct(
-                        fields
-                            .iter()
-                            .map(|(id, val)| {
-                                self.elide_zst_expr(builder.literal(
-                                    *val,
-                                    element_types[id],
-                                    expr.span,
-                                ))
+                    let fields = fields
+                        .iter()
+                        .map(|(id, val)| {
+                            self.elide_zst_expr(builder.literal(*val, element_types[id], expr.span))
                                 .map(|e| (*id, e))
-                            })
-                            .collect::<Result<Vec<_>, _>>()?,
-                        expr.ty,
-                        expr.span,
-                    )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.lower_aggregate(builder.r#struct(fields, expr.ty, expr.span))
                 }
                 Value::Uninitialized if expr.ty.is_zero_sized() => {
                     builder.void(expr.ty, ValueType::RValue, expr.span)
                 }
+                // `{0}` would need a compound literal, a fresh variable is just as uninitialized
+                Value::Uninitialized if self.lower_aggregates => self.temporary(expr.ty, expr.span),
                 Value::Pointer(lvalue) => {
                     let lvalue_expr = self.elide_zst_lvalue(&lvalue, expr.span);
                     self.elide_zst_expr(builder.r#ref(lvalue_expr, expr.span))?
@@ -533,3 +590,127 @@ impl<'ir> ZstElider<'ir> {
         Ok(())
     }
 }
+
+/// Whether both expressions refer to the same place (a local or a static, or a field of or
+/// a dereference of one).
+fn same_place(a: ExprP<'_>, b: ExprP<'_>) -> bool {
+    match (&a.kind, &b.kind) {
+        (ExprKind::Local(a), ExprKind::Local(b)) => a == b,
+        (ExprKind::Static(a), ExprKind::Static(b)) => a.id == b.id,
+        (ExprKind::Field(a, f), ExprKind::Field(b, g)) => f == g && same_place(a, b),
+        (ExprKind::Deref(a), ExprKind::Deref(b)) => same_place(a, b),
+        _ => false,
+    }
+}
+
+/// The local or static variable that a place is a (possibly nested) field of.
+fn place_root(expr: ExprP<'_>) -> Option<IrId> {
+    match expr.kind {
+        ExprKind::Local(id) => Some(id),
+        ExprKind::Static(item) => Some(item.id),
+        ExprKind::Field(inner, _) => place_root(inner),
+        _ => None,
+    }
+}
+
+/// Non-zero-sized fields of a struct (not union) type
+fn struct_fields(ty: TyP<'_>) -> Option<HashSet<IrId>> {
+    let Ty::Item(item) = ty else {
+        return None;
+    };
+    let s = item.get_struct_like().ok().filter(|s| !s.is_union)?;
+
+    Some(
+        s.fields
+            .iter()
+            .filter(|f| !f.ty.is_zero_sized())
+            .map(|f| f.id)
+            .collect(),
+    )
+}
+
+/// If the field initializers of a struct copy every field from the same field of another
+/// struct of the same type (`Foo { a: x.a, b: x.b }`), returns that struct (`x`).
+fn fieldwise_copy_source<'ir>(ty: TyP<'ir>, inits: &[(IrId, ExprP<'ir>)]) -> Option<ExprP<'ir>> {
+    let mut fields = struct_fields(ty)?;
+    let mut source: Option<ExprP<'ir>> = None;
+
+    for (field, value) in inits {
+        let ExprKind::Field(inner, id) = value.kind else {
+            return None;
+        };
+        if id != *field || inner.ty != ty || !fields.remove(field) {
+            return None;
+        }
+        match source {
+            Some(source) if !same_place(source, inner) => return None,
+            // Only places are the same place as themselves, so this rejects impure sources
+            None if !same_place(inner, inner) => return None,
+            _ => source = Some(inner),
+        }
+    }
+
+    source.filter(|_| fields.is_empty())
+}
+
+/// Replaces runs of assignments that copy every field of a struct from another struct
+/// (`a.x = b.x; a.y = b.y;`) with an assignment of the whole struct (`a = b;`), which the
+/// C compiler can lower to a single `memcpy`.
+///
+/// Only copies between distinct variables (and their nested fields) are fused, so that the
+/// order of the individual assignments does not matter.
+fn fuse_field_copies<'ir>(
+    ir: &'ir IrCtx<'ir>,
+    statements: Vec<Statement<'ir>>,
+) -> Vec<Statement<'ir>> {
+    let field_copy = |stmt: &Statement<'ir>| match stmt {
+        Statement::Expression(Expr {
+            kind: ExprKind::Assign(lhs, rhs),
+            ..
+        }) => match (&lhs.kind, &rhs.kind) {
+            (ExprKind::Field(dst, f), ExprKind::Field(src, g)) if f == g && dst.ty == src.ty => {
+                Some((*dst, *src, *f))
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+
+    let mut result = Vec::with_capacity(statements.len());
+    let mut idx = 0;
+
+    while idx < statements.len() {
+        let fused = field_copy(&statements[idx]).and_then(|(dst, src, _)| {
+            match (place_root(dst), place_root(src)) {
+                (Some(a), Some(b)) if a != b => {}
+                _ => return None,
+            }
+
+            let mut fields = struct_fields(dst.ty)?;
+            let mut end = idx;
+            while !fields.is_empty() {
+                let (d, s, field) = statements.get(end).and_then(field_copy)?;
+                if !same_place(d, dst) || !same_place(s, src) || !fields.remove(&field) {
+                    return None;
+                }
+                end += 1;
+            }
+
+            Some((dst, src, end))
+        });
+
+        match fused {
+            Some((dst, src, end)) => {
+                let builder = ExpressionBuilder::new(ir);
+                result.push(Statement::Expression(builder.assign(dst, src, dst.span)));
+                idx = end;
+            }
+            None => {
+                result.push(statements[idx].clone());
+                idx += 1;
+            }
+        }
+    }
+
+    result
+}
@@ -6,10 +6,21 @@ use crate::ir::lang::LangTypeKind;
 use crate::ir::mono::MonoCtx;
 use crate::{ast, ir};
 
+/// Reason why the generic arguments could not be inferred.
+pub enum InferError<'ir> {
+    /// The placeholder could not be inferred.
+    Incomplete(ast::AstId),
+    /// An unsuffixed numeric literal (given by its index) cannot have the type that the
+    /// parameter has been inferred to from the other arguments. `None` if the conflict is
+    /// between two literals of different kinds (e.g. `1` and `1.0`).
+    Literal(usize, Option<ir::TyP<'ir>>),
+}
+
 pub struct TypeInferer<'a, 'ast, 'ir> {
     ast: &'ast ast::AstCtx<'ast>,
     mono_ctx: &'a mut MonoCtx<'ast, 'ir>,
     placeholders: Vec<ast::Placeholder<'ast>>,
+    explicit_args: Vec<ir::TyP<'ir>>,
 }
 
 impl<'a, 'ast, 'ir> TypeInferer<'a, 'ast, 'ir> {
@@ -22,9 +33,17 @@ impl<'a, 'ast, 'ir> TypeInferer<'a, 'ast, 'ir> {
             ast,
             mono_ctx,
             placeholders,
+            explicit_args: Vec::new(),
         }
     }
 
+    /// Fixes the leading placeholders to explicitly provided generic arguments (e.g.
+    /// `foo::<i32>(...)`), so that only the remaining ones are inferred.
+    pub fn with_explicit_args(mut self, args: &[ir::TyP<'ir>]) -> Self {
+        self.explicit_args = args.to_vec();
+        self
+    }
+
     fn match_slot(
         &mut self,
         inferred: &mut HashMap<ast::AstId, ir::TyP<'ir>>,
@@ -180,52 +199,95 @@ impl<'a, 'ast, 'ir> TypeInferer<'a, 'ast, 'ir> {
         // builtin protocols and even there it only works in the reverse direction, e.g. if
         // we have <A, B, C, F: Fn(A, B) -> C> and F is a known function/function pointer,
         // we can infer A, B and C.
-        if let Some(tgt) = inferred.get(&placeholder.id).copied() {
-            if placeholder.bounds.kind == ast::ProtocolBoundsKind::Any {
-                return;
+        let tgt = match inferred.get(&placeholder.id).copied() {
+            Some(tgt) => tgt,
+            None => return,
+        };
+
+        // Bounds of variadic parameters apply to the elements, not the tuple itself
+        if placeholder.variadic {
+            return;
+        }
+
+        match placeholder.bounds.kind {
+            ast::ProtocolBoundsKind::All => {
+                // Negated bounds do not tell us anything about the other placeholders
+                for bound in placeholder.bounds.bounds.iter().filter(|b| !b.negated) {
+                    self.match_protocol_bound(inferred, tgt, bound);
+                }
             }
+            ast::ProtocolBoundsKind::Any => {
+                // With a disjunction we do not know which of the alternatives will be satisfied,
+                // so we only take the inferences if all the alternatives that produce any agree
+                // with each other.
+                let mut agreed: Option<HashMap<ast::AstId, ir::TyP<'ir>>> = None;
+                for bound in placeholder.bounds.bounds {
+                    if bound.negated {
+                        continue;
+                    }
 
-            for bound in placeholder.bounds.bounds {
-                let (item, args) = match bound.typ {
-                    ast::Ty::Generic(ast::Ty::Item(item), args) => (item, args),
-                    ast::Ty::FunctionProtocol(args, ret) => {
-                        self.match_callable(inferred, tgt, args, ret);
+                    let mut candidate = inferred.clone();
+                    self.match_protocol_bound(&mut candidate, tgt, bound);
+                    if candidate.len() == inferred.len() {
                         continue;
                     }
-                    _ => continue,
-                };
 
-                match self.ast.lang_item_kind(item) {
-                    Some(LangItemKind::ProtoArrayOf) => {
-                        if let [src] = args {
-                            if let ir::Ty::Array(tgt, _) = tgt {
-                                let _ = self.match_slot(inferred, src, tgt);
-                            }
-                        }
+                    match &agreed {
+                        Some(previous) if *previous != candidate => return,
+                        _ => agreed = Some(candidate),
                     }
-                    Some(LangItemKind::ProtoPointerOf) => {
-                        if let [src] = args {
-                            if let ir::Ty::Pointer(tgt, _) = tgt {
-                                let _ = self.match_slot(inferred, src, tgt);
-                            }
-                        }
+                }
+
+                if let Some(agreed) = agreed {
+                    *inferred = agreed;
+                }
+            }
+        }
+    }
+
+    fn match_protocol_bound(
+        &mut self,
+        inferred: &mut HashMap<ast::AstId, ir::TyP<'ir>>,
+        tgt: ir::TyP<'ir>,
+        bound: &ast::Bound<'ast>,
+    ) {
+        let (item, args) = match bound.typ {
+            ast::Ty::Generic(ast::Ty::Item(item), args) => (item, args),
+            ast::Ty::FunctionProtocol(args, ret) => {
+                self.match_callable(inferred, tgt, args, ret);
+                return;
+            }
+            _ => return,
+        };
+
+        match self.ast.lang_item_kind(item) {
+            Some(LangItemKind::ProtoArrayOf) => {
+                if let [src] = args {
+                    if let ir::Ty::Array(tgt, _) = tgt {
+                        let _ = self.match_slot(inferred, src, tgt);
                     }
-                    Some(LangItemKind::ProtoRangeOf) => {
-                        if let [src] = args {
-                            if let Some(LangTypeKind::Range(inner)) =
-                                self.mono_ctx.get_lang_type_kind(tgt)
-                            {
-                                let _ = self.match_slot(inferred, src, inner);
-                            }
-                        }
+                }
+            }
+            Some(LangItemKind::ProtoPointerOf) => {
+                if let [src] = args {
+                    if let ir::Ty::Pointer(tgt, _) = tgt {
+                        let _ = self.match_slot(inferred, src, tgt);
                     }
-                    Some(LangItemKind::ProtoCallable) => match args {
-                        [ast::Ty::Tuple(a1), a2] => self.match_callable(inferred, tgt, a1, a2),
-                        _ => {}
-                    },
-                    _ => {}
                 }
             }
+            Some(LangItemKind::ProtoRangeOf) => {
+                if let [src] = args {
+                    if let Some(LangTypeKind::Range(inner)) = self.mono_ctx.get_lang_type_kind(tgt)
+                    {
+                        let _ = self.match_slot(inferred, src, inner);
+                    }
+                }
+            }
+            Some(LangItemKind::ProtoCallable) => match args {
+                [ast::Ty::Tuple(a1), a2] => self.match_callable(inferred, tgt, a1, a2),
+                _ => {}
+            },
+            _ => {}
         }
     }
 
@@ -269,7 +331,27 @@ impl<'a, 'ast, 'ir> TypeInferer<'a, 'ast, 'ir> {
         self_slot: Option<(ast::TyP<'ast>, ir::TyP<'ir>)>,
         pairs: impl IntoIterator<Item = (ast::TyP<'ast>, ir::TyP<'ir>)>,
     ) -> Option<Vec<ir::TyP<'ir>>> {
-        let mut inferred = HashMap::default();
+        self.try_infer_with_literals(self_slot, pairs, &[]).ok()
+    }
+
+    /// Like [try_infer], but also takes the parameters that receive unsuffixed numeric literals,
+    /// along with the default type of each literal (`i32` or `f64`).
+    ///
+    /// The literals act as type variables: they take the type that the placeholder is inferred
+    /// to from the other arguments (if it is compatible) and only fall back to the default type
+    /// if there are no other constraints.
+    pub fn try_infer_with_literals(
+        &mut self,
+        self_slot: Option<(ast::TyP<'ast>, ir::TyP<'ir>)>,
+        pairs: impl IntoIterator<Item = (ast::TyP<'ast>, ir::TyP<'ir>)>,
+        literals: &[(ast::TyP<'ast>, ir::TyP<'ir>)],
+    ) -> Result<Vec<ir::TyP<'ir>>, InferError<'ir>> {
+        let mut inferred: HashMap<_, _> = self
+            .placeholders
+            .iter()
+            .zip(self.explicit_args.iter())
+            .map(|(placeholder, ty)| (placeholder.id, *ty))
+            .collect();
 
         if let Some((src, tgt)) = self_slot {
             let _ = self.match_slot(&mut inferred, src.canonical_type(), tgt.canonical_type());
@@ -279,26 +361,59 @@ impl<'a, 'ast, 'ir> TypeInferer<'a, 'ast, 'ir> {
             let _ = self.match_slot(&mut inferred, param, actual);
         }
 
+        let mut literal_defaults: HashMap<ast::AstId, (usize, ir::TyP<'ir>)> = HashMap::default();
+        for (idx, (param, default)) in literals.iter().enumerate() {
+            let ast::Ty::Placeholder(id) = param else {
+                continue;
+            };
+
+            if let Some(existing) = inferred.get(id) {
+                if !literal_compatible(default, existing) {
+                    return Err(InferError::Literal(idx, Some(*existing)));
+                }
+            } else if let Some((_, other)) = literal_defaults.get(id) {
+                if other != default {
+                    return Err(InferError::Literal(idx, None));
+                }
+            } else {
+                literal_defaults.insert(*id, (idx, *default));
+            }
+        }
+
+        for (id, (_, default)) in literal_defaults {
+            inferred.insert(id, default);
+        }
+
         let placeholders: Vec<_> = self.placeholders.to_vec();
         for placeholder in placeholders {
             self.match_protocol_bounds(&mut inferred, &placeholder)
         }
 
-        let mut defaults_only = false;
+        let mut defaults_only = None;
         let mut result = Vec::new();
         for placeholder in self.placeholders.iter() {
             if let Some(ty) = inferred.get(&placeholder.id) {
-                if defaults_only {
-                    return None;
+                if let Some(id) = defaults_only {
+                    return Err(InferError::Incomplete(id));
                 }
                 result.push(*ty);
             } else if placeholder.default.is_some() {
-                defaults_only = true;
+                defaults_only = defaults_only.or(Some(placeholder.id));
             } else {
-                return None;
+                return Err(InferError::Incomplete(placeholder.id));
             }
         }
 
-        Some(result)
+        Ok(result)
+    }
+}
+
+/// Whether a literal whose default type is `default` can be given the type `ty` instead.
+fn literal_compatible(default: ir::TyP<'_>, ty: ir::TyP<'_>) -> bool {
+    match (default, ty) {
+        (ir::Ty::Builtin(a), ir::Ty::Builtin(b)) => {
+            (a.is_integer() && b.is_integer()) || (a.is_float() && b.is_float())
+        }
+        _ => false,
     }
 }
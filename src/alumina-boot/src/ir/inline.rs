@@ -196,7 +196,9 @@ impl<'ir> IrInliner<'ir> {
                 ),
                 IntrinsicValueKind::SizeOfLike(_, _)
                 | IntrinsicValueKind::Dangling(_)
-                | IntrinsicValueKind::Asm(_)
+                | IntrinsicValueKind::Asm(..)
+                | IntrinsicValueKind::CInline(_)
+                | IntrinsicValueKind::MemCopy(_)
                 | IntrinsicValueKind::FunctionLike(_)
                 | IntrinsicValueKind::ConstLike(_)
                 | IntrinsicValueKind::Uninitialized
@@ -6,4 +6,6 @@ pub enum LangTypeKind<'ir> {
     Range(TyP<'ir>),
     Dyn(TyP<'ir>, TyP<'ir>),
     ProtoCallable(&'ir [TyP<'ir>], TyP<'ir>),
+    Option(TyP<'ir>),
+    Result(TyP<'ir>, TyP<'ir>),
 }
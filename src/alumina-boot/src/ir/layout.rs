@@ -66,6 +66,7 @@ type FieldLayout<T> = (Layout, Vec<(Option<T>, Layout)>);
 
 pub struct Layouter<'ir> {
     pointer_width: PointerWidth,
+    soft_int128: bool,
     cycle_guardian: CycleGuardian<IRItemP<'ir>>,
 }
 
@@ -83,6 +84,7 @@ impl<'ir> Layouter<'ir> {
 
         Self {
             pointer_width,
+            soft_int128: global_ctx.soft_int128(),
             cycle_guardian: CycleGuardian::new(),
         }
     }
@@ -217,10 +219,13 @@ impl<'ir> Layouter<'ir> {
                 BuiltinType::Bool => Ok(Layout::bool()),
                 BuiltinType::U8 | BuiltinType::I8 => Ok(Layout::integer(8)),
                 BuiltinType::U16 | BuiltinType::I16 => Ok(Layout::integer(16)),
-                BuiltinType::U32 | BuiltinType::I32 => Ok(Layout::integer(32)),
+                BuiltinType::U32 | BuiltinType::I32 | BuiltinType::Char => Ok(Layout::integer(32)),
                 BuiltinType::U64 | BuiltinType::I64 => Ok(Layout::integer(64)),
+                // Pair of `uint64_t`s
+                BuiltinType::U128 | BuiltinType::I128 if self.soft_int128 => Ok(Layout::new(16, 8)),
                 BuiltinType::U128 | BuiltinType::I128 => Ok(Layout::integer(128)),
                 BuiltinType::USize | BuiltinType::ISize => Ok(Layout::pointer(self.pointer_width)),
+                BuiltinType::F16 | BuiltinType::BF16 => Ok(Layout::float(16)),
                 BuiltinType::F32 => Ok(Layout::float(32)),
                 BuiltinType::F64 => Ok(Layout::float(64)),
             },
@@ -1,20 +1,33 @@
 pub mod builder;
+pub mod call_graph;
+pub mod cfg;
 pub mod const_eval;
+pub mod const_ub;
 pub mod dce;
+pub mod dead_store;
 pub mod elide_zst;
 pub mod infer;
 pub mod inline;
 pub mod lang;
 pub mod layout;
 pub mod mono;
+#[cfg(feature = "plugins")]
+pub mod plugins;
+pub mod pointer_lints;
+pub mod query;
+pub mod stats;
+pub mod text;
+pub mod uninit;
+pub mod union_reads;
 
 use crate::ast::{Attribute, BinOp, BuiltinType, Span, UnOp};
 use crate::common::{
     impl_allocatable, Allocatable, AluminaError, ArenaAllocatable, CodeErrorKind, HashSet,
-    Incrementable,
+    Incrementable, IndexSet,
 };
 use crate::intrinsics::IntrinsicValueKind;
 use crate::ir::const_eval::Value;
+use crate::utils::DebugItemGuard;
 
 use bumpalo::Bump;
 use once_cell::unsync::OnceCell;
@@ -28,6 +41,7 @@ pub struct IrCtx<'ir> {
     pub arena: Bump,
     pub counter: Cell<usize>,
     types: RefCell<HashSet<TyP<'ir>>>,
+    strings: RefCell<IndexSet<&'ir [u8]>>,
 }
 
 impl<'ir> IrCtx<'ir> {
@@ -36,6 +50,7 @@ impl<'ir> IrCtx<'ir> {
             arena: Bump::new(),
             counter: Cell::new(0),
             types: RefCell::new(HashSet::default()),
+            strings: RefCell::new(IndexSet::default()),
         }
     }
 
@@ -45,6 +60,15 @@ impl<'ir> IrCtx<'ir> {
         }
     }
 
+    /// Returns the canonical (interned) instance of the type, so types can be compared
+    /// by pointer.
+    ///
+    /// Equality and hashing of [Ty] are structural all the way down (`&Ty` compares and
+    /// hashes the pointee), so tuples and function pointers whose components were
+    /// allocated separately still map to the same instance. Named types compare by symbol
+    /// identity. The set is only used for lookups and never iterated, so interning does
+    /// not depend on the hasher. The order of the generated code does, see
+    /// [crate::common::BuildHasher].
     pub fn intern_type(&'ir self, ty: Ty<'ir>) -> TyP<'ir> {
         if let Some(key) = self.types.borrow().get(&ty) {
             return key;
@@ -56,6 +80,22 @@ impl<'ir> IrCtx<'ir> {
         inner
     }
 
+    /// Adds the string to the string table (if it is not there already).
+    pub fn intern_str(&'ir self, value: &[u8]) -> StrId {
+        if let Some(index) = self.strings.borrow().get_index_of(value) {
+            return StrId(index);
+        }
+
+        let inner = self.arena.alloc_slice_copy(value);
+        let (index, _) = self.strings.borrow_mut().insert_full(inner);
+
+        StrId(index)
+    }
+
+    pub fn get_str(&self, id: StrId) -> &'ir [u8] {
+        self.strings.borrow()[id.0]
+    }
+
     pub fn make_symbol(&'ir self) -> IRItemP<'ir> {
         self.arena.alloc(IRItemCell {
             id: self.make_id(),
@@ -105,6 +145,11 @@ where
     }
 }
 
+/// Index into the string table of `IrCtx`. Identical string literals share the same
+/// entry, so that they are stored (and emitted) only once.
+#[derive(PartialEq, Copy, Clone, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct StrId(usize);
+
 #[derive(PartialEq, Copy, Clone, Eq, Hash, PartialOrd, Ord)]
 pub struct IrId {
     pub id: usize,
@@ -139,7 +184,12 @@ impl Debug for Ty<'_> {
                 let inner = cell.get();
                 match inner {
                     Ok(IRItem::StructLike(s)) => {
-                        write!(f, "{} {{ ", s.name.unwrap_or("(unnamed)"))?;
+                        let name = s.name.unwrap_or("(unnamed)");
+                        // Structs can contain pointers to themselves
+                        let Some(_guard) = DebugItemGuard::enter("ir", cell.id.id) else {
+                            return write!(f, "{}", name);
+                        };
+                        write!(f, "{} {{ ", name)?;
                         for field in s.fields {
                             write!(f, "{:?} ", field.ty)?;
                         }
@@ -315,6 +365,8 @@ pub struct ProtocolFunction<'ir> {
     pub name: &'ir str,
     pub arg_types: &'ir [TyP<'ir>],
     pub return_type: TyP<'ir>,
+    /// The implementation must be `#[const_eval]`
+    pub is_const: bool,
 }
 
 #[derive(Debug)]
@@ -343,6 +395,8 @@ pub struct Static<'ir> {
     pub init: Option<ExprP<'ir>>,
     pub attributes: &'ir [Attribute],
     pub r#extern: bool,
+    /// Declared as `extern const`, cannot be assigned to
+    pub is_const: bool,
 }
 
 #[derive(Debug)]
@@ -505,12 +559,13 @@ impl Eq for IRItemCell<'_> {}
 
 impl Debug for IRItemCell<'_> {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        if fmt.alternate() {
-            writeln!(fmt, "{} {{", self.id)?;
-            writeln!(fmt, "\t{:?}", self.contents.get())?;
-            writeln!(fmt, "}}")?;
-        } else {
-            write!(fmt, "{}", self.id)?
+        match DebugItemGuard::enter("ir", self.id.id) {
+            Some(_guard) if fmt.alternate() => {
+                writeln!(fmt, "{} {{", self.id)?;
+                writeln!(fmt, "\t{:?}", self.contents.get())?;
+                writeln!(fmt, "}}")?;
+            }
+            _ => write!(fmt, "{}", self.id)?,
         }
 
         Ok(())
@@ -652,7 +707,9 @@ impl<'ir> Expr<'ir> {
             ExprKind::Intrinsic(ref kind) => match kind {
                 IntrinsicValueKind::SizeOfLike(_, _) => true,
                 IntrinsicValueKind::Dangling(_) => true,
-                IntrinsicValueKind::Asm(_) => false,
+                IntrinsicValueKind::Asm(..) => false,
+                IntrinsicValueKind::CInline(_) => false,
+                IntrinsicValueKind::MemCopy(_) => false,
                 IntrinsicValueKind::FunctionLike(_) => false,
                 IntrinsicValueKind::ConstLike(_) => false,
                 IntrinsicValueKind::Uninitialized => true,
@@ -1,21 +1,29 @@
+use crate::ast::float;
 use crate::ast::lang::LangItemKind;
+use crate::ast::pretty::PrettyPrinter;
 use crate::ast::rebind::Rebinder;
 use crate::ast::{Attribute, BuiltinType, Span, TestMetadata};
 use crate::common::{
-    ice, AluminaError, ArenaAllocatable, CodeErrorBuilder, CodeErrorKind, CycleGuardian, HashMap,
-    HashSet, Marker,
+    ice, AluminaError, ArenaAllocatable, CodeError, CodeErrorBuilder, CodeErrorKind, CycleGuardian,
+    HashMap, HashSet, Marker,
 };
-use crate::diagnostics::DiagnosticsStack;
-use crate::global_ctx::GlobalCtx;
-use crate::intrinsics::{IntrinsicKind, IntrinsicValueKind};
+use crate::completion::CompletionItem;
+use crate::diagnostics::{DiagnosticsStack, DiagnosticsStackGuard};
+use crate::global_ctx::{GlobalCtx, IndexChecks};
+use crate::intrinsics::{expand_c_inline, IntrinsicKind, IntrinsicValueKind};
 use crate::ir::builder::{ExpressionBuilder, TypeBuilder};
+use crate::ir::call_graph;
 use crate::ir::const_eval::{numeric_of_kind, Value};
 use crate::ir::elide_zst::ZstElider;
-use crate::ir::infer::TypeInferer;
+use crate::ir::infer::{InferError, TypeInferer};
 use crate::ir::inline::IrInliner;
 use crate::ir::lang::LangTypeKind;
-use crate::ir::{FuncBody, IRItemP, LocalDef, ValueType};
+use crate::ir::{ExpressionVisitor, FuncBody, IRItemP, LocalDef, ValueType};
+use crate::lints::IrLintCtx;
+use crate::name_index::{NameRef, SymbolKind};
 use crate::name_resolution::scope::BoundItemType;
+use crate::target::is_register;
+use crate::utils::{bounded_debug, MAX_DEBUG_LEN};
 use crate::{ast, ir};
 
 use once_cell::unsync::OnceCell;
@@ -36,12 +44,19 @@ macro_rules! mismatch {
         ))
     };
 
-    ($self:expr, $expected:expr, $actual:expr) => {
-        $self.diag.err(crate::common::CodeErrorKind::TypeMismatch(
-            $self.mono_ctx.type_name($expected).unwrap(),
-            $self.mono_ctx.type_name($actual).unwrap(),
-        ))
-    };
+    ($self:expr, $expected:expr, $actual:expr) => {{
+        let (expected, actual) = $self.mono_ctx.type_diff($expected, $actual).unwrap();
+        $self
+            .diag
+            .err(match $self.mono_ctx.mismatch_hint($expected, $actual) {
+                Some(hint) => crate::common::CodeErrorKind::TypeMismatchWithHint(
+                    expected,
+                    actual,
+                    hint.to_string(),
+                ),
+                None => crate::common::CodeErrorKind::TypeMismatch(expected, actual),
+            })
+    }};
 }
 
 macro_rules! bail {
@@ -50,12 +65,32 @@ macro_rules! bail {
     };
 }
 
+/// Maximum number of `Deref` steps taken when resolving a method on a wrapper type.
+const MAX_DEREF_CHAIN: usize = 16;
+
 #[derive(Default)]
 pub struct Caches<'ast, 'ir> {
     associated_fns: HashMap<ir::TyP<'ir>, Rc<HashMap<&'ast str, ast::ItemP<'ast>>>>,
     associated_fns_ast: HashMap<ast::TyP<'ast>, Rc<HashMap<&'ast str, ast::ItemP<'ast>>>>,
     struct_field_maps: HashMap<ir::IRItemP<'ir>, Rc<HashMap<&'ast str, &'ir ir::Field<'ir>>>>,
     protocol_bound_matches: HashMap<(ir::TyP<'ir>, ir::TyP<'ir>), BoundCheckResult>,
+    closure_thunks: HashMap<ir::IRItemP<'ir>, ir::IRItemP<'ir>>,
+    bitfield_accessors: HashMap<(ir::IRItemP<'ir>, &'ast str, bool), ir::IRItemP<'ir>>,
+}
+
+/// A `#[bitfield]` struct, lowered to a struct with a single unsigned integer field that
+/// holds all the members.
+struct Bitfield<'ast, 'ir> {
+    storage: ir::Field<'ir>,
+    storage_kind: BuiltinType,
+    members: Vec<BitfieldMember<'ast, 'ir>>,
+}
+
+struct BitfieldMember<'ast, 'ir> {
+    name: &'ast str,
+    ty: ir::TyP<'ir>,
+    offset: usize,
+    width: usize,
 }
 
 pub struct MonoCtx<'ast, 'ir> {
@@ -71,6 +106,8 @@ pub struct MonoCtx<'ast, 'ir> {
     static_local_defs: HashMap<ir::IRItemP<'ir>, Vec<LocalDef<'ir>>>,
     vtable_layouts: HashMap<&'ir [ir::TyP<'ir>], ir::VtableLayout<'ir>>,
     static_inits: Vec<ir::IRItemP<'ir>>,
+    interrupt_vectors: HashMap<usize, ir::IRItemP<'ir>>,
+    bitfields: HashMap<ir::IRItemP<'ir>, Rc<Bitfield<'ast, 'ir>>>,
     malloc_bag: MallocBag<'ir>,
     caches: Caches<'ast, 'ir>,
 }
@@ -102,6 +139,8 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
             vtable_layouts: HashMap::default(),
             malloc_bag: MallocBag::new(),
             static_inits: Vec::new(),
+            interrupt_vectors: HashMap::default(),
+            bitfields: HashMap::default(),
             caches: Caches::default(),
         }
     }
@@ -110,6 +149,14 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
         *self.id_map.entry(id).or_insert_with(|| self.ir.make_id())
     }
 
+    /// Binds the AST id to a fresh IR id, used when the same AST is lowered more than once
+    /// within a function (e.g. unrolled `for const` loops).
+    fn remap_id(&mut self, id: ast::AstId) -> ir::IrId {
+        let new_id = self.ir.make_id();
+        self.id_map.insert(id, new_id);
+        new_id
+    }
+
     pub fn reverse_lookup(&self, item: ir::IRItemP<'ir>) -> MonoKey<'ast, 'ir> {
         self.reverse_map
             .get(&item)
@@ -117,6 +164,18 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
             .expect("reverse lookup failed")
     }
 
+    /// All the items that have been monomorphized so far, with the keys they were created from.
+    pub fn instantiations(
+        &self,
+    ) -> impl Iterator<Item = (&MonoKey<'ast, 'ir>, ir::IRItemP<'ir>)> + '_ {
+        self.finished.iter().map(|(key, item)| (key, *item))
+    }
+
+    /// The AST item (and generic arguments) an IR item was created from, if any.
+    pub fn source_item(&self, item: ir::IRItemP<'ir>) -> Option<MonoKey<'ast, 'ir>> {
+        self.reverse_map.get(&item).cloned()
+    }
+
     pub fn get_lang_type_kind(&self, typ: ir::TyP<'ir>) -> Option<LangTypeKind<'ir>> {
         let item = match typ {
             ir::Ty::Item(item) => item,
@@ -160,6 +219,14 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
             return Some(LangTypeKind::DynSelf);
         }
 
+        if self.ast.lang_item(LangItemKind::Option).ok() == Some(item.0) {
+            return Some(LangTypeKind::Option(item.1[0]));
+        }
+
+        if self.ast.lang_item(LangItemKind::Result).ok() == Some(item.0) {
+            return Some(LangTypeKind::Result(item.1[0], item.1[1]));
+        }
+
         if self.ast.lang_item(LangItemKind::ProtoCallable).ok() == Some(item.0) {
             if let ir::Ty::Tuple(args) = item.1[0] {
                 return Some(LangTypeKind::ProtoCallable(
@@ -175,7 +242,164 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
         None
     }
 
+    /// Suggestion for a type mismatch where a value is (or should be) wrapped in an
+    /// `Option` or a `Result`
+    pub fn mismatch_hint(
+        &self,
+        expected: ir::TyP<'ir>,
+        actual: ir::TyP<'ir>,
+    ) -> Option<&'static str> {
+        // Items generated by the compiler do not have an AST item to look up
+        let lang_kind = |ty: ir::TyP<'ir>| match ty {
+            ir::Ty::Item(item) if self.reverse_map.contains_key(item) => {
+                self.get_lang_type_kind(ty)
+            }
+            _ => None,
+        };
+
+        match lang_kind(actual) {
+            Some(LangTypeKind::Result(ok, _)) if ok == expected => {
+                return Some("the value is a `Result`, did you forget `?`")
+            }
+            Some(LangTypeKind::Option(inner)) if inner == expected => {
+                return Some("the value is an `Option`, consider unwrapping it")
+            }
+            _ => {}
+        }
+
+        match lang_kind(expected) {
+            Some(LangTypeKind::Option(inner)) if inner == actual => {
+                Some("wrap the value with `Option::some`")
+            }
+            Some(LangTypeKind::Result(ok, _)) if ok == actual => {
+                Some("wrap the value with `Result::ok`")
+            }
+            _ => None,
+        }
+    }
+
+    /// Name of a generic instantiation with its concrete arguments (e.g. `insert<i32, Foo>`)
+    /// for use in diagnostics.
+    pub fn instantiation_name(&self, key: &MonoKey<'ast, 'ir>) -> Result<String, AluminaError> {
+        let MonoKey(item, args, _, _) = key;
+
+        let args = args
+            .iter()
+            .map(|arg| self.type_name(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(format!("{}<{}>", item_name(item), args.join(", ")))
+    }
+
+    /// Renders two mismatched types with the parts that are identical in both replaced by `_`,
+    /// e.g. `Vec<Result<_, i32>>` and `Vec<Result<_, u32>>`, so that the difference is easy
+    /// to spot in nested generic types.
+    pub fn type_diff(
+        &self,
+        expected: ir::TyP<'ir>,
+        actual: ir::TyP<'ir>,
+    ) -> Result<(String, String), AluminaError> {
+        if expected == actual {
+            return Ok((self.type_name(expected)?, self.type_name(actual)?));
+        }
+
+        let (e, a) = self.type_diff_inner(expected, actual)?;
+        if e == a {
+            // Different types that print the same (e.g. structs with the same name from
+            // different modules), so the paths are needed to tell them apart
+            return Ok((
+                self.type_name_with(expected, true)?,
+                self.type_name_with(actual, true)?,
+            ));
+        }
+
+        Ok((e, a))
+    }
+
+    fn type_diff_inner(
+        &self,
+        expected: ir::TyP<'ir>,
+        actual: ir::TyP<'ir>,
+    ) -> Result<(String, String), AluminaError> {
+        use ir::Ty::*;
+
+        if expected == actual {
+            return Ok(("_".to_string(), "_".to_string()));
+        }
+
+        match (expected, actual) {
+            (Item(e), Item(a))
+                if self.get_lang_type_kind(expected).is_none()
+                    && self.get_lang_type_kind(actual).is_none() =>
+            {
+                let MonoKey(e_item, e_args, _, _) = self.reverse_lookup(e);
+                let MonoKey(a_item, a_args, _, _) = self.reverse_lookup(a);
+
+                if e_item == a_item && e_args.len() == a_args.len() && !e_args.is_empty() {
+                    let (e, a) = self.type_diff_list(e_args, a_args)?;
+                    let name = item_name(e_item);
+
+                    return Ok((format!("{}<{}>", name, e), format!("{}<{}>", name, a)));
+                }
+            }
+            (Pointer(e, e_const), Pointer(a, a_const)) if e_const == a_const => {
+                let prefix = if *e_const { "&" } else { "&mut " };
+                let (e, a) = self.type_diff_inner(e, a)?;
+
+                return Ok((format!("{}{}", prefix, e), format!("{}{}", prefix, a)));
+            }
+            (Array(e, e_len), Array(a, a_len)) if e_len == a_len => {
+                let (e, a) = self.type_diff_inner(e, a)?;
+
+                return Ok((format!("[{}; {}]", e, e_len), format!("[{}; {}]", a, a_len)));
+            }
+            (Tuple(e), Tuple(a)) if e.len() == a.len() => {
+                let (e, a) = self.type_diff_list(e, a)?;
+
+                return Ok((format!("({})", e), format!("({})", a)));
+            }
+            (FunctionPointer(e_args, e_ret), FunctionPointer(a_args, a_ret))
+                if e_args.len() == a_args.len() =>
+            {
+                let (e, a) = self.type_diff_list(e_args, a_args)?;
+                if e_ret == a_ret && e_ret.is_void() {
+                    return Ok((format!("fn({})", e), format!("fn({})", a)));
+                }
+
+                let (e_ret, a_ret) = self.type_diff_inner(e_ret, a_ret)?;
+                return Ok((
+                    format!("fn({}) -> {}", e, e_ret),
+                    format!("fn({}) -> {}", a, a_ret),
+                ));
+            }
+            _ => {}
+        }
+
+        Ok((self.type_name(expected)?, self.type_name(actual)?))
+    }
+
+    fn type_diff_list(
+        &self,
+        expected: &[ir::TyP<'ir>],
+        actual: &[ir::TyP<'ir>],
+    ) -> Result<(String, String), AluminaError> {
+        let mut lhs = Vec::new();
+        let mut rhs = Vec::new();
+        for (e, a) in expected.iter().zip(actual.iter()) {
+            let (e, a) = self.type_diff_inner(e, a)?;
+            lhs.push(e);
+            rhs.push(a);
+        }
+
+        Ok((lhs.join(", "), rhs.join(", ")))
+    }
+
     pub fn type_name(&self, typ: ir::TyP<'ir>) -> Result<String, AluminaError> {
+        self.type_name_with(typ, false)
+    }
+
+    /// Name of the type, with the full paths of the named types if `qualified`
+    fn type_name_with(&self, typ: ir::TyP<'ir>, qualified: bool) -> Result<String, AluminaError> {
         use ir::Ty::*;
         use std::fmt::Write;
 
@@ -202,9 +426,9 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
 
                         for (idx, arg) in protos.iter().enumerate() {
                             if idx > 0 {
-                                let _ = write!(f, " + {}", self.type_name(arg)?);
+                                let _ = write!(f, " + {}", self.type_name_with(arg, qualified)?);
                             } else {
-                                let _ = write!(f, "{}", self.type_name(arg)?);
+                                let _ = write!(f, "{}", self.type_name_with(arg, qualified)?);
                             }
                         }
 
@@ -216,9 +440,9 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
 
                     Some(LangTypeKind::Slice(ir::Ty::Pointer(inner, is_const))) => {
                         if *is_const {
-                            let _ = write!(f, "&[{}]", self.type_name(inner)?);
+                            let _ = write!(f, "&[{}]", self.type_name_with(inner, qualified)?);
                         } else {
-                            let _ = write!(f, "&mut [{}]", self.type_name(inner)?);
+                            let _ = write!(f, "&mut [{}]", self.type_name_with(inner, qualified)?);
                         }
                         return Ok(f);
                     }
@@ -229,33 +453,43 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
                             if i > 0 {
                                 let _ = write!(f, ", ");
                             }
-                            let _ = write!(f, "{}", self.type_name(arg)?);
+                            let _ = write!(f, "{}", self.type_name_with(arg, qualified)?);
                         }
                         let _ = write!(f, ")");
                         if !ret.is_void() {
-                            let _ = write!(f, " -> {}", self.type_name(ret)?);
+                            let _ = write!(f, " -> {}", self.type_name_with(ret, qualified)?);
                         }
                         return Ok(f);
                     }
                     _ => {}
                 }
 
-                let _ = match cell.get() {
-                    ast::Item::Enum(e) => write!(f, "{}", e.name.unwrap_or("{{anonymous}}")),
-                    ast::Item::StructLike(e) => write!(f, "{}", e.name.unwrap_or("{{anonymous}}")),
-                    ast::Item::Protocol(e) => write!(f, "{}", e.name.unwrap_or("{{anonymous}}")),
-                    ast::Item::Function(e) => write!(f, "{}", e.name.unwrap_or("{{anonymous}}")),
-                    ast::Item::TypeDef(e) => write!(f, "{}", e.name.unwrap_or("{{anonymous}}")),
-                    _ => unreachable!(),
-                };
+                if let Some(path) = self.ast.item_path(cell).filter(|_| qualified) {
+                    let _ = write!(f, "{}", path);
+                } else {
+                    let _ = match cell.get() {
+                        ast::Item::Enum(e) => write!(f, "{}", e.name.unwrap_or("{{anonymous}}")),
+                        ast::Item::StructLike(e) => {
+                            write!(f, "{}", e.name.unwrap_or("{{anonymous}}"))
+                        }
+                        ast::Item::Protocol(e) => {
+                            write!(f, "{}", e.name.unwrap_or("{{anonymous}}"))
+                        }
+                        ast::Item::Function(e) => {
+                            write!(f, "{}", e.name.unwrap_or("{{anonymous}}"))
+                        }
+                        ast::Item::TypeDef(e) => write!(f, "{}", e.name.unwrap_or("{{anonymous}}")),
+                        _ => unreachable!(),
+                    };
+                }
 
                 if !args.is_empty() {
                     let _ = write!(f, "<");
                     for (idx, arg) in args.iter().enumerate() {
                         if idx > 0 {
-                            let _ = write!(f, ", {}", self.type_name(arg)?);
+                            let _ = write!(f, ", {}", self.type_name_with(arg, qualified)?);
                         } else {
-                            let _ = write!(f, "{}", self.type_name(arg)?);
+                            let _ = write!(f, "{}", self.type_name_with(arg, qualified)?);
                         }
                     }
                     let _ = write!(f, ">");
@@ -266,6 +500,7 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
                 let _ = match builtin {
                     BuiltinType::Never => write!(f, "!"),
                     BuiltinType::Bool => write!(f, "bool"),
+                    BuiltinType::Char => write!(f, "char"),
                     BuiltinType::U8 => write!(f, "u8"),
                     BuiltinType::U16 => write!(f, "u16"),
                     BuiltinType::U32 => write!(f, "u32"),
@@ -278,19 +513,21 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
                     BuiltinType::I32 => write!(f, "i32"),
                     BuiltinType::I64 => write!(f, "i64"),
                     BuiltinType::I128 => write!(f, "i128"),
+                    BuiltinType::F16 => write!(f, "f16"),
+                    BuiltinType::BF16 => write!(f, "bf16"),
                     BuiltinType::F32 => write!(f, "f32"),
                     BuiltinType::F64 => write!(f, "f64"),
                 };
             }
             Pointer(ty, is_const) => {
                 if *is_const {
-                    let _ = write!(f, "&{}", self.type_name(ty)?);
+                    let _ = write!(f, "&{}", self.type_name_with(ty, qualified)?);
                 } else {
-                    let _ = write!(f, "&mut {}", self.type_name(ty)?);
+                    let _ = write!(f, "&mut {}", self.type_name_with(ty, qualified)?);
                 }
             }
             Array(ty, len) => {
-                let _ = write!(f, "[{}; {}]", self.type_name(ty)?, len);
+                let _ = write!(f, "[{}; {}]", self.type_name_with(ty, qualified)?, len);
             }
             Tuple(tys) => {
                 let _ = write!(f, "(");
@@ -298,7 +535,7 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
                     if i > 0 {
                         let _ = write!(f, ", ");
                     }
-                    let _ = write!(f, "{}", self.type_name(ty)?);
+                    let _ = write!(f, "{}", self.type_name_with(ty, qualified)?);
                 }
                 let _ = write!(f, ")");
             }
@@ -308,11 +545,11 @@ impl<'ast, 'ir> MonoCtx<'ast, 'ir> {
                     if i > 0 {
                         let _ = write!(f, ", ");
                     }
-                    let _ = write!(f, "{}", self.type_name(arg)?);
+                    let _ = write!(f, "{}", self.type_name_with(arg, qualified)?);
                 }
                 let _ = write!(f, ")");
                 if !ret.is_void() {
-                    let _ = write!(f, " -> {}", self.type_name(ret)?);
+                    let _ = write!(f, " -> {}", self.type_name_with(ret, qualified)?);
                 }
             }
         };
@@ -361,9 +598,93 @@ pub struct Monomorphizer<'a, 'ast, 'ir> {
     local_types: HashMap<ir::IrId, ir::TyP<'ir>>,
     local_type_hints: HashMap<ir::IrId, ir::TyP<'ir>>,
     local_defs: Vec<ir::LocalDef<'ir>>,
+    /// Locals declared with `let`, as opposed to temporaries introduced during lowering
+    named_locals: HashSet<ir::IrId>,
     defer_context: Option<DeferContext<'ir>>,
     diag: DiagnosticsStack,
     tentative: bool,
+    /// Outcomes of type predicates (`T is Protocol`) evaluated while lowering the condition
+    /// of a `when`, used to explain which branch was taken.
+    predicate_log: Option<Vec<String>>,
+    /// Whether we are lowering the body of a `for const` loop, in which case local variables
+    /// need to be given fresh ids on every iteration.
+    unrolling: bool,
+}
+
+/// Constants and calls in an expression that was evaluated at compile time
+#[derive(Default)]
+struct ConstantOperands<'ir> {
+    consts: Vec<IRItemP<'ir>>,
+    has_calls: bool,
+}
+
+impl<'ir> ExpressionVisitor<'ir> for ConstantOperands<'ir> {
+    fn visit_const(&mut self, item: IRItemP<'ir>) -> Result<(), AluminaError> {
+        self.consts.push(item);
+        Ok(())
+    }
+
+    fn visit_call(
+        &mut self,
+        callee: ir::ExprP<'ir>,
+        args: &'ir [ir::ExprP<'ir>],
+    ) -> Result<(), AluminaError> {
+        self.has_calls = true;
+        self.visit_expr(callee)?;
+        for arg in args {
+            self.visit_expr(arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Name of an AST item (without generic arguments) for use in diagnostics.
+pub fn item_name<'ast>(item: ast::ItemP<'ast>) -> &'ast str {
+    let name = match item.get() {
+        ast::Item::Enum(e) => e.name,
+        ast::Item::StructLike(s) => s.name,
+        ast::Item::Protocol(p) => p.name,
+        ast::Item::Function(f) => f.name,
+        ast::Item::TypeDef(t) => t.name,
+        ast::Item::StaticOrConst(s) => s.name,
+        _ => None,
+    };
+
+    name.unwrap_or("{anonymous}")
+}
+
+/// If the expression is a numeric literal without a suffix (or arithmetic on such literals),
+/// returns the type it would have by default.
+fn unsuffixed_literal(expr: &ast::Expr<'_>) -> Option<BuiltinType> {
+    use ast::BinOp::*;
+
+    match &expr.kind {
+        ast::ExprKind::Lit(ast::Lit::Int(_, _, None)) => Some(BuiltinType::I32),
+        ast::ExprKind::Lit(ast::Lit::Float(_, None)) => Some(BuiltinType::F64),
+        ast::ExprKind::Unary(ast::UnOp::Neg | ast::UnOp::BitNot, inner) => {
+            unsuffixed_literal(inner)
+        }
+        ast::ExprKind::Binary(
+            Plus | Minus | Mul | Div | Mod | BitAnd | BitOr | BitXor,
+            lhs,
+            rhs,
+        ) => match (unsuffixed_literal(lhs), unsuffixed_literal(rhs)) {
+            (Some(a), Some(b)) if a == b => Some(a),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Source form of a plain numeric literal, for use in suggestions.
+fn literal_text(expr: &ast::Expr<'_>) -> Option<String> {
+    match &expr.kind {
+        ast::ExprKind::Lit(ast::Lit::Int(sign, value, _)) => {
+            Some(format!("{}{}", if *sign { "-" } else { "" }, value))
+        }
+        ast::ExprKind::Lit(ast::Lit::Float(value, _)) => Some(format!("{:?}", value.to_f64())),
+        _ => None,
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -403,10 +724,13 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             loop_contexts: Vec::new(),
             local_type_hints: HashMap::default(),
             local_defs: Vec::new(),
+            named_locals: HashSet::default(),
             defer_context: None,
             tentative,
             current_item: parent_item,
             diag: DiagnosticsStack::new(diag),
+            predicate_log: None,
+            unrolling: false,
         }
     }
 
@@ -427,11 +751,14 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             return_type: None,
             loop_contexts: Vec::new(),
             local_defs: Vec::new(),
+            named_locals: HashSet::default(),
             local_type_hints: HashMap::default(),
             defer_context: None,
             tentative,
             current_item: parent_item,
             diag: diag_stack,
+            predicate_log: None,
+            unrolling: false,
         }
     }
 
@@ -452,9 +779,15 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
 
         let mut members = Vec::new();
         let mut child = Self::new(self.mono_ctx, self.tentative, self.current_item);
-        let mut type_hint = None;
         let mut taken_values = HashSet::default();
 
+        // `#[repr(C)]` enums have the same representation as C enums (`int`)
+        let mut type_hint = en.attributes.iter().find_map(|a| match a {
+            ast::Attribute::Repr(kind) => Some(child.types.builtin(*kind)),
+            ast::Attribute::ReprC => Some(child.types.builtin(BuiltinType::I32)),
+            _ => None,
+        });
+
         let (valued, non_valued): (Vec<_>, Vec<_>) =
             en.members.iter().copied().partition(|m| m.value.is_some());
 
@@ -613,6 +946,30 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             })
             .collect::<Result<Vec<_>, AluminaError>>()?;
 
+        // The members of bitfield structs are only accessible through the generated accessors
+        // (see `bitfield_accessor`).
+        let fields = if s.attributes.contains(&ast::Attribute::Bitfield) {
+            vec![self.lower_bitfield(item, s, &fields)?]
+        } else {
+            fields
+        };
+
+        // C does not have zero-sized types, so such fields would be missing from the C struct
+        if s.attributes.contains(&ast::Attribute::ReprC) {
+            if let Some((field, _)) = s
+                .fields
+                .iter()
+                .zip(fields.iter())
+                .find(|(_, f)| f.ty.is_zero_sized())
+            {
+                let _guard = self.diag.push_span(field.span);
+                bail!(
+                    self,
+                    CodeErrorKind::ReprCZeroSizedField(field.name.to_string())
+                );
+            }
+        }
+
         let res = ir::IRItem::StructLike(ir::StructLike {
             name: s.name.map(|n| n.alloc_on(child.mono_ctx.ir)),
             fields: fields.alloc_on(child.mono_ctx.ir),
@@ -632,13 +989,161 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         Ok(())
     }
 
+    /// Packs the members of a bitfield struct (in declaration order, starting from the least
+    /// significant bit) into the smallest unsigned integer that fits all of them.
+    fn lower_bitfield(
+        &mut self,
+        item: ir::IRItemP<'ir>,
+        s: &ast::StructLike<'ast>,
+        fields: &[ir::Field<'ir>],
+    ) -> Result<ir::Field<'ir>, AluminaError> {
+        let mut members = Vec::new();
+        let mut offset = 0;
+
+        for (ast_field, field) in s.fields.iter().zip(fields) {
+            let _guard = self.diag.push_span(ast_field.span);
+
+            let width = ast_field
+                .attributes
+                .iter()
+                .find_map(|a| match a {
+                    ast::Attribute::Bits(width) => Some(*width),
+                    _ => None,
+                })
+                .unwrap();
+
+            let max_width = match field.ty {
+                ir::Ty::Builtin(BuiltinType::Bool) => 1,
+                ir::Ty::Builtin(kind) if kind.is_integer() => {
+                    self.mono_ctx
+                        .layouter
+                        .layout_of(field.ty)
+                        .with_backtrace(&self.diag)?
+                        .size
+                        * 8
+                }
+                _ => bail!(
+                    self,
+                    CodeErrorKind::InvalidBitfield(format!(
+                        "`{}` must be an integer or `bool`",
+                        ast_field.name
+                    ))
+                ),
+            };
+
+            if width > max_width {
+                bail!(
+                    self,
+                    CodeErrorKind::InvalidBitfield(format!(
+                        "`{}` is {} bits wide, but its type only has {} bits",
+                        ast_field.name, width, max_width
+                    ))
+                );
+            }
+
+            members.push(BitfieldMember {
+                name: ast_field.name,
+                ty: field.ty,
+                offset,
+                width,
+            });
+            offset += width;
+        }
+
+        let storage_kind = match offset {
+            0..=8 => BuiltinType::U8,
+            9..=16 => BuiltinType::U16,
+            17..=32 => BuiltinType::U32,
+            33..=64 => BuiltinType::U64,
+            _ => bail!(
+                self,
+                CodeErrorKind::InvalidBitfield(format!(
+                    "the members take {} bits, but at most 64 are supported",
+                    offset
+                ))
+            ),
+        };
+
+        let storage = ir::Field {
+            id: self.mono_ctx.ir.make_id(),
+            ty: self.types.builtin(storage_kind),
+        };
+
+        self.mono_ctx.bitfields.insert(
+            item,
+            Rc::new(Bitfield {
+                storage,
+                storage_kind,
+                members,
+            }),
+        );
+
+        Ok(storage)
+    }
+
+    /// Aliases that expand to themselves (directly or through other aliases) would never
+    /// finish expanding, so the chain of alias targets is followed first to rule that out.
+    fn check_alias_cycle(&self, alias: ast::ItemP<'ast>) -> Result<(), AluminaError> {
+        let mut visited = HashSet::default();
+        let mut chain = vec![alias];
+        let mut current = alias;
+
+        while let ast::Item::TypeDef(ast::TypeDef {
+            target: Some(target),
+            ..
+        }) = current.get()
+        {
+            visited.insert(current);
+            current = match target {
+                ast::Ty::Item(next) | ast::Ty::Generic(ast::Ty::Item(next), _) => *next,
+                _ => return Ok(()),
+            };
+
+            if visited.contains(&current) {
+                // If the cycle does not include this alias, it will be reported when the
+                // alias that is part of it is expanded.
+                if current != alias {
+                    return Ok(());
+                }
+
+                let mut guards = Vec::new();
+                for other in chain.iter().skip(1) {
+                    if let ast::Item::TypeDef(ast::TypeDef {
+                        span: Some(span), ..
+                    }) = other.get()
+                    {
+                        guards.push(self.diag.push(Marker::RelatedSpan(
+                            format!("`{}` is defined here", item_name(*other)),
+                            *span,
+                        )));
+                    }
+                }
+
+                let path = chain
+                    .iter()
+                    .chain([&current])
+                    .map(|a| item_name(*a))
+                    .collect::<Vec<_>>()
+                    .join("` -> `");
+
+                return Err(self.diag.err(CodeErrorKind::TypeAliasCycle(path)));
+            }
+
+            chain.push(current);
+        }
+
+        Ok(())
+    }
+
     fn monomorphize_typedef(
         &mut self,
         item: ir::IRItemP<'ir>,
+        ast_item: ast::ItemP<'ast>,
         s: &ast::TypeDef<'ast>,
         generic_args: &'ir [ir::TyP<'ir>],
     ) -> Result<(), AluminaError> {
         let _guard = self.diag.push_span(s.span);
+        self.check_alias_cycle(ast_item)?;
 
         let replacements = self.resolve_placeholders(s.placeholders, generic_args)?;
         let mut child = Self::with_replacements(
@@ -724,6 +1229,8 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                 name: m.name.alloc_on(child.mono_ctx.ir),
                 arg_types: param_types.alloc_on(child.mono_ctx.ir),
                 return_type: ret,
+                is_const: s.attributes.contains(&ast::Attribute::ConstEval)
+                    || fun.attributes.contains(&ast::Attribute::ConstEval),
             });
         }
 
@@ -750,46 +1257,51 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             return Ok(());
         }
 
+        // Reasons why the individual alternatives of an `A | B` bound were not satisfied, used
+        // for the diagnostic if none of them are.
+        let mut reasons = Vec::new();
         let mut found = false;
         for (span, bound, negated) in bounds.iter().copied() {
             let _guard = self.diag.push_span(span);
 
+            let bound_name = self.mono_ctx.type_name(bound).unwrap();
             match self.check_protocol_bound(bound, typ)? {
                 BoundCheckResult::Matches if negated => {
                     if kind == ast::ProtocolBoundsKind::Any {
+                        reasons.push(format!("`!{}`: it matches `{}`", bound_name, bound_name));
                         continue;
                     }
-                    if negated {
-                        bail!(
-                            self,
-                            CodeErrorKind::ProtocolMatch(
-                                self.mono_ctx.type_name(typ).unwrap(),
-                                self.mono_ctx.type_name(bound).unwrap()
-                            )
-                        );
-                    }
+                    bail!(
+                        self,
+                        CodeErrorKind::ProtocolMatch(
+                            self.mono_ctx.type_name(typ).unwrap(),
+                            bound_name
+                        )
+                    );
                 }
                 BoundCheckResult::DoesNotMatch if !negated => {
                     if kind == ast::ProtocolBoundsKind::Any {
+                        reasons.push(format!("`{}`: it does not match", bound_name));
                         continue;
                     }
                     bail!(
                         self,
                         CodeErrorKind::ProtocolMismatch(
                             self.mono_ctx.type_name(typ).unwrap(),
-                            self.mono_ctx.type_name(bound).unwrap()
+                            bound_name
                         )
                     );
                 }
                 BoundCheckResult::DoesNotMatchBecause(detail) if !negated => {
                     if kind == ast::ProtocolBoundsKind::Any {
+                        reasons.push(format!("`{}`: {}", bound_name, detail));
                         continue;
                     }
                     bail!(
                         self,
                         CodeErrorKind::ProtocolMismatchDetail(
                             self.mono_ctx.type_name(typ).unwrap(),
-                            self.mono_ctx.type_name(bound).unwrap(),
+                            bound_name,
                             detail
                         )
                     );
@@ -808,7 +1320,7 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
 
             bail!(
                 self,
-                CodeErrorKind::ProtocolMismatch(
+                CodeErrorKind::ProtocolMismatchDetail(
                     self.mono_ctx.type_name(typ).unwrap(),
                     bounds
                         .iter()
@@ -818,7 +1330,8 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                             self.mono_ctx.type_name(bound).unwrap()
                         })
                         .collect::<Vec<_>>()
-                        .join(" | ")
+                        .join(" | "),
+                    format!("none of the alternatives apply: {}", reasons.join(", "))
                 )
             );
         }
@@ -1182,6 +1695,17 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                     proto_fun.name
                 )));
             }
+
+            if proto_fun.is_const
+                && !candidate_fun
+                    .attributes
+                    .contains(&ast::Attribute::ConstEval)
+            {
+                return Ok(BoundCheckResult::DoesNotMatchBecause(format!(
+                    "`{}` is not `#[const_eval]`",
+                    proto_fun.name
+                )));
+            }
         }
 
         Ok(BoundCheckResult::Matches)
@@ -1223,7 +1747,8 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         let typ = s.typ.map(|t| child.lower_type_for_value(t)).transpose()?;
         let mut init = s.init.map(|t| child.lower_expr(t, typ)).transpose()?;
 
-        if s.is_const {
+        // Extern constants are read-only statics defined elsewhere
+        if s.is_const && !s.r#extern {
             let init = if let Some(typ) = typ {
                 let init = init.unwrap();
                 child.try_coerce(typ, init)?
@@ -1265,6 +1790,7 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                 init,
                 attributes: s.attributes.alloc_on(child.mono_ctx.ir),
                 r#extern: s.r#extern,
+                is_const: s.is_const,
             });
             item.assign(res);
             child.mono_ctx.static_inits.push(item);
@@ -1297,6 +1823,40 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
 
         let mut protocol_bounds = Vec::new();
         for (placeholder, ty) in func.placeholders.iter().zip(generic_args.iter()) {
+            if placeholder.variadic {
+                let elements = match ty {
+                    ir::Ty::Tuple(elements) => *elements,
+                    _ => bail!(
+                        self,
+                        CodeErrorKind::VariadicArgsNotTuple(child.mono_ctx.type_name(ty)?)
+                    ),
+                };
+
+                // Bounds of a variadic parameter apply to each element, so that e.g. in
+                // `Args...: Formattable<Args>` the `Args` in the bound refers to the element.
+                for elem in elements {
+                    let mut replacements = child.replacements.clone();
+                    replacements.insert(placeholder.id, *elem);
+                    let mut elem_child = Self::with_replacements(
+                        child.mono_ctx,
+                        replacements,
+                        self.tentative,
+                        self.current_item,
+                        self.diag.fork(),
+                    );
+
+                    let mut grouped_bounds = Vec::new();
+                    for bound in placeholder.bounds.bounds {
+                        let _guard = self.diag.push_span(bound.span);
+
+                        let ir_bound = elem_child.lower_type_unrestricted(bound.typ)?;
+                        grouped_bounds.push((bound.span, ir_bound, bound.negated));
+                    }
+                    protocol_bounds.push((placeholder.bounds.kind, *elem, grouped_bounds));
+                }
+                continue;
+            }
+
             let mut grouped_bounds = Vec::new();
             for bound in placeholder.bounds.bounds {
                 let _guard = self.diag.push_span(bound.span);
@@ -1307,6 +1867,20 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             protocol_bounds.push((placeholder.bounds.kind, *ty, grouped_bounds));
         }
 
+        for predicate in func.where_predicates {
+            let _guard = self.diag.push_span(predicate.span);
+
+            let ty = child.lower_type_unrestricted(predicate.typ)?;
+            let mut grouped_bounds = Vec::new();
+            for bound in predicate.bounds.bounds {
+                let _guard = self.diag.push_span(bound.span);
+
+                let ir_bound = child.lower_type_unrestricted(bound.typ)?;
+                grouped_bounds.push((bound.span, ir_bound, bound.negated));
+            }
+            protocol_bounds.push((predicate.bounds.kind, ty, grouped_bounds));
+        }
+
         let parameters = func
             .args
             .iter()
@@ -1345,9 +1919,11 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
 
         child.return_type = Some(return_type);
         if let Some(body) = func.body {
+            let instrument_name = child.instrument_name(func, generic_args)?;
             let body = child.lower_function_body(
                 body,
                 func.attributes.contains(&ast::Attribute::InlineDuringMono),
+                instrument_name.as_deref(),
             )?;
             item.get_function().unwrap().body.set(body).unwrap();
         }
@@ -1374,12 +1950,24 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             }
             _ => bail!(
                 self,
-                CodeErrorKind::NotAProtocol(format!("{:?}", mixin.protocol))
+                CodeErrorKind::NotAProtocol(bounded_debug(&mixin.protocol, MAX_DEBUG_LEN))
             ),
         };
 
         let protocol = protocol.get_protocol();
 
+        // Sealed protocols can only be mixed in next to their definition
+        if protocol.attributes.contains(&ast::Attribute::Sealed)
+            && mixin.span.map(|s| s.file) != protocol.span.map(|s| s.file)
+        {
+            bail!(
+                self,
+                CodeErrorKind::SealedProtocolMixin(
+                    protocol.name.unwrap_or("<unnamed>").to_string()
+                )
+            );
+        }
+
         // TODO: Default generic args
         if protocol.placeholders.len() != generic_args.len() {
             bail!(
@@ -1429,11 +2017,32 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                 None => continue,
             };
 
+            // Default implementations of a `#[const_eval]` protocol are themselves
+            // `#[const_eval]`, otherwise they would not satisfy the protocol they come from.
+            let attributes = if protocol.attributes.contains(&ast::Attribute::ConstEval)
+                && !fun.attributes.contains(&ast::Attribute::ConstEval)
+            {
+                fun.attributes
+                    .iter()
+                    .copied()
+                    .chain(once(ast::Attribute::ConstEval))
+                    .collect::<Vec<_>>()
+                    .alloc_on(self.mono_ctx.ast)
+            } else {
+                fun.attributes
+            };
+
             let new_func = self.mono_ctx.ast.make_symbol();
             new_func.assign(ast::Item::Function(ast::Function {
                 name: fun.name,
-                attributes: fun.attributes,
+                attributes,
                 placeholders,
+                where_predicates: fun
+                    .where_predicates
+                    .iter()
+                    .map(|p| rebinder.visit_where_predicate(p))
+                    .collect::<Result<Vec<_>, AluminaError>>()?
+                    .alloc_on(self.mono_ctx.ast),
                 return_type: rebinder.visit_typ(fun.return_type)?,
                 args: fun
                     .args
@@ -1470,15 +2079,123 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         Ok(())
     }
 
-    pub fn lower_function_body(
-        mut self,
-        expr: ast::ExprP<'ast>,
-        is_ir_inline: bool,
-    ) -> Result<ir::FuncBody<'ir>, AluminaError> {
-        let return_type = self.return_type.unwrap();
-        let body = self.lower_expr(expr, Some(return_type))?;
+    /// Name passed to the `-Zinstrument-functions` hooks if the function should be
+    /// instrumented. Inline functions (and the hooks themselves) are not.
+    fn instrument_name(
+        &self,
+        func: &ast::Function<'ast>,
+        generic_args: &'ir [ir::TyP<'ir>],
+    ) -> Result<Option<String>, AluminaError> {
+        if self.tentative || !self.mono_ctx.global_ctx.has_option("instrument-functions") {
+            return Ok(None);
+        }
+
+        let excluded = func.attributes.iter().any(|a| {
+            matches!(
+                a,
+                ast::Attribute::Inline
+                    | ast::Attribute::AlwaysInline
+                    | ast::Attribute::InlineDuringMono
+                    | ast::Attribute::NoInstrument
+            )
+        });
+        if excluded {
+            return Ok(None);
+        }
+
+        let name = func.name.unwrap_or("{anonymous}");
+        if generic_args.is_empty() {
+            return Ok(Some(name.to_string()));
+        }
+
+        let args = generic_args
+            .iter()
+            .map(|arg| self.mono_ctx.type_name(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Some(format!("{}<{}>", name, args.join(", "))))
+    }
+
+    /// Call to an instrumentation hook with the name of the function as a C string. The
+    /// hooks are external functions, so the call is skipped during constant evaluation.
+    fn instrument_call(
+        &mut self,
+        kind: LangItemKind,
+        name: &str,
+    ) -> Result<ir::Statement<'ir>, AluminaError> {
+        let hook = self.monomorphize_lang_item(kind, [])?;
+        let hook_fn = hook.get_function().with_backtrace(&self.diag)?;
+
+        let mut value = name.as_bytes().to_vec();
+        value.push(0);
+        let name = self.exprs.literal(
+            Value::Str(self.mono_ctx.ir.intern_str(&value), 0),
+            self.types
+                .pointer(self.types.builtin(BuiltinType::U8), true),
+            None,
+        );
+
+        let call = self.exprs.call(
+            self.exprs.function(hook, None),
+            [name],
+            hook_fn.return_type,
+            None,
+        );
+        let bool_ty = self.types.builtin(BuiltinType::Bool);
+        let void = self
+            .exprs
+            .void(self.types.void(), ir::ValueType::RValue, None);
+
+        Ok(ir::Statement::Expression(
+            self.exprs.if_then(
+                self.exprs
+                    .unary(ast::UnOp::Not, self.in_const_context(None)?, bool_ty, None),
+                call,
+                void,
+                Some(true),
+                None,
+            ),
+        ))
+    }
+
+    pub fn lower_function_body(
+        mut self,
+        expr: ast::ExprP<'ast>,
+        is_ir_inline: bool,
+        instrument_name: Option<&str>,
+    ) -> Result<ir::FuncBody<'ir>, AluminaError> {
+        let return_type = self.return_type.unwrap();
+
+        // The exit hook runs after the deferred expressions, so all the returns need to go
+        // through the epilogue.
+        let instrument_exit = instrument_name.filter(|_| !return_type.is_never());
+        if instrument_exit.is_some() {
+            self.defer_context = Some(DeferContext::new(
+                self.mono_ctx.ir.make_id(),
+                self.mono_ctx.ir.make_id(),
+            ));
+            self.local_defs.push(ir::LocalDef {
+                id: self.defer_context.as_ref().unwrap().return_local,
+                typ: return_type,
+            });
+        }
+
+        let body = self.lower_expr(expr, Some(return_type))?;
 
         let body = self.try_coerce(return_type, body)?;
+        if !self.tentative {
+            self.mono_ctx
+                .global_ctx
+                .lints()
+                .check_function(&IrLintCtx {
+                    diag: &self.diag,
+                    body,
+                    local_defs: &self.local_defs,
+                    named_locals: &self.named_locals,
+                    has_defer: self.defer_context.is_some(),
+                })?;
+        }
+
         if is_ir_inline {
             if self.defer_context.is_some() {
                 bail!(self, CodeErrorKind::IrInlineFlowControl);
@@ -1489,6 +2206,9 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         };
 
         let mut statements = Vec::new();
+        if let Some(name) = instrument_name {
+            statements.push(self.instrument_call(LangItemKind::InstrumentEnter, name)?);
+        }
         if self.defer_context.is_some() {
             self.generate_defer_prologue(&mut statements);
         }
@@ -1503,7 +2223,10 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         };
 
         if self.defer_context.is_some() {
-            self.generate_defer_epilogue(&mut statements);
+            let exit = instrument_exit
+                .map(|name| self.instrument_call(LangItemKind::InstrumentExit, name))
+                .transpose()?;
+            self.generate_defer_epilogue(&mut statements, exit);
         }
 
         let function_body = FuncBody {
@@ -1512,7 +2235,13 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             raw_body: Some(body),
         };
 
-        let elider = ZstElider::new(self.diag.fork(), self.mono_ctx.ir);
+        let lower_aggregates = !self
+            .mono_ctx
+            .global_ctx
+            .c_dialect()
+            .has_aggregate_literals();
+        let elider = ZstElider::new(self.diag.fork(), self.mono_ctx.ir)
+            .with_aggregate_lowering(lower_aggregates);
         let optimized = elider.elide_zst_func_body(function_body)?;
 
         Ok(optimized)
@@ -1632,8 +2361,41 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         item: ir::IRItemP<'ir>,
         signature_only: bool,
     ) -> Result<(), AluminaError> {
-        let _guard = self.diag.push(Marker::Monomorphization);
+        if self.tentative || key.1.is_empty() {
+            let _guard = self.diag.push(Marker::Monomorphization);
+            return self.monomorphize_item_type_inner(key, item, signature_only);
+        }
+
+        // Generic instantiations are recorded in the backtrace, so that errors in generic code
+        // can show which chain of instantiations led to them. Formatting the name is not cheap,
+        // so it is only done for the errors that actually leave the instantiation.
+        let pending = Marker::PendingInstantiation(item.id.id);
+        let result = {
+            let _guard = self.diag.push(pending.clone());
+            self.monomorphize_item_type_inner(key.clone(), item, signature_only)
+        };
+
+        match result {
+            Err(AluminaError::CodeErrors(mut errors)) => {
+                let name = self.mono_ctx.instantiation_name(&key)?;
+                for marker in errors.iter_mut().flat_map(|e| e.backtrace.iter_mut()) {
+                    if *marker == pending {
+                        *marker = Marker::Instantiation(name.clone());
+                    }
+                }
+
+                Err(AluminaError::CodeErrors(errors))
+            }
+            result => result,
+        }
+    }
 
+    fn monomorphize_item_type_inner(
+        &mut self,
+        key: MonoKey<'ast, 'ir>,
+        item: ir::IRItemP<'ir>,
+        signature_only: bool,
+    ) -> Result<(), AluminaError> {
         match key.0.get() {
             ast::Item::Enum(en) => {
                 self.monomorphize_enum(item, en, key.1)?;
@@ -1650,6 +2412,31 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                     let metadata = self.mono_ctx.ast.test_metadata(key.0).unwrap();
                     self.mono_ctx.tests.insert(item, metadata);
                 }
+
+                if !self.tentative && func.attributes.contains(&ast::Attribute::Interrupt) {
+                    let _guard = self.diag.push_span(func.span);
+
+                    let fun = item.get_function().unwrap();
+                    if let Err(detail) = self.check_interrupt_signature(fun) {
+                        bail!(
+                            self,
+                            CodeErrorKind::InvalidInterruptHandlerSignature(detail.to_string())
+                        );
+                    }
+
+                    for attr in func.attributes {
+                        if let ast::Attribute::InterruptVector(vector) = attr {
+                            if self
+                                .mono_ctx
+                                .interrupt_vectors
+                                .insert(*vector, item)
+                                .is_some()
+                            {
+                                bail!(self, CodeErrorKind::DuplicateInterruptVector(*vector));
+                            }
+                        }
+                    }
+                }
             }
             ast::Item::StructLike(s) => {
                 self.monomorphize_struct(item, s, key.1)?;
@@ -1670,13 +2457,46 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                 self.monomorphize_protocol(item, p, key.1)?;
             }
             ast::Item::TypeDef(i) => {
-                self.monomorphize_typedef(item, i, key.1)?;
+                self.monomorphize_typedef(item, key.0, i, key.1)?;
             }
         };
 
         Ok(())
     }
 
+    /// Checks the parameters of an interrupt handler against what the C compiler accepts
+    /// on the target. On x86, GCC and Clang pass a pointer to the interrupt frame and, for
+    /// exceptions that push one, the error code. Elsewhere handlers take no parameters.
+    fn check_interrupt_signature(&self, fun: &ir::Function<'ir>) -> Result<(), &'static str> {
+        let arch = self.mono_ctx.global_ctx.cfg("target_arch").flatten();
+        let is_x86 = matches!(arch.as_deref(), Some("x86" | "x86_64"));
+
+        let is_frame = |param: &ir::Parameter<'ir>| matches!(param.ty, ir::Ty::Pointer(_, _));
+        let is_error_code =
+            |param: &ir::Parameter<'ir>| matches!(param.ty, ir::Ty::Builtin(BuiltinType::USize));
+
+        if is_x86 {
+            match fun.args {
+                [frame] if is_frame(frame) => {}
+                [frame, error_code] if is_frame(frame) && is_error_code(error_code) => {}
+                _ => {
+                    return Err(
+                        "on x86, interrupt handlers take a pointer to the interrupt \
+                        frame and optionally a `usize` error code",
+                    );
+                }
+            }
+        } else if !fun.args.is_empty() {
+            return Err("interrupt handlers cannot take any parameters on this target");
+        }
+
+        if !(fun.return_type.is_void() || fun.return_type.is_never()) {
+            return Err("interrupt handlers must return void or `!`");
+        }
+
+        Ok(())
+    }
+
     pub fn generate_static_constructor(
         &mut self,
         alive: &HashSet<IRItemP<'ir>>,
@@ -1731,7 +2551,13 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             raw_body: None,
         };
 
-        let elider = ZstElider::new(self.diag.fork(), self.mono_ctx.ir);
+        let lower_aggregates = !self
+            .mono_ctx
+            .global_ctx
+            .c_dialect()
+            .has_aggregate_literals();
+        let elider = ZstElider::new(self.diag.fork(), self.mono_ctx.ir)
+            .with_aggregate_lowering(lower_aggregates);
         let optimized = elider.elide_zst_func_body(function_body)?;
 
         item.assign(ir::IRItem::Function(ir::Function {
@@ -1746,6 +2572,83 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         Ok(item)
     }
 
+    /// Generates a plain function that calls a stateless closure, so that it can be used as
+    /// a function pointer.
+    fn closure_thunk(
+        &mut self,
+        closure_item: ir::IRItemP<'ir>,
+        closure: &ir::Closure<'ir>,
+    ) -> Result<ir::IRItemP<'ir>, AluminaError> {
+        if let Some(thunk) = self.mono_ctx.caches.closure_thunks.get(&closure_item) {
+            return Ok(*thunk);
+        }
+
+        let fun_item = closure.function.get().unwrap();
+        let fun = fun_item.get_function().with_backtrace(&self.diag)?;
+
+        let closure_ty = self.types.named(closure_item);
+        let self_id = self.mono_ctx.ir.make_id();
+
+        let args: Vec<_> = fun
+            .args
+            .iter()
+            .skip(1)
+            .map(|p| ir::Parameter {
+                id: self.mono_ctx.ir.make_id(),
+                ty: p.ty,
+            })
+            .collect();
+
+        let call = self.exprs.call(
+            self.exprs.function(fun_item, None),
+            once(
+                self.exprs
+                    .r#ref(self.exprs.local(self_id, closure_ty, None), None),
+            )
+            .chain(args.iter().map(|p| self.exprs.local(p.id, p.ty, None)))
+            .collect::<Vec<_>>(),
+            fun.return_type,
+            None,
+        );
+
+        let function_body = FuncBody {
+            statements: [ir::Statement::Expression(self.exprs.ret(call, None))]
+                .alloc_on(self.mono_ctx.ir),
+            local_defs: [LocalDef {
+                id: self_id,
+                typ: closure_ty,
+            }]
+            .alloc_on(self.mono_ctx.ir),
+            raw_body: Some(call),
+        };
+
+        let lower_aggregates = !self
+            .mono_ctx
+            .global_ctx
+            .c_dialect()
+            .has_aggregate_literals();
+        let elider = ZstElider::new(self.diag.fork(), self.mono_ctx.ir)
+            .with_aggregate_lowering(lower_aggregates);
+        let optimized = elider.elide_zst_func_body(function_body)?;
+
+        let thunk = self.mono_ctx.ir.make_symbol();
+        thunk.assign(ir::IRItem::Function(ir::Function {
+            name: None,
+            attributes: [].alloc_on(self.mono_ctx.ir),
+            args: args.alloc_on(self.mono_ctx.ir),
+            return_type: fun.return_type,
+            varargs: false,
+            body: OnceCell::from(optimized),
+        }));
+
+        self.mono_ctx
+            .caches
+            .closure_thunks
+            .insert(closure_item, thunk);
+
+        Ok(thunk)
+    }
+
     pub fn monomorphize_lang_item<I>(
         &mut self,
         kind: LangItemKind,
@@ -1938,6 +2841,9 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                 let mut child = self.make_tentative_child();
                 let len_expr =
                     child.lower_expr(len, Some(child.types.builtin(BuiltinType::USize)))?;
+                // The length is folded here no matter how it is written (`size_of` and
+                // `align_of` are already constants by now), so the C code only ever sees
+                // the final number.
                 let len = ir::const_eval::ConstEvaluator::new(
                     child.diag.fork(),
                     child.mono_ctx.malloc_bag.clone(),
@@ -1945,6 +2851,21 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                     child.local_types.iter().map(|(k, v)| (*k, *v)),
                 )
                 .const_eval(len_expr)
+                .map_err(|e| match e {
+                    AluminaError::CodeErrors(errors) => AluminaError::CodeErrors(
+                        errors
+                            .into_iter()
+                            .map(|err| match err.kind {
+                                CodeErrorKind::CannotConstEvaluate(kind) => CodeError {
+                                    kind: CodeErrorKind::NonConstantArrayLength(kind),
+                                    ..err
+                                },
+                                _ => err,
+                            })
+                            .collect(),
+                    ),
+                    e => e,
+                })
                 .and_then(|v| match v {
                     Value::USize(v) => Ok(v),
                     _ => Err(mismatch!(
@@ -2094,7 +3015,10 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             ast::Ty::When(ref cond, then, els) => {
                 // Do not move outside the branch, this must evaluate lazily as the non-matching
                 // branch may contain a compile error.
-                if self.static_cond_matches(cond)? {
+                let (matched, predicates) = self.static_cond_matches(cond)?;
+                let _guard = self.push_static_cond_reason(matched, predicates);
+
+                if matched {
                     self.lower_type_unrestricted(then)?
                 } else {
                     self.lower_type_unrestricted(els)?
@@ -2186,6 +3110,7 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                     name: proto_fun.name,
                     arg_types: args,
                     return_type: proto_fun.return_type,
+                    is_const: proto_fun.is_const,
                 });
             }
         }
@@ -2287,6 +3212,11 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         &mut self,
         item: ir::IRItemP<'ir>,
     ) -> Result<HashMap<&'ast str, &'ir ir::Field<'ir>>, AluminaError> {
+        // Members of bitfields are not fields of the lowered struct
+        if self.mono_ctx.bitfields.contains_key(&item) {
+            return Ok(HashMap::default());
+        }
+
         let MonoKey(ast_item, _, _, _) = self.mono_ctx.reverse_lookup(item);
         let ir_struct = item.get_struct_like().with_backtrace(&self.diag)?;
         let ast_struct = ast_item.get_struct_like();
@@ -2380,6 +3310,8 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
 
         associated_fns.extend(fns.iter().map(|f| (f.name, f.item)));
 
+        // Which mixin provided each of the mixed-in functions
+        let mut providers: HashMap<&'ast str, &ast::Mixin<'ast>> = HashMap::default();
         for mixin in mixins {
             let mixin_fns = match mixin.contents.contents.get() {
                 Some(fns) => fns,
@@ -2391,10 +3323,33 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
 
             for fun in *mixin_fns {
                 // Mixin functions are weaker than native associated functions, so they can be
-                // shadowed. If there are multiple mixins with the same function name, the order
-                // is undefined (FIXME: make it predictable somehow)
-                if !associated_fns.contains_key(fun.name) {
+                // shadowed.
+                if let Some(previous) = providers.get(fun.name).copied() {
+                    // If two mixins provide the same function, the one for the more specific protocol
+                    // wins (e.g. `Comparable` provides `not_equals` that overrides the one from
+                    // `Equatable`). Otherwise it is ambiguous which one should be used.
+                    if self.protocol_refines(mixin.protocol, previous.protocol) {
+                        associated_fns.insert(fun.name, fun.item);
+                        providers.insert(fun.name, mixin);
+                    } else if !self.protocol_refines(previous.protocol, mixin.protocol) {
+                        let _guard = self.diag.push(Marker::RelatedSpan(
+                            "previously provided by this mixin".to_string(),
+                            previous.span.unwrap(),
+                        ));
+                        let _guard = self.diag.push_span(mixin.span);
+
+                        bail!(
+                            self,
+                            CodeErrorKind::ConflictingMixinFn(
+                                fun.name.to_string(),
+                                self.protocol_name(previous.protocol),
+                                self.protocol_name(mixin.protocol)
+                            )
+                        );
+                    }
+                } else if !associated_fns.contains_key(fun.name) {
                     associated_fns.insert(fun.name, fun.item);
+                    providers.insert(fun.name, mixin);
                 }
             }
         }
@@ -2402,6 +3357,54 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         Ok(associated_fns)
     }
 
+    fn mixin_protocol(typ: ast::TyP<'ast>) -> Option<ast::ItemP<'ast>> {
+        match typ {
+            ast::Ty::Item(item) | ast::Ty::Generic(ast::Ty::Item(item), _) => Some(item),
+            _ => None,
+        }
+    }
+
+    fn protocol_name(&self, typ: ast::TyP<'ast>) -> String {
+        Self::mixin_protocol(typ)
+            .and_then(|item| item.get_protocol().name)
+            .unwrap_or("<unnamed>")
+            .to_string()
+    }
+
+    /// Whether `protocol` (transitively) requires `other` through the bounds on its `Self`
+    /// parameter, such as `protocol Comparable<Self: Equatable<Self>>` requiring `Equatable`.
+    fn protocol_refines(&self, protocol: ast::TyP<'ast>, other: ast::TyP<'ast>) -> bool {
+        let (protocol, other) = match (Self::mixin_protocol(protocol), Self::mixin_protocol(other))
+        {
+            (Some(protocol), Some(other)) => (protocol, other),
+            _ => return false,
+        };
+
+        let mut visited = HashSet::default();
+        let mut stack = vec![protocol];
+        while let Some(item) = stack.pop() {
+            if !visited.insert(item) {
+                continue;
+            }
+
+            let self_placeholder = match item.get() {
+                ast::Item::Protocol(p) => p.placeholders.first(),
+                _ => None,
+            };
+
+            for bound in self_placeholder.iter().flat_map(|p| p.bounds.bounds) {
+                match Self::mixin_protocol(bound.typ) {
+                    Some(_) if bound.negated => {}
+                    Some(required) if required == other => return true,
+                    Some(required) => stack.push(required),
+                    None => {}
+                }
+            }
+        }
+
+        false
+    }
+
     fn make_tentative_child<'b>(&'b mut self) -> Monomorphizer<'b, 'ast, 'ir> {
         let ir = self.mono_ctx.ir;
 
@@ -2415,11 +3418,14 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             return_type: self.return_type,
             loop_contexts: self.loop_contexts.clone(),
             local_defs: self.local_defs.clone(),
+            named_locals: self.named_locals.clone(),
             local_type_hints: self.local_type_hints.clone(),
             defer_context: self.defer_context.clone(),
             current_item: self.current_item,
             tentative: true,
             diag: self.diag.fork(),
+            predicate_log: None,
+            unrolling: self.unrolling,
         }
     }
 
@@ -2458,7 +3464,42 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
 
                         return Ok(result.alloc_on(self.mono_ctx.ir));
                     }
-                    ir::IRItem::Closure(_) => bail!(self, CodeErrorKind::ClosuresAreNotFns),
+                    ir::IRItem::Closure(closure) => {
+                        // Closures that do not carry any state can be coerced through a thunk that
+                        // conjures up the (zero-sized) closure value and forwards the arguments.
+                        if !closure.data.fields.iter().all(|f| f.ty.is_zero_sized()) {
+                            bail!(self, CodeErrorKind::ClosuresAreNotFns);
+                        }
+
+                        let fun = closure
+                            .function
+                            .get()
+                            .unwrap()
+                            .get_function()
+                            .with_backtrace(&self.diag)?;
+
+                        if fun.args.len() != args.len() + 1 || fun.return_type != *ret {
+                            return Err(mismatch!(self, lhs_typ, rhs.ty));
+                        }
+                        for (a, b) in fun.args.iter().skip(1).zip(args.iter()) {
+                            if a.ty != *b {
+                                return Err(mismatch!(self, lhs_typ, rhs.ty));
+                            }
+                        }
+
+                        let thunk = self.closure_thunk(item, closure)?;
+                        let result = self.exprs.block(
+                            [ir::Statement::Expression(rhs)],
+                            self.exprs.cast(
+                                self.exprs.function(thunk, rhs.span),
+                                lhs_typ,
+                                rhs.span,
+                            ),
+                            rhs.span,
+                        );
+
+                        return Ok(result);
+                    }
                     _ => {}
                 }
             }
@@ -2567,15 +3608,33 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
     ) -> Result<ir::IRItemP<'ir>, AluminaError> {
         let fun = item.get_function();
 
-        // If the function is not generic, we don't need to infer the args
+        // If the generic args are provided explicitly, we don't need to infer them. Only a prefix
+        // may be given, in which case the rest is filled in from the defaults or, failing that,
+        // inferred from the arguments.
+        let mut explicit_args = Vec::new();
         if let Some(generic_args) = generic_args {
-            let generic_args = generic_args
+            explicit_args = generic_args
                 .iter()
                 .map(|typ| self.lower_type_unrestricted(typ))
-                .collect::<Result<Vec<_>, _>>()?
-                .alloc_on(self.mono_ctx.ir);
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // Arguments past the fixed parameters are packed into a tuple for the variadic one
+            if let Some(ast::Placeholder { variadic: true, .. }) = fun.placeholders.last() {
+                let fixed = fun.placeholders.len() - 1;
+                if explicit_args.len() > fixed {
+                    let packed = explicit_args.split_off(fixed);
+                    explicit_args.push(self.types.tuple(packed));
+                }
+            }
 
-            return self.monomorphize_item(item, generic_args);
+            if fun
+                .placeholders
+                .iter()
+                .skip(explicit_args.len())
+                .all(|p| p.default.is_some())
+            {
+                return self.monomorphize_item(item, explicit_args.alloc_on(self.mono_ctx.ir));
+            }
         }
 
         if fun.placeholders.is_empty() {
@@ -2590,6 +3649,8 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         // but do not rethrow the error as the resolution might still succeed.
 
         let mut infer_pairs = Vec::new();
+        let mut literals = Vec::new();
+        let mut literal_exprs = Vec::new();
 
         let self_slot = self_expr.map(|self_expr| (fun.args[0].typ, self_expr.ty));
 
@@ -2605,25 +3666,29 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             }
 
             let mut child = self.make_tentative_child();
-            infer_pairs.extend(
-                fun.args
-                    .iter()
-                    .skip(self_count)
-                    .zip(args.iter())
-                    .filter_map(|(p, e)| match child.lower_expr(e, None) {
-                        Ok(e) => Some(Ok((p.typ, e.ty))),
-                        Err(AluminaError::CodeErrors(errors)) => {
-                            tentative_errors.extend(
-                                errors.into_iter().filter(|f| {
-                                    !matches!(f.kind, CodeErrorKind::TypeInferenceFailed)
-                                }),
-                            );
-                            None
-                        }
-                        Err(e) => Some(Err(e)),
-                    })
-                    .collect::<Result<Vec<_>, _>>()?,
-            );
+            for (p, e) in fun.args.iter().skip(self_count).zip(args.iter()) {
+                // Unsuffixed numeric literals do not constrain the placeholders by themselves,
+                // they take whatever type the other arguments require.
+                if let Some(kind) = unsuffixed_literal(e) {
+                    literals.push((p.typ, child.types.builtin(kind)));
+                    literal_exprs.push(*e);
+                    continue;
+                }
+
+                match child.lower_expr(e, None) {
+                    Ok(e) => infer_pairs.push((p.typ, e.ty)),
+                    Err(AluminaError::CodeErrors(errors)) => {
+                        tentative_errors.extend(errors.into_iter().filter(|f| {
+                            !matches!(
+                                f.kind,
+                                CodeErrorKind::TypeInferenceFailed
+                                    | CodeErrorKind::GenericParamInferenceFailed(_)
+                            )
+                        }));
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
 
             if !tentative_errors.is_empty() {
                 return Err(AluminaError::CodeErrors(tentative_errors));
@@ -2647,16 +3712,73 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         }
 
         let mut type_inferer =
-            TypeInferer::new(self.mono_ctx.ast, self.mono_ctx, fun.placeholders.to_vec());
+            TypeInferer::new(self.mono_ctx.ast, self.mono_ctx, fun.placeholders.to_vec())
+                .with_explicit_args(&explicit_args);
 
-        match type_inferer.try_infer(self_slot, infer_pairs) {
-            Some(generic_args) => {
+        match type_inferer.try_infer_with_literals(self_slot, infer_pairs, &literals) {
+            Ok(generic_args) => {
                 self.monomorphize_item(item, generic_args.alloc_on(self.mono_ctx.ir))
             }
-            None => Err(self.diag.err(CodeErrorKind::TypeInferenceFailed)),
+            Err(InferError::Incomplete(id)) => bail!(
+                self,
+                CodeErrorKind::GenericParamInferenceFailed(
+                    self.mono_ctx.ast.local_name(id).unwrap_or("_").to_string()
+                )
+            ),
+            Err(InferError::Literal(idx, expected)) => {
+                let expr: &ast::Expr<'ast> = literal_exprs[idx];
+                let _guard = self.diag.push_span(expr.span);
+                let detail = self.literal_mismatch_detail(expr, literals[idx].1, expected)?;
+
+                bail!(self, CodeErrorKind::LiteralTypeInferenceFailed(detail))
+            }
         }
     }
 
+    /// Explains why an unsuffixed numeric literal could not be given a type and suggests how
+    /// to spell it instead.
+    fn literal_mismatch_detail(
+        &self,
+        expr: &ast::Expr<'ast>,
+        default: ir::TyP<'ir>,
+        expected: Option<ir::TyP<'ir>>,
+    ) -> Result<String, AluminaError> {
+        let is_float = matches!(default, ir::Ty::Builtin(k) if k.is_float());
+        let text = literal_text(expr);
+
+        let detail = match expected {
+            Some(expected) => {
+                let expected_name = self.mono_ctx.type_name(expected)?;
+                match (expected, text) {
+                    (ir::Ty::Builtin(k), Some(text)) if k.is_float() && !is_float => format!(
+                        "`{}` is expected here, write it as `{}.0` or `{}.0{}`",
+                        expected_name, text, text, expected_name
+                    ),
+                    (ir::Ty::Builtin(k), Some(text)) if k.is_integer() && is_float => format!(
+                        "`{}` is expected here, but `{}` is a floating point literal",
+                        expected_name, text
+                    ),
+                    _ => format!("`{}` is expected here", expected_name),
+                }
+            }
+            None => {
+                let suffix = if is_float { "f64" } else { "i32" };
+                match text {
+                    Some(text) => format!(
+                        "it is mixed with a literal of a different kind, add a suffix such as `{}{}`",
+                        text, suffix
+                    ),
+                    None => format!(
+                        "it is mixed with a literal of a different kind, add a suffix such as `{}`",
+                        suffix
+                    ),
+                }
+            }
+        };
+
+        Ok(detail)
+    }
+
     fn try_resolve_struct(
         &mut self,
         typ: ast::TyP<'ast>,
@@ -2720,11 +3842,13 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             .filter_map(|(p, e)| match child.lower_expr(e.value, None) {
                 Ok(e) => Some(Ok((p.typ, e.ty))),
                 Err(AluminaError::CodeErrors(errors)) => {
-                    tentative_errors.extend(
-                        errors
-                            .into_iter()
-                            .filter(|f| !matches!(f.kind, CodeErrorKind::TypeInferenceFailed)),
-                    );
+                    tentative_errors.extend(errors.into_iter().filter(|f| {
+                        !matches!(
+                            f.kind,
+                            CodeErrorKind::TypeInferenceFailed
+                                | CodeErrorKind::GenericParamInferenceFailed(_)
+                        )
+                    }));
                     None
                 }
                 Err(e) => Some(Err(e)),
@@ -2852,6 +3976,13 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                 }
             }
 
+            // Characters can only be compared
+            (
+                Builtin(BuiltinType::Char),
+                Eq | Neq | Lt | LEq | Gt | GEq,
+                Builtin(BuiltinType::Char),
+            ) => self.types.builtin(BuiltinType::Bool),
+
             // Logical operators
             (
                 Builtin(BuiltinType::Bool),
@@ -2930,6 +4061,13 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                     })
                     .transpose()?;
 
+                let id = if self.unrolling {
+                    self.mono_ctx.remap_id(decl.id)
+                } else {
+                    id
+                };
+                self.named_locals.insert(id);
+
                 match (type_hint, init) {
                     (None, None) => bail!(self, CodeErrorKind::TypeHintRequired),
                     (Some(ty), None) => {
@@ -3034,12 +4172,8 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         ))
     }
 
-    fn convert_int_literal(
-        &mut self,
-        sign: bool,
-        value: u128,
-        ty: ir::TyP<'ir>,
-    ) -> Result<Value<'ir>, AluminaError> {
+    /// The value of an integer literal, if it fits into the type
+    fn int_literal_value(sign: bool, value: u128, ty: ir::TyP<'ir>) -> Option<Value<'ir>> {
         let maybe_value = if sign {
             let as_i128 = if value == (i128::MAX as u128) + 1 {
                 Ok(i128::MIN)
@@ -3077,22 +4211,108 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             val.map(Some)
         };
 
-        match maybe_value {
-            Ok(Some(v)) => Ok(v),
-            _ => {
+        maybe_value.ok().flatten()
+    }
+
+    fn convert_int_literal(
+        &mut self,
+        sign: bool,
+        value: u128,
+        ty: ir::TyP<'ir>,
+    ) -> Result<Value<'ir>, AluminaError> {
+        match Self::int_literal_value(sign, value, ty) {
+            Some(v) => Ok(v),
+            None => {
+                let ir::Ty::Builtin(kind) = ty else {
+                    ice!(self.diag, "integer literal with a non-integer type");
+                };
+
+                // Suggest the narrowest type of at least the same width that can hold the
+                // value (a signed one if the value is negative)
+                let candidates = if sign || kind.is_signed() {
+                    [
+                        BuiltinType::I8,
+                        BuiltinType::I16,
+                        BuiltinType::I32,
+                        BuiltinType::I64,
+                        BuiltinType::I128,
+                    ]
+                } else {
+                    [
+                        BuiltinType::U8,
+                        BuiltinType::U16,
+                        BuiltinType::U32,
+                        BuiltinType::U64,
+                        BuiltinType::U128,
+                    ]
+                };
+
+                let bits = self.integer_bits(*kind)?;
+                for candidate in candidates {
+                    if self.integer_bits(candidate)? >= bits
+                        && self.integer_fits(candidate, sign, value)?
+                    {
+                        self.diag.note(CodeErrorKind::ConsiderWiderType(
+                            format!("{:?}", candidate).to_lowercase(),
+                        ));
+                        break;
+                    }
+                }
+
+                let (min, max) = self.integer_range(*kind)?;
                 let value = format!("{}{}", if sign { "-" } else { "" }, value);
                 let type_name = self.mono_ctx.type_name(ty)?;
 
-                Err(self
-                    .diag
-                    .err(CodeErrorKind::IntegerOutOfRange(value, type_name)))
+                Err(self.diag.err(CodeErrorKind::IntegerOutOfRange(
+                    value,
+                    type_name,
+                    min.to_string(),
+                    max.to_string(),
+                )))
             }
         }
     }
 
-    fn lower_lit(
-        &mut self,
-        ret: &ast::Lit<'ast>,
+    fn integer_bits(&self, kind: BuiltinType) -> Result<usize, AluminaError> {
+        let layout = self
+            .mono_ctx
+            .layouter
+            .layout_of(self.types.builtin(kind))
+            .with_backtrace(&self.diag)?;
+
+        Ok(layout.size * 8)
+    }
+
+    /// Smallest and largest value of an integer type
+    fn integer_range(&self, kind: BuiltinType) -> Result<(i128, u128), AluminaError> {
+        let bits = self.integer_bits(kind)?;
+
+        if kind.is_signed() {
+            let max = u128::MAX >> (129 - bits);
+            Ok((-(max as i128) - 1, max))
+        } else {
+            Ok((0, u128::MAX >> (128 - bits)))
+        }
+    }
+
+    fn integer_fits(
+        &self,
+        kind: BuiltinType,
+        sign: bool,
+        value: u128,
+    ) -> Result<bool, AluminaError> {
+        let (_, max) = self.integer_range(kind)?;
+
+        Ok(match (sign, kind.is_signed()) {
+            (true, true) => value <= max + 1,
+            (true, false) => value == 0,
+            (false, _) => value <= max,
+        })
+    }
+
+    fn lower_lit(
+        &mut self,
+        ret: &ast::Lit<'ast>,
         type_hint: Option<ir::TyP<'ir>>,
         ast_span: Option<Span>,
     ) -> Result<ir::ExprP<'ir>, AluminaError> {
@@ -3127,18 +4347,33 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                     _ => self.types.builtin(BuiltinType::F64),
                 };
 
-                let ir_str = v.alloc_on(self.mono_ctx.ir);
-
-                match ty {
-                    ir::Ty::Builtin(BuiltinType::F32) => {
-                        self.exprs.literal(Value::F32(ir_str), ty, ast_span)
-                    }
-                    ir::Ty::Builtin(BuiltinType::F64) => {
-                        self.exprs.literal(Value::F64(ir_str), ty, ast_span)
+                let value = match ty {
+                    // Rounding the decimal value to `f32` directly can give a different
+                    // result than going through `f64`. For `f16` and `bf16` the difference
+                    // is negligible.
+                    ir::Ty::Builtin(BuiltinType::F32) => Value::F32(v.to_f32().to_bits()),
+                    ir::Ty::Builtin(kind) if kind.is_float() => {
+                        Value::float(*kind, float::to_bits(*kind, v.to_f64()))
                     }
                     _ => ice!(self.diag, "unexpected type for the float literal"),
-                }
+                };
+
+                self.exprs.literal(value, ty, ast_span)
             }
+            ast::Lit::Char(ch, kind) => match (kind, type_hint) {
+                (Some(BuiltinType::Char), _) | (None, Some(ir::Ty::Builtin(BuiltinType::Char))) => {
+                    self.exprs.literal(
+                        Value::Char(*ch),
+                        self.types.builtin(BuiltinType::Char),
+                        ast_span,
+                    )
+                }
+                _ => self.exprs.literal(
+                    Value::U8(*ch as u8),
+                    self.types.builtin(BuiltinType::U8),
+                    ast_span,
+                ),
+            },
             ast::Lit::Str(v) => self.string_of(v, ast_span)?,
         };
 
@@ -3273,7 +4508,11 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         };
 
         let item = item_cell.get_static().with_backtrace(&self.diag)?;
-        Ok(self.exprs.static_var(item_cell, item.typ, ast_span))
+        if item.is_const {
+            Ok(self.exprs.const_static_var(item_cell, item.typ, ast_span))
+        } else {
+            Ok(self.exprs.static_var(item_cell, item.typ, ast_span))
+        }
     }
 
     fn lower_const(
@@ -3294,6 +4533,11 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         } else {
             self.monomorphize_item(item, &[])?
         };
+        if let Ok(ir::IRItem::Static(s)) = item_cell.get() {
+            // `extern const`
+            return Ok(self.exprs.const_static_var(item_cell, s.typ, ast_span));
+        }
+
         let r#const = item_cell.get_const().with_backtrace(&self.diag)?;
         Ok(self.exprs.const_var(item_cell, r#const.typ, ast_span))
     }
@@ -3360,14 +4604,25 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         use ast::BinOp::*;
         use ir::Ty::*;
 
-        let lhs = self.lower_expr(
-            lhs,
-            match op {
-                Eq | Neq | GEq | LEq | Lt | Gt => None,
-                And | Or => Some(self.types.builtin(BuiltinType::Bool)),
-                _ => type_hint,
-            },
-        )?;
+        let lhs_hint = match op {
+            Eq | Neq | GEq | LEq | Lt | Gt => None,
+            And | Or => Some(self.types.builtin(BuiltinType::Bool)),
+            _ => type_hint,
+        };
+
+        // In `1 < x` the literal should take the type of `x`, so the right-hand side is
+        // lowered first.
+        if !matches!(op, And | Or | LShift | RShift)
+            && unsuffixed_literal(lhs).is_some()
+            && unsuffixed_literal(rhs).is_none()
+        {
+            let rhs = self.lower_expr(rhs, lhs_hint)?;
+            let lhs = self.lower_expr(lhs, Some(rhs.ty))?;
+
+            return self.lower_binary_typed(op, lhs, rhs, ast_span);
+        }
+
+        let lhs = self.lower_expr(lhs, lhs_hint)?;
 
         let rhs = self.lower_expr(
             rhs,
@@ -3381,6 +4636,18 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             },
         )?;
 
+        self.lower_binary_typed(op, lhs, rhs, ast_span)
+    }
+
+    fn lower_binary_typed(
+        &mut self,
+        op: ast::BinOp,
+        lhs: ir::ExprP<'ir>,
+        rhs: ir::ExprP<'ir>,
+        ast_span: Option<Span>,
+    ) -> Result<ir::ExprP<'ir>, AluminaError> {
+        use ast::BinOp::*;
+
         if lhs.diverges() || rhs.diverges() {
             return Ok(self.exprs.diverges([lhs, rhs], ast_span));
         }
@@ -3523,6 +4790,7 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         // but also exclude inreachable branches in codegen (so we don't accidentally codegen
         // const-only code)
         let mut const_cond = None;
+        let mut is_constant = false;
         let child = self.make_tentative_child();
         match ir::const_eval::ConstEvaluator::new(
             child.diag.fork(),
@@ -3542,10 +4810,7 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                 .const_eval(cond)
                 {
                     Ok(Value::Bool(for_codegen)) => {
-                        if for_const_eval == for_codegen {
-                            self.diag
-                                .warn(CodeErrorKind::ConstantCondition(for_const_eval));
-                        }
+                        is_constant = for_const_eval == for_codegen;
                         const_cond = Some(for_codegen);
                     }
                     _ => {}
@@ -3554,12 +4819,28 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             _ => {}
         }
 
+        if let (true, Some(value)) = (is_constant, const_cond) {
+            let _guard = self.diag.push_span(cond.span);
+            let detail = self.constant_condition_detail(cond, value, els_)?;
+            self.diag
+                .warn(CodeErrorKind::ConstantCondition(value, detail));
+        }
+
         Ok(self.exprs.if_then(cond, then, els, const_cond, ast_span))
     }
 
-    fn static_cond_matches(&mut self, cond: &ast::ExprP<'ast>) -> Result<bool, AluminaError> {
+    /// Evaluates the condition of a `when`, returning the selected branch along with
+    /// the outcomes of the type predicates it contained.
+    fn static_cond_matches(
+        &mut self,
+        cond: &ast::ExprP<'ast>,
+    ) -> Result<(bool, Vec<String>), AluminaError> {
         let mut child = self.make_tentative_child();
+        child.predicate_log = Some(Vec::new());
+
         let ir_expr = child.lower_expr(cond, Some(child.types.builtin(BuiltinType::Bool)))?;
+        let predicates = child.predicate_log.take().unwrap_or_default();
+
         let ret = ir::const_eval::ConstEvaluator::new(
             child.diag.fork(),
             child.mono_ctx.malloc_bag.clone(),
@@ -3567,6 +4848,21 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             child.local_types.iter().map(|(k, v)| (*k, *v)),
         )
         .const_eval(ir_expr)
+        .map_err(|e| match e {
+            AluminaError::CodeErrors(errors) => AluminaError::CodeErrors(
+                errors
+                    .into_iter()
+                    .map(|err| match err.kind {
+                        CodeErrorKind::CannotConstEvaluate(kind) => CodeError {
+                            kind: CodeErrorKind::NonConstantWhenCondition(kind),
+                            ..err
+                        },
+                        _ => err,
+                    })
+                    .collect(),
+            ),
+            e => e,
+        })
         .and_then(|v| match v {
             Value::Bool(v) => Ok(v),
             _ => Err(mismatch!(
@@ -3576,7 +4872,49 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             )),
         })?;
 
-        Ok(ret)
+        Ok((ret, predicates))
+    }
+
+    /// Pushes a note explaining why a branch of `when` was selected, so that errors in
+    /// that branch show which predicates held and which did not.
+    fn push_static_cond_reason(
+        &mut self,
+        matched: bool,
+        predicates: Vec<String>,
+    ) -> Option<DiagnosticsStackGuard> {
+        if predicates.is_empty() {
+            return None;
+        }
+
+        Some(self.diag.push(Marker::StaticCondition(format!(
+            "`when` condition evaluated to `{}` ({})",
+            matched,
+            predicates.join(", ")
+        ))))
+    }
+
+    fn record_predicate(
+        &mut self,
+        typ: ir::TyP<'ir>,
+        bound: ir::TyP<'ir>,
+        result: &BoundCheckResult,
+    ) -> Result<(), AluminaError> {
+        if self.predicate_log.is_none() {
+            return Ok(());
+        }
+
+        let typ = self.mono_ctx.type_name(typ)?;
+        let bound = self.mono_ctx.type_name(bound)?;
+        let outcome = match result {
+            BoundCheckResult::Matches => format!("`{}` is `{}`", typ, bound),
+            BoundCheckResult::DoesNotMatch => format!("`{}` is not `{}`", typ, bound),
+            BoundCheckResult::DoesNotMatchBecause(detail) => {
+                format!("`{}` is not `{}`: {}", typ, bound, detail)
+            }
+        };
+
+        self.predicate_log.as_mut().unwrap().push(outcome);
+        Ok(())
     }
 
     fn lower_typecheck(
@@ -3588,18 +4926,74 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
     ) -> Result<ir::ExprP<'ir>, AluminaError> {
         let value = self.lower_expr(value, None)?;
         let typ = self.lower_type_unrestricted(typ)?;
-        let value = matches!(
-            self.check_protocol_bound(typ, value.ty)?,
-            BoundCheckResult::Matches
-        );
+        let result = self.check_protocol_bound(typ, value.ty)?;
+        self.record_predicate(value.ty, typ, &result)?;
+
+        Ok(self.exprs.literal(
+            Value::Bool(matches!(result, BoundCheckResult::Matches)),
+            self.types.builtin(BuiltinType::Bool),
+            ast_span,
+        ))
+    }
+
+    fn lower_type_predicate(
+        &mut self,
+        typ: &ast::TyP<'ast>,
+        bound: &ast::TyP<'ast>,
+        _type_hint: Option<ir::TyP<'ir>>,
+        ast_span: Option<Span>,
+    ) -> Result<ir::ExprP<'ir>, AluminaError> {
+        let typ = self.lower_type_unrestricted(typ)?;
+        let bound = self.lower_type_unrestricted(bound)?;
+        let result = self.check_protocol_bound(bound, typ)?;
+        self.record_predicate(typ, bound, &result)?;
 
         Ok(self.exprs.literal(
-            Value::Bool(value),
+            Value::Bool(matches!(result, BoundCheckResult::Matches)),
             self.types.builtin(BuiltinType::Bool),
             ast_span,
         ))
     }
 
+    /// Why the condition of an `if` is constant and which branch is never taken (for
+    /// `ConstantCondition`)
+    fn constant_condition_detail(
+        &self,
+        cond: ir::ExprP<'ir>,
+        value: bool,
+        els: ast::ExprP<'ast>,
+    ) -> Result<String, AluminaError> {
+        let mut operands = ConstantOperands::default();
+        operands.visit_expr(cond)?;
+
+        let mut consts: Vec<_> = operands
+            .consts
+            .iter()
+            .filter_map(|item| self.mono_ctx.source_item(*item))
+            .map(|key| format!("`{}`", item_name(key.0)))
+            .collect();
+        consts.sort();
+        consts.dedup();
+
+        let reason = match consts.len() {
+            0 if operands.has_calls => "it can be evaluated at compile time".to_string(),
+            0 => "it only contains literals".to_string(),
+            1 => format!("it only depends on the constant {}", consts[0]),
+            _ => format!("it only depends on the constants {}", consts.join(", ")),
+        };
+
+        let unreachable = match (value, &els.kind) {
+            (true, ast::ExprKind::Void) => return Ok(format!(" ({})", reason)),
+            (true, _) => "the `else` branch",
+            (false, _) => "the body",
+        };
+
+        Ok(format!(
+            " ({}), so {} is never executed",
+            reason, unreachable
+        ))
+    }
+
     fn lower_static_if(
         &mut self,
         cond: &ast::ExprP<'ast>,
@@ -3608,7 +5002,10 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         type_hint: Option<ir::TyP<'ir>>,
         _ast_span: Option<Span>,
     ) -> Result<ir::ExprP<'ir>, AluminaError> {
-        if self.static_cond_matches(cond)? {
+        let (matched, predicates) = self.static_cond_matches(cond)?;
+        let _guard = self.push_static_cond_reason(matched, predicates);
+
+        if matched {
             self.lower_expr(then, type_hint)
         } else {
             self.lower_expr(els, type_hint)
@@ -3729,11 +5126,29 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             _ => {}
         }
 
+        // Casting a literal that does not correspond to any of the variants is almost
+        // certainly a mistake.
+        if let (ir::ExprKind::Literal(value), ir::Ty::Item(item)) = (&expr.kind, typ) {
+            if let (Ok(e), Some(int)) = (item.get_enum(), value.as_int()) {
+                let members = self.enum_member_values(e)?;
+                if !self.tentative && !members.iter().any(|m| m.as_int() == Some(int)) {
+                    self.diag.warn(CodeErrorKind::InvalidEnumValue(
+                        value.to_string(),
+                        self.mono_ctx.type_name(typ)?,
+                    ));
+                }
+            }
+        }
+
         match (expr.ty, typ) {
             // Numeric casts
             (ir::Ty::Builtin(a), ir::Ty::Builtin(b)) if a.is_numeric() && b.is_numeric() => {}
             // bool -> integer (but not vice-versa)
             (ir::Ty::Builtin(BuiltinType::Bool), ir::Ty::Builtin(b)) if b.is_numeric() => {}
+            // char -> integer and u8 -> char (every byte is a valid scalar value, for wider
+            // integers `char::from_u32` has to be used)
+            (ir::Ty::Builtin(BuiltinType::Char), ir::Ty::Builtin(b)) if b.is_integer() => {}
+            (ir::Ty::Builtin(BuiltinType::U8), ir::Ty::Builtin(BuiltinType::Char)) => {}
 
             // Enums
             (ir::Ty::Item(a), ir::Ty::Builtin(b))
@@ -3768,9 +5183,50 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             ),
         }
 
+        if let (ir::Ty::Builtin(from), ir::Ty::Builtin(to)) = (expr.ty, typ) {
+            if from.is_integer() && to.is_integer() && !self.tentative {
+                let _guard = self.diag.push_span(ast_span);
+                self.check_lossy_cast(expr, *from, *to)?;
+            }
+        }
+
         Ok(self.exprs.cast(expr, typ, ast_span))
     }
 
+    /// Warns about integer casts that can change the value, unless the value is a constant
+    /// that fits into the target type.
+    fn check_lossy_cast(
+        &self,
+        expr: ir::ExprP<'ir>,
+        from: BuiltinType,
+        to: BuiltinType,
+    ) -> Result<(), AluminaError> {
+        let to_type = self.types.builtin(to);
+        if let ir::ExprKind::Literal(value) = &expr.kind {
+            if let Some((sign, magnitude)) = value.as_int() {
+                if Self::int_literal_value(sign, magnitude, to_type).is_some() {
+                    return Ok(());
+                }
+            }
+        }
+
+        let from_bits = self.integer_bits(from)?;
+        let to_bits = self.integer_bits(to)?;
+        let from_name = self.mono_ctx.type_name(expr.ty)?;
+        let to_name = self.mono_ctx.type_name(to_type)?;
+
+        if to_bits < from_bits {
+            self.diag
+                .warn(CodeErrorKind::TruncatingCast(from_name, to_name));
+        } else if from.is_signed() != to.is_signed() && (from.is_signed() || to_bits == from_bits) {
+            // Widening unsigned to signed is always lossless
+            self.diag
+                .warn(CodeErrorKind::SignChangingCast(from_name, to_name));
+        }
+
+        Ok(())
+    }
+
     fn lower_loop(
         &mut self,
         body: ast::ExprP<'ast>,
@@ -3820,6 +5276,121 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         Ok(result)
     }
 
+    fn lower_static_for(
+        &mut self,
+        value: ast::ExprP<'ast>,
+        index: Option<ast::AstId>,
+        elem: ast::AstId,
+        body: ast::ExprP<'ast>,
+        _type_hint: Option<ir::TyP<'ir>>,
+        ast_span: Option<Span>,
+    ) -> Result<ir::ExprP<'ir>, AluminaError> {
+        let value = self.lower_expr(value, None)?;
+        let elem_types = match value.ty.canonical_type() {
+            ir::Ty::Tuple(types) => *types,
+            _ => bail!(
+                self,
+                CodeErrorKind::StaticForNotTuple(self.mono_ctx.type_name(value.ty)?)
+            ),
+        };
+
+        // The tuple is evaluated once and the elements are read through a pointer to it.
+        let mut tup = self.r#ref(value, ast_span);
+        let tup_ptr = self.mono_ctx.ir.make_id();
+        self.local_defs.push(ir::LocalDef {
+            id: tup_ptr,
+            typ: tup.ty,
+        });
+        self.local_types.insert(tup_ptr, tup.ty);
+
+        let mut statements = vec![ir::Statement::Expression(self.exprs.assign(
+            self.exprs.local(tup_ptr, tup.ty, ast_span),
+            tup,
+            ast_span,
+        ))];
+        tup = self.exprs.local(tup_ptr, tup.ty, ast_span);
+        while let ir::Ty::Pointer(_, _) = tup.ty {
+            tup = self.exprs.deref(tup, ast_span);
+        }
+
+        let loop_result = self.mono_ctx.ir.make_id();
+        let break_label = self.mono_ctx.ir.make_id();
+
+        let unrolling = std::mem::replace(&mut self.unrolling, true);
+        for (idx, elem_ty) in elem_types.iter().copied().enumerate() {
+            let continue_label = self.mono_ctx.ir.make_id();
+
+            if let Some(index) = index {
+                let usize_ty = self.types.builtin(BuiltinType::USize);
+                let index = self.mono_ctx.remap_id(index);
+                self.local_defs.push(ir::LocalDef {
+                    id: index,
+                    typ: usize_ty,
+                });
+                self.local_types.insert(index, usize_ty);
+                statements.push(ir::Statement::Expression(self.exprs.assign(
+                    self.exprs.local(index, usize_ty, ast_span),
+                    self.exprs.literal(Value::USize(idx), usize_ty, ast_span),
+                    ast_span,
+                )));
+            }
+
+            let elem = self.mono_ctx.remap_id(elem);
+            self.local_defs.push(ir::LocalDef {
+                id: elem,
+                typ: elem_ty,
+            });
+            self.local_types.insert(elem, elem_ty);
+            statements.push(ir::Statement::Expression(self.exprs.assign(
+                self.exprs.local(elem, elem_ty, ast_span),
+                self.exprs.tuple_index(tup, idx, elem_ty, ast_span),
+                ast_span,
+            )));
+
+            self.loop_contexts.push(LoopContext {
+                loop_result,
+                type_hint: None,
+                break_label,
+                continue_label,
+            });
+            let body = self.lower_expr(body, None);
+            self.loop_contexts.pop();
+
+            let body = match body {
+                Ok(body) => body,
+                Err(e) => {
+                    self.unrolling = unrolling;
+                    return Err(e);
+                }
+            };
+
+            statements.push(ir::Statement::Expression(body));
+            statements.push(ir::Statement::Label(continue_label));
+        }
+        self.unrolling = unrolling;
+
+        statements.push(ir::Statement::Label(break_label));
+
+        // `break` in a `for const` loop cannot have a value, as the loop can also complete
+        // normally after the last element.
+        if let Some(typ) = self.local_types.get(&loop_result).copied() {
+            if !typ.is_void() {
+                return Err(mismatch!(self, "void", typ));
+            }
+            self.local_defs.push(ir::LocalDef {
+                id: loop_result,
+                typ,
+            });
+        }
+
+        Ok(self.exprs.block(
+            statements,
+            self.exprs
+                .void(self.types.void(), ir::ValueType::RValue, ast_span),
+            ast_span,
+        ))
+    }
+
     fn lower_break(
         &mut self,
         expr: Option<ast::ExprP<'ast>>,
@@ -3932,6 +5503,7 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                 }
             }
             IntrinsicKind::EnumVariants => self.generate_enum_variants(generic_args[0]),
+            IntrinsicKind::IsEnumValue => self.is_enum_value(generic_args[0], args[0], span),
             IntrinsicKind::TypeName => {
                 let typ = generic_args[0];
                 let name = self.mono_ctx.type_name(typ)?;
@@ -3946,7 +5518,8 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             IntrinsicKind::CompileWarn => self.compile_warn(args[0], span),
             IntrinsicKind::CompileNote => self.compile_note(args[0], span),
             IntrinsicKind::Unreachable => self.unreachable(span),
-            IntrinsicKind::Asm => self.asm(args[0], span),
+            IntrinsicKind::Asm => self.asm(args[0], &args[1..], span),
+            IntrinsicKind::CInline => self.c_inline(args[0], &args[1..], span),
             IntrinsicKind::CodegenFunc => {
                 self.codegen_func(args[0], &args[1..], generic_args[0], span)
             }
@@ -3956,6 +5529,13 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             }
             IntrinsicKind::Uninitialized => self.uninitialized(generic_args[0], span),
             IntrinsicKind::Zeroed => self.zeroed(generic_args[0], span),
+            IntrinsicKind::StackAlloc => self.stack_alloc(generic_args[0], args[0], span),
+            IntrinsicKind::CopyNonoverlapping => {
+                self.copy(generic_args[0], args[0], args[1], args[2], false, span)
+            }
+            IntrinsicKind::Copy => {
+                self.copy(generic_args[0], args[0], args[1], args[2], true, span)
+            }
             IntrinsicKind::Dangling => self.dangling(generic_args[0], span),
             IntrinsicKind::InConstContext => self.in_const_context(span),
             IntrinsicKind::ConstEval => self.const_eval(args[0], span),
@@ -3996,7 +5576,7 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         let make_slice = self.monomorphize_lang_item(LangItemKind::SliceNew, [ptr_type])?;
 
         let data = self.exprs.literal(
-            Value::Str(self.mono_ctx.ir.arena.alloc_slice_copy(value), 0),
+            Value::Str(self.mono_ctx.ir.intern_str(value), 0),
             ptr_type,
             span,
         );
@@ -4032,16 +5612,28 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             ir::ExprKind::Fn(item) => {
                 let func = item.get_function().with_backtrace(&self.diag)?;
                 if func.attributes.contains(&ast::Attribute::InlineDuringMono) {
+                    let raw_body = func
+                        .body
+                        .get()
+                        .ok_or_else(|| self.diag.err(CodeErrorKind::UnpopulatedSymbol))?
+                        .raw_body
+                        .unwrap();
+
+                    // The stack allocation ends up in the caller (e.g. `std::mem::stack_alloc`),
+                    // so the lint has to look at the loops of the caller.
+                    if !self.loop_contexts.is_empty()
+                        && !self.tentative
+                        && call_graph::allocates_on_stack(raw_body)?
+                    {
+                        self.diag.warn(CodeErrorKind::StackAllocInALoop);
+                    }
+
                     // no silent fallback to a regular function call, since the only thing that can go wrong is that
                     // the callee is not compatible with IR inlining, so this should not lead to surprises
                     let (expr, mut additional_defs) = IrInliner::inline(
                         self.diag.fork(),
                         self.mono_ctx.ir,
-                        func.body
-                            .get()
-                            .ok_or_else(|| self.diag.err(CodeErrorKind::UnpopulatedSymbol))?
-                            .raw_body
-                            .unwrap(),
+                        raw_body,
                         func.args
                             .iter()
                             .zip(args.into_iter())
@@ -4060,12 +5652,81 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                         return Ok(expr);
                     }
                 }
+
+                if func.attributes.contains(&ast::Attribute::Allocator) {
+                    let call = self.exprs.call(callee, args, return_ty, span);
+                    return self.record_alloc_site(call, span);
+                }
+
+                if self.is_lang_item(item, LangItemKind::IndexFailure) {
+                    // The arguments only go into the panic message, so they can be dropped
+                    match self.mono_ctx.global_ctx.index_checks() {
+                        IndexChecks::Panic => {}
+                        IndexChecks::Trap => return self.trap(span),
+                        IndexChecks::Unchecked => return Ok(self.exprs.unreachable(span)),
+                    }
+                }
             }
             _ => {}
         }
         Ok(self.exprs.call(callee, args, return_ty, span))
     }
 
+    fn is_lang_item(&self, item: ir::IRItemP<'ir>, kind: LangItemKind) -> bool {
+        match (
+            self.mono_ctx.ast.lang_item(kind),
+            self.mono_ctx.source_item(item),
+        ) {
+            (Ok(lang_item), Some(key)) => key.0 == lang_item,
+            _ => false,
+        }
+    }
+
+    /// Stores the id of the call site in the `alloc_site` static before a call to an
+    /// `#[allocator]` function (`-Zinstrument-allocs`), so that the allocation hooks
+    /// can attribute the allocation to it.
+    fn record_alloc_site(
+        &mut self,
+        call: ir::ExprP<'ir>,
+        span: Option<Span>,
+    ) -> Result<ir::ExprP<'ir>, AluminaError> {
+        if self.tentative || !self.mono_ctx.global_ctx.has_option("instrument-allocs") {
+            return Ok(call);
+        }
+        let Some(span) = span else {
+            return Ok(call);
+        };
+
+        let site_id = self.mono_ctx.global_ctx.alloc_site_id(span);
+        let site = self.monomorphize_lang_item(LangItemKind::AllocSite, [])?;
+        let site_ty = site.get_static().with_backtrace(&self.diag)?.typ;
+
+        let assign = self.exprs.assign(
+            self.exprs.static_var(site, site_ty, Some(span)),
+            self.exprs.literal(Value::U32(site_id), site_ty, Some(span)),
+            Some(span),
+        );
+
+        // Statics cannot be written during constant evaluation, so the site is only
+        // recorded at runtime.
+        let bool_ty = self.types.builtin(BuiltinType::Bool);
+        let void = self
+            .exprs
+            .void(self.types.void(), ir::ValueType::RValue, Some(span));
+        let guarded = self.exprs.if_then(
+            self.exprs
+                .unary(ast::UnOp::Not, self.in_const_context(None)?, bool_ty, None),
+            assign,
+            void,
+            Some(true),
+            Some(span),
+        );
+
+        Ok(self
+            .exprs
+            .block([ir::Statement::Expression(guarded)], call, Some(span)))
+    }
+
     fn lower_virtual_call(
         &mut self,
         protocol_types: &'ir [ir::TyP<'ir>],
@@ -4192,7 +5853,226 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             ast_span,
         );
 
-        Ok(Some(ret))
+        Ok(Some(ret))
+    }
+
+    /// Calls to `.member()` and `.set_member(value)` on a bitfield struct (unless the struct
+    /// has its own associated function with that name).
+    fn lower_bitfield_access(
+        &mut self,
+        item: ir::IRItemP<'ir>,
+        self_arg: ir::ExprP<'ir>,
+        name: &'ast str,
+        args: &[ast::ExprP<'ast>],
+        ast_span: Option<Span>,
+    ) -> Result<Option<ir::ExprP<'ir>>, AluminaError> {
+        let bitfield = self.mono_ctx.bitfields.get(&item).unwrap().clone();
+
+        let (member_name, setter) = match name.strip_prefix("set_") {
+            Some(member) if bitfield.members.iter().any(|m| m.name == member) => (member, true),
+            _ => (name, false),
+        };
+        let Some(member) = bitfield.members.iter().find(|m| m.name == member_name) else {
+            return Ok(None);
+        };
+
+        let expected_args = if setter { 1 } else { 0 };
+        if args.len() != expected_args {
+            bail!(
+                self,
+                CodeErrorKind::ParamCountMismatch(expected_args, args.len())
+            );
+        }
+
+        let accessor = self.bitfield_accessor(item, &bitfield, member, setter)?;
+        let func = accessor.get_function().with_backtrace(&self.diag)?;
+
+        let self_arg = self.autoref(self_arg, func.args[0].ty, ast_span)?;
+        let mut ir_args = vec![self.try_coerce(func.args[0].ty, self_arg)?];
+        for arg in args {
+            let arg = self.lower_expr(arg, Some(member.ty))?;
+            ir_args.push(self.try_coerce(member.ty, arg)?);
+        }
+
+        if ir_args.iter().any(|e| e.diverges()) {
+            return Ok(Some(self.exprs.diverges(ir_args, ast_span)));
+        }
+
+        Ok(Some(self.call(
+            self.exprs.function(accessor, ast_span),
+            ir_args,
+            func.return_type,
+            ast_span,
+        )?))
+    }
+
+    /// Generates the getter (`fn member(self: &T) -> M`) or the setter
+    /// (`fn set_member(self: &mut T, value: M)`) of a bitfield member.
+    fn bitfield_accessor(
+        &mut self,
+        item: ir::IRItemP<'ir>,
+        bitfield: &Bitfield<'ast, 'ir>,
+        member: &BitfieldMember<'ast, 'ir>,
+        setter: bool,
+    ) -> Result<ir::IRItemP<'ir>, AluminaError> {
+        let key = (item, member.name, setter);
+        if let Some(accessor) = self.mono_ctx.caches.bitfield_accessors.get(&key) {
+            return Ok(*accessor);
+        }
+
+        let storage_ty = bitfield.storage.ty;
+        let storage_bits = match bitfield.storage_kind {
+            BuiltinType::U8 => 8,
+            BuiltinType::U16 => 16,
+            BuiltinType::U32 => 32,
+            _ => 64,
+        };
+        let literal = |value: u64| {
+            let value = match bitfield.storage_kind {
+                BuiltinType::U8 => Value::U8(value as u8),
+                BuiltinType::U16 => Value::U16(value as u16),
+                BuiltinType::U32 => Value::U32(value as u32),
+                _ => Value::U64(value),
+            };
+            self.exprs.literal(value, storage_ty, None)
+        };
+        let mask = literal(u64::MAX >> (64 - member.width));
+        let offset = literal(member.offset as u64);
+
+        let self_param = ir::Parameter {
+            id: self.mono_ctx.ir.make_id(),
+            ty: self.types.pointer(self.types.named(item), !setter),
+        };
+        let storage = self.exprs.field(
+            self.exprs
+                .deref(self.exprs.local(self_param.id, self_param.ty, None), None),
+            bitfield.storage.id,
+            storage_ty,
+            None,
+        );
+
+        let mut params = vec![self_param];
+        let (body, return_type) = if setter {
+            let value_param = ir::Parameter {
+                id: self.mono_ctx.ir.make_id(),
+                ty: member.ty,
+            };
+            params.push(value_param);
+
+            // storage = (storage & ~(mask << offset)) | ((value as storage & mask) << offset)
+            let value = self.exprs.binary(
+                ast::BinOp::BitAnd,
+                self.exprs.cast(
+                    self.exprs.local(value_param.id, member.ty, None),
+                    storage_ty,
+                    None,
+                ),
+                mask,
+                storage_ty,
+                None,
+            );
+            let cleared = self.exprs.binary(
+                ast::BinOp::BitAnd,
+                storage,
+                self.exprs.unary(
+                    ast::UnOp::BitNot,
+                    self.exprs
+                        .binary(ast::BinOp::LShift, mask, offset, storage_ty, None),
+                    storage_ty,
+                    None,
+                ),
+                storage_ty,
+                None,
+            );
+            let updated = self.exprs.binary(
+                ast::BinOp::BitOr,
+                cleared,
+                self.exprs
+                    .binary(ast::BinOp::LShift, value, offset, storage_ty, None),
+                storage_ty,
+                None,
+            );
+
+            (self.exprs.assign(storage, updated, None), self.types.void())
+        } else {
+            let value = match member.ty {
+                // Shift the member to the top and back down to sign-extend it
+                ir::Ty::Builtin(kind) if kind.is_signed() => {
+                    let signed_ty = self
+                        .types
+                        .builtin(bitfield.storage_kind.to_signed().unwrap());
+                    let top = self.exprs.binary(
+                        ast::BinOp::LShift,
+                        storage,
+                        literal((storage_bits - member.offset - member.width) as u64),
+                        storage_ty,
+                        None,
+                    );
+                    self.exprs.binary(
+                        ast::BinOp::RShift,
+                        self.exprs.cast(top, signed_ty, None),
+                        literal((storage_bits - member.width) as u64),
+                        signed_ty,
+                        None,
+                    )
+                }
+                _ => self.exprs.binary(
+                    ast::BinOp::BitAnd,
+                    self.exprs
+                        .binary(ast::BinOp::RShift, storage, offset, storage_ty, None),
+                    mask,
+                    storage_ty,
+                    None,
+                ),
+            };
+
+            let value = match member.ty {
+                ir::Ty::Builtin(BuiltinType::Bool) => {
+                    self.exprs
+                        .binary(ast::BinOp::Neq, value, literal(0), member.ty, None)
+                }
+                _ => self.exprs.cast(value, member.ty, None),
+            };
+
+            (value, member.ty)
+        };
+
+        let statement = if setter {
+            ir::Statement::Expression(body)
+        } else {
+            ir::Statement::Expression(self.exprs.ret(body, None))
+        };
+        let function_body = FuncBody {
+            statements: [statement].alloc_on(self.mono_ctx.ir),
+            local_defs: [].alloc_on(self.mono_ctx.ir),
+            raw_body: Some(body),
+        };
+
+        let lower_aggregates = !self
+            .mono_ctx
+            .global_ctx
+            .c_dialect()
+            .has_aggregate_literals();
+        let elider = ZstElider::new(self.diag.fork(), self.mono_ctx.ir)
+            .with_aggregate_lowering(lower_aggregates);
+        let optimized = elider.elide_zst_func_body(function_body)?;
+
+        let accessor = self.mono_ctx.ir.make_symbol();
+        accessor.assign(ir::IRItem::Function(ir::Function {
+            name: None,
+            attributes: [ast::Attribute::Inline].alloc_on(self.mono_ctx.ir),
+            args: params.alloc_on(self.mono_ctx.ir),
+            return_type,
+            varargs: false,
+            body: OnceCell::from(optimized),
+        }));
+
+        self.mono_ctx
+            .caches
+            .bitfield_accessors
+            .insert(key, accessor);
+
+        Ok(accessor)
     }
 
     fn lower_method_call(
@@ -4202,9 +6082,11 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         name: &'ast str,
         args: &[ast::ExprP<'ast>],
         type_hint: Option<ir::TyP<'ir>>,
+        name_span: Option<Span>,
         ast_span: Option<Span>,
     ) -> Result<Option<ir::ExprP<'ir>>, AluminaError> {
-        let ir_self_arg = self.lower_expr(self_arg, None)?;
+        let mut ir_self_arg = self.lower_expr(self_arg, None)?;
+        self.query_members(ir_self_arg, name_span, name)?;
 
         // Special case for struct fields (they have precedence over methods in .name resolution)
         if let ir::Ty::Item(item) = ir_self_arg.ty.canonical_type() {
@@ -4220,6 +6102,18 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
 
         let canonical = ir_self_arg.ty.canonical_type();
 
+        if let ir::Ty::Item(item) = canonical {
+            if self.mono_ctx.bitfields.contains_key(item)
+                && !self.get_associated_fns(canonical)?.contains_key(name)
+            {
+                if let Some(result) =
+                    self.lower_bitfield_access(*item, ir_self_arg, name, args, ast_span)?
+                {
+                    return Ok(Some(result));
+                }
+            }
+        }
+
         if let Some(LangTypeKind::Dyn(ir::Ty::Tuple(protocols), dyn_ptr)) =
             self.mono_ctx.get_lang_type_kind(canonical)
         {
@@ -4230,17 +6124,55 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             }
         }
 
-        let method = self
-            .get_associated_fns(canonical)?
-            .get(name)
-            .copied()
-            .or(unified_fn)
-            .ok_or_else(|| {
-                self.diag.err(CodeErrorKind::MethodNotFound(
-                    name.into(),
-                    self.mono_ctx.type_name(canonical).unwrap(),
-                ))
-            })?;
+        // If the method is not found on the type itself, follow the `Deref` chain of wrapper
+        // types until one of them has it.
+        let mut deref_chain = Vec::new();
+        let method = loop {
+            let current = ir_self_arg.ty.canonical_type();
+            if let Some(method) = self.get_associated_fns(current)?.get(name).copied() {
+                break method;
+            }
+
+            if deref_chain.is_empty() {
+                if let Some(unified_fn) = unified_fn {
+                    break unified_fn;
+                }
+            }
+
+            match self.auto_deref(ir_self_arg, &mut deref_chain, ast_span)? {
+                Some(inner) => ir_self_arg = inner,
+                None => {
+                    let type_name = self.mono_ctx.type_name(canonical).unwrap();
+                    let hint = match self.mono_ctx.get_lang_type_kind(canonical) {
+                        Some(LangTypeKind::Result(inner, _))
+                            if self.get_associated_fns(inner)?.contains_key(name) =>
+                        {
+                            Some("the value is a `Result`, did you forget `?`")
+                        }
+                        Some(LangTypeKind::Option(inner))
+                            if self.get_associated_fns(inner)?.contains_key(name) =>
+                        {
+                            Some("the value is an `Option`, consider unwrapping it")
+                        }
+                        _ => None,
+                    };
+
+                    match hint {
+                        Some(hint) => bail!(
+                            self,
+                            CodeErrorKind::MethodNotFoundWithHint(
+                                name.into(),
+                                type_name,
+                                hint.to_string()
+                            )
+                        ),
+                        None => bail!(self, CodeErrorKind::MethodNotFound(name.into(), type_name)),
+                    }
+                }
+            }
+        };
+
+        self.record_name(name_span, name, method);
 
         let method = self.try_resolve_function(
             method,
@@ -4295,6 +6227,69 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         Ok(Some(self.call(callee, args, return_type, ast_span)?))
     }
 
+    /// Performs a single step of auto-dereferencing for method resolution. If the type of `expr`
+    /// implements the `Deref` protocol, returns the call to `deref` (a pointer to the wrapped
+    /// value), otherwise `None`.
+    fn auto_deref(
+        &mut self,
+        expr: ir::ExprP<'ir>,
+        chain: &mut Vec<ir::TyP<'ir>>,
+        ast_span: Option<Span>,
+    ) -> Result<Option<ir::ExprP<'ir>>, AluminaError> {
+        if self
+            .mono_ctx
+            .ast
+            .lang_item(LangItemKind::ProtoDeref)
+            .is_err()
+        {
+            return Ok(None);
+        }
+
+        let canonical = expr.ty.canonical_type();
+        let Some(deref_fn) = self.get_associated_fns(canonical)?.get("deref").copied() else {
+            return Ok(None);
+        };
+
+        if chain.contains(&canonical) {
+            let cycle = chain
+                .iter()
+                .copied()
+                .chain(once(canonical))
+                .map(|ty| self.mono_ctx.type_name(ty))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            bail!(self, CodeErrorKind::DerefCycle(cycle.join("` -> `")));
+        }
+
+        chain.push(canonical);
+        if chain.len() > MAX_DEREF_CHAIN {
+            bail!(self, CodeErrorKind::DerefChainTooLong(MAX_DEREF_CHAIN));
+        }
+
+        let func = self.try_resolve_function(deref_fn, None, Some(expr), Some(&[]), None, None)?;
+        let fun = func.get_function().with_backtrace(&self.diag)?;
+
+        let (self_ty, target_ptr) = match (fun.args, fun.return_type) {
+            ([self_arg], ret @ ir::Ty::Pointer(..)) => (self_arg.ty, ret),
+            _ => return Ok(None),
+        };
+
+        let bound =
+            self.monomorphize_lang_item(LangItemKind::ProtoDeref, [canonical, target_ptr])?;
+        let bound = self.types.named(bound);
+        if !matches!(
+            self.check_protocol_bound(bound, canonical)?,
+            BoundCheckResult::Matches
+        ) {
+            return Ok(None);
+        }
+
+        let callee = self.exprs.function(func, ast_span);
+        let arg = self.autoref(expr, self_ty, ast_span)?;
+
+        Ok(Some(self.call(callee, [arg], target_ptr, ast_span)?))
+    }
+
     fn resolve_ast_type(
         &mut self,
         ast_type: ast::TyP<'ast>,
@@ -4327,6 +6322,150 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         Ok(typ)
     }
 
+    /// Records an associated function or a method that was resolved during monomorphization
+    /// (for editor tooling outputs). `usage` is the span of the expression ending with the name.
+    fn record_name(&self, usage: Option<Span>, name: &str, item: ast::ItemP<'ast>) {
+        if let Some(ast::Item::Function(fun)) = item.try_get() {
+            self.mono_ctx
+                .global_ctx
+                .record_name(usage.map(|usage| NameRef {
+                    usage: usage.tail(name.len()),
+                    definition: fun.span,
+                    kind: SymbolKind::Method,
+                    mutable: false,
+                    generic: !fun.placeholders.is_empty(),
+                }));
+        }
+    }
+
+    /// Same as [Self::record_name], but for struct and union fields.
+    fn record_field_name(&self, usage: Option<Span>, name: &str, item: ir::IRItemP<'ir>) {
+        let Some(MonoKey(ast_item, _, _, _)) = self.mono_ctx.reverse_map.get(&item) else {
+            return;
+        };
+
+        if let Some(ast::Item::StructLike(s)) = ast_item.try_get() {
+            let field = s.fields.iter().find(|f| f.name == name);
+            self.mono_ctx
+                .global_ctx
+                .record_name(usage.map(|usage| NameRef {
+                    usage: usage.tail(name.len()),
+                    definition: field.and_then(|f| f.span),
+                    kind: SymbolKind::Field,
+                    mutable: true,
+                    generic: false,
+                }));
+        }
+    }
+
+    /// Fields of `obj` and the methods that can be called on it (following the `Deref`
+    /// chain, same as method resolution).
+    fn members(&mut self, obj: ir::ExprP<'ir>) -> Result<Vec<CompletionItem>, AluminaError> {
+        let mut items = Vec::new();
+        if let ir::Ty::Item(item) = obj.ty.canonical_type() {
+            if let ir::IRItem::StructLike(_) = item.get().with_backtrace(&self.diag)? {
+                for (field_name, field) in self.get_struct_field_map(item)?.iter() {
+                    items.push(CompletionItem {
+                        name: field_name.to_string(),
+                        kind: SymbolKind::Field,
+                        signature: format!(
+                            "{}: {}",
+                            field_name,
+                            self.mono_ctx.type_name(field.ty)?
+                        ),
+                    });
+                }
+            }
+        }
+
+        let mut obj = obj;
+        let mut deref_chain = Vec::new();
+        // Not every type has associated functions (e.g. closures), which is only an error
+        // once a method is actually called on it.
+        while let Ok(associated_fns) = self.get_associated_fns(obj.ty.canonical_type()) {
+            for (fn_name, item) in associated_fns.iter() {
+                if let Some(ast::Item::Function(fun)) = item.try_get() {
+                    if !fun.args.is_empty() {
+                        items.push(CompletionItem {
+                            name: fn_name.to_string(),
+                            kind: SymbolKind::Method,
+                            signature: PrettyPrinter::new(self.mono_ctx.ast).print_signature(fun),
+                        });
+                    }
+                }
+            }
+
+            match self.auto_deref(obj, &mut deref_chain, obj.span) {
+                Ok(Some(inner)) => obj = inner,
+                _ => break,
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Whether `obj` has a field or an associated function called `name` (cheaper than
+    /// [Self::members], as this is checked for every member access when renaming).
+    fn has_member(&mut self, obj: ir::ExprP<'ir>, name: &str) -> Result<bool, AluminaError> {
+        if let ir::Ty::Item(item) = obj.ty.canonical_type() {
+            if let ir::IRItem::StructLike(_) = item.get().with_backtrace(&self.diag)? {
+                if self.get_struct_field_map(item)?.contains_key(name) {
+                    return Ok(true);
+                }
+            }
+        }
+
+        let mut obj = obj;
+        let mut deref_chain = Vec::new();
+        while let Ok(associated_fns) = self.get_associated_fns(obj.ty.canonical_type()) {
+            if associated_fns.contains_key(name) {
+                return Ok(true);
+            }
+
+            match self.auto_deref(obj, &mut deref_chain, obj.span) {
+                Ok(Some(inner)) => obj = inner,
+                _ => break,
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Handles the editor queries for a member access `obj.name` (`usage` is the span of
+    /// the expression ending with the name): offers the members as completions if the
+    /// name is at the completion position and records a rename conflict if the type
+    /// already has a member with the new name.
+    fn query_members(
+        &mut self,
+        obj: ir::ExprP<'ir>,
+        usage: Option<Span>,
+        name: &str,
+    ) -> Result<(), AluminaError> {
+        let Some(name_span) = usage.map(|usage| usage.tail(name.len())) else {
+            return Ok(());
+        };
+
+        let global_ctx = self.mono_ctx.global_ctx.clone();
+        if let Some(position) = global_ctx
+            .completion_position()
+            .filter(|position| name_span.contains(position))
+        {
+            let prefix = name
+                .get(..position.start - name_span.start)
+                .unwrap_or_default();
+            let members = self.members(obj)?;
+            global_ctx.add_completions(prefix, members);
+        }
+
+        if let Some(new_name) = global_ctx.rename_to() {
+            if self.has_member(obj, &new_name)? {
+                global_ctx.record_rename_conflict(name_span);
+            }
+        }
+
+        Ok(())
+    }
+
     fn resolve_defered_func(
         &mut self,
         spec: &ast::Defered<'ast>,
@@ -4377,6 +6516,8 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             }
             ast::ExprKind::Defered(spec) => {
                 let func = self.resolve_defered_func(spec)?;
+                self.record_name(callee.span, spec.name, func);
+
                 let item =
                     self.try_resolve_function(func, None, None, Some(args), type_hint, None)?;
 
@@ -4384,6 +6525,8 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             }
             ast::ExprKind::Fn(ast::FnKind::Defered(spec), generic_args) => {
                 let func = self.resolve_defered_func(spec)?;
+                self.record_name(callee.span, spec.name, func);
+
                 let item = self.try_resolve_function(
                     func,
                     *generic_args,
@@ -4399,7 +6542,15 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                 // Methods are resolved in the following order - field has precedence, then associated
                 // functions, then free functions with UFCS. We never want UFCS to shadow native fields
                 // and methods.
-                match self.lower_method_call(e, *unified_fn, field, args, type_hint, ast_span)? {
+                match self.lower_method_call(
+                    e,
+                    *unified_fn,
+                    field,
+                    args,
+                    type_hint,
+                    callee.span,
+                    ast_span,
+                )? {
                     Some(result) => return Ok(result),
                     None => self.lower_expr(callee, None)?,
                 }
@@ -4558,7 +6709,25 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                     // The cell may be empty at this point if we are dealing with recursive references
                     // In this case, we will just return the item as is, but it will not
                     // be populated until the top-level item is finished.
-                    Entry::Occupied(entry) => self.types.named(entry.get()),
+                    Entry::Occupied(entry) => {
+                        let closure = *entry.get();
+                        // An unrolled `for const` body lowers the same closure once per element,
+                        // but there is only one closure type to go around.
+                        if self.unrolling {
+                            if let Ok(existing) = closure.get_closure() {
+                                if existing
+                                    .data
+                                    .fields
+                                    .iter()
+                                    .map(|f| f.ty)
+                                    .ne(fields.iter().map(|f| f.ty))
+                                {
+                                    bail!(self, CodeErrorKind::ClosureCaptureInStaticFor);
+                                }
+                            }
+                        }
+                        self.types.named(closure)
+                    }
                     Entry::Vacant(entry) => {
                         let closure = self.mono_ctx.ir.make_symbol();
                         self.mono_ctx.reverse_map.insert(closure, key.clone());
@@ -4668,10 +6837,12 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         ast_span: Option<Span>,
     ) -> Result<ir::ExprP<'ir>, AluminaError> {
         let obj = self.lower_expr(obj, None)?;
+        self.query_members(obj, ast_span, field)?;
 
         let result = match obj.ty.canonical_type() {
             ir::Ty::Item(item) => {
                 let field_map = self.get_struct_field_map(item)?;
+                self.record_field_name(ast_span, field, item);
                 let field = field_map.get(field).ok_or_else(|| {
                     self.diag
                         .err(CodeErrorKind::UnresolvedItem(field.to_string()))
@@ -4901,7 +7072,11 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         }
     }
 
-    fn generate_defer_epilogue(&self, statements: &mut Vec<ir::Statement<'ir>>) {
+    fn generate_defer_epilogue(
+        &self,
+        statements: &mut Vec<ir::Statement<'ir>>,
+        exit: Option<ir::Statement<'ir>>,
+    ) {
         let defer_context = self.defer_context.as_ref().unwrap();
 
         statements.push(ir::Statement::Label(defer_context.return_label));
@@ -4923,6 +7098,7 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
                 ),
             ));
         }
+        statements.extend(exit);
         statements.push(ir::Statement::Expression(
             self.exprs.ret(
                 self.exprs
@@ -5209,6 +7385,9 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             }
             ast::ExprKind::Cast(expr, typ) => self.lower_cast(expr, typ, type_hint, expr.span),
             ast::ExprKind::Loop(body) => self.lower_loop(body, type_hint, expr.span),
+            ast::ExprKind::StaticFor(value, index, elem, body) => {
+                self.lower_static_for(value, index, elem, body, type_hint, expr.span)
+            }
             ast::ExprKind::Binary(op, lhs, rhs) => {
                 self.lower_binary(*op, lhs, rhs, type_hint, expr.span)
             }
@@ -5253,6 +7432,9 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
             ast::ExprKind::TypeCheck(value, typ) => {
                 self.lower_typecheck(value, typ, type_hint, expr.span)
             }
+            ast::ExprKind::TypePredicate(typ, bound) => {
+                self.lower_type_predicate(typ, bound, type_hint, expr.span)
+            }
             ast::ExprKind::StaticIf(cond, then, els) => {
                 self.lower_static_if(cond, then, els, type_hint, expr.span)
             }
@@ -5326,6 +7508,98 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         ))
     }
 
+    fn stack_alloc(
+        &self,
+        ty: ir::TyP<'ir>,
+        len: ir::ExprP<'ir>,
+        span: Option<Span>,
+    ) -> Result<ir::ExprP<'ir>, AluminaError> {
+        let usize_ty = self.types.builtin(BuiltinType::USize);
+        if len.ty != usize_ty {
+            return Err(mismatch!(self, "usize", len.ty));
+        }
+
+        if !self.loop_contexts.is_empty() && !self.tentative {
+            self.diag.warn(CodeErrorKind::StackAllocInALoop);
+        }
+
+        let size = self.exprs.binary(
+            ast::BinOp::Mul,
+            len,
+            self.size_of(ty, span)?,
+            usize_ty,
+            span,
+        );
+        let ptr_ty = self.types.pointer(ty, false);
+
+        let alloca = self.exprs.call(
+            self.exprs.codegen_intrinsic(
+                IntrinsicValueKind::FunctionLike("__builtin_alloca"),
+                self.types.function([usize_ty], ptr_ty),
+                span,
+            ),
+            [size],
+            ptr_ty,
+            span,
+        );
+
+        // There is no stack during constant evaluation, so the memory is allocated on the
+        // const heap instead (`const_cond` lets the const branch be dropped before codegen).
+        // Only one of the branches is evaluated, so `len` can appear in both.
+        Ok(self.exprs.if_then(
+            self.in_const_context(span)?,
+            self.const_alloc(ty, len, span)?,
+            alloca,
+            Some(false),
+            span,
+        ))
+    }
+
+    fn copy(
+        &self,
+        ty: ir::TyP<'ir>,
+        src: ir::ExprP<'ir>,
+        dst: ir::ExprP<'ir>,
+        count: ir::ExprP<'ir>,
+        overlapping: bool,
+        span: Option<Span>,
+    ) -> Result<ir::ExprP<'ir>, AluminaError> {
+        let usize_ty = self.types.builtin(BuiltinType::USize);
+        let dst_ty = self.types.pointer(ty, false);
+
+        if !matches!(src.ty, ir::Ty::Pointer(inner, _) if *inner == ty) {
+            return Err(mismatch!(self, self.types.pointer(ty, true), src.ty));
+        }
+        if dst.ty != dst_ty {
+            return Err(mismatch!(self, dst_ty, dst.ty));
+        }
+        if count.ty != usize_ty {
+            return Err(mismatch!(self, "usize", count.ty));
+        }
+
+        // Still evaluate the arguments for their side effects
+        if ty.is_zero_sized() {
+            return Ok(self.exprs.block(
+                [src, dst, count].into_iter().map(ir::Statement::Expression),
+                self.exprs
+                    .void(self.types.void(), ir::ValueType::RValue, span),
+                span,
+            ));
+        }
+
+        let fn_type = self
+            .types
+            .function([dst_ty, src.ty, usize_ty], self.types.void());
+
+        Ok(self.exprs.call(
+            self.exprs
+                .codegen_intrinsic(IntrinsicValueKind::MemCopy(overlapping), fn_type, span),
+            [dst, src, count],
+            self.types.void(),
+            span,
+        ))
+    }
+
     fn type_id(
         &self,
         ty: ir::TyP<'ir>,
@@ -5361,7 +7635,7 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
 
         Err(self.diag.err(CodeErrorKind::TypeMismatch(
             "array".to_string(),
-            format!("{:?}", ty),
+            self.mono_ctx.type_name(ty)?,
         )))
     }
 
@@ -5461,13 +7735,82 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
     fn asm(
         &self,
         assembly: ir::ExprP<'ir>,
+        clobbers: &[ir::ExprP<'ir>],
         span: Option<Span>,
     ) -> Result<ir::ExprP<'ir>, AluminaError> {
         let assembly = self.get_const_string(assembly)?;
 
-        Ok(self
-            .exprs
-            .codegen_intrinsic(IntrinsicValueKind::Asm(assembly), self.types.void(), span))
+        // The C compiler would only complain about the generated code, so invalid registers
+        // are reported here, at the argument that names them.
+        let arch = self.mono_ctx.global_ctx.cfg("target_arch").flatten();
+        let clobbers = clobbers
+            .iter()
+            .map(|clobber| {
+                let _guard = self.diag.push_span(clobber.span);
+                let name = self.get_const_string(*clobber)?;
+                let valid = matches!(name, "memory" | "cc")
+                    || arch
+                        .as_deref()
+                        .and_then(|arch| is_register(arch, name))
+                        .unwrap_or(true);
+                if !valid {
+                    bail!(
+                        self,
+                        CodeErrorKind::InvalidAsmClobber(
+                            name.to_string(),
+                            arch.clone().unwrap_or_default()
+                        )
+                    );
+                }
+
+                Ok(name)
+            })
+            .collect::<Result<Vec<_>, AluminaError>>()?;
+
+        Ok(self.exprs.codegen_intrinsic(
+            IntrinsicValueKind::Asm(assembly, self.mono_ctx.ir.arena.alloc_slice_copy(&clobbers)),
+            self.types.void(),
+            span,
+        ))
+    }
+
+    fn c_inline(
+        &self,
+        code: ir::ExprP<'ir>,
+        args: &[ir::ExprP<'ir>],
+        span: Option<Span>,
+    ) -> Result<ir::ExprP<'ir>, AluminaError> {
+        if !self.mono_ctx.global_ctx.has_option("c-inline") {
+            bail!(self, CodeErrorKind::CInlineNotEnabled);
+        }
+
+        let code = self.get_const_string(code)?;
+
+        // Zero-sized values are elided from calls, so they would shift the placeholders
+        if let Some(idx) = args.iter().position(|arg| arg.ty.is_zero_sized()) {
+            bail!(
+                self,
+                CodeErrorKind::InvalidCInline(format!("argument `${}` is zero-sized", idx))
+            );
+        }
+
+        if let Err(placeholder) = expand_c_inline(code, &vec![String::new(); args.len()]) {
+            bail!(
+                self,
+                CodeErrorKind::InvalidCInline(format!("no argument for `{}`", placeholder))
+            );
+        }
+
+        let arg_types = args.iter().map(|arg| arg.ty).collect::<Vec<_>>();
+        let fn_type = self.types.function(arg_types, self.types.void());
+
+        Ok(self.exprs.call(
+            self.exprs
+                .codegen_intrinsic(IntrinsicValueKind::CInline(code), fn_type, span),
+            args.iter().copied(),
+            self.types.void(),
+            span,
+        ))
     }
 
     fn codegen_const(
@@ -5515,7 +7858,7 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
         } else {
             Err(self.diag.err(CodeErrorKind::TypeMismatch(
                 "pointer".to_string(),
-                format!("{:?}", ret_ty),
+                self.mono_ctx.type_name(ret_ty)?,
             )))
         }
     }
@@ -5773,4 +8116,80 @@ impl<'a, 'ast, 'ir> Monomorphizer<'a, 'ast, 'ir> {
 
         self.array_of(enum_variant_new_func.return_type, exprs, None)
     }
+
+    fn enum_member_values(&self, e: &ir::Enum<'ir>) -> Result<Vec<Value<'ir>>, AluminaError> {
+        let mut evaluator = ir::const_eval::ConstEvaluator::new(
+            self.diag.fork(),
+            self.mono_ctx.malloc_bag.clone(),
+            self.mono_ctx.ir,
+            [],
+        );
+
+        e.members
+            .iter()
+            .map(|m| evaluator.const_eval(m.value))
+            .collect()
+    }
+
+    /// Checks whether an integer is equal to the value of one of the enum variants.
+    fn is_enum_value(
+        &mut self,
+        typ: ir::TyP<'ir>,
+        value: ir::ExprP<'ir>,
+        span: Option<Span>,
+    ) -> Result<ir::ExprP<'ir>, AluminaError> {
+        let e = match typ {
+            ir::Ty::Item(item) => item.get_enum().with_backtrace(&self.diag)?,
+            _ => ice!(self.diag, "enum expected"),
+        };
+
+        if !matches!(value.ty, ir::Ty::Builtin(b) if b.is_integer()) {
+            return Err(mismatch!(self, "integer", value.ty));
+        }
+
+        // Variants whose value cannot be represented in the type of the argument can
+        // never match, so we skip them instead of comparing against a truncated value.
+        let mut candidates = Vec::new();
+        for member_value in self.enum_member_values(e)? {
+            let Some((sign, magnitude)) = member_value.as_int() else {
+                continue;
+            };
+            if let Some(v) = Self::int_literal_value(sign, magnitude, value.ty) {
+                if !candidates.contains(&v) {
+                    candidates.push(v);
+                }
+            }
+        }
+
+        let bool_ty = self.types.builtin(BuiltinType::Bool);
+        let local = self.mono_ctx.ir.make_id();
+        self.local_defs.push(ir::LocalDef {
+            id: local,
+            typ: value.ty,
+        });
+        self.local_types.insert(local, value.ty);
+
+        let local_expr = self.exprs.local(local, value.ty, span);
+        let result = candidates.into_iter().fold(
+            self.exprs.literal(Value::Bool(false), bool_ty, span),
+            |acc, v| {
+                let eq = self.exprs.binary(
+                    ast::BinOp::Eq,
+                    local_expr,
+                    self.exprs.literal(v, value.ty, span),
+                    bool_ty,
+                    span,
+                );
+                self.exprs.binary(ast::BinOp::Or, acc, eq, bool_ty, span)
+            },
+        );
+
+        Ok(self.exprs.block(
+            [ir::Statement::Expression(
+                self.exprs.assign(local_expr, value, span),
+            )],
+            result,
+            span,
+        ))
+    }
 }
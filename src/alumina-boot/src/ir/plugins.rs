@@ -0,0 +1,24 @@
+//! Custom IR passes (`plugins` feature)
+//!
+//! Passes are registered with [`Compiler::add_ir_pass`](crate::compiler::Compiler::add_ir_pass)
+//! by the program that embeds the compiler and run in the order they were registered. Each
+//! pass gets the whole program after monomorphization and dead code elimination, in the
+//! order the items will be emitted, and can add, remove or reorder items (e.g. replace a
+//! function with an instrumented copy built with [`IrCtx`]) before the C code is generated.
+use crate::common::AluminaError;
+use crate::global_ctx::GlobalCtx;
+use crate::ir::{IRItemP, IrCtx};
+
+pub struct PassCtx<'a, 'ir> {
+    /// For options (`-Z`), `cfg` values and diagnostics
+    pub global_ctx: &'a GlobalCtx,
+    pub ir: &'ir IrCtx<'ir>,
+}
+
+pub trait IrPass {
+    fn run<'ir>(
+        &mut self,
+        ctx: &PassCtx<'_, 'ir>,
+        items: &mut Vec<IRItemP<'ir>>,
+    ) -> Result<(), AluminaError>;
+}
@@ -0,0 +1,361 @@
+use crate::ast::BinOp;
+use crate::common::{AluminaError, CodeErrorKind, HashMap};
+use crate::diagnostics::DiagnosticsStack;
+use crate::intrinsics::IntrinsicValueKind;
+use crate::ir::const_eval::Value;
+use crate::ir::{ExprKind, ExprP, ExpressionVisitor, IrId, Statement, Ty};
+
+/// Where a pointer value provably comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    Null,
+    Dangling,
+    /// Pointer to the `offset`-th element of an object with `len` elements
+    Object {
+        offset: i128,
+        len: i128,
+    },
+}
+
+/// Pointer locals with a known origin at a point in the function. `None` means that
+/// the point is unreachable.
+type State = Option<HashMap<IrId, Origin>>;
+
+fn meet(a: State, b: State) -> State {
+    match (a, b) {
+        (None, b) => b,
+        (a, None) => a,
+        (Some(a), Some(b)) => Some(
+            a.into_iter()
+                .filter(|(id, origin)| b.get(id) == Some(origin))
+                .collect(),
+        ),
+    }
+}
+
+fn int_literal(expr: ExprP<'_>) -> Option<i128> {
+    match expr.kind {
+        ExprKind::Literal(Value::ISize(v)) => Some(v as i128),
+        ExprKind::Literal(Value::USize(v)) => Some(v as i128),
+        ExprKind::Literal(Value::I64(v)) => Some(v as i128),
+        ExprKind::Literal(Value::U64(v)) => Some(v as i128),
+        ExprKind::Literal(Value::I32(v)) => Some(v as i128),
+        ExprKind::Literal(Value::U32(v)) => Some(v as i128),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct GotoCounter {
+    counts: HashMap<IrId, usize>,
+}
+
+impl<'ir> ExpressionVisitor<'ir> for GotoCounter {
+    fn visit_goto(&mut self, label: IrId) -> Result<(), AluminaError> {
+        *self.counts.entry(label).or_default() += 1;
+        Ok(())
+    }
+}
+
+/// Lints for misuse of pointers with a provably bad value: dereferences of pointers that
+/// come from `null` or `std::mem::dangling()` without an intervening comparison, and
+/// pointer arithmetic with constant offsets that leaves the bounds of a local object.
+///
+/// Only pointers stored in locals (or used directly) are tracked. Taking the address of
+/// a pointer local or comparing it forgets what is known about it. At labels that can
+/// be reached from a jump that has not been seen yet (loops), everything is forgotten.
+pub struct PointerMisuseChecker {
+    diag: DiagnosticsStack,
+    state: State,
+    labels: HashMap<IrId, (usize, State)>,
+    goto_counts: HashMap<IrId, usize>,
+}
+
+impl PointerMisuseChecker {
+    pub fn new(diag: DiagnosticsStack) -> Self {
+        Self {
+            diag,
+            state: Some(HashMap::default()),
+            labels: HashMap::default(),
+            goto_counts: HashMap::default(),
+        }
+    }
+
+    pub fn check(mut self, body: ExprP<'_>) -> Result<(), AluminaError> {
+        let mut counter = GotoCounter::default();
+        counter.visit_expr(body)?;
+        self.goto_counts = counter.counts;
+
+        self.visit_expr(body);
+        Ok(())
+    }
+
+    fn warn(&self, expr: ExprP<'_>, kind: CodeErrorKind) {
+        let _guard = self.diag.push_span(expr.span);
+        self.diag.warn(kind);
+    }
+
+    fn forget(&mut self, expr: ExprP<'_>) {
+        if let (ExprKind::Local(id), Some(state)) = (&expr.kind, &mut self.state) {
+            state.remove(id);
+        }
+    }
+
+    fn origin(&self, expr: ExprP<'_>) -> Option<Origin> {
+        match expr.kind {
+            ExprKind::Literal(Value::USize(0)) if matches!(expr.ty, Ty::Pointer(_, _)) => {
+                Some(Origin::Null)
+            }
+            ExprKind::Intrinsic(IntrinsicValueKind::Dangling(_)) => Some(Origin::Dangling),
+            ExprKind::Local(id) => self.state.as_ref().and_then(|s| s.get(&id).copied()),
+            ExprKind::Ref(inner) => match inner.kind {
+                ExprKind::Index(base, index) => match (base.ty, int_literal(index)) {
+                    (Ty::Array(_, len), Some(offset)) => Some(Origin::Object {
+                        offset,
+                        len: *len as i128,
+                    }),
+                    _ => None,
+                },
+                ExprKind::Local(_) | ExprKind::Static(_) => {
+                    Some(Origin::Object { offset: 0, len: 1 })
+                }
+                _ => None,
+            },
+            ExprKind::Cast(inner) => match (self.origin(inner)?, inner.ty, expr.ty) {
+                (origin @ (Origin::Null | Origin::Dangling), _, Ty::Pointer(_, _)) => Some(origin),
+                (
+                    Origin::Object { offset: 0, len: 1 },
+                    Ty::Pointer(Ty::Array(elem, len), _),
+                    Ty::Pointer(target, _),
+                ) if elem == target => Some(Origin::Object {
+                    offset: 0,
+                    len: *len as i128,
+                }),
+                (origin, Ty::Pointer(from, _), Ty::Pointer(to, _)) if from == to => Some(origin),
+                _ => None,
+            },
+            ExprKind::Binary(op @ (BinOp::Plus | BinOp::Minus), lhs, rhs) => {
+                match (self.origin(lhs)?, int_literal(rhs)?) {
+                    (Origin::Object { offset, len }, delta) => Some(Origin::Object {
+                        offset: if op == BinOp::Plus {
+                            offset + delta
+                        } else {
+                            offset - delta
+                        },
+                        len,
+                    }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks pointer arithmetic `base +/- offset`. Only reports the first step that goes
+    /// out of bounds.
+    fn check_bounds(&self, expr: ExprP<'_>, base: ExprP<'_>) {
+        let in_bounds = |origin: Option<Origin>| match origin {
+            // One past the end is fine
+            Some(Origin::Object { offset, len }) => offset >= 0 && offset <= len,
+            _ => true,
+        };
+
+        if let Some(Origin::Object { offset, len }) = self.origin(expr) {
+            if in_bounds(self.origin(base)) && (offset < 0 || offset > len) {
+                self.warn(expr, CodeErrorKind::PointerOutOfBounds(offset, len));
+            }
+        }
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement<'_>) {
+        match stmt {
+            Statement::Expression(expr) => self.visit_expr(expr),
+            Statement::Label(id) => {
+                let total = self.goto_counts.get(id).copied().unwrap_or(0);
+                self.state = match self.labels.remove(id) {
+                    Some((seen, incoming)) if seen == total => meet(self.state.take(), incoming),
+                    None if total == 0 => self.state.take(),
+                    // There is a backward jump to this label
+                    _ => Some(HashMap::default()),
+                };
+            }
+        }
+    }
+
+    /// Visits an expression whose address is taken or that is assigned to. Dereferences
+    /// here are just address computations (e.g. `&(*ptr).field`).
+    fn visit_place(&mut self, expr: ExprP<'_>) {
+        match expr.kind {
+            ExprKind::Local(_) => self.forget(expr),
+            ExprKind::Field(inner, _) | ExprKind::TupleIndex(inner, _) => self.visit_place(inner),
+            ExprKind::Index(inner, index) => {
+                self.visit_expr(index);
+                self.visit_place(inner);
+            }
+            ExprKind::Deref(inner) => self.visit_expr(inner),
+            _ => self.visit_expr(expr),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: ExprP<'_>) {
+        match expr.kind {
+            ExprKind::Block(statements, ret) => {
+                for stmt in statements {
+                    self.visit_statement(stmt);
+                }
+                self.visit_expr(ret);
+            }
+            ExprKind::Binary(
+                BinOp::Eq | BinOp::Neq | BinOp::Lt | BinOp::LEq | BinOp::Gt | BinOp::GEq,
+                lhs,
+                rhs,
+            ) => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+                // Comparing the pointer counts as checking it
+                self.forget(lhs);
+                self.forget(rhs);
+            }
+            ExprKind::Binary(BinOp::And | BinOp::Or, lhs, rhs) => {
+                self.visit_expr(lhs);
+                let state = self.state.clone();
+                self.visit_expr(rhs);
+                self.state = meet(state, self.state.take());
+            }
+            ExprKind::Binary(_, lhs, rhs) => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+                self.check_bounds(expr, lhs);
+            }
+            ExprKind::Index(lhs, rhs) => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            ExprKind::AssignOp(op, lhs, rhs) => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+
+                let updated = match (op, self.origin(lhs), int_literal(rhs)) {
+                    (BinOp::Plus, Some(Origin::Object { offset, len }), Some(delta)) => {
+                        Some(Origin::Object {
+                            offset: offset + delta,
+                            len,
+                        })
+                    }
+                    (BinOp::Minus, Some(Origin::Object { offset, len }), Some(delta)) => {
+                        Some(Origin::Object {
+                            offset: offset - delta,
+                            len,
+                        })
+                    }
+                    _ => None,
+                };
+
+                if let Some(Origin::Object { offset, len }) = updated {
+                    if offset < 0 || offset > len {
+                        self.warn(expr, CodeErrorKind::PointerOutOfBounds(offset, len));
+                    }
+                }
+
+                if let (ExprKind::Local(id), Some(origin)) = (&lhs.kind, updated) {
+                    if let Some(state) = &mut self.state {
+                        state.insert(*id, origin);
+                    }
+                } else {
+                    self.forget(lhs);
+                }
+            }
+            ExprKind::Assign(lhs, rhs) => {
+                self.visit_expr(rhs);
+                match (&lhs.kind, self.origin(rhs)) {
+                    (ExprKind::Local(id), Some(origin)) => {
+                        if let Some(state) = &mut self.state {
+                            state.insert(*id, origin);
+                        }
+                    }
+                    _ => self.visit_place(lhs),
+                }
+            }
+            ExprKind::Call(callee, args) => {
+                self.visit_expr(callee);
+                for arg in args {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprKind::Ref(inner) => self.visit_place(inner),
+            ExprKind::Deref(inner) => {
+                self.visit_expr(inner);
+                match self.origin(inner) {
+                    Some(Origin::Null) => {
+                        self.warn(expr, CodeErrorKind::NullPointerDereference);
+                    }
+                    Some(Origin::Dangling) if !expr.ty.is_zero_sized() => {
+                        self.warn(expr, CodeErrorKind::DanglingPointerDereference);
+                    }
+                    _ => {}
+                }
+            }
+            ExprKind::Unary(_, inner)
+            | ExprKind::Field(inner, _)
+            | ExprKind::TupleIndex(inner, _)
+            | ExprKind::Cast(inner) => self.visit_expr(inner),
+            ExprKind::Return(inner) => {
+                self.visit_expr(inner);
+                self.state = None;
+            }
+            ExprKind::Goto(label) => {
+                let state = self.state.take();
+                let (seen, incoming) = match self.labels.remove(&label) {
+                    Some((seen, existing)) => (seen + 1, meet(existing, state)),
+                    None => (1, state),
+                };
+                self.labels.insert(label, (seen, incoming));
+            }
+            ExprKind::If(cond, then, els, const_cond) => match const_cond {
+                Some(true) => self.visit_expr(then),
+                Some(false) => self.visit_expr(els),
+                None => {
+                    self.visit_expr(cond);
+                    let state = self.state.clone();
+                    self.visit_expr(then);
+                    let then_state = std::mem::replace(&mut self.state, state);
+                    self.visit_expr(els);
+                    self.state = meet(then_state, self.state.take());
+                }
+            },
+            ExprKind::Intrinsic(ref kind) => match kind {
+                IntrinsicValueKind::ConstPanic(inner)
+                | IntrinsicValueKind::ConstWrite(inner, _)
+                | IntrinsicValueKind::ConstAlloc(_, inner)
+                | IntrinsicValueKind::ConstFree(inner) => self.visit_expr(inner),
+                _ => {}
+            },
+            ExprKind::Array(elems) => {
+                for elem in elems {
+                    self.visit_expr(elem);
+                }
+            }
+            ExprKind::Tuple(inits) => {
+                for init in inits {
+                    self.visit_expr(init.value);
+                }
+            }
+            ExprKind::Struct(inits) => {
+                for init in inits {
+                    self.visit_expr(init.value);
+                }
+            }
+            ExprKind::Unreachable => self.state = None,
+            ExprKind::Local(_)
+            | ExprKind::Fn(_)
+            | ExprKind::Static(_)
+            | ExprKind::Const(_)
+            | ExprKind::Literal(_)
+            | ExprKind::Void => {}
+        }
+
+        if expr.diverges() {
+            self.state = None;
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::ast::Span;
+use crate::common::{AluminaError, CodeErrorBuilder};
+use crate::ir::mono::MonoCtx;
+use crate::ir::{ExprKind, ExprP, ExpressionVisitor, IRItem, IRItemP, Statement};
+
+use super::default_visit_expr;
+
+/// Finds the innermost expression at a given source position and describes its type
+/// (and the item it refers to, if it is a name).
+///
+/// Generic functions can be monomorphized multiple times, so the same position can
+/// have multiple different types.
+pub struct TypeAtQuery<'a, 'ast, 'ir> {
+    mono_ctx: &'a MonoCtx<'ast, 'ir>,
+    position: Span,
+    innermost: Option<Span>,
+    results: Vec<String>,
+}
+
+impl<'a, 'ast, 'ir> TypeAtQuery<'a, 'ast, 'ir> {
+    pub fn new(mono_ctx: &'a MonoCtx<'ast, 'ir>, position: Span) -> Self {
+        Self {
+            mono_ctx,
+            position,
+            innermost: None,
+            results: Vec::new(),
+        }
+    }
+
+    pub fn visit_item(&mut self, item: IRItemP<'ir>) -> Result<(), AluminaError> {
+        match item.get().with_no_span()? {
+            IRItem::Function(f) => {
+                if let Some(body) = f.body.get() {
+                    for stmt in body.statements {
+                        if let Statement::Expression(e) = stmt {
+                            self.visit_expr(e)?;
+                        }
+                    }
+                }
+            }
+            IRItem::Static(s) => {
+                if let Some(init) = s.init {
+                    self.visit_expr(init)?;
+                }
+            }
+            IRItem::Const(c) => self.visit_expr(c.init)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn into_results(self) -> Vec<String> {
+        self.results
+    }
+
+    fn describe(&self, expr: ExprP<'ir>) -> Result<String, AluminaError> {
+        let type_name = self.mono_ctx.type_name(expr.ty)?;
+        let item = match expr.kind {
+            ExprKind::Fn(item) => match item.get() {
+                Ok(IRItem::Function(f)) => f.name.map(|n| format!("fn {}", n)),
+                _ => None,
+            },
+            ExprKind::Static(item) => match item.get() {
+                Ok(IRItem::Static(s)) => s.name.map(|n| format!("static {}", n)),
+                _ => None,
+            },
+            ExprKind::Const(item) => match item.get() {
+                Ok(IRItem::Const(c)) => c.name.map(|n| format!("const {}", n)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        Ok(match item {
+            Some(item) => format!("{}: {}", item, type_name),
+            None => type_name,
+        })
+    }
+}
+
+impl<'ir> ExpressionVisitor<'ir> for TypeAtQuery<'_, '_, 'ir> {
+    fn visit_expr(&mut self, expr: ExprP<'ir>) -> Result<(), AluminaError> {
+        if let Some(span) = expr.span {
+            if span.contains(&self.position) {
+                match self.innermost {
+                    Some(innermost) if innermost.len() < span.len() => {}
+                    Some(innermost) if innermost.len() == span.len() => {
+                        let description = self.describe(expr)?;
+                        if !self.results.contains(&description) {
+                            self.results.push(description);
+                        }
+                    }
+                    _ => {
+                        self.innermost = Some(span);
+                        self.results = vec![self.describe(expr)?];
+                    }
+                }
+            }
+        }
+
+        default_visit_expr(self, expr)
+    }
+}
@@ -0,0 +1,113 @@
+use crate::ast;
+use crate::common::{AluminaError, CodeErrorBuilder, HashMap};
+use crate::ir::mono::{item_name, MonoCtx, MonoKey};
+use crate::ir::{ExprP, ExpressionVisitor, IRItem, IRItemP, Statement};
+
+use super::default_visit_expr;
+
+use std::fmt::Write;
+
+/// How many of the largest instantiations are listed for each generic item.
+const TOP_INSTANTIATIONS: usize = 5;
+
+struct NodeCounter {
+    count: usize,
+}
+
+impl<'ir> ExpressionVisitor<'ir> for NodeCounter {
+    fn visit_expr(&mut self, expr: ExprP<'ir>) -> Result<(), AluminaError> {
+        self.count += 1;
+        default_visit_expr(self, expr)
+    }
+}
+
+fn count_nodes(item: IRItemP<'_>) -> Result<usize, AluminaError> {
+    let mut counter = NodeCounter { count: 0 };
+    match item.get().with_no_span()? {
+        IRItem::Function(f) => {
+            if let Some(body) = f.body.get() {
+                for stmt in body.statements {
+                    counter.count += 1;
+                    if let Statement::Expression(e) = stmt {
+                        counter.visit_expr(e)?;
+                    }
+                }
+            }
+        }
+        IRItem::Static(s) => {
+            if let Some(init) = s.init {
+                counter.visit_expr(init)?;
+            }
+        }
+        IRItem::Const(c) => counter.visit_expr(c.init)?,
+        _ => {}
+    }
+
+    Ok(counter.count)
+}
+
+struct Instantiation {
+    args: String,
+    nodes: usize,
+}
+
+/// Per generic item statistics of the monomorphization (`-Zmono-stats`): the number of
+/// distinct instantiations, the total number of IR nodes generated for them and the
+/// argument combinations that produced the most code.
+///
+/// Returns one line per generic item, sorted by the total number of IR nodes.
+pub fn mono_stats(mono_ctx: &MonoCtx<'_, '_>) -> Result<Vec<String>, AluminaError> {
+    let mut groups: HashMap<ast::ItemP<'_>, Vec<Instantiation>> = HashMap::default();
+
+    for (MonoKey(ast_item, args, _, tentative), item) in mono_ctx.instantiations() {
+        if *tentative || args.is_empty() {
+            continue;
+        }
+
+        let args = args
+            .iter()
+            .map(|arg| mono_ctx.type_name(*arg))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(", ");
+
+        groups.entry(*ast_item).or_default().push(Instantiation {
+            args,
+            nodes: count_nodes(item)?,
+        });
+    }
+
+    let mut groups: Vec<_> = groups
+        .into_iter()
+        .map(|(item, mut instantiations)| {
+            instantiations.sort_by(|a, b| b.nodes.cmp(&a.nodes).then_with(|| a.args.cmp(&b.args)));
+            let total: usize = instantiations.iter().map(|i| i.nodes).sum();
+            (item_name(item), total, instantiations)
+        })
+        .collect();
+
+    groups.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    let mut lines = Vec::new();
+    for (name, total, instantiations) in groups {
+        let mut line = format!(
+            "mono stats: `{}` has {} instantiation(s), {} IR nodes",
+            name,
+            instantiations.len(),
+            total
+        );
+
+        for (idx, instantiation) in instantiations.iter().take(TOP_INSTANTIATIONS).enumerate() {
+            let _ = write!(
+                line,
+                "{} <{}> ({} nodes)",
+                if idx == 0 { "; top:" } else { "," },
+                instantiation.args,
+                instantiation.nodes
+            );
+        }
+
+        lines.push(line);
+    }
+
+    Ok(lines)
+}
@@ -0,0 +1,1423 @@
+//! Textual form of the IR (`--emit ir`) and a parser for it.
+//!
+//! The format is meant for looking at what the optimizations did and for testing the IR
+//! passes on hand-written snippets (see `--ir-test`), so it only covers what function
+//! bodies need after monomorphization:
+//!
+//! ```text
+//! struct @Point { $1: i32, $2: i32 }
+//!
+//! fn @main::norm($3: &@Point) -> i32 {
+//!     let $4: i32;
+//!     $4 = ((*$3).$1 * (*$3).$1);
+//!     if ($4 > 100i32) then {
+//!         return 100i32;
+//!     } else void;
+//!     $5:
+//!     return ($4 + ((*$3).$2 * (*$3).$2));
+//! }
+//! ```
+//!
+//! Locals, fields and labels are all named by their IR id (`$N`), items by `@name` (or
+//! `@"name"` if it is not a plain path). Binary operators are always parenthesized and
+//! integer and float literals carry their type as a suffix, so the types of all the
+//! expressions can be reconstructed when parsing. Spans, attributes and static initializers
+//! are not included, intrinsics and literals that cannot be written as a number or a
+//! boolean are printed as `unsupported "..."`, which the parser rejects.
+use crate::ast::{BinOp, BuiltinType, UnOp};
+use crate::common::{AluminaError, ArenaAllocatable, CodeErrorBuilder, HashMap, HashSet};
+use crate::diagnostics::DiagnosticsStack;
+use crate::ir::builder::{ExpressionBuilder, TypeBuilder};
+use crate::ir::const_eval::Value;
+use crate::ir::{
+    Enum, Expr, ExprKind, ExprP, Field, FuncBody, Function, IRItem, IRItemP, IrCtx, IrId, LocalDef,
+    Parameter, Statement, Static, StructLike, Ty, TyP, ValueType,
+};
+use crate::utils::bounded_debug;
+
+use once_cell::unsync::OnceCell;
+use std::fmt::Write;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+const BUILTIN_TYPES: [(&str, BuiltinType); 18] = [
+    ("bool", BuiltinType::Bool),
+    ("char", BuiltinType::Char),
+    ("u8", BuiltinType::U8),
+    ("u16", BuiltinType::U16),
+    ("u32", BuiltinType::U32),
+    ("u64", BuiltinType::U64),
+    ("u128", BuiltinType::U128),
+    ("usize", BuiltinType::USize),
+    ("isize", BuiltinType::ISize),
+    ("i8", BuiltinType::I8),
+    ("i16", BuiltinType::I16),
+    ("i32", BuiltinType::I32),
+    ("i64", BuiltinType::I64),
+    ("i128", BuiltinType::I128),
+    ("f16", BuiltinType::F16),
+    ("bf16", BuiltinType::BF16),
+    ("f32", BuiltinType::F32),
+    ("f64", BuiltinType::F64),
+];
+
+const BINARY_OPS: [(&str, BinOp); 18] = [
+    ("&&", BinOp::And),
+    ("||", BinOp::Or),
+    ("&", BinOp::BitAnd),
+    ("|", BinOp::BitOr),
+    ("^", BinOp::BitXor),
+    ("==", BinOp::Eq),
+    ("!=", BinOp::Neq),
+    ("<", BinOp::Lt),
+    ("<=", BinOp::LEq),
+    (">", BinOp::Gt),
+    (">=", BinOp::GEq),
+    ("<<", BinOp::LShift),
+    (">>", BinOp::RShift),
+    ("+", BinOp::Plus),
+    ("-", BinOp::Minus),
+    ("*", BinOp::Mul),
+    ("/", BinOp::Div),
+    ("%", BinOp::Mod),
+];
+
+const UNARY_OPS: [(&str, UnOp); 3] = [("-", UnOp::Neg), ("!", UnOp::Not), ("~", UnOp::BitNot)];
+
+// Longest first, so that the lexer can take the first one that matches
+const PUNCTUATION: [&str; 43] = [
+    "<<=", ">>=", "...", "->", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=",
+    "/=", "%=", "&=", "|=", "^=", "+", "-", "*", "/", "%", "&", "|", "^", "<", ">", "=", "!", "~",
+    "(", ")", "{", "}", "[", "]", ",", ";", ":", ".",
+];
+
+fn builtin_name(kind: BuiltinType) -> &'static str {
+    match kind {
+        BuiltinType::Never => "!",
+        _ => BUILTIN_TYPES
+            .iter()
+            .find(|(_, k)| *k == kind)
+            .map(|(name, _)| *name)
+            .unwrap(),
+    }
+}
+
+fn binary_op_text(op: BinOp) -> &'static str {
+    BINARY_OPS.iter().find(|(_, o)| *o == op).unwrap().0
+}
+
+fn item_name(item: IRItemP<'_>) -> Option<&str> {
+    match item.get().ok()? {
+        IRItem::Function(f) => f.name,
+        IRItem::StructLike(s) => s.name,
+        IRItem::Closure(c) => c.data.name,
+        IRItem::Static(s) => s.name,
+        IRItem::Enum(e) => e.name,
+        IRItem::Const(c) => c.name,
+        IRItem::Protocol(p) => p.name,
+        IRItem::Alias(_) => None,
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | ':' | '#'))
+}
+
+/// Number or boolean literal, `None` for values that have no textual form
+fn format_literal(value: &Value<'_>) -> Option<String> {
+    Some(match *value {
+        Value::Bool(v) => v.to_string(),
+        Value::U8(v) => format!("{}u8", v),
+        Value::U16(v) => format!("{}u16", v),
+        Value::U32(v) => format!("{}u32", v),
+        Value::U64(v) => format!("{}u64", v),
+        Value::U128(v) => format!("{}u128", v),
+        Value::USize(v) => format!("{}usize", v),
+        Value::I8(v) => format!("{}i8", v),
+        Value::I16(v) => format!("{}i16", v),
+        Value::I32(v) => format!("{}i32", v),
+        Value::I64(v) => format!("{}i64", v),
+        Value::I128(v) => format!("{}i128", v),
+        Value::ISize(v) => format!("{}isize", v),
+        Value::F32(bits) if f32::from_bits(bits).is_finite() => {
+            format!("{:?}f32", f32::from_bits(bits))
+        }
+        Value::F64(bits) if f64::from_bits(bits).is_finite() => {
+            format!("{:?}f64", f64::from_bits(bits))
+        }
+        _ => return None,
+    })
+}
+
+struct IrPrinter<'a, 'ir> {
+    diag: &'a DiagnosticsStack,
+    names: HashMap<IRItemP<'ir>, String>,
+    taken: HashSet<String>,
+    /// Items that were referenced, but are not among the ones being printed
+    pending: Vec<IRItemP<'ir>>,
+    out: String,
+    indent: usize,
+}
+
+impl<'a, 'ir> IrPrinter<'a, 'ir> {
+    fn new(diag: &'a DiagnosticsStack) -> Self {
+        Self {
+            diag,
+            names: HashMap::default(),
+            taken: HashSet::default(),
+            pending: Vec::new(),
+            out: String::new(),
+            indent: 0,
+        }
+    }
+
+    fn assign_name(&mut self, item: IRItemP<'ir>, name: &str) -> String {
+        let name = if self.taken.contains(name) {
+            format!("{}#{}", name, item.id.id)
+        } else {
+            name.to_string()
+        };
+        let formatted = if is_plain_name(&name) {
+            format!("@{}", name)
+        } else {
+            format!("@{}", quote(&name))
+        };
+
+        self.taken.insert(name);
+        self.names.insert(item, formatted.clone());
+        formatted
+    }
+
+    fn name(&mut self, item: IRItemP<'ir>) -> String {
+        if let Some(name) = self.names.get(&item) {
+            return name.clone();
+        }
+
+        self.pending.push(item);
+        let name = item_name(item).unwrap_or("anonymous");
+        self.assign_name(item, name)
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    fn ty(&mut self, ty: TyP<'ir>) -> String {
+        match ty {
+            Ty::Builtin(kind) => builtin_name(*kind).to_string(),
+            Ty::Item(item) => match item.get_alias() {
+                Some(inner) => self.ty(inner),
+                None => self.name(item),
+            },
+            Ty::Pointer(inner, true) => format!("&{}", self.ty(inner)),
+            Ty::Pointer(inner, false) => format!("&mut {}", self.ty(inner)),
+            Ty::Array(inner, len) => format!("[{}; {}]", self.ty(inner), len),
+            Ty::Tuple([single]) => format!("({},)", self.ty(single)),
+            Ty::Tuple(elems) => {
+                let elems: Vec<_> = elems.iter().map(|e| self.ty(e)).collect();
+                format!("({})", elems.join(", "))
+            }
+            Ty::FunctionPointer(args, ret) => {
+                let args: Vec<_> = args.iter().map(|a| self.ty(a)).collect();
+                format!("fn({}) -> {}", args.join(", "), self.ty(ret))
+            }
+        }
+    }
+
+    fn unsupported(&mut self, description: &str) {
+        write!(self.out, "unsupported {}", quote(description)).unwrap();
+    }
+
+    fn item(&mut self, item: IRItemP<'ir>, with_body: bool) -> Result<(), AluminaError> {
+        let name = self.name(item);
+        match item.get().with_backtrace(self.diag)? {
+            IRItem::Function(func) => {
+                let mut params: Vec<_> = func
+                    .args
+                    .iter()
+                    .map(|p| format!("{}: {}", p.id, self.ty(p.ty)))
+                    .collect();
+                if func.varargs {
+                    params.push("...".to_string());
+                }
+                let ret = self.ty(func.return_type);
+                write!(self.out, "fn {}({}) -> {}", name, params.join(", "), ret).unwrap();
+
+                match func.body.get().filter(|_| with_body) {
+                    Some(body) => {
+                        self.out.push_str(" {");
+                        self.indent += 1;
+                        for def in body.local_defs {
+                            let ty = self.ty(def.typ);
+                            self.newline();
+                            write!(self.out, "let {}: {};", def.id, ty).unwrap();
+                        }
+                        for stmt in body.statements {
+                            self.newline();
+                            self.statement(stmt)?;
+                        }
+                        self.indent -= 1;
+                        self.out.push_str("\n}\n\n");
+                    }
+                    None => self.out.push_str(";\n"),
+                }
+            }
+            IRItem::StructLike(StructLike {
+                fields, is_union, ..
+            }) => self.struct_like(&name, fields, *is_union),
+            IRItem::Closure(closure) => self.struct_like(&name, closure.data.fields, false),
+            IRItem::Static(s) => {
+                let ty = self.ty(s.typ);
+                writeln!(self.out, "static {}: {};", name, ty).unwrap();
+            }
+            IRItem::Enum(e) => {
+                let ty = self.ty(e.underlying_type);
+                writeln!(self.out, "enum {}: {};", name, ty).unwrap();
+            }
+            IRItem::Const(_) | IRItem::Alias(_) | IRItem::Protocol(_) => {}
+        }
+
+        Ok(())
+    }
+
+    fn struct_like(&mut self, name: &str, fields: &[Field<'ir>], is_union: bool) {
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|f| format!("{}: {}", f.id, self.ty(f.ty)))
+            .collect();
+        writeln!(
+            self.out,
+            "{} {} {{ {} }}",
+            if is_union { "union" } else { "struct" },
+            name,
+            fields.join(", ")
+        )
+        .unwrap();
+    }
+
+    fn statement(&mut self, stmt: &Statement<'ir>) -> Result<(), AluminaError> {
+        match stmt {
+            Statement::Expression(expr) => {
+                self.expr(expr, true)?;
+                self.out.push(';');
+            }
+            Statement::Label(id) => write!(self.out, "{}:", id).unwrap(),
+        }
+
+        Ok(())
+    }
+
+    fn list<T>(
+        &mut self,
+        elems: impl IntoIterator<Item = T>,
+        mut f: impl FnMut(&mut Self, T) -> Result<(), AluminaError>,
+    ) -> Result<(), AluminaError> {
+        for (idx, elem) in elems.into_iter().enumerate() {
+            if idx > 0 {
+                self.out.push_str(", ");
+            }
+            f(self, elem)?;
+        }
+
+        Ok(())
+    }
+
+    /// Operand of a postfix operator (call, index, field access), prefix operators bind
+    /// weaker so they need to be parenthesized (`(*$1).$2`)
+    fn postfix_operand(&mut self, expr: ExprP<'ir>) -> Result<(), AluminaError> {
+        match expr.kind {
+            ExprKind::Ref(_)
+            | ExprKind::Deref(_)
+            | ExprKind::Unary(..)
+            | ExprKind::Return(_)
+            | ExprKind::If(..) => {
+                self.out.push('(');
+                self.expr(expr, false)?;
+                self.out.push(')');
+                Ok(())
+            }
+            _ => self.expr(expr, false),
+        }
+    }
+
+    /// `top` is set for expressions in statement position, where assignments do not need
+    /// to be parenthesized.
+    fn expr(&mut self, expr: ExprP<'ir>, top: bool) -> Result<(), AluminaError> {
+        match expr.kind {
+            ExprKind::Block(statements, ret) => {
+                self.out.push('{');
+                self.indent += 1;
+                for stmt in statements {
+                    self.newline();
+                    self.statement(stmt)?;
+                }
+                if !(ret.is_void() && ret.ty.is_void()) {
+                    self.newline();
+                    self.expr(ret, true)?;
+                }
+                self.indent -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                self.out.push('(');
+                self.expr(lhs, false)?;
+                write!(self.out, " {} ", binary_op_text(op)).unwrap();
+                self.expr(rhs, false)?;
+                self.out.push(')');
+            }
+            ExprKind::Assign(lhs, rhs) | ExprKind::AssignOp(_, lhs, rhs) => {
+                if !top {
+                    self.out.push('(');
+                }
+                self.expr(lhs, false)?;
+                match expr.kind {
+                    ExprKind::AssignOp(op, _, _) => {
+                        write!(self.out, " {}= ", binary_op_text(op)).unwrap()
+                    }
+                    _ => self.out.push_str(" = "),
+                }
+                self.expr(rhs, false)?;
+                if !top {
+                    self.out.push(')');
+                }
+            }
+            ExprKind::Call(callee, args) => {
+                self.postfix_operand(callee)?;
+                self.out.push('(');
+                self.list(args.iter(), |this, arg| this.expr(arg, false))?;
+                self.out.push(')');
+            }
+            ExprKind::Fn(item) | ExprKind::Static(item) => {
+                let name = self.name(item);
+                self.out.push_str(&name);
+            }
+            ExprKind::Const(item) => {
+                let value = &item.get_const().with_backtrace(self.diag)?.value;
+                match format_literal(value) {
+                    Some(literal) => self.out.push_str(&literal),
+                    None => {
+                        let name = self.name(item);
+                        self.unsupported(&format!("const {}", name))
+                    }
+                }
+            }
+            ExprKind::Ref(inner) => {
+                self.out.push('&');
+                self.expr(inner, false)?;
+            }
+            ExprKind::Deref(inner) => {
+                self.out.push('*');
+                self.expr(inner, false)?;
+            }
+            ExprKind::Unary(op, inner) => {
+                let text = UNARY_OPS.iter().find(|(_, o)| *o == op).unwrap().0;
+                self.out.push_str(text);
+                // `-(1i32)` rather than a negative literal
+                if let ExprKind::Literal(_) = inner.kind {
+                    self.out.push('(');
+                    self.expr(inner, false)?;
+                    self.out.push(')');
+                } else {
+                    self.expr(inner, false)?;
+                }
+            }
+            ExprKind::Return(inner) => {
+                self.out.push_str("return");
+                if !(inner.is_void() && inner.ty.is_void()) {
+                    self.out.push(' ');
+                    self.expr(inner, false)?;
+                }
+            }
+            ExprKind::Goto(label) => write!(self.out, "goto {}", label).unwrap(),
+            ExprKind::Local(id) => write!(self.out, "{}", id).unwrap(),
+            ExprKind::Literal(ref value) => match format_literal(value) {
+                Some(literal) => self.out.push_str(&literal),
+                None => self.unsupported(&bounded_debug(value, 60)),
+            },
+            ExprKind::Index(inner, index) => {
+                self.postfix_operand(inner)?;
+                self.out.push('[');
+                self.expr(index, false)?;
+                self.out.push(']');
+            }
+            ExprKind::Field(inner, field) => {
+                self.postfix_operand(inner)?;
+                write!(self.out, ".{}", field).unwrap();
+            }
+            ExprKind::TupleIndex(inner, index) => {
+                self.postfix_operand(inner)?;
+                write!(self.out, ".{}", index).unwrap();
+            }
+            ExprKind::If(cond, then, els, const_cond) => {
+                self.out.push_str("if ");
+                self.expr(cond, false)?;
+                if let Some(value) = const_cond {
+                    write!(self.out, " is {}", value).unwrap();
+                }
+                self.out.push_str(" then ");
+                self.expr(then, false)?;
+                self.out.push_str(" else ");
+                self.expr(els, false)?;
+            }
+            ExprKind::Cast(inner) => {
+                self.out.push('(');
+                self.expr(inner, false)?;
+                let ty = self.ty(expr.ty);
+                write!(self.out, " as {})", ty).unwrap();
+            }
+            ExprKind::Intrinsic(ref kind) => {
+                self.unsupported(&format!("intrinsic {}", bounded_debug(kind, 60)))
+            }
+            ExprKind::Array(elems) => {
+                let ty = self.ty(expr.ty);
+                write!(self.out, "array {} {{ ", ty).unwrap();
+                self.list(elems.iter(), |this, elem| this.expr(elem, false))?;
+                self.out.push_str(" }");
+            }
+            ExprKind::Tuple(inits) => {
+                let ty = self.ty(expr.ty);
+                write!(self.out, "tuple {} {{ ", ty).unwrap();
+                self.list(inits.iter(), |this, init| {
+                    write!(this.out, "{}: ", init.index).unwrap();
+                    this.expr(init.value, false)
+                })?;
+                self.out.push_str(" }");
+            }
+            ExprKind::Struct(inits) => {
+                let ty = self.ty(expr.ty);
+                write!(self.out, "struct {} {{ ", ty).unwrap();
+                self.list(inits.iter(), |this, init| {
+                    write!(this.out, "{}: ", init.field).unwrap();
+                    this.expr(init.value, false)
+                })?;
+                self.out.push_str(" }");
+            }
+            ExprKind::Unreachable => self.out.push_str("unreachable"),
+            ExprKind::Void => {
+                if expr.ty.is_void() {
+                    self.out.push_str("void");
+                } else {
+                    let ty = self.ty(expr.ty);
+                    write!(self.out, "void<{}>", ty).unwrap();
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats the given items (with the names to show for them) in the IR text format. Items
+/// that they refer to are declared before them.
+pub fn format_ir<'ir>(
+    diag: &DiagnosticsStack,
+    items: &[(String, IRItemP<'ir>)],
+) -> Result<String, AluminaError> {
+    let mut printer = IrPrinter::new(diag);
+    for (name, item) in items {
+        printer.assign_name(*item, name);
+    }
+    for (_, item) in items {
+        printer.item(*item, true)?;
+    }
+    let definitions = std::mem::take(&mut printer.out);
+
+    // Declarations can refer to further items (e.g. the types of struct fields)
+    let mut idx = 0;
+    while idx < printer.pending.len() {
+        let item = printer.pending[idx];
+        printer.item(item, false)?;
+        idx += 1;
+    }
+    let mut result = std::mem::take(&mut printer.out);
+    if !result.is_empty() && !definitions.is_empty() {
+        result.push('\n');
+    }
+    result.push_str(&definitions);
+
+    Ok(result)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token<'src> {
+    Ident(&'src str),
+    Number(&'src str),
+    Id(usize),
+    Item(String),
+    Str(String),
+    Punct(&'static str),
+}
+
+impl Token<'_> {
+    fn ends_operand(&self) -> bool {
+        matches!(
+            self,
+            Token::Number(_) | Token::Id(_) | Token::Item(_) | Token::Punct(")" | "]" | "}")
+        )
+    }
+}
+
+fn read_quoted(chars: &mut Peekable<CharIndices<'_>>) -> Option<String> {
+    let mut result = String::new();
+    chars.next();
+    loop {
+        match chars.next()?.1 {
+            '"' => return Some(result),
+            '\\' => result.push(chars.next()?.1),
+            c => result.push(c),
+        }
+    }
+}
+
+/// Consumes characters while `f` (called with the text so far and the next character)
+/// returns true and returns the text from `start`
+fn take_while<'src>(
+    source: &'src str,
+    chars: &mut Peekable<CharIndices<'src>>,
+    start: usize,
+    f: impl Fn(&str, char) -> bool,
+) -> &'src str {
+    while let Some(&(idx, c)) = chars.peek() {
+        if !f(&source[start..idx], c) {
+            break;
+        }
+        chars.next();
+    }
+
+    let end = chars.peek().map_or(source.len(), |(idx, _)| *idx);
+    &source[start..end]
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token<'_>, usize)>, String> {
+    let mut tokens: Vec<(Token<'_>, usize)> = Vec::new();
+    let mut line = 1;
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        let rest = &source[start..];
+        let previous = tokens.last().map(|(t, _)| t);
+        let token = if c == '\n' {
+            line += 1;
+            chars.next();
+            continue;
+        } else if c.is_whitespace() {
+            chars.next();
+            continue;
+        } else if rest.starts_with("//") {
+            take_while(source, &mut chars, start, |_, c| c != '\n');
+            continue;
+        } else if c == '$' {
+            chars.next();
+            let digits = take_while(source, &mut chars, start + 1, |_, c| c.is_ascii_digit());
+            Token::Id(
+                digits
+                    .parse()
+                    .map_err(|_| format!("line {}: expected a number after `$`", line))?,
+            )
+        } else if c == '@' {
+            chars.next();
+            match chars.peek() {
+                Some((_, '"')) => Token::Item(
+                    read_quoted(&mut chars)
+                        .ok_or_else(|| format!("line {}: unterminated name", line))?,
+                ),
+                _ => {
+                    let name = take_while(source, &mut chars, start + 1, |_, c| {
+                        c.is_ascii_alphanumeric() || matches!(c, '_' | ':' | '#')
+                    });
+                    if name.is_empty() {
+                        return Err(format!("line {}: expected a name after `@`", line));
+                    }
+                    Token::Item(name.to_string())
+                }
+            }
+        } else if c == '"' {
+            Token::Str(
+                read_quoted(&mut chars)
+                    .ok_or_else(|| format!("line {}: unterminated string", line))?,
+            )
+        } else if c.is_ascii_digit()
+            || (c == '-'
+                && rest[1..].starts_with(|c: char| c.is_ascii_digit())
+                && !previous.map_or(false, Token::ends_operand))
+        {
+            // A dot is only part of the number if it is not a tuple index (`$1.0.1`)
+            let after_dot = previous == Some(&Token::Punct("."));
+            chars.next();
+            Token::Number(take_while(source, &mut chars, start, |text, c| {
+                let exponent = matches!(c, '+' | '-')
+                    && (text.ends_with('e') || text.ends_with('E'))
+                    && text[..text.len() - 1]
+                        .trim_start_matches('-')
+                        .chars()
+                        .all(|c| c.is_ascii_digit() || c == '.');
+                c.is_ascii_alphanumeric() || c == '_' || (c == '.' && !after_dot) || exponent
+            }))
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            Token::Ident(take_while(source, &mut chars, start, |_, c| {
+                c.is_ascii_alphanumeric() || c == '_'
+            }))
+        } else if let Some(&punct) = PUNCTUATION.iter().find(|p| rest.starts_with(**p)) {
+            for _ in 0..punct.len() {
+                chars.next();
+            }
+            Token::Punct(punct)
+        } else {
+            return Err(format!("line {}: unexpected character `{}`", line, c));
+        };
+
+        tokens.push((token, line));
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Option<(Value<'static>, BuiltinType)> {
+    let (kind, digits) = BUILTIN_TYPES.iter().find_map(|(name, kind)| {
+        text.strip_suffix(name)
+            .filter(|d| !d.is_empty())
+            .map(|d| (*kind, d))
+    })?;
+
+    let value = match kind {
+        BuiltinType::U8 => Value::U8(digits.parse().ok()?),
+        BuiltinType::U16 => Value::U16(digits.parse().ok()?),
+        BuiltinType::U32 => Value::U32(digits.parse().ok()?),
+        BuiltinType::U64 => Value::U64(digits.parse().ok()?),
+        BuiltinType::U128 => Value::U128(digits.parse().ok()?),
+        BuiltinType::USize => Value::USize(digits.parse().ok()?),
+        BuiltinType::I8 => Value::I8(digits.parse().ok()?),
+        BuiltinType::I16 => Value::I16(digits.parse().ok()?),
+        BuiltinType::I32 => Value::I32(digits.parse().ok()?),
+        BuiltinType::I64 => Value::I64(digits.parse().ok()?),
+        BuiltinType::I128 => Value::I128(digits.parse().ok()?),
+        BuiltinType::ISize => Value::ISize(digits.parse().ok()?),
+        BuiltinType::F32 => Value::F32(digits.parse::<f32>().ok()?.to_bits()),
+        BuiltinType::F64 => Value::F64(digits.parse::<f64>().ok()?.to_bits()),
+        _ => return None,
+    };
+
+    Some((value, kind))
+}
+
+/// An item that was read from the IR text format
+pub struct ParsedItem<'ir> {
+    pub name: String,
+    pub item: IRItemP<'ir>,
+    /// Body of a function definition. It is not attached to the function yet, so that
+    /// it can be transformed first.
+    pub body: Option<FuncBody<'ir>>,
+}
+
+struct IrParser<'src, 'ir> {
+    ir: &'ir IrCtx<'ir>,
+    exprs: ExpressionBuilder<'ir>,
+    types: TypeBuilder<'ir>,
+    tokens: Vec<(Token<'src>, usize)>,
+    pos: usize,
+    items: HashMap<String, IRItemP<'ir>>,
+    locals: HashMap<IrId, TyP<'ir>>,
+}
+
+impl<'src, 'ir> IrParser<'src, 'ir> {
+    fn error<T>(&self, message: impl std::fmt::Display) -> Result<T, String> {
+        let line = self
+            .tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line);
+
+        Err(format!("line {}: {}", line, message))
+    }
+
+    fn peek(&self) -> Option<&Token<'src>> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token<'src>> {
+        self.tokens.get(self.pos + offset).map(|(t, _)| t)
+    }
+
+    fn next(&mut self) -> Result<Token<'src>, String> {
+        match self.tokens.get(self.pos) {
+            Some((token, _)) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => self.error("unexpected end of input"),
+        }
+    }
+
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, punct: &str) -> Result<(), String> {
+        if !self.eat_punct(punct) {
+            return self.error(format!("expected `{}`", punct));
+        }
+        Ok(())
+    }
+
+    fn eat_ident(&mut self, ident: &str) -> bool {
+        if self.peek() == Some(&Token::Ident(ident)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_ident(&mut self, ident: &str) -> Result<(), String> {
+        if !self.eat_ident(ident) {
+            return self.error(format!("expected `{}`", ident));
+        }
+        Ok(())
+    }
+
+    fn id(&mut self) -> Result<IrId, String> {
+        match self.next()? {
+            Token::Id(id) => Ok(IrId { id }),
+            _ => {
+                self.pos -= 1;
+                self.error("expected an id (`$N`)")
+            }
+        }
+    }
+
+    fn index(&mut self) -> Result<usize, String> {
+        match self.next()? {
+            Token::Number(n) => match n.parse() {
+                Ok(n) => Ok(n),
+                Err(_) => self.error(format!("invalid index `{}`", n)),
+            },
+            _ => {
+                self.pos -= 1;
+                self.error("expected a number")
+            }
+        }
+    }
+
+    fn item(&mut self) -> Result<IRItemP<'ir>, String> {
+        match self.next()? {
+            Token::Item(name) => match self.items.get(&name) {
+                Some(&item) => Ok(item),
+                None => self.error(format!("`@{}` is not defined", name)),
+            },
+            _ => {
+                self.pos -= 1;
+                self.error("expected an item name (`@name`)")
+            }
+        }
+    }
+
+    fn comma_separated<T>(
+        &mut self,
+        close: &str,
+        mut f: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        let mut result = Vec::new();
+        while !self.eat_punct(close) {
+            result.push(f(self)?);
+            if !self.eat_punct(",") {
+                self.expect_punct(close)?;
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn ty(&mut self) -> Result<TyP<'ir>, String> {
+        match self.next()? {
+            Token::Punct("!") => Ok(self.types.builtin(BuiltinType::Never)),
+            Token::Punct("&") => {
+                let is_const = !self.eat_ident("mut");
+                let inner = self.ty()?;
+                Ok(self.types.pointer(inner, is_const))
+            }
+            Token::Punct("&&") => {
+                let is_const = !self.eat_ident("mut");
+                let inner = self.ty()?;
+                Ok(self
+                    .types
+                    .pointer(self.types.pointer(inner, is_const), true))
+            }
+            Token::Punct("[") => {
+                let inner = self.ty()?;
+                self.expect_punct(";")?;
+                let len = self.index()?;
+                self.expect_punct("]")?;
+                Ok(self.types.array(inner, len))
+            }
+            Token::Punct("(") => {
+                let mut elems = Vec::new();
+                while !self.eat_punct(")") {
+                    elems.push(self.ty()?);
+                    if !self.eat_punct(",") {
+                        self.expect_punct(")")?;
+                        break;
+                    }
+                }
+                Ok(self.types.tuple(elems))
+            }
+            Token::Ident("fn") => {
+                self.expect_punct("(")?;
+                let args = self.comma_separated(")", |this| this.ty())?;
+                self.expect_punct("->")?;
+                let ret = self.ty()?;
+                Ok(self.types.function(args, ret))
+            }
+            Token::Item(_) => {
+                self.pos -= 1;
+                let item = self.item()?;
+                Ok(self.types.named(item))
+            }
+            Token::Ident(name) => match BUILTIN_TYPES.iter().find(|(n, _)| *n == name) {
+                Some((_, kind)) => Ok(self.types.builtin(*kind)),
+                None => {
+                    self.pos -= 1;
+                    self.error(format!("unknown type `{}`", name))
+                }
+            },
+            _ => {
+                self.pos -= 1;
+                self.error("expected a type")
+            }
+        }
+    }
+
+    /// Skips over an item, so that the names of all the items are known before any of
+    /// them is parsed
+    fn skip_item(&mut self) -> Result<(), String> {
+        let mut depth = 0usize;
+        loop {
+            match self.next()? {
+                Token::Punct(";") if depth == 0 => return Ok(()),
+                Token::Punct("{" | "(" | "[") => depth += 1,
+                Token::Punct(")" | "]") => depth = depth.saturating_sub(1),
+                Token::Punct("}") => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn field_list(&mut self) -> Result<Vec<Field<'ir>>, String> {
+        self.expect_punct("{")?;
+        self.comma_separated("}", |this| {
+            let id = this.id()?;
+            this.expect_punct(":")?;
+            let ty = this.ty()?;
+            Ok(Field { id, ty })
+        })
+    }
+
+    /// Parses the part of an item that other items can depend on. Returns the position of
+    /// the body for function definitions.
+    fn item_header(
+        &mut self,
+        keyword: &str,
+        name: &str,
+        item: IRItemP<'ir>,
+    ) -> Result<Option<usize>, String> {
+        let name = Some(name.alloc_on(self.ir));
+        match keyword {
+            "fn" => {
+                self.expect_punct("(")?;
+                let mut varargs = false;
+                let args = self.comma_separated(")", |this| {
+                    if this.eat_punct("...") {
+                        varargs = true;
+                        return Ok(None);
+                    }
+                    let id = this.id()?;
+                    this.expect_punct(":")?;
+                    let ty = this.ty()?;
+                    Ok(Some(Parameter { id, ty }))
+                })?;
+                self.expect_punct("->")?;
+                let return_type = self.ty()?;
+
+                item.assign(IRItem::Function(Function {
+                    name,
+                    attributes: &[],
+                    args: args
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .alloc_on(self.ir),
+                    return_type,
+                    body: OnceCell::new(),
+                    varargs,
+                }));
+
+                if self.eat_punct(";") {
+                    Ok(None)
+                } else {
+                    let body = self.pos;
+                    self.skip_item()?;
+                    Ok(Some(body))
+                }
+            }
+            "struct" | "union" => {
+                let fields = self.field_list()?;
+                item.assign(IRItem::StructLike(StructLike {
+                    name,
+                    attributes: &[],
+                    fields: fields.alloc_on(self.ir),
+                    is_union: keyword == "union",
+                }));
+                Ok(None)
+            }
+            "static" | "enum" => {
+                self.expect_punct(":")?;
+                let ty = self.ty()?;
+                self.expect_punct(";")?;
+                item.assign(if keyword == "static" {
+                    IRItem::Static(Static {
+                        name,
+                        typ: ty,
+                        init: None,
+                        attributes: &[],
+                        r#extern: false,
+                        is_const: false,
+                    })
+                } else {
+                    IRItem::Enum(Enum {
+                        name,
+                        underlying_type: ty,
+                        members: &[],
+                    })
+                });
+                Ok(None)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn function_body(&mut self, func: &Function<'ir>) -> Result<FuncBody<'ir>, String> {
+        self.locals = func.args.iter().map(|p| (p.id, p.ty)).collect();
+        self.expect_punct("{")?;
+
+        let mut local_defs = Vec::new();
+        while self.eat_ident("let") {
+            let id = self.id()?;
+            self.expect_punct(":")?;
+            let typ = self.ty()?;
+            self.expect_punct(";")?;
+            self.locals.insert(id, typ);
+            local_defs.push(LocalDef { id, typ });
+        }
+
+        let (statements, ret) = self.statements()?;
+        if ret.is_some() {
+            return self.error("expected `;` after the last statement of a function");
+        }
+
+        Ok(FuncBody {
+            local_defs: local_defs.alloc_on(self.ir),
+            statements: statements.alloc_on(self.ir),
+            raw_body: None,
+        })
+    }
+
+    /// Statements until the closing brace, and the trailing expression if there is one
+    #[allow(clippy::type_complexity)]
+    fn statements(&mut self) -> Result<(Vec<Statement<'ir>>, Option<ExprP<'ir>>), String> {
+        let mut statements = Vec::new();
+        loop {
+            if self.eat_punct("}") {
+                return Ok((statements, None));
+            }
+            if let (Some(Token::Id(_)), Some(Token::Punct(":"))) = (self.peek(), self.peek_at(1)) {
+                let id = self.id()?;
+                self.pos += 1;
+                statements.push(Statement::Label(id));
+                continue;
+            }
+
+            let expr = self.expr()?;
+            if self.eat_punct(";") {
+                statements.push(Statement::Expression(expr));
+                continue;
+            }
+            self.expect_punct("}")?;
+            return Ok((statements, Some(expr)));
+        }
+    }
+
+    fn expr(&mut self) -> Result<ExprP<'ir>, String> {
+        let lhs = self.unary()?;
+        let Some(&Token::Punct(punct)) = self.peek() else {
+            return Ok(lhs);
+        };
+
+        if punct == "=" {
+            self.pos += 1;
+            let rhs = self.expr()?;
+            return Ok(self.exprs.assign(lhs, rhs, None));
+        }
+
+        let assign_op = BINARY_OPS.iter().find(|(text, op)| {
+            !op.is_comparison() && !op.is_logical() && punct.strip_suffix('=') == Some(*text)
+        });
+        if let Some((_, op)) = assign_op {
+            self.pos += 1;
+            let rhs = self.expr()?;
+            return Ok(self.exprs.assign_op(*op, lhs, rhs, None));
+        }
+
+        Ok(lhs)
+    }
+
+    fn reference(&self, inner: ExprP<'ir>) -> ExprP<'ir> {
+        Expr::rvalue(
+            ExprKind::Ref(inner),
+            self.types.pointer(inner.ty, inner.is_const),
+            None,
+        )
+        .alloc_on(self.ir)
+    }
+
+    fn unary(&mut self) -> Result<ExprP<'ir>, String> {
+        match self.peek() {
+            Some(Token::Punct("&")) => {
+                self.pos += 1;
+                let inner = self.unary()?;
+                Ok(self.reference(inner))
+            }
+            Some(Token::Punct("&&")) => {
+                self.pos += 1;
+                let inner = self.unary()?;
+                Ok(self.reference(self.reference(inner)))
+            }
+            Some(Token::Punct("*")) => {
+                self.pos += 1;
+                let inner = self.unary()?;
+                let expr = match inner.ty {
+                    Ty::Pointer(ty, false) => Expr::lvalue(ExprKind::Deref(inner), ty, None),
+                    Ty::Pointer(ty, true) => Expr::const_lvalue(ExprKind::Deref(inner), ty, None),
+                    _ => return self.error("dereferencing a value that is not a pointer"),
+                };
+                Ok(expr.alloc_on(self.ir))
+            }
+            Some(&Token::Punct(punct)) => match UNARY_OPS.iter().find(|(text, _)| *text == punct) {
+                Some((_, op)) => {
+                    self.pos += 1;
+                    let inner = self.unary()?;
+                    Ok(self.exprs.unary(*op, inner, inner.ty, None))
+                }
+                None => self.postfix(),
+            },
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Result<ExprP<'ir>, String> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat_punct("(") {
+                let args = self.comma_separated(")", |this| this.expr())?;
+                let return_type = match expr.ty {
+                    Ty::FunctionPointer(_, ret) => *ret,
+                    Ty::Item(item) => match item.get_function() {
+                        Ok(func) => func.return_type,
+                        Err(_) => return self.error("calling a value that is not a function"),
+                    },
+                    _ => return self.error("calling a value that is not a function"),
+                };
+                expr = self.exprs.call(expr, args, return_type, None);
+            } else if self.eat_punct("[") {
+                let index = self.expr()?;
+                self.expect_punct("]")?;
+                if !matches!(expr.ty, Ty::Array(..)) {
+                    return self.error("indexing a value that is not an array");
+                }
+                expr = self.exprs.index(expr, index, None);
+            } else if self.eat_punct(".") {
+                if let Some(Token::Id(_)) = self.peek() {
+                    let id = self.id()?;
+                    let fields = match expr.ty {
+                        Ty::Item(item) => match item.get() {
+                            Ok(IRItem::StructLike(s)) => s.fields,
+                            Ok(IRItem::Closure(c)) => c.data.fields,
+                            _ => &[],
+                        },
+                        _ => &[],
+                    };
+                    let Some(field) = fields.iter().find(|f| f.id == id) else {
+                        return self.error(format!("no field `{}` on this type", id));
+                    };
+                    expr = self.exprs.field(expr, id, field.ty, None);
+                } else {
+                    let index = self.index()?;
+                    let Ty::Tuple(elems) = expr.ty else {
+                        return self.error("tuple index on a value that is not a tuple");
+                    };
+                    let Some(ty) = elems.get(index) else {
+                        return self.error(format!("tuple index {} is out of bounds", index));
+                    };
+                    expr = self.exprs.tuple_index(expr, index, *ty, None);
+                }
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<ExprP<'ir>, String> {
+        match self.next()? {
+            Token::Punct("(") => {
+                let inner = self.expr()?;
+                if self.eat_punct(")") {
+                    return Ok(inner);
+                }
+                if self.eat_ident("as") {
+                    let ty = self.ty()?;
+                    self.expect_punct(")")?;
+                    return Ok(self.exprs.cast(inner, ty, None));
+                }
+
+                let op = match self.next()? {
+                    Token::Punct(punct) => BINARY_OPS.iter().find(|(text, _)| *text == punct),
+                    _ => None,
+                };
+                let Some((_, op)) = op else {
+                    self.pos -= 1;
+                    return self.error("expected a binary operator, `as` or `)`");
+                };
+                let rhs = self.expr()?;
+                self.expect_punct(")")?;
+                let ty = if op.is_comparison() || op.is_logical() {
+                    self.types.builtin(BuiltinType::Bool)
+                } else {
+                    inner.ty
+                };
+                Ok(self.exprs.binary(*op, inner, rhs, ty, None))
+            }
+            Token::Punct("{") => {
+                let (statements, ret) = self.statements()?;
+                let ret = ret
+                    .unwrap_or_else(|| self.exprs.void(self.types.void(), ValueType::RValue, None));
+                Ok(Expr::rvalue(
+                    ExprKind::Block(statements.alloc_on(self.ir), ret),
+                    ret.ty,
+                    None,
+                )
+                .alloc_on(self.ir))
+            }
+            Token::Id(_) => {
+                self.pos -= 1;
+                let id = self.id()?;
+                match self.locals.get(&id) {
+                    Some(ty) => Ok(self.exprs.local(id, ty, None)),
+                    None => self.error(format!("`{}` is not a parameter or a local", id)),
+                }
+            }
+            Token::Item(_) => {
+                self.pos -= 1;
+                let item = self.item()?;
+                match item.get() {
+                    Ok(IRItem::Function(_)) => Ok(self.exprs.function(item, None)),
+                    Ok(IRItem::Static(s)) => Ok(self.exprs.static_var(item, s.typ, None)),
+                    _ => self.error("only functions and statics can be used as values"),
+                }
+            }
+            Token::Number(text) => match parse_number(text) {
+                Some((value, kind)) => {
+                    Ok(self.exprs.literal(value, self.types.builtin(kind), None))
+                }
+                None => {
+                    self.pos -= 1;
+                    self.error(format!(
+                        "invalid literal `{}` (literals need a type suffix)",
+                        text
+                    ))
+                }
+            },
+            Token::Ident(value @ ("true" | "false")) => Ok(self.exprs.literal(
+                Value::Bool(value == "true"),
+                self.types.builtin(BuiltinType::Bool),
+                None,
+            )),
+            Token::Ident("return") => {
+                let inner = match self.peek() {
+                    Some(Token::Punct(";" | "}" | ")" | "," | "]"))
+                    | Some(Token::Ident("else")) => {
+                        self.exprs.void(self.types.void(), ValueType::RValue, None)
+                    }
+                    _ => self.expr()?,
+                };
+                Ok(self.exprs.ret(inner, None))
+            }
+            Token::Ident("goto") => {
+                let label = self.id()?;
+                Ok(self.exprs.goto(label, None))
+            }
+            Token::Ident("if") => {
+                let cond = self.expr()?;
+                let const_cond = if self.eat_ident("is") {
+                    match self.next()? {
+                        Token::Ident("true") => Some(true),
+                        Token::Ident("false") => Some(false),
+                        _ => return self.error("expected `true` or `false`"),
+                    }
+                } else {
+                    None
+                };
+                self.expect_ident("then")?;
+                let then = self.expr()?;
+                self.expect_ident("else")?;
+                let els = self.expr()?;
+                Ok(self.exprs.if_then(cond, then, els, const_cond, None))
+            }
+            Token::Ident("void") => {
+                let ty = if self.eat_punct("<") {
+                    let ty = self.ty()?;
+                    self.expect_punct(">")?;
+                    ty
+                } else {
+                    self.types.void()
+                };
+                Ok(self.exprs.void(ty, ValueType::RValue, None))
+            }
+            Token::Ident("unreachable") => Ok(self.exprs.unreachable(None)),
+            Token::Ident("array") => {
+                let ty = self.ty()?;
+                self.expect_punct("{")?;
+                let elems = self.comma_separated("}", |this| this.expr())?;
+                Ok(self.exprs.array(elems, ty, None))
+            }
+            Token::Ident("tuple") => {
+                let ty = self.ty()?;
+                self.expect_punct("{")?;
+                let inits = self.comma_separated("}", |this| {
+                    let index = this.index()?;
+                    this.expect_punct(":")?;
+                    Ok((index, this.expr()?))
+                })?;
+                Ok(self.exprs.tuple(inits, ty, None))
+            }
+            Token::Ident("struct") => {
+                let ty = self.ty()?;
+                self.expect_punct("{")?;
+                let inits = self.comma_separated("}", |this| {
+                    let id = this.id()?;
+                    this.expect_punct(":")?;
+                    Ok((id, this.expr()?))
+                })?;
+                Ok(self.exprs.r#struct(inits, ty, None))
+            }
+            Token::Ident("unsupported") => match self.next()? {
+                Token::Str(description) => {
+                    self.pos -= 2;
+                    self.error(format!("`{}` is not supported by the parser", description))
+                }
+                _ => {
+                    self.pos -= 2;
+                    self.error("expected a description after `unsupported`")
+                }
+            },
+            _ => {
+                self.pos -= 1;
+                self.error("expected an expression")
+            }
+        }
+    }
+}
+
+/// Parses items in the IR text format. Items can refer to each other regardless of the
+/// order in which they are defined. The ids of locals, fields and labels are the ones in
+/// the text, so `ir` should not contain any other items.
+pub fn parse<'ir>(ir: &'ir IrCtx<'ir>, source: &str) -> Result<Vec<ParsedItem<'ir>>, String> {
+    let tokens = tokenize(source)?;
+
+    // Ids are kept as they are written, so new ones (for items and for anything that the
+    // passes add) have to come after them
+    let max_id = tokens
+        .iter()
+        .filter_map(|(t, _)| match t {
+            Token::Id(id) => Some(*id),
+            _ => None,
+        })
+        .max();
+    if let Some(max_id) = max_id {
+        ir.counter.set(ir.counter.get().max(max_id + 1));
+    }
+
+    let mut parser = IrParser {
+        ir,
+        exprs: ExpressionBuilder::new(ir),
+        types: TypeBuilder::new(ir),
+        tokens,
+        pos: 0,
+        items: HashMap::default(),
+        locals: HashMap::default(),
+    };
+
+    let mut headers = Vec::new();
+    while parser.pos < parser.tokens.len() {
+        let keyword = match parser.next()? {
+            Token::Ident(keyword @ ("fn" | "struct" | "union" | "static" | "enum")) => keyword,
+            _ => {
+                parser.pos -= 1;
+                return parser.error("expected `fn`, `struct`, `union`, `static` or `enum`");
+            }
+        };
+        let Token::Item(name) = parser.next()? else {
+            parser.pos -= 1;
+            return parser.error("expected an item name (`@name`)");
+        };
+        let item = ir.make_symbol();
+        if parser.items.insert(name.clone(), item).is_some() {
+            parser.pos -= 1;
+            return parser.error(format!("`@{}` is defined more than once", name));
+        }
+        headers.push((keyword, name, item, parser.pos));
+        parser.skip_item()?;
+    }
+
+    let mut result = Vec::new();
+    let mut bodies = Vec::new();
+    for (keyword, name, item, pos) in headers {
+        parser.pos = pos;
+        if let Some(body) = parser.item_header(keyword, &name, item)? {
+            bodies.push((result.len(), body));
+        }
+        result.push(ParsedItem {
+            name,
+            item,
+            body: None,
+        });
+    }
+
+    for (index, pos) in bodies {
+        parser.pos = pos;
+        let item = result[index].item;
+        let func = item.get_function().unwrap();
+        result[index].body = Some(parser.function_body(func)?);
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,191 @@
+use crate::ast::BinOp;
+use crate::common::{CodeErrorKind, HashMap, HashSet};
+use crate::diagnostics::DiagnosticsStack;
+use crate::intrinsics::IntrinsicValueKind;
+use crate::ir::{ExprKind, ExprP, IrId, LocalDef, Statement};
+
+/// Set of definitely assigned locals at a point in the function. `None` means
+/// that the point is unreachable (every local is vacuously assigned).
+type State = Option<HashSet<IrId>>;
+
+fn meet(a: State, b: State) -> State {
+    match (a, b) {
+        (None, b) => b,
+        (a, None) => a,
+        (Some(a), Some(b)) => Some(a.intersection(&b).copied().collect()),
+    }
+}
+
+/// Definite assignment analysis over a function body. Warns when a local variable is read
+/// on some path before it is assigned.
+///
+/// The analysis is conservative: taking a reference to a local (or to a part of it) and
+/// assigning to a field or an element counts as an assignment of the whole variable.
+/// Assignments are never undone, so jumps backwards (loops) do not need a fixpoint
+/// iteration - the state at a backward jump is always a superset of the state at the label.
+pub struct DefiniteAssignment {
+    diag: DiagnosticsStack,
+    tracked: HashSet<IrId>,
+    assigned: State,
+    labels: HashMap<IrId, State>,
+    reported: HashSet<IrId>,
+}
+
+impl DefiniteAssignment {
+    pub fn new(diag: DiagnosticsStack, local_defs: &[LocalDef<'_>]) -> Self {
+        Self {
+            diag,
+            tracked: local_defs
+                .iter()
+                .filter(|def| !def.typ.is_zero_sized())
+                .map(|def| def.id)
+                .collect(),
+            assigned: Some(HashSet::default()),
+            labels: HashMap::default(),
+            reported: HashSet::default(),
+        }
+    }
+
+    pub fn check(mut self, body: ExprP<'_>) {
+        self.visit_expr(body);
+    }
+
+    fn read(&mut self, id: IrId, expr: ExprP<'_>) {
+        if let Some(assigned) = &mut self.assigned {
+            if self.tracked.contains(&id) && assigned.insert(id) && self.reported.insert(id) {
+                let _guard = self.diag.push_span(expr.span);
+                self.diag.warn(CodeErrorKind::PossiblyUninitialized);
+            }
+        }
+    }
+
+    fn assign(&mut self, id: IrId) {
+        if let Some(assigned) = &mut self.assigned {
+            assigned.insert(id);
+        }
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement<'_>) {
+        match stmt {
+            Statement::Expression(expr) => self.visit_expr(expr),
+            Statement::Label(id) => {
+                if let Some(incoming) = self.labels.get(id) {
+                    self.assigned = meet(self.assigned.take(), incoming.clone());
+                }
+            }
+        }
+    }
+
+    /// Visits an expression that is being assigned to (or has its address taken).
+    fn visit_place(&mut self, expr: ExprP<'_>) {
+        match expr.kind {
+            ExprKind::Local(id) => self.assign(id),
+            ExprKind::Field(inner, _) | ExprKind::TupleIndex(inner, _) => self.visit_place(inner),
+            ExprKind::Index(inner, index) => {
+                self.visit_expr(index);
+                self.visit_place(inner);
+            }
+            _ => self.visit_expr(expr),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: ExprP<'_>) {
+        match expr.kind {
+            ExprKind::Block(statements, ret) => {
+                for stmt in statements {
+                    self.visit_statement(stmt);
+                }
+                self.visit_expr(ret);
+            }
+            ExprKind::Binary(BinOp::And | BinOp::Or, lhs, rhs) => {
+                // The right-hand side is not always evaluated, so the assignments in it
+                // do not count.
+                self.visit_expr(lhs);
+                let state = self.assigned.clone();
+                self.visit_expr(rhs);
+                self.assigned = state;
+            }
+            ExprKind::Binary(_, lhs, rhs) | ExprKind::Index(lhs, rhs) => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            ExprKind::AssignOp(_, lhs, rhs) => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            ExprKind::Assign(lhs, rhs) => {
+                self.visit_expr(rhs);
+                self.visit_place(lhs);
+            }
+            ExprKind::Call(callee, args) => {
+                self.visit_expr(callee);
+                for arg in args {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprKind::Ref(inner) => self.visit_place(inner),
+            ExprKind::Deref(inner)
+            | ExprKind::Unary(_, inner)
+            | ExprKind::Field(inner, _)
+            | ExprKind::TupleIndex(inner, _)
+            | ExprKind::Cast(inner) => self.visit_expr(inner),
+            ExprKind::Return(inner) => {
+                self.visit_expr(inner);
+                self.assigned = None;
+            }
+            ExprKind::Goto(label) => {
+                let state = self.assigned.take();
+                let incoming = match self.labels.remove(&label) {
+                    Some(existing) => meet(existing, state),
+                    None => state,
+                };
+                self.labels.insert(label, incoming);
+            }
+            ExprKind::Local(id) => self.read(id, expr),
+            ExprKind::If(cond, then, els, const_cond) => match const_cond {
+                Some(true) => self.visit_expr(then),
+                Some(false) => self.visit_expr(els),
+                None => {
+                    self.visit_expr(cond);
+                    let state = self.assigned.clone();
+                    self.visit_expr(then);
+                    let then_state = std::mem::replace(&mut self.assigned, state);
+                    self.visit_expr(els);
+                    self.assigned = meet(then_state, self.assigned.take());
+                }
+            },
+            ExprKind::Intrinsic(ref kind) => match kind {
+                IntrinsicValueKind::ConstPanic(inner)
+                | IntrinsicValueKind::ConstWrite(inner, _)
+                | IntrinsicValueKind::ConstAlloc(_, inner)
+                | IntrinsicValueKind::ConstFree(inner) => self.visit_expr(inner),
+                _ => {}
+            },
+            ExprKind::Array(elems) => {
+                for elem in elems {
+                    self.visit_expr(elem);
+                }
+            }
+            ExprKind::Tuple(inits) => {
+                for init in inits {
+                    self.visit_expr(init.value);
+                }
+            }
+            ExprKind::Struct(inits) => {
+                for init in inits {
+                    self.visit_expr(init.value);
+                }
+            }
+            ExprKind::Unreachable => self.assigned = None,
+            ExprKind::Fn(_)
+            | ExprKind::Static(_)
+            | ExprKind::Const(_)
+            | ExprKind::Literal(_)
+            | ExprKind::Void => {}
+        }
+
+        if expr.diverges() {
+            self.assigned = None;
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::common::{CodeErrorKind, HashMap, HashSet};
+use crate::diagnostics::DiagnosticsStack;
+use crate::intrinsics::IntrinsicValueKind;
+use crate::ir::{ExprKind, ExprP, IrId, Statement, Ty};
+
+/// Finds reads of a union field other than the one that was last written to, which
+/// reinterprets the bytes of the other field (type punning).
+///
+/// Only local variables of union type are tracked, and only in straight-line code: the
+/// last written field is forgotten at labels (loops and jumps) and when the branches of a
+/// conditional disagree. Variables that have their address taken are ignored altogether,
+/// since they can be written through the pointer.
+pub struct UnionFieldReads {
+    diag: DiagnosticsStack,
+    last_written: HashMap<IrId, IrId>,
+    escaped: HashSet<IrId>,
+}
+
+impl UnionFieldReads {
+    pub fn new(diag: DiagnosticsStack) -> Self {
+        Self {
+            diag,
+            last_written: HashMap::default(),
+            escaped: HashSet::default(),
+        }
+    }
+
+    pub fn check(mut self, body: ExprP<'_>) {
+        self.visit_expr(body);
+    }
+
+    /// The local variable if the expression is a (non-escaped) local of a union type
+    fn union_local(&self, expr: ExprP<'_>) -> Option<IrId> {
+        let ExprKind::Local(id) = expr.kind else {
+            return None;
+        };
+
+        let is_union = match expr.ty {
+            Ty::Item(item) => item.get_struct_like().map(|s| s.is_union).unwrap_or(false),
+            _ => false,
+        };
+
+        (is_union && !self.escaped.contains(&id)).then_some(id)
+    }
+
+    fn read_field(&mut self, id: IrId, field: IrId, expr: ExprP<'_>) {
+        if matches!(self.last_written.get(&id), Some(written) if *written != field) {
+            let _guard = self.diag.push_span(expr.span);
+            self.diag.warn(CodeErrorKind::InactiveUnionFieldRead);
+        }
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement<'_>) {
+        match stmt {
+            Statement::Expression(expr) => self.visit_expr(expr),
+            // Control can come from anywhere
+            Statement::Label(_) => self.last_written.clear(),
+        }
+    }
+
+    /// Visits the target of an assignment and returns the union local and the field that
+    /// is (fully or partially) written, if any.
+    fn visit_place(&mut self, expr: ExprP<'_>) -> Option<(IrId, IrId)> {
+        match expr.kind {
+            ExprKind::Field(inner, field) => match self.union_local(inner) {
+                Some(id) => Some((id, field)),
+                None => self.visit_place(inner),
+            },
+            ExprKind::TupleIndex(inner, _) => self.visit_place(inner),
+            ExprKind::Index(inner, index) => {
+                self.visit_expr(index);
+                self.visit_place(inner)
+            }
+            ExprKind::Local(_) => None,
+            _ => {
+                self.visit_expr(expr);
+                None
+            }
+        }
+    }
+
+    /// Forgets the local that the place is rooted in, as it can now be written through
+    /// a pointer.
+    fn escape(&mut self, expr: ExprP<'_>) {
+        match expr.kind {
+            ExprKind::Local(id) => {
+                self.escaped.insert(id);
+                self.last_written.remove(&id);
+            }
+            ExprKind::Field(inner, _) | ExprKind::TupleIndex(inner, _) => self.escape(inner),
+            ExprKind::Index(inner, index) => {
+                self.visit_expr(index);
+                self.escape(inner);
+            }
+            _ => self.visit_expr(expr),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: ExprP<'_>) {
+        match expr.kind {
+            ExprKind::Block(statements, ret) => {
+                for stmt in statements {
+                    self.visit_statement(stmt);
+                }
+                self.visit_expr(ret);
+            }
+            ExprKind::Field(inner, field) => match self.union_local(inner) {
+                Some(id) => self.read_field(id, field, expr),
+                None => self.visit_expr(inner),
+            },
+            ExprKind::Assign(lhs, rhs) => {
+                self.visit_expr(rhs);
+                if let Some(id) = self.union_local(lhs) {
+                    // A union literal initializes exactly one field
+                    match rhs.kind {
+                        ExprKind::Struct([init]) => {
+                            self.last_written.insert(id, init.field);
+                        }
+                        _ => {
+                            self.last_written.remove(&id);
+                        }
+                    }
+                } else if let Some((id, field)) = self.visit_place(lhs) {
+                    self.last_written.insert(id, field);
+                }
+            }
+            ExprKind::AssignOp(_, lhs, rhs) => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            ExprKind::Ref(inner) => self.escape(inner),
+            ExprKind::If(cond, then, els, const_cond) => match const_cond {
+                Some(true) => self.visit_expr(then),
+                Some(false) => self.visit_expr(els),
+                None => {
+                    self.visit_expr(cond);
+                    let state = self.last_written.clone();
+                    self.visit_expr(then);
+                    let then_state = std::mem::replace(&mut self.last_written, state);
+                    self.visit_expr(els);
+                    self.last_written
+                        .retain(|id, field| then_state.get(id) == Some(field));
+                }
+            },
+            ExprKind::Binary(_, lhs, rhs) | ExprKind::Index(lhs, rhs) => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            ExprKind::Call(callee, args) => {
+                self.visit_expr(callee);
+                for arg in args {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprKind::Deref(inner)
+            | ExprKind::Unary(_, inner)
+            | ExprKind::TupleIndex(inner, _)
+            | ExprKind::Cast(inner)
+            | ExprKind::Return(inner) => self.visit_expr(inner),
+            ExprKind::Intrinsic(ref kind) => match kind {
+                IntrinsicValueKind::ConstPanic(inner)
+                | IntrinsicValueKind::ConstWrite(inner, _)
+                | IntrinsicValueKind::ConstAlloc(_, inner)
+                | IntrinsicValueKind::ConstFree(inner) => self.visit_expr(inner),
+                _ => {}
+            },
+            ExprKind::Array(elems) => {
+                for elem in elems {
+                    self.visit_expr(elem);
+                }
+            }
+            ExprKind::Tuple(inits) => {
+                for init in inits {
+                    self.visit_expr(init.value);
+                }
+            }
+            ExprKind::Struct(inits) => {
+                for init in inits {
+                    self.visit_expr(init.value);
+                }
+            }
+            ExprKind::Goto(_)
+            | ExprKind::Unreachable
+            | ExprKind::Local(_)
+            | ExprKind::Fn(_)
+            | ExprKind::Static(_)
+            | ExprKind::Const(_)
+            | ExprKind::Literal(_)
+            | ExprKind::Void => {}
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! IR pass tests (`--ir-test`)
+//!
+//! Each test is a file in the IR text format (see `ir::text`) that names the pass to run on
+//! all the function bodies in it and has FileCheck-style expectations about the IR that
+//! comes out, so that passes can be tested without compiling a whole program:
+//!
+//! ```text
+//! // RUN: elide-zst
+//! // CHECK: fn @main() -> () {
+//! // CHECK-NOT: let $2
+//! // CHECK: $1 = 5i32;
+//! // CHECK-NEXT: return;
+//! struct @Empty {  }
+//!
+//! fn @main() -> () {
+//!     let $1: i32;
+//!     let $2: @Empty;
+//!     $2 = struct @Empty {  };
+//!     $1 = 5i32;
+//!     return;
+//! }
+//! ```
+//!
+//! `CHECK` has to match (as a substring) a line of the output after the previous match,
+//! `CHECK-NEXT` the line right after it. `CHECK-NOT` must not match any of the lines between
+//! the surrounding matches.
+
+use crate::common::AluminaError;
+use crate::diagnostics::DiagnosticsStack;
+use crate::global_ctx::GlobalCtx;
+use crate::ir::elide_zst::ZstElider;
+use crate::ir::text::{format_ir, parse};
+use crate::ir::{FuncBody, IrCtx};
+
+use std::path::{Path, PathBuf};
+
+/// Passes that can be named in the `// RUN:` line
+const PASSES: [&str; 3] = ["none", "elide-zst", "lower-aggregates"];
+
+enum Check {
+    Match(String),
+    Next(String),
+    Not(String),
+}
+
+fn parse_directives(source: &str) -> Result<(&'static str, Vec<Check>), String> {
+    let mut pass = None;
+    let mut checks = Vec::new();
+
+    for line in source.lines() {
+        let Some(comment) = line.trim_start().strip_prefix("//") else {
+            continue;
+        };
+        let Some((directive, text)) = comment.trim_start().split_once(':') else {
+            continue;
+        };
+        let text = text.trim().to_string();
+        match directive {
+            "RUN" => match PASSES.into_iter().find(|p| *p == text) {
+                Some(p) => pass = Some(p),
+                None => {
+                    return Err(format!(
+                        "unknown pass `{}` (expected one of {})",
+                        text,
+                        PASSES.join(", ")
+                    ))
+                }
+            },
+            "CHECK" => checks.push(Check::Match(text)),
+            "CHECK-NEXT" => checks.push(Check::Next(text)),
+            "CHECK-NOT" => checks.push(Check::Not(text)),
+            _ => {}
+        }
+    }
+
+    match pass {
+        Some(pass) => Ok((pass, checks)),
+        None => Err("missing `// RUN: <pass>` line".to_string()),
+    }
+}
+
+fn check_output(output: &str, checks: &[Check]) -> Result<(), String> {
+    let lines: Vec<_> = output.lines().collect();
+    let mut cursor = 0;
+    let mut forbidden: Vec<&str> = Vec::new();
+
+    let check_forbidden = |forbidden: &mut Vec<&str>, range: &[&str]| {
+        for text in forbidden.drain(..) {
+            if let Some(line) = range.iter().find(|line| line.contains(text)) {
+                return Err(format!("`CHECK-NOT: {}` matched `{}`", text, line.trim()));
+            }
+        }
+        Ok(())
+    };
+
+    for check in checks {
+        match check {
+            Check::Match(text) => {
+                let Some(offset) = lines[cursor..]
+                    .iter()
+                    .position(|l| l.contains(text.as_str()))
+                else {
+                    return Err(format!("`CHECK: {}` did not match", text));
+                };
+                check_forbidden(&mut forbidden, &lines[cursor..cursor + offset])?;
+                cursor += offset + 1;
+            }
+            Check::Next(text) => {
+                if !lines
+                    .get(cursor)
+                    .map_or(false, |l| l.contains(text.as_str()))
+                {
+                    return Err(format!(
+                        "`CHECK-NEXT: {}` did not match the next line `{}`",
+                        text,
+                        lines.get(cursor).map_or("", |l| l.trim())
+                    ));
+                }
+                check_forbidden(&mut forbidden, &[])?;
+                cursor += 1;
+            }
+            Check::Not(text) => forbidden.push(text),
+        }
+    }
+
+    check_forbidden(&mut forbidden, &lines[cursor..])
+}
+
+fn run_pass<'ir>(
+    pass: &str,
+    diag: &DiagnosticsStack,
+    ir: &'ir IrCtx<'ir>,
+    body: FuncBody<'ir>,
+) -> Result<FuncBody<'ir>, AluminaError> {
+    match pass {
+        "none" => Ok(body),
+        "elide-zst" => ZstElider::new(diag.fork(), ir).elide_zst_func_body(body),
+        "lower-aggregates" => ZstElider::new(diag.fork(), ir)
+            .with_aggregate_lowering(true)
+            .elide_zst_func_body(body),
+        _ => unreachable!(),
+    }
+}
+
+fn run_test(global_ctx: &GlobalCtx, path: &Path) -> Result<(), String> {
+    let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let (pass, checks) = parse_directives(&source)?;
+
+    let ir = IrCtx::new();
+    let items = parse(&ir, &source)?;
+    let diag = DiagnosticsStack::new(global_ctx.diag().clone());
+
+    let mut names = Vec::new();
+    for parsed in items {
+        if let Some(body) = parsed.body {
+            let body = run_pass(pass, &diag, &ir, body).map_err(|e| {
+                let diag_ctx = global_ctx.diag();
+                diag_ctx.add_from_error(e).unwrap();
+                diag_ctx.print_error_report().unwrap();
+                format!("`{}` failed", pass)
+            })?;
+            let func = parsed.item.get_function().unwrap();
+            func.body.set(body).unwrap();
+        }
+        names.push((parsed.name, parsed.item));
+    }
+
+    let output = format_ir(&diag, &names).map_err(|e| format!("{:?}", e))?;
+    check_output(&output, &checks).map_err(|e| format!("{}\n\n{}", e, output))
+}
+
+/// Runs the given test files, printing the failures. Returns `false` if any of them failed.
+pub fn run(global_ctx: &GlobalCtx, files: &[PathBuf]) -> bool {
+    let mut failed = 0;
+    for path in files {
+        if let Err(e) = run_test(global_ctx, path) {
+            eprintln!("{}: {}", path.display(), e);
+            failed += 1;
+        }
+    }
+
+    println!(
+        "ir tests: {} passed, {} failed",
+        files.len() - failed,
+        failed
+    );
+
+    failed == 0
+}
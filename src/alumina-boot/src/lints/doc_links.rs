@@ -0,0 +1,184 @@
+use crate::ast::Span;
+use crate::common::{CodeError, CodeErrorKind, Marker};
+use crate::lints::{AstLintCtx, AstLintPass};
+use crate::name_resolution::resolver::NameResolver;
+use crate::name_resolution::scope::{NamedItem, NamedItemKind, Scope};
+use crate::parser::{NodeExt, NodeKind, ParseCtx};
+
+/// Whether the link target looks like a path (`foo`, `Vec::push`, `::std::mem`) rather than
+/// something else in brackets
+fn is_path(target: &str) -> bool {
+    target
+        .strip_prefix("::")
+        .unwrap_or(target)
+        .split("::")
+        .all(|segment| {
+            segment.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+}
+
+/// Byte ranges of the link targets in a line of a doc comment. Both the forms that
+/// alumina-doc understands are recognized: `[path]` and `[text](path)`. Links to URLs and
+/// brackets inside inline code are skipped.
+fn link_targets(line: &str) -> Vec<(usize, usize)> {
+    let mut result = Vec::new();
+    let mut in_code = false;
+    let mut pos = 0;
+
+    while let Some(offset) = line[pos..].find(['`', '[']) {
+        let start = pos + offset;
+        pos = start + 1;
+
+        if line.as_bytes()[start] == b'`' {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        let Some(close) = line[pos..].find(']').map(|i| pos + i) else {
+            break;
+        };
+
+        let (target_start, target_end) = if line[close + 1..].starts_with('(') {
+            match line[close + 2..].find(')') {
+                Some(i) => (close + 2, close + 2 + i),
+                None => break,
+            }
+        } else {
+            (start + 1, close)
+        };
+
+        pos = target_end;
+        if is_path(&line[target_start..target_end]) {
+            result.push((target_start, target_end));
+        }
+    }
+
+    result
+}
+
+/// Links in doc comments (`[Vec::push]`, `[the docs](std::mem)`) that do not resolve to an
+/// item from the scope of the documented item. Fenced code blocks are not checked.
+pub struct DocLinks;
+
+impl DocLinks {
+    fn check_comments<'ast, 'src>(
+        &self,
+        ctx: &AstLintCtx<'_, 'ast>,
+        scope: &Scope<'ast, 'src>,
+        code: &'src ParseCtx<'src>,
+        comments: &[tree_sitter::Node<'src>],
+    ) {
+        let mut in_code_block = false;
+        for comment in comments {
+            let text = code.node_text(*comment);
+            if text[3..].trim().starts_with("```") {
+                in_code_block = !in_code_block;
+                continue;
+            }
+            if in_code_block {
+                continue;
+            }
+
+            for (start, end) in link_targets(text) {
+                let target = &text[start..end];
+                let path = ctx.ast.parse_path(target);
+                if NameResolver::new()
+                    .resolve_item(scope.clone(), path)
+                    .is_ok()
+                {
+                    continue;
+                }
+
+                let position = comment.start_position();
+                ctx.diag.add_warning(CodeError {
+                    kind: CodeErrorKind::UnresolvedDocLink(target.to_string()),
+                    backtrace: vec![Marker::Span(Span {
+                        start: comment.start_byte() + start,
+                        end: comment.start_byte() + end,
+                        line: position.row,
+                        column: position.column + start,
+                        file: code.file_id(),
+                    })],
+                });
+            }
+        }
+    }
+}
+
+impl AstLintPass for DocLinks {
+    fn check_item<'ast, 'src>(
+        &self,
+        ctx: &AstLintCtx<'_, 'ast>,
+        scope: &Scope<'ast, 'src>,
+        _name: Option<&'ast str>,
+        item: &NamedItem<'ast, 'src>,
+    ) {
+        // Links are resolved from the scope of the item itself, so that they can refer to
+        // its generic parameters.
+        let (node, item_scope) = match &item.kind {
+            NamedItemKind::Function(_, node, child)
+            | NamedItemKind::Method(_, node, child)
+            | NamedItemKind::TypeDef(_, node, child)
+            | NamedItemKind::Static(_, node, child)
+            | NamedItemKind::Const(_, node, child)
+            | NamedItemKind::Macro(_, node, child)
+            | NamedItemKind::Type(_, node, child)
+            | NamedItemKind::Protocol(_, node, child)
+            | NamedItemKind::Mixin(node, child) => (*node, child),
+            NamedItemKind::EnumMember(_, _, node) | NamedItemKind::Field(node) => (*node, scope),
+            NamedItemKind::Module(child) => {
+                // File doc comments (`//!`) are checked from the module of the file itself.
+                // Modules declared inline share the code of their parent.
+                let Some(child_code) = child.code() else {
+                    return;
+                };
+                if ctx.is_sysroot(child)
+                    || scope
+                        .code()
+                        .map_or(false, |code| std::ptr::eq(code, child_code))
+                {
+                    return;
+                }
+
+                let root = child_code.root_node();
+                let mut cursor = root.walk();
+                let comments: Vec<_> = root
+                    .children(&mut cursor)
+                    .filter(|node| matches!(node.kind_typed(), NodeKind::FileDocComment))
+                    .collect();
+
+                self.check_comments(ctx, child, child_code, &comments);
+                return;
+            }
+            _ => return,
+        };
+
+        if ctx.is_sysroot(scope) {
+            return;
+        }
+        let Some(code) = scope.code() else {
+            return;
+        };
+
+        // Doc comments are extras in the grammar, so they are the siblings right before
+        // the item rather than a part of it.
+        let mut comments = Vec::new();
+        let mut prev = node.prev_sibling();
+        while let Some(sibling) = prev {
+            if !matches!(sibling.kind_typed(), NodeKind::DocComment) {
+                break;
+            }
+            comments.push(sibling);
+            prev = sibling.prev_sibling();
+        }
+        comments.reverse();
+
+        self.check_comments(ctx, item_scope, code, &comments);
+    }
+}
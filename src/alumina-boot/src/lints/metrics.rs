@@ -0,0 +1,115 @@
+use crate::ast::BinOp;
+use crate::common::{AluminaError, CodeErrorKind, HashSet};
+use crate::ir::{default_visit_expr, ExprP, ExpressionVisitor};
+use crate::lints::{IrLintCtx, IrLintPass};
+
+/// Value of a `-Z name=value` option
+fn threshold(options: &HashSet<String>, name: &str) -> Option<usize> {
+    options
+        .iter()
+        .find_map(|option| option.strip_prefix(name)?.strip_prefix('=')?.parse().ok())
+}
+
+#[derive(Default)]
+struct Metrics {
+    size: usize,
+    depth: usize,
+    max_depth: usize,
+    branches: usize,
+}
+
+impl<'ir> ExpressionVisitor<'ir> for Metrics {
+    fn visit_binary(
+        &mut self,
+        op: BinOp,
+        a: ExprP<'ir>,
+        b: ExprP<'ir>,
+    ) -> Result<(), AluminaError> {
+        if matches!(op, BinOp::And | BinOp::Or) {
+            self.branches += 1;
+        }
+        self.visit_expr(a)?;
+        self.visit_expr(b)
+    }
+
+    fn visit_if(
+        &mut self,
+        cond: ExprP<'ir>,
+        then: ExprP<'ir>,
+        els: ExprP<'ir>,
+        const_cond: Option<bool>,
+    ) -> Result<(), AluminaError> {
+        // Branches that are resolved at compile time (`when`) do not make it into the
+        // generated code.
+        match const_cond {
+            Some(true) => return self.visit_expr(then),
+            Some(false) => return self.visit_expr(els),
+            None => {}
+        }
+
+        self.branches += 1;
+        self.visit_expr(cond)?;
+
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+        self.visit_expr(then)?;
+        self.visit_expr(els)?;
+        self.depth -= 1;
+
+        Ok(())
+    }
+
+    fn visit_expr(&mut self, expr: ExprP<'ir>) -> Result<(), AluminaError> {
+        self.size += 1;
+        default_visit_expr(self, expr)
+    }
+}
+
+/// Warns about functions that exceed the size and complexity thresholds given with
+/// `-Z max-function-size=N` (IR nodes), `-Z max-nesting-depth=N` (nested branches)
+/// and `-Z max-branches=N` (conditionals and short-circuiting operators).
+///
+/// Sizes are measured after monomorphization, so they include the code produced by
+/// macros and `for const` loops.
+pub struct FunctionMetrics {
+    max_size: Option<usize>,
+    max_depth: Option<usize>,
+    max_branches: Option<usize>,
+}
+
+impl FunctionMetrics {
+    /// The pass, if any of the thresholds are set
+    pub fn from_options(options: &HashSet<String>) -> Option<Self> {
+        let metrics = Self {
+            max_size: threshold(options, "max-function-size"),
+            max_depth: threshold(options, "max-nesting-depth"),
+            max_branches: threshold(options, "max-branches"),
+        };
+
+        (metrics.max_size.is_some()
+            || metrics.max_depth.is_some()
+            || metrics.max_branches.is_some())
+        .then_some(metrics)
+    }
+}
+
+impl IrLintPass for FunctionMetrics {
+    fn check_function(&self, ctx: &IrLintCtx<'_, '_>) -> Result<(), AluminaError> {
+        let mut metrics = Metrics::default();
+        metrics.visit_expr(ctx.body)?;
+
+        // The limit is in the message rather than the actual value, so that different
+        // instantiations of a generic function are only reported once.
+        if let Some(max) = self.max_size.filter(|max| metrics.size > *max) {
+            ctx.diag.warn(CodeErrorKind::FunctionTooLarge(max));
+        }
+        if let Some(max) = self.max_depth.filter(|max| metrics.max_depth > *max) {
+            ctx.diag.warn(CodeErrorKind::FunctionTooDeeplyNested(max));
+        }
+        if let Some(max) = self.max_branches.filter(|max| metrics.branches > *max) {
+            ctx.diag.warn(CodeErrorKind::FunctionTooComplex(max));
+        }
+
+        Ok(())
+    }
+}
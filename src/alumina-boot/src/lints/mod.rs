@@ -0,0 +1,245 @@
+//! Lint passes
+//!
+//! Lints that need to see a whole item or a whole function body (as opposed to ones that
+//! are naturally checked while lowering a single expression) are implemented as passes
+//! that are registered in the [LintRegistry] of the global context.
+//!
+//! AST passes run once after all the items have been constructed and see every named
+//! item in every scope along with its attributes. IR passes run on every function body
+//! after monomorphization, so the types of all expressions are known.
+//!
+//! Passes that are too noisy or opinionated to be on by default can be registered as
+//! extra passes, which only run with `-Z extra-lints`. Passes that need configuration
+//! (such as the function size limits) are registered when their `-Z` option is given.
+//!
+//! Some lints that are checked during lowering are allowed by default (see
+//! [ALLOWED_BY_DEFAULT]) and related lints can be controlled together as a group (see
+//! [LINT_GROUPS]).
+
+mod doc_links;
+mod metrics;
+mod naming;
+
+use crate::ast::AstCtx;
+use crate::common::{AluminaError, FileId, HashSet};
+use crate::diagnostics::{DiagnosticContext, DiagnosticsStack};
+use crate::ir::const_ub::ConstantUbChecker;
+use crate::ir::dead_store::DeadStores;
+use crate::ir::pointer_lints::PointerMisuseChecker;
+use crate::ir::uninit::DefiniteAssignment;
+use crate::ir::union_reads::UnionFieldReads;
+use crate::ir::{ExprP, IrId, LocalDef};
+use crate::name_resolution::scope::{NamedItem, NamedItemKind, Scope};
+
+pub struct AstLintCtx<'a, 'ast> {
+    pub ast: &'ast AstCtx<'ast>,
+    pub diag: &'a DiagnosticContext,
+    /// Files that were loaded from the sysroot
+    pub sysroot_files: &'a HashSet<FileId>,
+}
+
+impl AstLintCtx<'_, '_> {
+    /// Whether the scope comes from the standard library (or other sysroot code), which
+    /// the user has no control over.
+    pub fn is_sysroot(&self, scope: &Scope<'_, '_>) -> bool {
+        scope
+            .code()
+            .map(|code| self.sysroot_files.contains(&code.file_id()))
+            .unwrap_or(true)
+    }
+}
+
+/// A lint pass over the named items in the program
+pub trait AstLintPass {
+    /// Called for every named item in every scope. `scope` is the scope that contains the
+    /// item (e.g. the module or the impl block).
+    fn check_item<'ast, 'src>(
+        &self,
+        ctx: &AstLintCtx<'_, 'ast>,
+        scope: &Scope<'ast, 'src>,
+        name: Option<&'ast str>,
+        item: &NamedItem<'ast, 'src>,
+    );
+}
+
+/// Monomorphized function body that is being linted
+pub struct IrLintCtx<'a, 'ir> {
+    pub diag: &'a DiagnosticsStack,
+    pub body: ExprP<'ir>,
+    pub local_defs: &'a [LocalDef<'ir>],
+    /// Locals declared with `let` (as opposed to temporaries introduced by the compiler)
+    pub named_locals: &'a HashSet<IrId>,
+    /// Whether the function has deferred expressions. These are not part of `body`, as
+    /// they are only inserted at the exit points later.
+    pub has_defer: bool,
+}
+
+/// A lint pass over monomorphized function bodies
+pub trait IrLintPass {
+    fn check_function(&self, ctx: &IrLintCtx<'_, '_>) -> Result<(), AluminaError>;
+}
+
+/// Lints that can be enabled, allowed or denied together with a single attribute
+pub const LINT_GROUPS: &[(&str, &[&str])] = &[
+    ("lossy_casts", &["truncating_cast", "sign_changing_cast"]),
+    (
+        "constant_ub",
+        &[
+            "constant_division_by_zero",
+            "constant_shift_overflow",
+            "constant_index_out_of_bounds",
+        ],
+    ),
+];
+
+/// Lints that are emitted during lowering, but are too noisy to be shown unless they are
+/// explicitly enabled with `#[warn(...)]` on an enclosing scope
+pub const ALLOWED_BY_DEFAULT: &[&str] = &["truncating_cast", "sign_changing_cast"];
+
+/// The lints in a lint group
+pub fn lint_group(name: &str) -> Option<&'static [&'static str]> {
+    LINT_GROUPS
+        .iter()
+        .find(|(group, _)| *group == name)
+        .map(|(_, lints)| *lints)
+}
+
+#[derive(Default)]
+pub struct LintRegistry {
+    ast_passes: Vec<Box<dyn AstLintPass>>,
+    ir_passes: Vec<Box<dyn IrLintPass>>,
+}
+
+impl LintRegistry {
+    /// Registry with the built-in passes and the optional ones enabled by the `-Z` options
+    pub fn new(options: &HashSet<String>) -> Self {
+        let mut registry = Self::default();
+
+        registry.register_ast_pass(Box::new(doc_links::DocLinks));
+        registry.register_ir_pass(Box::new(UninitializedLint));
+        registry.register_ir_pass(Box::new(PointerMisuseLint));
+        registry.register_ir_pass(Box::new(UnusedAssignmentLint));
+        registry.register_ir_pass(Box::new(UnionFieldReadLint));
+        registry.register_ir_pass(Box::new(ConstantUbLint));
+
+        if options.contains("extra-lints") {
+            registry.register_extra_passes();
+        }
+
+        if let Some(pass) = metrics::FunctionMetrics::from_options(options) {
+            registry.register_ir_pass(Box::new(pass));
+        }
+
+        registry
+    }
+
+    /// Passes that only run with `-Z extra-lints`
+    fn register_extra_passes(&mut self) {
+        self.register_ast_pass(Box::new(naming::NamingConventions));
+    }
+
+    pub fn register_ast_pass(&mut self, pass: Box<dyn AstLintPass>) {
+        self.ast_passes.push(pass);
+    }
+
+    pub fn register_ir_pass(&mut self, pass: Box<dyn IrLintPass>) {
+        self.ir_passes.push(pass);
+    }
+
+    /// Runs the AST passes on all the items reachable from the root scope.
+    pub fn check_scopes<'ast>(&self, ctx: &AstLintCtx<'_, 'ast>, root: &Scope<'ast, '_>) {
+        if self.ast_passes.is_empty() {
+            return;
+        }
+
+        let mut visited = HashSet::default();
+        let mut stack = vec![root.clone()];
+
+        while let Some(scope) = stack.pop() {
+            if !visited.insert(scope.clone()) {
+                continue;
+            }
+
+            for (name, item) in scope.inner().all_items() {
+                for pass in &self.ast_passes {
+                    pass.check_item(ctx, &scope, name, item);
+                }
+
+                match &item.kind {
+                    NamedItemKind::Function(_, _, child)
+                    | NamedItemKind::Method(_, _, child)
+                    | NamedItemKind::TypeDef(_, _, child)
+                    | NamedItemKind::Static(_, _, child)
+                    | NamedItemKind::Const(_, _, child)
+                    | NamedItemKind::Macro(_, _, child)
+                    | NamedItemKind::Type(_, _, child)
+                    | NamedItemKind::Protocol(_, _, child)
+                    | NamedItemKind::Mixin(_, child)
+                    | NamedItemKind::Impl(_, child)
+                    | NamedItemKind::Module(child) => stack.push(child.clone()),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    pub fn check_function(&self, ctx: &IrLintCtx<'_, '_>) -> Result<(), AluminaError> {
+        for pass in &self.ir_passes {
+            pass.check_function(ctx)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads of local variables that may not have been assigned yet
+struct UninitializedLint;
+
+impl IrLintPass for UninitializedLint {
+    fn check_function(&self, ctx: &IrLintCtx<'_, '_>) -> Result<(), AluminaError> {
+        DefiniteAssignment::new(ctx.diag.fork(), ctx.local_defs).check(ctx.body);
+        Ok(())
+    }
+}
+
+/// Dereferences of null and dangling pointers and out of bounds pointer arithmetic
+struct PointerMisuseLint;
+
+impl IrLintPass for PointerMisuseLint {
+    fn check_function(&self, ctx: &IrLintCtx<'_, '_>) -> Result<(), AluminaError> {
+        PointerMisuseChecker::new(ctx.diag.fork()).check(ctx.body)
+    }
+}
+
+/// Assignments to local variables whose value is never read
+struct UnusedAssignmentLint;
+
+impl IrLintPass for UnusedAssignmentLint {
+    fn check_function(&self, ctx: &IrLintCtx<'_, '_>) -> Result<(), AluminaError> {
+        // Deferred expressions can read any local, but they are not visible yet.
+        if !ctx.has_defer {
+            DeadStores::new(ctx.diag.fork(), ctx.named_locals).check(ctx.body);
+        }
+        Ok(())
+    }
+}
+
+/// Reads of a union field other than the one that was last written
+struct UnionFieldReadLint;
+
+impl IrLintPass for UnionFieldReadLint {
+    fn check_function(&self, ctx: &IrLintCtx<'_, '_>) -> Result<(), AluminaError> {
+        UnionFieldReads::new(ctx.diag.fork()).check(ctx.body);
+        Ok(())
+    }
+}
+
+/// Division by a constant zero, shifts by a constant that is out of range and constant
+/// array indices that are out of bounds
+struct ConstantUbLint;
+
+impl IrLintPass for ConstantUbLint {
+    fn check_function(&self, ctx: &IrLintCtx<'_, '_>) -> Result<(), AluminaError> {
+        ConstantUbChecker::new(ctx.diag.fork()).check(ctx.body)
+    }
+}
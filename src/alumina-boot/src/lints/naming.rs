@@ -0,0 +1,158 @@
+use crate::ast::Span;
+use crate::common::{CodeError, CodeErrorKind, Marker};
+use crate::lints::{AstLintCtx, AstLintPass};
+use crate::name_resolution::scope::{NamedItem, NamedItemKind, Scope};
+use crate::parser::{FieldKind, NodeExt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Case {
+    /// `foo_bar`
+    Snake,
+    /// `FooBar`
+    Pascal,
+    /// `FOO_BAR`
+    UpperSnake,
+}
+
+/// Splits an identifier into words, both on underscores and on case boundaries
+/// (`HTTPServer_v2` -> `HTTP`, `Server`, `v2`).
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in name.split('_').filter(|p| !p.is_empty()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut current = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            let boundary = i > 0
+                && c.is_uppercase()
+                && (!chars[i - 1].is_uppercase()
+                    || chars.get(i + 1).map(|n| n.is_lowercase()).unwrap_or(false));
+            if boundary && !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            current.push(c);
+        }
+        words.push(current);
+    }
+    words
+}
+
+impl Case {
+    fn matches(self, name: &str) -> bool {
+        // Leading underscores are used for unused or private items and are fine in any case.
+        let name = name.trim_start_matches('_');
+        match self {
+            Case::Snake => !name.chars().any(char::is_uppercase),
+            Case::UpperSnake => !name.chars().any(char::is_lowercase),
+            Case::Pascal => {
+                !name.contains('_') && name.chars().next().map_or(true, |c| !c.is_lowercase())
+            }
+        }
+    }
+
+    fn convert(self, name: &str) -> String {
+        let prefix = &name[..name.len() - name.trim_start_matches('_').len()];
+        let words = words(name);
+        let converted = match self {
+            Case::Snake => words
+                .iter()
+                .map(|w| w.to_lowercase())
+                .collect::<Vec<_>>()
+                .join("_"),
+            Case::UpperSnake => words
+                .iter()
+                .map(|w| w.to_uppercase())
+                .collect::<Vec<_>>()
+                .join("_"),
+            Case::Pascal => words
+                .iter()
+                .map(|w| {
+                    let mut chars = w.chars();
+                    match chars.next() {
+                        Some(first) => first
+                            .to_uppercase()
+                            .chain(chars.flat_map(char::to_lowercase))
+                            .collect(),
+                        None => String::new(),
+                    }
+                })
+                .collect(),
+        };
+
+        format!("{}{}", prefix, converted)
+    }
+
+    fn lint(self, kind: String, name: String, suggestion: String) -> CodeErrorKind {
+        match self {
+            Case::Snake => CodeErrorKind::NonSnakeCase(kind, name, suggestion),
+            Case::Pascal => CodeErrorKind::NonPascalCase(kind, name, suggestion),
+            Case::UpperSnake => CodeErrorKind::NonUpperCase(kind, name, suggestion),
+        }
+    }
+}
+
+/// Checks that item names follow the conventions of the standard library: `snake_case`
+/// functions, macros and fields, `PascalCase` types, protocols and enum members
+/// and `SCREAMING_SNAKE_CASE` constants and statics.
+///
+/// Type aliases are exempt (type operators are conventionally `snake_case`), as are
+/// extern functions and statics, which have to match the foreign name. Items in the
+/// sysroot are not checked.
+pub struct NamingConventions;
+
+impl AstLintPass for NamingConventions {
+    fn check_item<'ast, 'src>(
+        &self,
+        ctx: &AstLintCtx<'_, 'ast>,
+        scope: &Scope<'ast, 'src>,
+        name: Option<&'ast str>,
+        item: &NamedItem<'ast, 'src>,
+    ) {
+        let Some(name) = name else {
+            return;
+        };
+
+        if ctx.is_sysroot(scope) {
+            return;
+        }
+
+        let (case, node) = match &item.kind {
+            NamedItemKind::Function(_, node, _)
+            | NamedItemKind::Method(_, node, _)
+            | NamedItemKind::Static(_, node, _)
+                if node.child_by_field(FieldKind::Extern).is_some() =>
+            {
+                return
+            }
+            NamedItemKind::Function(_, node, _)
+            | NamedItemKind::Method(_, node, _)
+            | NamedItemKind::Macro(_, node, _)
+            | NamedItemKind::Field(node) => (Case::Snake, node),
+            NamedItemKind::Type(_, node, _)
+            | NamedItemKind::Protocol(_, node, _)
+            | NamedItemKind::EnumMember(_, _, node) => (Case::Pascal, node),
+            NamedItemKind::Const(_, node, _) | NamedItemKind::Static(_, node, _) => {
+                (Case::UpperSnake, node)
+            }
+            _ => return,
+        };
+
+        if case.matches(name) {
+            return;
+        }
+
+        let suggestion = case.convert(name);
+        if suggestion.trim_start_matches('_').is_empty() || suggestion == name {
+            return;
+        }
+
+        let Some(code) = scope.code() else {
+            return;
+        };
+        let name_node = node.child_by_field(FieldKind::Name).unwrap_or(*node);
+
+        ctx.diag.add_warning(CodeError {
+            kind: case.lint(item.kind.to_string(), name.to_string(), suggestion),
+            backtrace: vec![Marker::Span(Span::from_node(code.file_id(), name_node))],
+        });
+    }
+}
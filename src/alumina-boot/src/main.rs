@@ -2,26 +2,45 @@
 #![allow(clippy::mutable_key_type)]
 
 mod ast;
+mod build_plan;
+mod cache;
 mod codegen;
 mod common;
 mod compiler;
+mod completion;
 mod diagnostics;
+mod doctest;
+mod features;
 mod global_ctx;
+mod golden;
 mod intrinsics;
 mod ir;
+mod ir_test;
+mod lints;
+mod meta;
+mod name_index;
 mod name_resolution;
 mod parser;
+mod profile;
+mod rename;
+mod repl;
+mod runner;
+mod symbols;
+mod target;
 mod utils;
 mod visitors;
 
-use crate::common::{AluminaError, CodeError};
-use crate::compiler::{Compiler, SourceFile};
-use crate::global_ctx::{GlobalCtx, OutputType};
+use crate::cache::{Cache, CacheKey};
+use crate::common::{CodeError, CodeErrorKind, HASHER_NAME};
+use crate::compiler::{Compiler, EmitKind, SourceFile, SourcePosition};
+use crate::diagnostics::DiagnosticContext;
+use crate::global_ctx::{CDialect, GlobalCtx, IndexChecks, OutputType};
+use crate::target::Target;
 
 use clap::builder::ValueParser;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use std::time::Instant;
 
@@ -35,6 +54,13 @@ fn parse_module(s: &str) -> Result<(Option<String>, PathBuf), std::convert::Infa
     })
 }
 
+fn parse_extern(s: &str) -> Result<(String, PathBuf), String> {
+    match s.split_once('=') {
+        Some((name, path)) => Ok((name.to_string(), path.into())),
+        None => Err("expected `name=path.alumina-meta`".to_string()),
+    }
+}
+
 fn parse_cfg(s: &str) -> Result<(String, Option<String>), std::convert::Infallible> {
     Ok(if let Some(pos) = s.find('=') {
         (s[..pos].to_string(), Some(s[pos + 1..].to_string()))
@@ -43,6 +69,24 @@ fn parse_cfg(s: &str) -> Result<(String, Option<String>), std::convert::Infallib
     })
 }
 
+fn parse_position(s: &str) -> Result<SourcePosition, String> {
+    let mut parts = s.rsplitn(3, ':');
+    let (column, line, filename) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(column), Some(line), Some(filename)) => (column, line, filename),
+        _ => return Err("expected `file:line:column`".to_string()),
+    };
+
+    Ok(SourcePosition {
+        filename: filename.into(),
+        line: line
+            .parse()
+            .map_err(|_| "invalid line number".to_string())?,
+        column: column
+            .parse()
+            .map_err(|_| "invalid column number".to_string())?,
+    })
+}
+
 #[derive(Parser, Debug)]
 #[clap(about, version, author)]
 struct Args {
@@ -58,59 +102,230 @@ struct Args {
     #[clap(value_parser=ValueParser::new(parse_module))]
     modules: Vec<(Option<String>, PathBuf)>,
 
+    /// Library to use from its metadata (see `--emit meta`), mounted as module `name`
+    #[clap(
+        long = "extern",
+        value_name = "NAME=PATH",
+        value_parser = ValueParser::new(parse_extern)
+    )]
+    externs: Vec<(String, PathBuf)>,
+
     /// Compile in debug mode
     #[clap(long, short)]
     debug: bool,
 
+    /// Apply a named set of cfg values and options (see `--profile-file`)
+    #[clap(long)]
+    profile: Option<String>,
+
+    /// File with the profile definitions (defaults to `alumina.profiles`)
+    #[clap(long, value_name = "FILE", requires = "profile")]
+    profile_file: Option<PathBuf>,
+
     /// Collect timings
     #[clap(long)]
     timings: bool,
 
+    /// List all the modules and whether they were excluded by a top-level `#![cfg(...)]`
+    #[clap(long)]
+    print_modules: bool,
+
     /// Whether a library should be output
     #[clap(long)]
     library: bool,
 
+    /// Build the test harness instead of the program: sets `cfg(test)` and replaces
+    /// `main` with the test runner (with `--run`, the tests are run)
+    #[clap(long, conflicts_with_all = ["library", "shared", "repl"])]
+    test: bool,
+
+    /// Also compile the code blocks in the doc comments of the modules being compiled (not
+    /// the sysroot) as tests
+    #[clap(long, requires = "test")]
+    doctests: bool,
+
+    /// Compile a shared library, only the exported functions are visible. If the output
+    /// filename ends with `.so`, `.dylib` or `.dll`, it is built with the system C compiler.
+    #[clap(long, conflicts_with_all = ["library", "run", "script", "repl"])]
+    shared: bool,
+
+    /// Write a linker version script for the exported symbols of a shared library
+    #[clap(long, value_name = "FILE", requires = "shared")]
+    version_script: Option<PathBuf>,
+
+    /// Version node of the symbols in the version script (e.g. 'MYLIB_1.0')
+    #[clap(long, value_name = "VERSION", requires = "version_script")]
+    symbol_version: Option<String>,
+
     /// Conditional compilation options
     #[clap(long, value_parser=ValueParser::new(parse_cfg), action=clap::ArgAction::Append)]
     cfg: Vec<(String, Option<String>)>,
 
+    /// Target triple to set the `target_*` cfg values from (defaults to the host)
+    #[clap(long, value_parser=ValueParser::new(Target::from_triple))]
+    target: Option<Target>,
+
+    /// Print the cfg values the program would be compiled with and exit
+    #[clap(long)]
+    print_cfg: bool,
+
     /// Unstable compiler options
     #[clap(long, short('Z'), action=clap::ArgAction::Append)]
     options: Vec<String>,
+
+    /// Build the program with the system C compiler and run it
+    #[clap(long, conflicts_with_all = ["output", "library"])]
+    run: bool,
+
+    /// Arguments to pass to the program when using `--run`
+    #[clap(last = true)]
+    program_args: Vec<String>,
+
+    /// Build and run a single-file program as module `main`, passing the remaining
+    /// arguments to it (for `#!/usr/bin/env -S alumina-boot --script` shebang lines)
+    #[clap(
+        long,
+        num_args = 1..,
+        allow_hyphen_values = true,
+        value_name = "FILE",
+        conflicts_with_all = ["modules", "output", "library", "run", "program_args"]
+    )]
+    script: Option<Vec<String>>,
+
+    /// Start an interactive session (expressions are evaluated at compile time)
+    #[clap(long, conflicts_with_all = ["modules", "output", "library", "run", "script"])]
+    repl: bool,
+
+    /// Kind of output to produce. Several kinds can be produced at once (e.g. 'c,ir,ast'),
+    /// each of them is then written to `--output` with the extension of the kind
+    #[clap(long, value_enum, value_delimiter = ',', default_value = "c")]
+    emit: Vec<EmitKind>,
+
+    /// Only include the functions whose path starts with the given prefix in `--emit cfg`
+    /// and `--emit ir` (e.g. 'main::foo', can be repeated)
+    #[clap(long, value_name = "PATH")]
+    cfg_filter: Vec<String>,
+
+    /// Dialect of the generated C code
+    #[clap(long, value_enum, default_value = "gnu")]
+    c_dialect: CDialect,
+
+    /// What happens when a slice is indexed out of bounds (by default, debug builds panic
+    /// and release builds do not check)
+    #[clap(long, value_enum)]
+    index_checks: Option<IndexChecks>,
+
+    /// Print the type of the expression at the given position ('file.alu:line:column')
+    #[clap(long, value_parser=ValueParser::new(parse_position), conflicts_with_all = ["output", "run", "repl"])]
+    type_at: Option<SourcePosition>,
+
+    /// List the names that can be completed at the given position ('file.alu:line:column'),
+    /// one `name<TAB>kind<TAB>signature` per line
+    #[clap(long, value_parser=ValueParser::new(parse_position), conflicts_with_all = ["output", "run", "repl", "type_at"])]
+    complete: Option<SourcePosition>,
+
+    /// Rename the local, item or field at the given position ('file.alu:line:column')
+    /// everywhere it is used, rewriting the source files
+    #[clap(
+        long,
+        num_args = 2,
+        value_names = ["POSITION", "NEW_NAME"],
+        conflicts_with_all = ["output", "run", "repl", "type_at", "complete"]
+    )]
+    rename: Option<Vec<String>>,
+
+    /// Run IR pass tests (files in the `--emit ir` text format with `// RUN:` and
+    /// `// CHECK:` lines) instead of compiling
+    #[clap(
+        long,
+        num_args = 1..,
+        value_name = "FILE",
+        conflicts_with_all = ["modules", "output", "run", "script", "repl"]
+    )]
+    ir_test: Vec<PathBuf>,
+
+    /// Directory to cache the generated C code in, so that unchanged programs are not
+    /// compiled again
+    #[clap(long, env = "ALUMINA_CACHE_DIR", value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Size limit of the cache in MiB, the least recently used entries are removed when it
+    /// is exceeded
+    #[clap(long, value_name = "MIB", default_value = "256")]
+    cache_size: u64,
+
+    /// Do not use the cache, even if `--cache-dir` or `ALUMINA_CACHE_DIR` is set
+    #[clap(long)]
+    no_cache: bool,
+
+    /// Explain the cache hits and misses in notes
+    #[clap(long)]
+    cache_verbose: bool,
 }
 
-fn infer_module_name(path: &std::path::Path) -> &str {
-    path.file_stem().unwrap().to_str().unwrap()
+fn infer_module_name(path: &Path) -> Option<&str> {
+    path.file_stem()?.to_str()
 }
 
-fn get_sysroot(args: &Args) -> Result<Vec<SourceFile>, AluminaError> {
+/// Collects the modules of the sysroot. Entries that cannot be read (permissions, broken
+/// symlinks, names that are not UTF-8) are skipped with a warning, so that one bad file does
+/// not stop the compilation. Only a sysroot that cannot be read at all is an error.
+fn get_sysroot(args: &Args, diag: &DiagnosticContext) -> Vec<SourceFile> {
     let mut result = Vec::new();
 
     let sysroot = if let Some(sysroot) = &args.sysroot {
         sysroot
     } else {
-        return Ok(vec![]);
+        return result;
     };
 
     for maybe_entry in WalkDir::new(sysroot).follow_links(true).into_iter() {
         use std::fmt::Write;
-        let entry = maybe_entry?;
+        let entry = match maybe_entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let path = e.path().unwrap_or(sysroot).display().to_string();
+                let reason = match e.io_error() {
+                    Some(io_error) => io_error.to_string(),
+                    None => e.to_string(),
+                };
+                if e.depth() == 0 {
+                    diag.add_error(CodeError::from_kind(
+                        CodeErrorKind::CannotReadPath(path, reason),
+                        None,
+                    ));
+                } else {
+                    diag.add_warning(CodeError::from_kind(
+                        CodeErrorKind::SysrootEntrySkipped(path, reason),
+                        None,
+                    ));
+                }
+                continue;
+            }
+        };
         if entry.file_type().is_dir() {
             continue;
         }
 
-        let filename = entry.file_name().to_string_lossy();
-        if !filename.ends_with(".alu") {
+        if entry.path().extension().map_or(true, |ext| ext != "alu") {
             continue;
         }
 
-        let path_segments: Vec<_> = entry
-            .path()
-            .strip_prefix(sysroot)
-            .unwrap()
+        let relative = entry.path().strip_prefix(sysroot).unwrap_or(entry.path());
+        let Some(path_segments) = relative
             .iter()
-            .map(|s| s.to_string_lossy())
-            .collect();
+            .map(|s| s.to_str())
+            .collect::<Option<Vec<_>>>()
+        else {
+            diag.add_warning(CodeError::from_kind(
+                CodeErrorKind::SysrootEntrySkipped(
+                    entry.path().display().to_string(),
+                    "the path is not valid UTF-8".to_string(),
+                ),
+                None,
+            ));
+            continue;
+        };
 
         let mut module_path = String::new();
         for (index, segment) in path_segments.iter().enumerate() {
@@ -129,34 +344,187 @@ fn get_sysroot(args: &Args) -> Result<Vec<SourceFile>, AluminaError> {
         result.push(SourceFile {
             filename: entry.into_path(),
             path: module_path,
+            contents: None,
+            strip_shebang: false,
+            sysroot: true,
         });
     }
 
-    Ok(result)
+    result
+}
+
+/// Checks that the kinds given with `--emit` can be produced together
+fn check_emit_kinds(args: &Args) -> Result<(), String> {
+    if args.emit.len() < 2 {
+        return Ok(());
+    }
+
+    for (index, kind) in args.emit.iter().enumerate() {
+        let name = kind.to_possible_value().unwrap().get_name().to_string();
+        if kind.multi_extension().is_none() {
+            return Err(format!(
+                "`--emit {}` cannot be combined with other kinds of output",
+                name
+            ));
+        }
+        if args.emit[..index].contains(kind) {
+            return Err(format!("`--emit {}` is given more than once", name));
+        }
+    }
+
+    if args.output.is_none() {
+        return Err("`--output` is required when emitting several kinds of output".to_string());
+    }
+    if args.shared {
+        return Err("`--shared` cannot be used when emitting several kinds of output".to_string());
+    }
+
+    Ok(())
+}
+
+/// Writes the output of the compilation. When several kinds are emitted, each of them goes
+/// to `--output` with the extension of its kind (`prog.c`, `prog.ir`, ...).
+fn write_outputs(output: Option<&str>, emit: &[EmitKind], compiler: &Compiler, program: &str) {
+    match output {
+        Some(filename) if emit.len() > 1 => {
+            let filename = Path::new(filename);
+            let outputs = std::iter::once((emit[0], program)).chain(
+                compiler
+                    .additional_outputs()
+                    .iter()
+                    .map(|(kind, output)| (*kind, output.as_str())),
+            );
+            for (kind, output) in outputs {
+                std::fs::write(
+                    filename.with_extension(kind.multi_extension().unwrap()),
+                    output,
+                )
+                .unwrap();
+            }
+        }
+        Some(filename) => std::fs::write(filename, program).unwrap(),
+        None => print!("{}", program),
+    }
 }
 
-fn run(args: Args) -> Result<(), ()> {
+fn run(mut args: Args) -> Result<ExitCode, ()> {
     let start_time = Instant::now();
-    let output_type = if args.library {
+
+    if let Some(name) = &args.profile {
+        match profile::load(name, args.profile_file.as_deref()) {
+            Ok(profile) => {
+                // The command line takes precedence, so the profile goes first
+                args.options.splice(0..0, profile.options);
+                args.cfg.splice(0..0, profile.cfg);
+                args.debug |= profile.debug.unwrap_or(false);
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return Err(());
+            }
+        }
+    }
+
+    if let Err(e) = check_emit_kinds(&args) {
+        eprintln!("error: {}", e);
+        return Err(());
+    }
+
+    let output_type = if args.library || args.repl {
         OutputType::Library
+    } else if args.shared {
+        OutputType::SharedLibrary
     } else {
         OutputType::Executable
     };
 
     let mut global_ctx = GlobalCtx::new(output_type, args.options.clone());
+    global_ctx.set_c_dialect(args.c_dialect);
+    if let Some(mode) = args.index_checks {
+        global_ctx.set_index_checks(mode);
+    }
+    if !args.ir_test.is_empty() {
+        return if ir_test::run(&global_ctx, &args.ir_test) {
+            Ok(ExitCode::SUCCESS)
+        } else {
+            Err(())
+        };
+    }
+
     let mut compiler = Compiler::new(global_ctx.clone());
+    compiler.set_emit(args.emit[0]);
+    compiler.set_also_emit(args.emit[1..].to_vec());
+    compiler.set_cfg_filters(args.cfg_filter.clone());
+    compiler.set_doctests(args.doctests);
+    if let Some(position) = args.type_at.clone() {
+        compiler.set_type_query(position);
+    }
+    if let Some(position) = args.complete.clone() {
+        compiler.set_completion_query(position);
+    }
+    if let Some([position, new_name]) = args.rename.as_deref() {
+        match parse_position(position) {
+            Ok(position) => compiler.set_rename_query(position, new_name.clone()),
+            Err(e) => {
+                eprintln!("error: invalid position `{}`: {}", position, e);
+                return Err(());
+            }
+        }
+    }
 
-    let mut files = get_sysroot(&args).unwrap();
+    let mut files = get_sysroot(&args, &global_ctx.diag());
     for (path, filename) in &args.modules {
+        let path = match path.as_deref().or_else(|| infer_module_name(filename)) {
+            Some(path) => path.to_string(),
+            None => {
+                global_ctx.diag().add_error(CodeError::from_kind(
+                    CodeErrorKind::InvalidModuleFilename(filename.display().to_string()),
+                    None,
+                ));
+                continue;
+            }
+        };
         files.push(SourceFile {
             filename: filename.clone(),
-            path: path
-                .as_deref()
-                .unwrap_or_else(|| infer_module_name(filename))
-                .to_string(),
+            path,
+            contents: None,
+            strip_shebang: false,
+            sysroot: false,
         });
     }
 
+    for (name, filename) in &args.externs {
+        match meta::read(&global_ctx.diag(), name, filename) {
+            Ok(modules) => files.extend(modules),
+            Err(e) => global_ctx.diag().add_from_error(e).unwrap(),
+        }
+    }
+
+    // All the problems with the inputs are reported at once
+    if global_ctx.diag().has_errors() {
+        global_ctx.diag().print_error_report().unwrap();
+        return Err(());
+    }
+
+    let (should_run, program_args) = match &args.script {
+        Some(script) => {
+            let (filename, program_args) = script.split_first().unwrap();
+            files.push(SourceFile {
+                filename: filename.into(),
+                path: "main".to_string(),
+                contents: None,
+                strip_shebang: true,
+                sysroot: false,
+            });
+            (true, program_args)
+        }
+        None => (args.run, &args.program_args[..]),
+    };
+
+    if let Some(target) = &args.target {
+        global_ctx.set_target(target);
+    }
+
     for (key, value) in args.cfg {
         if let Some(value) = value {
             global_ctx.add_cfg(key, value)
@@ -169,10 +537,180 @@ fn run(args: Args) -> Result<(), ()> {
         global_ctx.add_flag("debug");
     }
 
+    if args.test {
+        global_ctx.add_flag("test");
+    }
+
+    if args.print_cfg {
+        for (key, value) in global_ctx.cfg_values() {
+            match value {
+                Some(value) => println!("{}={:?}", key, value),
+                None => println!("{}", key),
+            }
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.emit[0] == EmitKind::BuildPlan {
+        let output = if should_run {
+            build_plan::Output::Run(program_args)
+        } else {
+            match args.output.as_deref() {
+                Some(output)
+                    if args.shared
+                        && [".so", ".dylib", ".dll"]
+                            .iter()
+                            .any(|ext| output.ends_with(ext)) =>
+                {
+                    build_plan::Output::SharedLibrary(Path::new(output))
+                }
+                output => build_plan::Output::C(output.map(Path::new)),
+            }
+        };
+
+        print!(
+            "{}",
+            build_plan::format(
+                &global_ctx,
+                &args.options,
+                &files,
+                &args.externs,
+                output,
+                args.version_script.as_deref()
+            )
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.repl {
+        return match repl::run(&global_ctx, files) {
+            Ok(()) => Ok(ExitCode::SUCCESS),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                Err(())
+            }
+        };
+    }
+
+    if args.emit[0] == EmitKind::DiagnosticsGolden {
+        let source_files = files.clone();
+        if let Err(e) = compiler.compile(files, start_time) {
+            global_ctx.diag().add_from_error(e).unwrap();
+        }
+
+        let diag_ctx = global_ctx.diag();
+        return if golden::report(&diag_ctx, &source_files, args.sysroot.as_deref()) {
+            Ok(ExitCode::SUCCESS)
+        } else {
+            Err(())
+        };
+    }
+
+    if let Some([_, new_name]) = args.rename.as_deref() {
+        let result = compiler.compile(files, start_time);
+        let diag_ctx = global_ctx.diag();
+        if let Err(e) = result {
+            diag_ctx.add_from_error(e).unwrap();
+        }
+        if diag_ctx.has_errors() {
+            diag_ctx.print_error_report().unwrap();
+            return Err(());
+        }
+
+        return match rename::apply(&diag_ctx, compiler.rename_edits(), new_name) {
+            Ok(locations) => {
+                for location in locations {
+                    println!("{}", location);
+                }
+                Ok(ExitCode::SUCCESS)
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                Err(())
+            }
+        };
+    }
+
+    if args.complete.is_some() {
+        // The code being completed is usually incomplete, so errors are only reported if
+        // there is nothing to offer.
+        let result = compiler.compile(files, start_time);
+        let completions = global_ctx.completions();
+        if completions.is_empty() {
+            let diag_ctx = global_ctx.diag();
+            if let Err(e) = result {
+                diag_ctx.add_from_error(e).unwrap();
+            }
+            if diag_ctx.has_errors() {
+                diag_ctx.print_error_report().unwrap();
+                return Err(());
+            }
+        }
+
+        print!("{}", completion::format_completions(completions));
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // Only plain C output is cached, with no other side effects or queries. The current time
+    // (`-Zbuild-timestamp` without `SOURCE_DATE_EPOCH`) would make every entry stale.
+    let cache = args
+        .cache_dir
+        .clone()
+        .filter(|_| {
+            !args.no_cache
+                && args.emit == [EmitKind::C]
+                && !should_run
+                && !args.shared
+                && !args.timings
+                && !args.print_modules
+                && args.type_at.is_none()
+                && !(global_ctx.has_option("build-timestamp")
+                    && std::env::var_os("SOURCE_DATE_EPOCH").is_none())
+        })
+        .map(|dir| Cache::new(dir, args.cache_size * 1024 * 1024, args.cache_verbose));
+
+    let cache_key = cache.as_ref().and_then(|_| {
+        let mut key = CacheKey::new(
+            &global_ctx,
+            &args.options,
+            &files,
+            args.output.as_deref().unwrap_or("<stdout>"),
+        )?;
+        key.add("c-dialect", format!("{:?}", args.c_dialect));
+        key.add("index-checks", format!("{:?}", global_ctx.index_checks()));
+        key.add("doctests", args.doctests.to_string());
+        Some(key)
+    });
+
+    if let (Some(cache), Some(key)) = (&cache, &cache_key) {
+        let diag_ctx = global_ctx.diag();
+        if let Some(program) = cache.lookup(&diag_ctx, key) {
+            diag_ctx.print_error_report().unwrap();
+            match &args.output {
+                Some(filename) => std::fs::write(filename, program).unwrap(),
+                None => print!("{}", program),
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+
     match compiler.compile(files, start_time) {
         Ok(program) => {
             let diag_ctx = global_ctx.diag();
+            if let (Some(cache), Some(key)) = (&cache, &cache_key) {
+                cache.store(
+                    &diag_ctx,
+                    key,
+                    &global_ctx.dependencies(),
+                    &global_ctx.env_vars(),
+                    &program,
+                );
+            }
             if args.timings {
+                diag_ctx.add_note(CodeError::freeform(format!(
+                    "compiler timings: using the {} hasher",
+                    HASHER_NAME
+                )));
                 for (stage, duration) in compiler.timings() {
                     diag_ctx.add_note(CodeError::freeform(format!(
                         "compiler timings: stage {:?} took {}ms",
@@ -181,16 +719,85 @@ fn run(args: Args) -> Result<(), ()> {
                     )));
                 }
             }
+            if args.print_modules {
+                for module in compiler.modules() {
+                    let status = match &module.excluded_by {
+                        Some(predicate) => format!("excluded, `{}` does not hold", predicate),
+                        None => "included".to_string(),
+                    };
+                    diag_ctx.add_note(CodeError::freeform(format!(
+                        "module {} ({}): {}",
+                        module.path,
+                        module.filename.display(),
+                        status
+                    )));
+                }
+            }
             diag_ctx.print_error_report().unwrap();
             if diag_ctx.has_errors() {
+                // Editor tooling outputs are still useful with a few unresolved names
+                if global_ctx.error_tolerant() {
+                    write_outputs(args.output.as_deref(), &args.emit, &compiler, &program);
+                }
                 return Err(());
             }
-            match args.output {
-                Some(filename) => std::fs::write(filename, program).unwrap(),
-                None => {
-                    print!("{}", program);
+            drop(diag_ctx);
+
+            if args.type_at.is_some() {
+                let results = compiler.type_query_results();
+                if results.is_empty() {
+                    eprintln!("error: no expression found at the given position");
+                    return Err(());
                 }
+                for result in results {
+                    println!("{}", result);
+                }
+                return Ok(ExitCode::SUCCESS);
             }
+
+            if should_run {
+                return match runner::build_and_run(&global_ctx, &program, program_args) {
+                    Ok(exit_code) => Ok(exit_code),
+                    Err(e) => {
+                        let diag_ctx = global_ctx.diag();
+                        diag_ctx.add_from_error(e).unwrap();
+                        diag_ctx.print_error_report().unwrap();
+                        Err(())
+                    }
+                };
+            }
+
+            if let Some(version_script) = &args.version_script {
+                std::fs::write(
+                    version_script,
+                    runner::version_script(
+                        compiler.exported_symbols(),
+                        args.symbol_version.as_deref(),
+                    ),
+                )
+                .unwrap();
+            }
+
+            if let Some(output) = args.output.as_deref().filter(|o| {
+                args.shared && [".so", ".dylib", ".dll"].iter().any(|ext| o.ends_with(ext))
+            }) {
+                return match runner::build_shared_library(
+                    &global_ctx,
+                    &program,
+                    output.as_ref(),
+                    args.version_script.as_deref(),
+                ) {
+                    Ok(()) => Ok(ExitCode::SUCCESS),
+                    Err(e) => {
+                        let diag_ctx = global_ctx.diag();
+                        diag_ctx.add_from_error(e).unwrap();
+                        diag_ctx.print_error_report().unwrap();
+                        Err(())
+                    }
+                };
+            }
+
+            write_outputs(args.output.as_deref(), &args.emit, &compiler, &program);
         }
         Err(e) => {
             let diag_ctx = global_ctx.diag();
@@ -200,14 +807,10 @@ fn run(args: Args) -> Result<(), ()> {
         }
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
 
 fn main() -> ExitCode {
     let args = Args::parse();
-    if run(args).is_err() {
-        ExitCode::FAILURE
-    } else {
-        ExitCode::SUCCESS
-    }
+    run(args).unwrap_or(ExitCode::FAILURE)
 }
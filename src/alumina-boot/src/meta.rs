@@ -0,0 +1,174 @@
+//! Library metadata (`--emit meta` and `--extern`)
+//!
+//! A library can be compiled into a single `.alumina-meta` file that downstream programs
+//! use with `--extern name=path.alumina-meta` instead of passing every module of the
+//! library on the command line. The library is mounted under `name`, replacing its own
+//! root module name.
+//!
+//! The interface is stored as the source of the library's modules (generic items and
+//! macros need their bodies to be instantiated, and the AST is not serializable), so the
+//! modules are parsed again when the library is used. What the file guarantees is that the
+//! library compiled cleanly with the same compiler version when it was written.
+//!
+//! Precompiled generic bodies are not stored yet. The IR is only produced during
+//! monomorphization, for concrete types, so storing them needs a serializable form of
+//! generic bodies that is independent of the consumer's instantiations. Until then, the
+//! format version (`alumina-meta 2`) and the per-module hashes are what detect files that
+//! are stale or were written by another compiler.
+//!
+//! ```text
+//! alumina-meta 2
+//! compiler 0.1.0
+//! module 1234 9f1c2b3a4d5e6f70 util src/util.alu
+//! <1234 bytes of source>
+//! ```
+//!
+//! The module path is relative to the root module (`.` for the root itself). Each module
+//! has a hash of its source, which is checked when the file is read (to detect a damaged
+//! file) and compared against the original file, if it still exists, to warn about
+//! metadata that is out of date.
+
+use crate::common::{AluminaError, CodeError, CodeErrorBuilder, CodeErrorKind, FileId};
+use crate::compiler::SourceFile;
+use crate::diagnostics::DiagnosticContext;
+use crate::parser::read_source;
+use crate::utils::stable_hash;
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+const MAGIC: &str = "alumina-meta 2";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+fn split_root(path: &str) -> (&str, &str) {
+    let path = path.trim_start_matches("::");
+    match path.split_once("::") {
+        Some((root, rest)) => (root, rest),
+        None => (path, ""),
+    }
+}
+
+/// Writes the metadata for the library made of the given modules (path, filename and
+/// source). All of them have to be under the same root module.
+pub fn write(modules: &[(String, PathBuf, &str)]) -> Result<String, AluminaError> {
+    let mut result = String::new();
+    let _ = writeln!(result, "{}", MAGIC);
+    let _ = writeln!(result, "compiler {}", VERSION);
+
+    let mut root = None;
+    for (path, filename, source) in modules {
+        let (module_root, rest) = split_root(path);
+        match root {
+            None => root = Some(module_root),
+            Some(root) if root != module_root => {
+                return Err(CodeErrorKind::MetaMultipleRoots(
+                    root.to_string(),
+                    module_root.to_string(),
+                ))
+                .with_no_span()
+            }
+            _ => {}
+        }
+
+        let _ = writeln!(
+            result,
+            "module {} {:016x} {} {}",
+            source.len(),
+            stable_hash(source.as_bytes()),
+            if rest.is_empty() { "." } else { rest },
+            filename.display()
+        );
+        result.push_str(source);
+        result.push('\n');
+    }
+
+    Ok(result)
+}
+
+/// Reads the modules of a library from its metadata, mounted under `name`. Modules whose
+/// original file has changed since the metadata was written are reported as warnings.
+pub fn read(
+    diag: &DiagnosticContext,
+    name: &str,
+    filename: &Path,
+) -> Result<Vec<SourceFile>, AluminaError> {
+    let invalid = |reason: &str| {
+        Err(CodeErrorKind::InvalidMeta(
+            filename.display().to_string(),
+            reason.to_string(),
+        ))
+        .with_no_span()
+    };
+
+    let contents = std::fs::read_to_string(filename)
+        .map_err(|e| CodeErrorKind::CannotReadPath(filename.display().to_string(), e.to_string()))
+        .with_no_span()?;
+    let Some(rest) = contents
+        .strip_prefix(MAGIC)
+        .and_then(|r| r.strip_prefix('\n'))
+    else {
+        return invalid("not a metadata file");
+    };
+
+    let Some((compiler, mut rest)) = rest.split_once('\n') else {
+        return invalid("truncated file");
+    };
+    if compiler.strip_prefix("compiler ") != Some(VERSION) {
+        return invalid(&format!(
+            "written by a different compiler version (`{}`, this is `{}`)",
+            compiler.trim_start_matches("compiler "),
+            VERSION
+        ));
+    }
+
+    let mut result = Vec::new();
+    while !rest.is_empty() {
+        let Some((header, after)) = rest.split_once('\n') else {
+            return invalid("truncated file");
+        };
+        let mut parts = header.splitn(5, ' ');
+        let (Some("module"), Some(len), Some(hash), Some(path), Some(original)) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return invalid("malformed module header");
+        };
+        let Some(source) = len.parse::<usize>().ok().and_then(|len| after.get(..len)) else {
+            return invalid("truncated module");
+        };
+        let Ok(hash) = u64::from_str_radix(hash, 16) else {
+            return invalid("malformed module header");
+        };
+        if stable_hash(source.as_bytes()) != hash {
+            return invalid(&format!("the source of module `{}` is damaged", path));
+        }
+
+        // The original file is compared the way the compiler would read it
+        if let Ok(current) = read_source(FileId { id: 0 }, Path::new(original)) {
+            if stable_hash(current.as_bytes()) != hash {
+                diag.add_warning(CodeError::from_kind(
+                    CodeErrorKind::StaleMeta(filename.display().to_string(), original.to_string()),
+                    None,
+                ));
+            }
+        }
+
+        result.push(SourceFile {
+            filename: format!("{}({})", filename.display(), original).into(),
+            path: match path {
+                "." => name.to_string(),
+                path => format!("{}::{}", name, path),
+            },
+            contents: Some(source.to_string()),
+            strip_shebang: false,
+            sysroot: true,
+        });
+
+        rest = after[source.len()..].strip_prefix('\n').unwrap_or("");
+    }
+
+    Ok(result)
+}
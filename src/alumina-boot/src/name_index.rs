@@ -0,0 +1,108 @@
+//! Resolved name occurrences, collected for editor tooling (`--emit defs-index`
+//! and `--emit semantic-tokens`).
+use crate::ast::Span;
+use crate::diagnostics::DiagnosticContext;
+
+use strum_macros::AsRefStr;
+
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum SymbolKind {
+    Type,
+    Protocol,
+    Function,
+    Method,
+    Macro,
+    Const,
+    Static,
+    Variable,
+    Parameter,
+    TypeParameter,
+    Field,
+    EnumMember,
+    Module,
+}
+
+/// An occurrence of a name that was resolved to an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NameRef {
+    pub usage: Span,
+    pub definition: Option<Span>,
+    pub kind: SymbolKind,
+    pub mutable: bool,
+    pub generic: bool,
+}
+
+/// `file:line:column:length` of the span (1-based line and column, column and length
+/// in bytes).
+pub fn location(diag: &DiagnosticContext, span: Span) -> String {
+    let path = diag.get_file_path(span.file);
+    let (line, column) = match diag.line_map(span.file) {
+        Some(line_map) => {
+            let line = line_map.line(span.start);
+            (line + 1, span.start - line_map.line_start(line) + 1)
+        }
+        None => (1, span.start + 1),
+    };
+
+    format!(
+        "{}:{}:{}:{}",
+        path.map(|p| p.display().to_string()).unwrap_or_default(),
+        line,
+        column,
+        span.len()
+    )
+}
+
+fn sorted(mut names: Vec<NameRef>) -> Vec<NameRef> {
+    names.sort_by_key(|n| (n.usage.file, n.usage.start, n.usage.end));
+    names.dedup();
+    names
+}
+
+/// Formats the definitions as `usage<TAB>definition` lines, where both are
+/// `file:line:column:length` (1-based line and column, length in bytes).
+pub fn format_defs_index(diag: &DiagnosticContext, names: Vec<NameRef>) -> String {
+    let mut result = String::new();
+
+    for name in sorted(names) {
+        if let Some(definition) = name.definition {
+            let _ = writeln!(
+                result,
+                "{}\t{}",
+                location(diag, name.usage),
+                location(diag, definition)
+            );
+        }
+    }
+
+    result
+}
+
+/// Formats the names as `location<TAB>kind<TAB>modifiers` lines, where location is
+/// `file:line:column:length` and modifiers is a comma-separated list (possibly empty).
+pub fn format_semantic_tokens(diag: &DiagnosticContext, names: Vec<NameRef>) -> String {
+    let mut result = String::new();
+
+    for name in sorted(names) {
+        let mut modifiers = Vec::new();
+        if name.mutable {
+            modifiers.push("mutable");
+        }
+        if name.generic {
+            modifiers.push("generic");
+        }
+
+        let _ = writeln!(
+            result,
+            "{}\t{}\t{}",
+            location(diag, name.usage),
+            name.kind.as_ref(),
+            modifiers.join(",")
+        );
+    }
+
+    result
+}
@@ -15,6 +15,15 @@ use tree_sitter::Node;
 type ItemMap<'ast, 'src> =
     IndexMap<(Scope<'ast, 'src>, Option<&'ast str>), Vec<NamedItem<'ast, 'src>>>;
 
+/// Which function is the entry point of the program
+enum MainKind<'ast> {
+    /// `main` in the given module
+    Program(Path<'ast>),
+    /// The `#[test_main]` function of the test harness (`--test`), the program's own
+    /// `main` is not used
+    TestHarness,
+}
+
 pub struct FirstPassVisitor<'ast, 'src> {
     global_ctx: GlobalCtx,
     ast: &'ast AstCtx<'ast>,
@@ -23,7 +32,7 @@ pub struct FirstPassVisitor<'ast, 'src> {
     enum_item: Option<ItemP<'ast>>,
 
     in_a_container: bool,
-    main_module_path: Option<Path<'ast>>,
+    main_kind: Option<MainKind<'ast>>,
     main_candidate: Option<ItemP<'ast>>,
 
     items: ItemMap<'ast, 'src>,
@@ -46,7 +55,7 @@ impl<'ast, 'src> FirstPassVisitor<'ast, 'src> {
             scope,
             in_a_container: false,
             enum_item: None,
-            main_module_path: None,
+            main_kind: None,
             main_candidate: None,
             items: IndexMap::default(),
             macro_ctx,
@@ -65,7 +74,11 @@ impl<'ast, 'src> FirstPassVisitor<'ast, 'src> {
             code: scope
                 .code()
                 .expect("cannot run on scope without parse context"),
-            main_module_path: Some(scope.path()),
+            main_kind: Some(if global_ctx.test_mode() {
+                MainKind::TestHarness
+            } else {
+                MainKind::Program(scope.path())
+            }),
             scope,
             in_a_container: false,
             enum_item: None,
@@ -333,24 +346,20 @@ impl<'ast, 'src> AluminaVisitor<'src> for FirstPassVisitor<'ast, 'src> {
 
         let name = self.parse_name(node);
 
-        if let Some(path) = self.main_module_path.as_ref() {
-            if self.global_ctx.cfg("test").is_some() {
-                if attributes.contains(&Attribute::TestMain)
-                    && self.main_candidate.replace(item).is_some()
-                {
-                    return Err(CodeErrorKind::MultipleMainFunctions)
-                        .with_span_from(&self.scope, node);
-                }
-            } else if &self.scope.path() == path
-                && name == "main"
-                && !attributes.contains(&Attribute::Export)
-                && !attributes
-                    .iter()
-                    .any(|a| matches!(a, Attribute::LinkName(..)))
-                && self.main_candidate.replace(item).is_some()
-            {
-                return Err(CodeErrorKind::MultipleMainFunctions).with_span_from(&self.scope, node);
+        let is_main = match &self.main_kind {
+            Some(MainKind::TestHarness) => attributes.contains(&Attribute::TestMain),
+            Some(MainKind::Program(path)) => {
+                &self.scope.path() == path
+                    && name == "main"
+                    && !attributes.contains(&Attribute::Export)
+                    && !attributes
+                        .iter()
+                        .any(|a| matches!(a, Attribute::LinkName(..)))
             }
+            None => false,
+        };
+        if is_main && self.main_candidate.replace(item).is_some() {
+            return Err(CodeErrorKind::MultipleMainFunctions).with_span_from(&self.scope, node);
         }
 
         let child_scope = self.scope.named_child(ScopeType::Function, name);
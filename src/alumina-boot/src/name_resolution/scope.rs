@@ -1,8 +1,9 @@
 use crate::ast::{AstId, Attribute, ItemP, Span};
 use crate::common::{CodeError, CodeErrorKind, FileId, HashSet, IndexMap, Marker};
 use crate::diagnostics::DiagnosticContext;
+use crate::name_index::{NameRef, SymbolKind};
 use crate::name_resolution::path::{Path, PathSegment};
-use crate::parser::ParseCtx;
+use crate::parser::{FieldKind, NodeExt, ParseCtx};
 
 use indexmap::map::Entry;
 use once_cell::unsync::OnceCell;
@@ -87,6 +88,98 @@ impl<'ast, 'src> NamedItem<'ast, 'src> {
         }
     }
 
+    /// Describes an occurrence of this item at `usage` for editor tooling. `scope` is the scope
+    /// in which the name was resolved (used for items that are always local to it, such as
+    /// parameters).
+    pub fn name_ref(&self, scope: &Scope<'ast, 'src>, usage: Option<Span>) -> Option<NameRef> {
+        let (kind, definition, mutable, generic) = self.symbol_info(scope)?;
+
+        Some(NameRef {
+            usage: usage?,
+            definition,
+            kind,
+            mutable,
+            generic,
+        })
+    }
+
+    /// Span of the definition, as it appears in the name index.
+    pub fn definition_span(&self, scope: &Scope<'ast, 'src>) -> Option<Span> {
+        self.symbol_info(scope)?.1
+    }
+
+    fn symbol_info(
+        &self,
+        scope: &Scope<'ast, 'src>,
+    ) -> Option<(SymbolKind, Option<Span>, bool, bool)> {
+        let node_span = |item_scope: &Scope<'ast, 'src>, node: &Node<'src>| {
+            item_scope
+                .code()
+                .map(|code| Span::from_node(code.file_id(), *node))
+        };
+        let is_generic =
+            |node: &Node<'src>| node.child_by_field(FieldKind::TypeArguments).is_some();
+
+        let info = match &self.kind {
+            NamedItemKind::Function(_, node, item_scope) => (
+                SymbolKind::Function,
+                node_span(item_scope, node),
+                false,
+                is_generic(node),
+            ),
+            NamedItemKind::Method(_, node, item_scope) => (
+                SymbolKind::Method,
+                node_span(item_scope, node),
+                false,
+                is_generic(node),
+            ),
+            NamedItemKind::TypeDef(_, node, item_scope)
+            | NamedItemKind::Type(_, node, item_scope) => (
+                SymbolKind::Type,
+                node_span(item_scope, node),
+                false,
+                is_generic(node),
+            ),
+            NamedItemKind::Protocol(_, node, item_scope) => (
+                SymbolKind::Protocol,
+                node_span(item_scope, node),
+                false,
+                is_generic(node),
+            ),
+            NamedItemKind::Static(_, node, item_scope) => (
+                SymbolKind::Static,
+                node_span(item_scope, node),
+                true,
+                is_generic(node),
+            ),
+            NamedItemKind::Const(_, node, item_scope) => {
+                (SymbolKind::Const, node_span(item_scope, node), false, false)
+            }
+            NamedItemKind::Macro(_, node, item_scope) => {
+                (SymbolKind::Macro, node_span(item_scope, node), false, false)
+            }
+            NamedItemKind::Placeholder(_, node) => (
+                SymbolKind::TypeParameter,
+                node_span(scope, node),
+                false,
+                false,
+            ),
+            NamedItemKind::Parameter(_, node) => {
+                (SymbolKind::Parameter, node_span(scope, node), false, false)
+            }
+            NamedItemKind::MacroParameter(_, _, span) => {
+                (SymbolKind::Parameter, Some(*span), false, false)
+            }
+            NamedItemKind::Local(_, span) | NamedItemKind::BoundValue(_, _, _, span) => {
+                (SymbolKind::Variable, Some(*span), true, false)
+            }
+            NamedItemKind::EnumMember(_, _, _) => (SymbolKind::EnumMember, None, false, false),
+            _ => return None,
+        };
+
+        Some(info)
+    }
+
     pub fn ast_id(&self) -> Option<AstId> {
         match &self.kind {
             NamedItemKind::Alias(_, _) => None,
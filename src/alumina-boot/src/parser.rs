@@ -1,14 +1,64 @@
 use crate::ast::Span;
-use crate::common::{AluminaError, CodeError, CodeErrorKind, FileId, Marker};
+use crate::common::{AluminaError, CodeError, CodeErrorBuilder, CodeErrorKind, FileId, Marker};
 
 use once_cell::unsync::OnceCell;
 
 use std::marker::PhantomData;
+use std::path::Path;
 
 use tree_sitter_traversal::{traverse, Order};
 
 include!(concat!(env!("OUT_DIR"), "/parser.rs"));
 
+/// Byte order mark, which some editors put at the start of UTF-8 files
+pub const BYTE_ORDER_MARK: &[u8] = b"\xef\xbb\xbf";
+
+/// Reads a source file, dropping the byte order mark if there is one.
+///
+/// Byte offsets in spans are relative to the text returned by this function (and not to
+/// the bytes of the file), so anything that maps spans back to the source should read
+/// the file through it (or [`read_source_lossy`]).
+pub fn read_source(file_id: FileId, filename: &Path) -> Result<String, AluminaError> {
+    let bytes = std::fs::read(filename)
+        .map_err(|e| CodeErrorKind::CannotReadPath(filename.display().to_string(), e.to_string()))
+        .with_no_span()?;
+    let (bom_len, bytes) = match bytes.strip_prefix(BYTE_ORDER_MARK) {
+        Some(rest) => (BYTE_ORDER_MARK.len(), rest),
+        None => (0, &bytes[..]),
+    };
+
+    match std::str::from_utf8(bytes) {
+        Ok(source) => Ok(source.to_string()),
+        Err(e) => {
+            let offset = e.valid_up_to();
+            let valid = &bytes[..offset];
+            let line_start = valid
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .map(|idx| idx + 1)
+                .unwrap_or(0);
+            let span = Span {
+                start: offset,
+                end: offset + e.error_len().unwrap_or(bytes.len() - offset),
+                line: valid.iter().filter(|&&b| b == b'\n').count(),
+                column: offset - line_start,
+                file: file_id,
+            };
+
+            Err(CodeErrorKind::InvalidUtf8(bytes[offset], bom_len + offset)).with_span(Some(span))
+        }
+    }
+}
+
+/// Like [`read_source`], but replaces invalid UTF-8 sequences (for showing the source in
+/// diagnostics).
+pub fn read_source_lossy(filename: &Path) -> Option<String> {
+    let bytes = std::fs::read(filename).ok()?;
+    let bytes = bytes.strip_prefix(BYTE_ORDER_MARK).unwrap_or(&bytes);
+
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
 pub struct ParseCtx<'src> {
     source: String,
     tree: OnceCell<tree_sitter::Tree>,
@@ -57,6 +107,13 @@ impl<'src> ParseCtx<'src> {
 
         for node in traverse(node.walk(), Order::Pre) {
             if node.is_error() {
+                // Everything after an unterminated raw string is garbage, so the other errors
+                // are not worth reporting.
+                if let Some((start, closing)) = self.unterminated_raw_string(node) {
+                    return Err(CodeErrorKind::UnterminatedRawString(closing))
+                        .with_span(Some(self.span_at(start, closing.len() + 1)));
+                }
+
                 errors.push(CodeError {
                     kind: CodeErrorKind::ParseError(self.node_text(node).to_string()),
                     backtrace: vec![Marker::Span(Span::from_node(self.file_id, node))],
@@ -76,7 +133,79 @@ impl<'src> ParseCtx<'src> {
         }
     }
 
+    /// Tree-sitter cannot tell that a raw string literal is unterminated, it just reports
+    /// the rest of the file as a syntax error. If there is a raw string opening in the
+    /// erroneous region whose closing delimiter does not appear anywhere after it, returns
+    /// its position and the missing delimiter.
+    fn unterminated_raw_string(&self, node: tree_sitter::Node<'_>) -> Option<(usize, String)> {
+        // The `r` is sometimes lexed as an identifier just before the error node.
+        let mut start = node.start_byte().saturating_sub(3);
+        while !self.source.is_char_boundary(start) {
+            start -= 1;
+        }
+
+        for (offset, _) in self.source[start..node.end_byte()].match_indices('r') {
+            let pos = start + offset;
+            let in_identifier = self.source[..pos]
+                .chars()
+                .next_back()
+                .map_or(false, |ch| ch == '_' || ch.is_alphanumeric());
+            if in_identifier {
+                continue;
+            }
+
+            let rest = &self.source[pos + 1..];
+            let hashes = rest.len() - rest.trim_start_matches('#').len();
+            if hashes > 2 || !rest[hashes..].starts_with('"') {
+                continue;
+            }
+
+            let closing = format!("\"{}", "#".repeat(hashes));
+            if !rest[hashes + 1..].contains(&closing) {
+                return Some((pos, closing));
+            }
+        }
+
+        None
+    }
+
+    /// Span of `len` bytes starting at the byte offset `start`, for errors that do not
+    /// correspond to a node.
+    fn span_at(&self, start: usize, len: usize) -> Span {
+        let line_start = self.source[..start].rfind('\n').map_or(0, |i| i + 1);
+
+        Span {
+            start,
+            end: start + len,
+            line: self.source[..start].matches('\n').count(),
+            column: start - line_start,
+            file: self.file_id,
+        }
+    }
+
     pub fn node_text(&'src self, node: tree_sitter::Node<'src>) -> &'src str {
         &self.source[node.byte_range()]
     }
+
+    /// Source text of a declaration without the attributes and the body or initializer,
+    /// on a single line (e.g. `fn foo(a: i32) -> i32`).
+    pub fn declaration_header(&'src self, node: tree_sitter::Node<'src>) -> String {
+        let start = match node.child_by_field(FieldKind::Attributes) {
+            Some(attributes) => attributes.end_byte(),
+            None => node.start_byte(),
+        };
+        let end = [FieldKind::Body, FieldKind::Init, FieldKind::Value]
+            .into_iter()
+            .filter_map(|field| node.child_by_field(field))
+            .map(|child| child.start_byte())
+            .min()
+            .unwrap_or(node.end_byte());
+
+        self.source[start..end]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .trim_end_matches(&['{', '}', '=', ';', ' '][..])
+            .to_string()
+    }
 }
@@ -0,0 +1,153 @@
+//! Build profiles (`--profile name`)
+//!
+//! A profile is a named set of `cfg` values, `-Z` options and the debug setting, so that
+//! common configurations can be shared instead of being spelled out on every command line.
+//! Profiles are read from a file (`alumina.profiles` in the working directory unless
+//! `--profile-file` is given) with one section per profile:
+//!
+//! ```text
+//! # Comments start with `#`
+//! [release]
+//! option = deny-warnings
+//!
+//! [embedded]
+//! inherits = release
+//! cfg = target_os=none
+//! cfg = no_alloc
+//! debug = false
+//! ```
+//!
+//! A profile can inherit from another one, in which case the settings of the parent are
+//! applied first. `debug` and `release` are predefined (only setting the debug mode), but
+//! can be redefined in the file. Settings given on the command line are applied on top
+//! of the profile.
+
+use crate::common::{HashMap, HashSet};
+
+use std::path::Path;
+
+pub const DEFAULT_PROFILE_FILE: &str = "alumina.profiles";
+
+#[derive(Debug, Default, Clone)]
+pub struct Profile {
+    pub cfg: Vec<(String, Option<String>)>,
+    pub options: Vec<String>,
+    pub debug: Option<bool>,
+}
+
+#[derive(Debug, Default)]
+struct ProfileDef {
+    inherits: Option<String>,
+    profile: Profile,
+}
+
+fn builtin(name: &str) -> Option<ProfileDef> {
+    let debug = match name {
+        "debug" => true,
+        "release" => false,
+        _ => return None,
+    };
+
+    Some(ProfileDef {
+        inherits: None,
+        profile: Profile {
+            debug: Some(debug),
+            ..Default::default()
+        },
+    })
+}
+
+fn parse(source: &str, filename: &Path) -> Result<HashMap<String, ProfileDef>, String> {
+    let mut profiles: HashMap<String, ProfileDef> = HashMap::default();
+    let mut current = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let error = |message: &str| format!("{}:{}: {}", filename.display(), index + 1, message);
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim();
+            if profiles.contains_key(name) {
+                return Err(error(&format!("profile `{}` is defined twice", name)));
+            }
+            profiles.insert(name.to_string(), ProfileDef::default());
+            current = Some(name.to_string());
+            continue;
+        }
+
+        let Some(profile) = current.as_ref().and_then(|c| profiles.get_mut(c)) else {
+            return Err(error("expected a `[profile]` header"));
+        };
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error("expected `key = value`"));
+        };
+
+        let value = value.trim();
+        match key.trim() {
+            "inherits" => profile.inherits = Some(value.to_string()),
+            "cfg" => profile.profile.cfg.push(match value.split_once('=') {
+                Some((key, value)) => (key.trim().to_string(), Some(value.trim().to_string())),
+                None => (value.to_string(), None),
+            }),
+            "option" => profile.profile.options.push(value.to_string()),
+            "debug" => {
+                profile.profile.debug = Some(
+                    value
+                        .parse()
+                        .map_err(|_| error("expected `debug = true` or `debug = false`"))?,
+                )
+            }
+            key => return Err(error(&format!("unknown setting `{}`", key))),
+        }
+    }
+
+    Ok(profiles)
+}
+
+/// Resolves the profile with the given name (including the profiles it inherits from).
+/// A missing file is only an error if it was given explicitly.
+pub fn load(name: &str, filename: Option<&Path>) -> Result<Profile, String> {
+    let explicit = filename.is_some();
+    let filename = filename.unwrap_or(Path::new(DEFAULT_PROFILE_FILE));
+
+    let mut profiles = match std::fs::read_to_string(filename) {
+        Ok(source) => parse(&source, filename)?,
+        Err(e) if explicit || e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(format!("cannot read {}: {}", filename.display(), e))
+        }
+        Err(_) => HashMap::default(),
+    };
+
+    // The chain of profiles, from the requested one to the root
+    let mut chain = Vec::new();
+    let mut seen = HashSet::default();
+    let mut next = Some(name.to_string());
+
+    while let Some(name) = next {
+        if !seen.insert(name.clone()) {
+            return Err(format!("profile `{}` inherits from itself", name));
+        }
+
+        let def = profiles
+            .remove(&name)
+            .or_else(|| builtin(&name))
+            .ok_or_else(|| format!("unknown profile `{}`", name))?;
+
+        next = def.inherits;
+        chain.push(def.profile);
+    }
+
+    let mut result = Profile::default();
+    for profile in chain.into_iter().rev() {
+        result.cfg.extend(profile.cfg);
+        result.options.extend(profile.options);
+        result.debug = profile.debug.or(result.debug);
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,272 @@
+//! Renaming a local, item or field together with all of its uses (`--rename`).
+//!
+//! The uses are taken from the name index (the same one as `--emit defs-index`), so the
+//! rename covers exactly the occurrences that the compiler resolved to the definition.
+//! Fields and methods are resolved during monomorphization, so their uses in generic
+//! code that is never instantiated are not found.
+//!
+//! Before anything is rewritten, every affected occurrence is checked: if the new name
+//! already refers to something where the old name is used, or if the scope of the
+//! definition already has an item with the new name, the rename is refused.
+use crate::ast::{AstCtx, Span};
+use crate::common::{AluminaError, CodeErrorBuilder, CodeErrorKind, FileId, HashMap, HashSet};
+use crate::diagnostics::DiagnosticContext;
+use crate::global_ctx::GlobalCtx;
+use crate::name_index::location;
+use crate::name_resolution::path::{Path, PathSegment};
+use crate::name_resolution::resolver::{ItemResolution, NameResolver};
+use crate::name_resolution::scope::{NamedItemKind, Scope};
+use crate::parser::{read_source, FieldKind, NodeExt, ParseCtx, BYTE_ORDER_MARK};
+
+use std::iter::once;
+
+#[derive(Debug)]
+pub struct RenameQuery {
+    pub new_name: String,
+    pub conflicts: HashSet<Span>,
+}
+
+impl RenameQuery {
+    pub fn new(new_name: String) -> Self {
+        Self {
+            new_name,
+            conflicts: HashSet::default(),
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first == '_' || first.is_ascii_alphabetic() => {
+            chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        }
+        _ => false,
+    }
+}
+
+/// Records a conflict at the last segment of `path` (at the end of `span`) if the new name
+/// in its place would resolve to something in `scope`.
+pub fn check_path<'ast, 'src>(
+    ast: &'ast AstCtx<'ast>,
+    global_ctx: &GlobalCtx,
+    scope: &Scope<'ast, 'src>,
+    path: &Path<'ast>,
+    span: Span,
+) {
+    let (Some(new_name), Some(last)) = (global_ctx.rename_to(), path.segments.last()) else {
+        return;
+    };
+
+    let candidate = path.pop().extend(PathSegment(ast.intern_str(&new_name)));
+    if let Ok(ItemResolution::Item(_)) = NameResolver::new().resolve_item(scope.clone(), candidate)
+    {
+        global_ctx.record_rename_conflict(span.tail(last.0.len()));
+    }
+}
+
+/// Records a conflict for every definition in a scope that already has an item with the new
+/// name. Block scopes are not reachable from the root, but a local can only conflict there
+/// with its uses (which are covered by [check_path]), since shadowing is allowed.
+pub fn check_scopes(global_ctx: &GlobalCtx, root: &Scope<'_, '_>) {
+    let Some(new_name) = global_ctx.rename_to() else {
+        return;
+    };
+
+    let mut visited = HashSet::default();
+    let mut stack = vec![root.clone()];
+    while let Some(scope) = stack.pop() {
+        if !visited.insert(scope.clone()) {
+            continue;
+        }
+
+        let inner = scope.inner();
+        let has_new_name = inner
+            .items
+            .keys()
+            .any(|name| *name == Some(new_name.as_str()));
+
+        for (_, item) in inner.all_items() {
+            if has_new_name {
+                let definition = match &item.kind {
+                    NamedItemKind::Field(node) => scope
+                        .code()
+                        .map(|code| Span::from_node(code.file_id(), *node)),
+                    _ => item.definition_span(&scope),
+                };
+                if let Some(definition) = definition {
+                    global_ctx.record_rename_conflict(definition);
+                }
+            }
+
+            match &item.kind {
+                NamedItemKind::Function(_, _, child)
+                | NamedItemKind::Method(_, _, child)
+                | NamedItemKind::TypeDef(_, _, child)
+                | NamedItemKind::Static(_, _, child)
+                | NamedItemKind::Const(_, _, child)
+                | NamedItemKind::Macro(_, _, child)
+                | NamedItemKind::Type(_, _, child)
+                | NamedItemKind::Protocol(_, _, child)
+                | NamedItemKind::Mixin(_, child)
+                | NamedItemKind::Impl(_, child)
+                | NamedItemKind::Module(child) => stack.push(child.clone()),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Span of the name in a definition (definitions in the name index span the whole item).
+fn definition_name(source: String, definition: Span) -> Option<Span> {
+    let code = ParseCtx::from_source(definition.file, source);
+    let node = code
+        .root_node()
+        .descendant_for_byte_range(definition.start, definition.end)?;
+    if node.start_byte() != definition.start || node.end_byte() != definition.end {
+        return None;
+    }
+
+    let name = node
+        .child_by_field(FieldKind::Name)
+        .or_else(|| node.child_by_field(FieldKind::Placeholder))
+        .unwrap_or(node);
+
+    Some(Span::from_node(definition.file, name))
+}
+
+/// Finds the definition of the name at `position` (or the definition whose name is at
+/// `position`) and returns the spans of all occurrences of the name that need to be
+/// replaced.
+pub fn plan(
+    global_ctx: &GlobalCtx,
+    position: Span,
+    sysroot_files: &HashSet<FileId>,
+) -> Result<Vec<Span>, AluminaError> {
+    let new_name = global_ctx.rename_to().unwrap_or_default();
+    if !is_identifier(&new_name) {
+        return Err(CodeErrorKind::InvalidIdentifier(new_name)).with_no_span();
+    }
+
+    let diag = global_ctx.diag();
+    let mut sources = HashMap::default();
+    let mut source = |file: FileId| -> Result<String, AluminaError> {
+        if let Some(source) = sources.get(&file) {
+            return Ok(source.clone());
+        }
+
+        let path = diag.get_file_path(file).unwrap_or_default();
+        let source = read_source(file, &path)?;
+        sources.insert(file, source.clone());
+
+        Ok(source)
+    };
+
+    let names = global_ctx.name_index();
+    let used_at_position = names
+        .iter()
+        .filter(|name| name.usage.contains(&position))
+        .min_by_key(|name| name.usage.len())
+        .and_then(|name| name.definition);
+
+    let target = match used_at_position {
+        Some(definition) => {
+            definition_name(source(definition.file)?, definition).map(|name| (definition, name))
+        }
+        None => {
+            let mut definitions: Vec<_> = names
+                .iter()
+                .filter_map(|name| name.definition)
+                .filter(|definition| definition.contains(&position))
+                .collect();
+            definitions.sort_by_key(|d| (d.start, d.end));
+            definitions.dedup();
+
+            let mut found = None;
+            for definition in definitions {
+                let name = definition_name(source(definition.file)?, definition);
+                if let Some(name) = name.filter(|name| name.contains(&position)) {
+                    found = Some((definition, name));
+                    break;
+                }
+            }
+            found
+        }
+    };
+
+    let Some((definition, name_span)) = target else {
+        return Err(CodeErrorKind::NothingToRename).with_span(Some(position));
+    };
+
+    let old_name = source(definition.file)?[name_span.start..name_span.end].to_string();
+    if sysroot_files.contains(&definition.file) {
+        return Err(CodeErrorKind::CannotRenameSysroot(old_name)).with_span(Some(name_span));
+    }
+
+    let mut spans: Vec<_> = once(name_span)
+        .chain(
+            names
+                .iter()
+                .filter(|name| name.definition == Some(definition))
+                .map(|name| name.usage),
+        )
+        .collect();
+    spans.sort_by_key(|span| (span.file, span.start, span.end));
+    spans.dedup_by_key(|span| (span.file, span.start, span.end));
+
+    let conflicts = global_ctx.rename_conflicts();
+    if let Some(conflict) = once(&definition)
+        .chain(spans.iter())
+        .find(|span| conflicts.contains(*span))
+    {
+        return Err(CodeErrorKind::RenameConflict(new_name)).with_span(Some(*conflict));
+    }
+
+    // Names that come from macro expansions point into the macro body, which may not
+    // contain the name itself.
+    let mut result = Vec::new();
+    for span in spans {
+        if source(span.file)?.get(span.start..span.end) == Some(old_name.as_str()) {
+            result.push(span);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Replaces the names at `spans` with `new_name` in the source files and returns the
+/// locations (`file:line:column:length`) that were changed.
+pub fn apply(
+    diag: &DiagnosticContext,
+    spans: &[Span],
+    new_name: &str,
+) -> Result<Vec<String>, AluminaError> {
+    let locations = spans.iter().map(|span| location(diag, *span)).collect();
+
+    let mut by_file: HashMap<FileId, Vec<Span>> = HashMap::default();
+    for span in spans {
+        by_file.entry(span.file).or_default().push(*span);
+    }
+
+    for (file, mut spans) in by_file {
+        let path = diag.get_file_path(file).unwrap_or_default();
+        let mut source = read_source(file, &path)?;
+
+        spans.sort_by_key(|span| span.start);
+        for span in spans.iter().rev() {
+            source.replace_range(span.start..span.end, new_name);
+        }
+
+        // Spans do not include the byte order mark, so it has to be put back
+        let mut contents = if std::fs::read(&path)?.starts_with(BYTE_ORDER_MARK) {
+            BYTE_ORDER_MARK.to_vec()
+        } else {
+            Vec::new()
+        };
+        contents.extend_from_slice(source.as_bytes());
+
+        std::fs::write(&path, contents)?;
+    }
+
+    Ok(locations)
+}
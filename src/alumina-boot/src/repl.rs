@@ -0,0 +1,154 @@
+use crate::common::AluminaError;
+use crate::compiler::{Compiler, Session, SourceFile};
+use crate::global_ctx::GlobalCtx;
+use crate::runner::TempDir;
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+const MODULE_PATH: &str = "repl";
+const RESULT_NAME: &str = "_repl_result";
+
+const ITEM_KEYWORDS: &[&str] = &[
+    "fn", "struct", "union", "enum", "const", "static", "type", "use", "mod", "impl", "protocol",
+    "macro", "extern", "#[",
+];
+
+/// Interactive evaluation of expressions.
+///
+/// Items (functions, types, ...) that are entered accumulate in a scratch module, expressions
+/// are evaluated at compile time with the const evaluator in the context of the items entered
+/// so far. The whole program is recompiled for every input (except for parsing the sysroot,
+/// which is done once, see [Session]), which is fine for small sessions.
+struct Repl {
+    global_ctx: GlobalCtx,
+    session: Session<'static>,
+    items: String,
+    scratch_file: PathBuf,
+    // Removed (with the scratch module) when the REPL exits
+    _scratch_dir: TempDir,
+}
+
+impl Repl {
+    fn eval(&self, source: &str) -> Result<Option<String>, AluminaError> {
+        std::fs::write(&self.scratch_file, source)?;
+
+        let global_ctx = self.global_ctx.fork();
+        let mut compiler = Compiler::new(global_ctx.clone());
+
+        let files = vec![SourceFile {
+            filename: self.scratch_file.clone(),
+            path: MODULE_PATH.to_string(),
+            contents: None,
+            strip_shebang: false,
+            sysroot: false,
+        }];
+
+        match compiler.eval_const(&self.session, files, MODULE_PATH, RESULT_NAME) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => {
+                let diag_ctx = global_ctx.diag();
+                diag_ctx.add_from_error(e)?;
+                diag_ctx.print_error_report()?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn process(&mut self, input: &str) -> Result<(), AluminaError> {
+        let is_item = ITEM_KEYWORDS.iter().any(|kw| {
+            input
+                .strip_prefix(kw)
+                .map(|rest| kw.ends_with('[') || rest.starts_with(char::is_whitespace))
+                .unwrap_or(false)
+        });
+
+        if is_item {
+            // Only keep the item if the program still compiles with it
+            let items = format!("{}\n{}\n", self.items, input);
+            let source = format!("{}const {} = ();\n", items, RESULT_NAME);
+            if self.eval(&source)?.is_some() {
+                self.items = items;
+            }
+        } else {
+            let source = format!("{}const {} = {{\n{}\n}};\n", self.items, RESULT_NAME, input);
+            if let Some(value) = self.eval(&source)? {
+                println!("{}", value);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns true if the input has unclosed brackets and more lines should be read.
+fn is_incomplete(input: &str) -> bool {
+    let mut depth = 0isize;
+    for c in input.chars() {
+        match c {
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth > 0
+}
+
+pub fn run(global_ctx: &GlobalCtx, sysroot: Vec<SourceFile>) -> Result<(), AluminaError> {
+    // The scratch module is in a fresh directory that only we can access, so that it cannot
+    // be replaced or read by someone else while the REPL runs.
+    let scratch_dir = TempDir::new("alumina-repl")?;
+    let scratch_file = scratch_dir.path().join("repl.alu");
+    std::fs::File::options()
+        .write(true)
+        .create_new(true)
+        .open(&scratch_file)?;
+
+    let mut repl = Repl {
+        global_ctx: global_ctx.clone(),
+        session: Session::new(sysroot)?,
+        items: String::new(),
+        scratch_file,
+        _scratch_dir: scratch_dir,
+    };
+
+    println!("Alumina REPL (expressions are evaluated at compile time). Type :help for help.");
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut input = String::new();
+
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { ". " });
+        std::io::stdout().flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+
+        input.push_str(&line);
+        input.push('\n');
+        if is_incomplete(&input) {
+            continue;
+        }
+
+        let current = std::mem::take(&mut input);
+        match current.trim() {
+            "" => {}
+            ":q" | ":quit" => break,
+            ":reset" => repl.items.clear(),
+            ":items" => print!("{}", repl.items),
+            ":help" => {
+                println!("Enter an item (fn, struct, const, ...) to define it or an expression to evaluate it.");
+                println!(":items  show the items defined so far");
+                println!(":reset  forget all items");
+                println!(":quit   exit the REPL");
+            }
+            trimmed => repl.process(trimmed)?,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,244 @@
+use crate::common::{AluminaError, CodeError};
+use crate::global_ctx::{GlobalCtx, LinkKind};
+
+use std::collections::hash_map::RandomState;
+use std::ffi::OsString;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+use std::time::SystemTime;
+
+/// Scratch directory, private to the current user and removed on drop.
+pub struct TempDir(PathBuf);
+
+/// Location of the scratch directory of a C compiler invocation in build plans. The actual
+/// directory has a random suffix in place of the `X`s.
+pub fn temp_dir_path() -> PathBuf {
+    std::env::temp_dir().join("alumina-run-XXXXXXXXXXXXXXXX")
+}
+
+impl TempDir {
+    /// Creates a new directory `<prefix>-<random>` in the system temporary directory. The
+    /// directory must not exist yet, so that a directory (or symlink) planted by someone
+    /// else in a shared temporary directory is never used.
+    pub fn new(prefix: &str) -> Result<Self, AluminaError> {
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+        let state = RandomState::new();
+        let mut attempt = 0;
+        loop {
+            let mut hasher = state.build_hasher();
+            (std::process::id(), SystemTime::now(), attempt).hash(&mut hasher);
+
+            let path = std::env::temp_dir().join(format!("{}-{:016x}", prefix, hasher.finish()));
+            match builder.create(&path) {
+                Ok(()) => return Ok(Self(path)),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempt < 16 => {
+                    attempt += 1
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Names of the files in the scratch directory
+pub const EXECUTABLE_SOURCE: &str = "main.c";
+pub const EXECUTABLE: &str = "main";
+pub const SHARED_LIBRARY_SOURCE: &str = "lib.c";
+
+fn freeform_error(s: impl ToString) -> AluminaError {
+    AluminaError::CodeErrors(vec![CodeError::freeform(s)])
+}
+
+/// Builds the generated C code with the system C compiler and runs the resulting
+/// executable, forwarding the arguments and stdio.
+///
+/// The C compiler can be overridden with the `CC` environment variable and additional
+/// flags can be passed with `CFLAGS` and `LDFLAGS`. The libraries the standard library
+/// depends on (`libm`, `libpthread` and `libbacktrace`) are linked based on the `cfg`
+/// flags of the compilation, together with the libraries requested with `#[link]`.
+pub fn build_and_run(
+    global_ctx: &GlobalCtx,
+    program: &str,
+    args: &[String],
+) -> Result<ExitCode, AluminaError> {
+    let temp_dir = TempDir::new("alumina-run")?;
+    let source = temp_dir.path().join(EXECUTABLE_SOURCE);
+    let executable = temp_dir.path().join(EXECUTABLE);
+
+    std::fs::write(&source, program)?;
+
+    let (cc, mut command) = executable_command(global_ctx, &source, &executable);
+    invoke(&cc, &mut command)?;
+
+    let status = Command::new(&executable).args(args).status()?;
+
+    // If the program was terminated by a signal, there is no exit code to forward.
+    Ok(match status.code() {
+        Some(code) => ExitCode::from(code as u8),
+        None => ExitCode::FAILURE,
+    })
+}
+
+/// Builds the generated C code into a shared library with the system C compiler.
+///
+/// Everything except the exported functions is hidden. If a version script is given,
+/// it is passed to the linker (not supported on macOS).
+pub fn build_shared_library(
+    global_ctx: &GlobalCtx,
+    program: &str,
+    output: &Path,
+    version_script: Option<&Path>,
+) -> Result<(), AluminaError> {
+    let temp_dir = TempDir::new("alumina-run")?;
+    let source = temp_dir.path().join(SHARED_LIBRARY_SOURCE);
+
+    std::fs::write(&source, program)?;
+
+    let (cc, mut command) = shared_library_command(global_ctx, &source, output, version_script);
+    invoke(&cc, &mut command)
+}
+
+/// C compiler invocation that builds the executable for `--run`
+pub fn executable_command(
+    global_ctx: &GlobalCtx,
+    source: &Path,
+    executable: &Path,
+) -> (OsString, Command) {
+    let (cc, mut command) = c_compiler(global_ctx);
+    command.arg("-o").arg(executable).arg(source);
+    add_libraries(global_ctx, &mut command);
+
+    (cc, command)
+}
+
+/// C compiler invocation that builds a shared library for `--shared`
+pub fn shared_library_command(
+    global_ctx: &GlobalCtx,
+    source: &Path,
+    output: &Path,
+    version_script: Option<&Path>,
+) -> (OsString, Command) {
+    let (cc, mut command) = c_compiler(global_ctx);
+    command.args(["-shared", "-fPIC", "-fvisibility=hidden"]);
+    command.arg("-o").arg(output).arg(source);
+    if let Some(version_script) = version_script {
+        if global_ctx.cfg("target_os") != Some(Some("macos".to_string())) {
+            let mut arg = OsString::from("-Wl,--version-script=");
+            arg.push(version_script);
+            command.arg(arg);
+        }
+    }
+    add_libraries(global_ctx, &mut command);
+
+    (cc, command)
+}
+
+/// Generates a linker version script that exports only the given symbols, optionally
+/// under a version node (e.g. `MYLIB_1.0`).
+pub fn version_script(symbols: &[String], version: Option<&str>) -> String {
+    let mut result = String::new();
+
+    if let Some(version) = version {
+        result.push_str(version);
+        result.push(' ');
+    }
+    result.push_str("{\n");
+    if !symbols.is_empty() {
+        result.push_str("  global:\n");
+        for symbol in symbols {
+            result.push_str(&format!("    {};\n", symbol));
+        }
+    }
+    result.push_str("  local:\n    *;\n};\n");
+
+    result
+}
+
+fn c_compiler(global_ctx: &GlobalCtx) -> (OsString, Command) {
+    let cc = std::env::var_os("CC").unwrap_or_else(|| OsString::from("cc"));
+    let mut command = Command::new(&cc);
+
+    if global_ctx.has_flag("debug") {
+        command.arg("-g3");
+    } else {
+        command.arg("-O2");
+    }
+
+    command.args(split_flags("CFLAGS"));
+
+    (cc, command)
+}
+
+/// Linker flags for the native libraries requested with `#[link(name = "...")]`.
+///
+/// Static libraries are linked with `-Bstatic` where the linker supports it (i.e. not
+/// on macOS, where the linker prefers a static library only if there is no dynamic one).
+pub fn link_flags(global_ctx: &GlobalCtx) -> Vec<String> {
+    let is_macos = global_ctx.cfg("target_os") == Some(Some("macos".to_string()));
+    let mut flags = Vec::new();
+
+    for library in global_ctx.link_libraries() {
+        match library.kind {
+            LinkKind::Static if !is_macos => {
+                flags.push("-Wl,-Bstatic".to_string());
+                flags.push(format!("-l{}", library.name));
+                flags.push("-Wl,-Bdynamic".to_string());
+            }
+            _ => flags.push(format!("-l{}", library.name)),
+        }
+    }
+
+    flags
+}
+
+fn add_libraries(global_ctx: &GlobalCtx, command: &mut Command) {
+    command.args(link_flags(global_ctx));
+    command.arg("-lm");
+    if global_ctx.has_flag("threading") {
+        command.arg("-lpthread");
+    }
+    if global_ctx.has_flag("use_libbacktrace") {
+        command.arg("-lbacktrace");
+    }
+    command.args(split_flags("LDFLAGS"));
+}
+
+fn invoke(cc: &OsString, command: &mut Command) -> Result<(), AluminaError> {
+    let status = command.status().map_err(|e| {
+        freeform_error(format!(
+            "could not invoke the C compiler `{}`: {}",
+            cc.to_string_lossy(),
+            e
+        ))
+    })?;
+
+    if !status.success() {
+        return Err(freeform_error(format!(
+            "C compiler `{}` failed ({})",
+            cc.to_string_lossy(),
+            status
+        )));
+    }
+
+    Ok(())
+}
+
+fn split_flags(var: &str) -> Vec<String> {
+    std::env::var(var)
+        .map(|s| s.split_whitespace().map(ToString::to_string).collect())
+        .unwrap_or_default()
+}
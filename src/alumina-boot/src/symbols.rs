@@ -0,0 +1,282 @@
+//! Index of all the items in the program for workspace symbol search (`--emit symbols`,
+//! `--emit ctags` and `--emit lsif`).
+//!
+//! The index only needs the scopes from the first pass, so it is produced without
+//! resolving the bodies of the items or monomorphizing anything.
+use crate::ast::Span;
+use crate::common::{FileId, HashMap, HashSet};
+use crate::diagnostics::DiagnosticContext;
+use crate::name_index::{location, SymbolKind};
+use crate::name_resolution::path::PathSegment;
+use crate::name_resolution::scope::{NamedItemKind, Scope};
+use crate::parser::{FieldKind, NodeExt, ParseCtx};
+use crate::utils::json_string;
+
+use std::fmt::Write;
+
+pub struct Symbol<'src> {
+    pub name: String,
+    pub path: String,
+    pub kind: SymbolKind,
+    pub signature: String,
+    /// Span of the name
+    pub span: Span,
+    /// Span of the whole definition
+    pub full_span: Span,
+    code: &'src ParseCtx<'src>,
+}
+
+/// Collects the named items in all the scopes reachable from `root`.
+pub fn collect<'src>(root: &Scope<'_, 'src>) -> Vec<Symbol<'src>> {
+    let mut result = Vec::new();
+    let mut visited = HashSet::default();
+    let mut stack = vec![root.clone()];
+
+    while let Some(scope) = stack.pop() {
+        if !visited.insert(scope.clone()) {
+            continue;
+        }
+
+        for (name, item) in scope.inner().all_items() {
+            let (kind, node, child) = match &item.kind {
+                NamedItemKind::Function(_, node, child) => (SymbolKind::Function, node, child),
+                NamedItemKind::Method(_, node, child) => (SymbolKind::Method, node, child),
+                NamedItemKind::TypeDef(_, node, child) | NamedItemKind::Type(_, node, child) => {
+                    (SymbolKind::Type, node, child)
+                }
+                NamedItemKind::Protocol(_, node, child) => (SymbolKind::Protocol, node, child),
+                NamedItemKind::Static(_, node, child) => (SymbolKind::Static, node, child),
+                NamedItemKind::Const(_, node, child) => (SymbolKind::Const, node, child),
+                NamedItemKind::Macro(_, node, child) => (SymbolKind::Macro, node, child),
+                NamedItemKind::EnumMember(_, _, node) => (SymbolKind::EnumMember, node, &scope),
+                NamedItemKind::Field(node) => (SymbolKind::Field, node, &scope),
+                NamedItemKind::Module(child) | NamedItemKind::Impl(_, child) => {
+                    stack.push(child.clone());
+                    continue;
+                }
+                _ => continue,
+            };
+
+            if child != &scope {
+                stack.push(child.clone());
+            }
+
+            let (Some(name), Some(code)) = (name, child.code()) else {
+                continue;
+            };
+
+            let name_node = node.child_by_field(FieldKind::Name).unwrap_or(*node);
+            let path = scope.path().extend(PathSegment(name));
+
+            result.push(Symbol {
+                name: name.to_string(),
+                path: path.to_string(),
+                kind,
+                signature: code.declaration_header(*node),
+                span: Span::from_node(code.file_id(), name_node),
+                full_span: Span::from_node(code.file_id(), *node),
+                code,
+            });
+        }
+    }
+
+    result.sort_by(|a, b| a.path.cmp(&b.path));
+    result
+}
+
+/// Formats the symbols as `path<TAB>kind<TAB>location<TAB>signature` lines, where
+/// location is `file:line:column:length` of the name.
+pub fn format_symbols(diag: &DiagnosticContext, symbols: &[Symbol<'_>]) -> String {
+    let mut result = String::new();
+
+    for symbol in symbols {
+        let _ = writeln!(
+            result,
+            "{}\t{}\t{}\t{}",
+            symbol.path,
+            symbol.kind.as_ref(),
+            location(diag, symbol.span),
+            symbol.signature
+        );
+    }
+
+    result
+}
+
+/// Formats the symbols as an extended format `tags` file (as produced by `ctags`).
+pub fn format_ctags(diag: &DiagnosticContext, symbols: &[Symbol<'_>]) -> String {
+    let mut tags: Vec<_> = symbols
+        .iter()
+        .map(|symbol| {
+            let file = diag
+                .get_file_path(symbol.span.file)
+                .map(|p| p.display().to_string())
+                .unwrap_or_default();
+            let scope = symbol
+                .path
+                .strip_suffix(&symbol.name)
+                .and_then(|p| p.strip_suffix("::"))
+                .filter(|p| !p.is_empty());
+
+            let mut tag = format!(
+                "{}\t{}\t{};\"\tkind:{}",
+                symbol.name,
+                file,
+                symbol.span.line + 1,
+                symbol.kind.as_ref()
+            );
+            if let Some(scope) = scope {
+                let _ = write!(tag, "\tscope:{}", scope);
+            }
+            if !symbol.signature.is_empty() {
+                let _ = write!(tag, "\tsignature:{}", symbol.signature);
+            }
+            tag
+        })
+        .collect();
+
+    // Sorted by the tag name (byte order), so that editors can binary search
+    tags.sort();
+
+    let mut result = String::new();
+    result.push_str("!_TAG_FILE_FORMAT\t2\t/extended format/\n");
+    result.push_str("!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/\n");
+    result.push_str("!_TAG_PROGRAM_NAME\talumina-boot\t//\n");
+    for tag in tags {
+        result.push_str(&tag);
+        result.push('\n');
+    }
+
+    result
+}
+
+/// LSP position (0-based line and UTF-16 character offset) of a byte offset
+fn lsp_position(source: &str, offset: usize) -> String {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+    format!(
+        "{{\"line\":{},\"character\":{}}}",
+        before.matches('\n').count(),
+        before[line_start..].encode_utf16().count()
+    )
+}
+
+fn lsp_range(source: &str, span: Span) -> String {
+    format!(
+        "{{\"start\":{},\"end\":{}}}",
+        lsp_position(source, span.start),
+        lsp_position(source, span.end)
+    )
+}
+
+/// LSP `SymbolKind` of a symbol
+fn lsp_kind(kind: SymbolKind) -> u32 {
+    match kind {
+        SymbolKind::Module => 2,
+        SymbolKind::Method => 6,
+        SymbolKind::Field => 8,
+        SymbolKind::Protocol => 11,
+        SymbolKind::Function | SymbolKind::Macro => 12,
+        SymbolKind::Variable | SymbolKind::Static | SymbolKind::Parameter => 13,
+        SymbolKind::Const => 14,
+        SymbolKind::EnumMember => 22,
+        SymbolKind::Type => 23,
+        SymbolKind::TypeParameter => 26,
+    }
+}
+
+/// Formats the symbols as an LSIF dump (one JSON object per line) with a document for each
+/// source file and a definition range for each symbol.
+pub fn format_lsif(diag: &DiagnosticContext, symbols: &[Symbol<'_>]) -> String {
+    let mut result = String::new();
+    let mut next_id = 0;
+    let mut id = || {
+        next_id += 1;
+        next_id
+    };
+
+    let uri = |path: &std::path::Path| {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        json_string(&format!("file://{}", path.display()))
+    };
+
+    let project_root = std::env::current_dir().unwrap_or_default();
+    let _ = writeln!(
+        result,
+        "{{\"id\":{},\"type\":\"vertex\",\"label\":\"metaData\",\"version\":\"0.4.3\",\
+         \"positionEncoding\":\"utf-16\",\"projectRoot\":{}}}",
+        id(),
+        uri(&project_root)
+    );
+    let project = id();
+    let _ = writeln!(
+        result,
+        "{{\"id\":{},\"type\":\"vertex\",\"label\":\"project\",\"kind\":\"alumina\"}}",
+        project
+    );
+
+    let mut files: Vec<FileId> = Vec::new();
+    let mut by_file: HashMap<FileId, Vec<&Symbol<'_>>> = HashMap::default();
+    for symbol in symbols {
+        let file_symbols = by_file.entry(symbol.span.file).or_insert_with(|| {
+            files.push(symbol.span.file);
+            Vec::new()
+        });
+        file_symbols.push(symbol);
+    }
+
+    let mut documents = Vec::new();
+    for file in files {
+        let file_symbols = &by_file[&file];
+        let Some(path) = diag.get_file_path(file) else {
+            continue;
+        };
+
+        let document = id();
+        documents.push(document);
+        let _ = writeln!(
+            result,
+            "{{\"id\":{},\"type\":\"vertex\",\"label\":\"document\",\"uri\":{},\
+             \"languageId\":\"alumina\"}}",
+            document,
+            uri(&path)
+        );
+
+        let mut ranges = Vec::new();
+        for symbol in file_symbols {
+            let source = symbol.code.source();
+            let range = id();
+            ranges.push(range.to_string());
+            let _ = writeln!(
+                result,
+                "{{\"id\":{},\"type\":\"vertex\",\"label\":\"range\",\"start\":{},\"end\":{},\
+                 \"tag\":{{\"type\":\"definition\",\"text\":{},\"kind\":{},\"fullRange\":{}}}}}",
+                range,
+                lsp_position(source, symbol.span.start),
+                lsp_position(source, symbol.span.end),
+                json_string(&symbol.name),
+                lsp_kind(symbol.kind),
+                lsp_range(source, symbol.full_span)
+            );
+        }
+
+        let _ = writeln!(
+            result,
+            "{{\"id\":{},\"type\":\"edge\",\"label\":\"contains\",\"outV\":{},\"inVs\":[{}]}}",
+            id(),
+            document,
+            ranges.join(",")
+        );
+    }
+
+    let documents: Vec<_> = documents.iter().map(|d| d.to_string()).collect();
+    let _ = writeln!(
+        result,
+        "{{\"id\":{},\"type\":\"edge\",\"label\":\"contains\",\"outV\":{},\"inVs\":[{}]}}",
+        id(),
+        project,
+        documents.join(",")
+    );
+
+    result
+}
@@ -0,0 +1,179 @@
+//! Target platform description used to populate the `target_*` cfg values.
+//!
+//! By default the program is compiled for the host that the compiler itself was built
+//! for. `--target` takes a target triple (e.g. `aarch64-unknown-linux-gnu`) instead, so
+//! that build scripts do not have to pass the individual cfg values themselves.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub arch: String,
+    pub os: String,
+    /// `unix`, `windows` or `wasm`, bare-metal targets do not belong to a family
+    pub family: Option<String>,
+    pub pointer_width: u32,
+    pub endian: &'static str,
+}
+
+impl Target {
+    pub fn host() -> Self {
+        Self {
+            arch: std::env::consts::ARCH.to_string(),
+            os: std::env::consts::OS.to_string(),
+            family: Some(std::env::consts::FAMILY)
+                .filter(|f| !f.is_empty())
+                .map(String::from),
+            pointer_width: (std::mem::size_of::<usize>() * 8) as u32,
+            endian: if cfg!(target_endian = "big") {
+                "big"
+            } else {
+                "little"
+            },
+        }
+    }
+
+    /// Parses a target triple such as `x86_64-unknown-linux-gnu`, `aarch64-apple-darwin`
+    /// or `thumbv7em-none-eabihf`.
+    pub fn from_triple(triple: &str) -> Result<Self, String> {
+        let parts: Vec<_> = triple.split('-').collect();
+        if parts.len() < 2 || parts.iter().any(|p| p.is_empty()) {
+            return Err(format!("invalid target triple `{}`", triple));
+        }
+
+        let (arch, pointer_width, endian) = match parts[0] {
+            "x86_64" | "amd64" => ("x86_64", 64, "little"),
+            "i386" | "i486" | "i586" | "i686" | "x86" => ("x86", 32, "little"),
+            "aarch64" | "arm64" => ("aarch64", 64, "little"),
+            "aarch64_be" => ("aarch64", 64, "big"),
+            "riscv64" | "riscv64gc" | "riscv64imac" => ("riscv64", 64, "little"),
+            "powerpc64le" => ("powerpc64", 64, "little"),
+            "powerpc64" => ("powerpc64", 64, "big"),
+            "powerpc" => ("powerpc", 32, "big"),
+            "s390x" => ("s390x", 64, "big"),
+            "mips" => ("mips", 32, "big"),
+            "mipsel" => ("mips", 32, "little"),
+            "wasm32" => ("wasm32", 32, "little"),
+            "wasm64" => ("wasm64", 64, "little"),
+            arch if arch.starts_with("armeb") || arch.starts_with("thumbeb") => ("arm", 32, "big"),
+            arch if arch.starts_with("arm") || arch.starts_with("thumb") => ("arm", 32, "little"),
+            arch if arch.starts_with("riscv32") => ("riscv32", 32, "little"),
+            arch => return Err(format!("unknown architecture `{}`", arch)),
+        };
+
+        // The vendor is optional (`arm-none-eabi`), so the OS is looked up in the
+        // remaining components.
+        let os = parts[1..]
+            .iter()
+            .find_map(|part| match *part {
+                "linux" => Some("linux"),
+                "android" | "androideabi" => Some("android"),
+                "darwin" | "macos" | "macosx" => Some("macos"),
+                "ios" => Some("ios"),
+                "windows" | "win32" | "mingw32" => Some("windows"),
+                "freebsd" => Some("freebsd"),
+                "netbsd" => Some("netbsd"),
+                "openbsd" => Some("openbsd"),
+                "wasi" => Some("wasi"),
+                "emscripten" => Some("emscripten"),
+                "none" | "elf" | "eabi" | "eabihf" => Some("none"),
+                _ => None,
+            })
+            .or_else(|| arch.starts_with("wasm").then_some("unknown"))
+            .ok_or_else(|| format!("unknown operating system in target triple `{}`", triple))?;
+
+        let family = match os {
+            "windows" => Some("windows"),
+            "none" => None,
+            "unknown" | "wasi" | "emscripten" if arch.starts_with("wasm") => Some("wasm"),
+            _ => Some("unix"),
+        };
+
+        Ok(Self {
+            arch: arch.to_string(),
+            os: os.to_string(),
+            family: family.map(String::from),
+            pointer_width,
+            endian,
+        })
+    }
+
+    /// The `target_*` cfg values for this target
+    pub fn cfg(&self) -> Vec<(&'static str, String)> {
+        let mut result = vec![
+            ("target_os", self.os.clone()),
+            ("target_arch", self.arch.clone()),
+            ("target_pointer_width", self.pointer_width.to_string()),
+            ("target_endian", self.endian.to_string()),
+        ];
+        if let Some(family) = &self.family {
+            result.push(("target_family", family.clone()));
+        }
+
+        result
+    }
+}
+
+fn numbered(prefixes: &[&str], count: usize, name: &str) -> bool {
+    prefixes.iter().any(|prefix| {
+        name.strip_prefix(prefix)
+            .filter(|n| n.len() == 1 || !n.starts_with('0'))
+            .and_then(|n| n.parse::<usize>().ok())
+            .map_or(false, |n| n < count)
+    })
+}
+
+/// Whether `name` is a register that inline assembly can clobber on the architecture (as
+/// in the `target_arch` cfg value). Returns `None` for architectures that are not known,
+/// where anything is accepted.
+pub fn is_register(arch: &str, name: &str) -> Option<bool> {
+    let result = match arch {
+        "x86_64" => {
+            [
+                "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "eax", "ebx", "ecx", "edx",
+                "esi", "edi", "ebp", "esp", "ax", "bx", "cx", "dx", "si", "di", "bp", "sp", "al",
+                "bl", "cl", "dl", "sil", "dil", "bpl", "spl", "ah", "bh", "ch", "dh", "st",
+                "flags", "dirflag", "fpsr",
+            ]
+            .contains(&name)
+                || name.strip_suffix(['d', 'w', 'b']).map_or(false, |r| {
+                    numbered(&["r"], 16, r) && !numbered(&["r"], 8, r)
+                })
+                || (numbered(&["r"], 16, name) && !numbered(&["r"], 8, name))
+                || numbered(&["xmm", "ymm", "zmm"], 32, name)
+                || numbered(&["k", "mm", "st"], 8, name)
+        }
+        "x86" => {
+            [
+                "eax", "ebx", "ecx", "edx", "esi", "edi", "ebp", "esp", "ax", "bx", "cx", "dx",
+                "si", "di", "bp", "sp", "al", "bl", "cl", "dl", "ah", "bh", "ch", "dh", "st",
+                "flags", "dirflag", "fpsr",
+            ]
+            .contains(&name)
+                || numbered(&["xmm", "ymm", "zmm"], 8, name)
+                || numbered(&["k", "mm", "st"], 8, name)
+        }
+        "aarch64" => {
+            ["sp", "wsp", "xzr", "wzr"].contains(&name)
+                || numbered(&["x", "w"], 31, name)
+                || numbered(&["v", "q", "d", "s", "h", "b", "z"], 32, name)
+                || numbered(&["p"], 16, name)
+        }
+        "arm" => {
+            ["sp", "lr", "pc", "ip", "fp", "sl", "sb"].contains(&name)
+                || numbered(&["r"], 16, name)
+                || numbered(&["s", "d"], 32, name)
+                || numbered(&["q"], 16, name)
+        }
+        "riscv32" | "riscv64" => {
+            ["zero", "ra", "sp", "gp", "tp", "fp"].contains(&name)
+                || numbered(&["x", "f"], 32, name)
+                || numbered(&["t"], 7, name)
+                || numbered(&["s", "fs"], 12, name)
+                || numbered(&["a", "fa"], 8, name)
+                || numbered(&["ft"], 12, name)
+                || numbered(&["v"], 32, name)
+        }
+        _ => return None,
+    };
+
+    Some(result)
+}
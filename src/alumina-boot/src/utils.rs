@@ -1,4 +1,5 @@
-use std::fmt::Debug;
+use std::cell::RefCell;
+use std::fmt::{Debug, Write};
 
 use tree_sitter::Node;
 
@@ -34,3 +35,102 @@ macro_rules! regex {
 }
 
 pub(crate) use regex;
+
+/// Maximum length of the `Debug` output of IR and AST values in diagnostics and panics
+pub const MAX_DEBUG_LEN: usize = 4096;
+
+/// How many items deep the `Debug` impls of IR and AST items recurse into their contents
+const MAX_DEBUG_DEPTH: usize = 4;
+
+thread_local! {
+    static DEBUG_ITEMS: RefCell<Vec<(&'static str, usize)>> = RefCell::new(Vec::new());
+}
+
+/// Marks an item as being formatted by a `Debug` impl, to stop at cycles (e.g. a struct
+/// that has a pointer to itself) and at deeply nested items.
+pub struct DebugItemGuard(());
+
+impl DebugItemGuard {
+    /// Returns `None` if the item (`kind` distinguishes AST and IR ids) is already being
+    /// formatted further up or the nesting is too deep. Only a reference to the item (a name
+    /// or an id) should be printed in that case.
+    pub fn enter(kind: &'static str, id: usize) -> Option<Self> {
+        DEBUG_ITEMS.with(|items| {
+            let mut items = items.borrow_mut();
+            if items.len() >= MAX_DEBUG_DEPTH || items.contains(&(kind, id)) {
+                return None;
+            }
+            items.push((kind, id));
+            Some(DebugItemGuard(()))
+        })
+    }
+}
+
+impl Drop for DebugItemGuard {
+    fn drop(&mut self) {
+        DEBUG_ITEMS.with(|items| items.borrow_mut().pop());
+    }
+}
+
+struct BoundedWriter {
+    buf: String,
+    max_len: usize,
+}
+
+impl Write for BoundedWriter {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        let remaining = self.max_len - self.buf.len();
+        if s.len() <= remaining {
+            self.buf.push_str(s);
+            return Ok(());
+        }
+
+        let mut end = remaining;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.buf.push_str(&s[..end]);
+        Err(std::fmt::Error)
+    }
+}
+
+/// Formats a value with `Debug`, cutting the output off after `max_len` bytes.
+pub fn bounded_debug(value: &impl Debug, max_len: usize) -> String {
+    let mut writer = BoundedWriter {
+        buf: String::new(),
+        max_len,
+    };
+    if write!(writer, "{:?}", value).is_err() {
+        writer.buf.push_str("... (truncated)");
+    }
+    writer.buf
+}
+
+/// 64-bit FNV-1a, which (unlike the hashers of the compiler's hash maps) is the same in
+/// every build of the compiler, so it can be used for hashes that are written to files
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Quotes and escapes a string for JSON output
+pub fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(result, "\\u{:04x}", c as u32);
+            }
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
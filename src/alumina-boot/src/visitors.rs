@@ -1,10 +1,14 @@
 use crate::ast::expressions::parse_string_literal;
-use crate::ast::{AstCtx, Attribute, ItemP, MacroCtx, Span, TestMetadata};
+use crate::ast::{
+    AstCtx, Attribute, BuiltinType, ItemP, MacroCtx, OptimizationLevel, Span, TestMetadata,
+};
 use crate::common::{
     AluminaError, ArenaAllocatable, CodeError, CodeErrorKind, Marker, WithSpanDuringParsing,
 };
 use crate::diagnostics;
-use crate::global_ctx::GlobalCtx;
+use crate::features::Feature;
+use crate::global_ctx::{GlobalCtx, LinkKind, LinkLibrary};
+use crate::lints;
 use crate::name_resolution::path::{Path, PathSegment};
 use crate::name_resolution::scope::{NamedItem, NamedItemKind, Scope};
 use crate::parser::{AluminaVisitor, FieldKind, NodeExt, ParseCtx};
@@ -252,6 +256,51 @@ impl<'ast, 'src> AluminaVisitor<'src> for UseClauseVisitor<'ast, 'src> {
     }
 }
 
+/// Attributes that the compiler understands and the maximum number of arguments they take
+/// (`None` if the attribute validates its arguments itself). The arguments are parsed by
+/// [AttributeVisitor], so this only catches the arguments that would otherwise be ignored.
+///
+/// Attributes in the `custom::` namespace are reserved for external tools and are never
+/// reported.
+const KNOWN_ATTRIBUTES: &[(&str, Option<usize>)] = &[
+    ("align", Some(1)),
+    ("allocator", Some(0)),
+    ("allow", Some(1)),
+    ("bitfield", Some(0)),
+    ("bits", Some(1)),
+    ("builtin", Some(0)),
+    ("c_attribute", Some(1)),
+    ("cfg", Some(1)),
+    ("cfg_attr", None),
+    ("cold", Some(0)),
+    ("const_eval", Some(0)),
+    ("deny", Some(1)),
+    ("docs", None),
+    ("export", Some(1)),
+    ("feature", None),
+    ("inline", Some(1)),
+    ("interrupt", Some(0)),
+    ("isr", Some(1)),
+    ("lang", Some(1)),
+    ("link", None),
+    ("link_name", Some(1)),
+    ("multiversion", None),
+    ("must_use", Some(0)),
+    ("no_builtins", Some(0)),
+    ("no_instrument", Some(0)),
+    ("no_recursion", Some(0)),
+    ("optimize", Some(1)),
+    ("packed", Some(0)),
+    ("repr", Some(1)),
+    ("sealed", Some(0)),
+    ("target_feature", Some(1)),
+    ("test", None),
+    ("test_main", Some(0)),
+    ("thread_local", Some(0)),
+    ("transparent", Some(0)),
+    ("warn", Some(1)),
+];
+
 pub struct AttributeVisitor<'ast, 'src> {
     global_ctx: GlobalCtx,
     ast: &'ast AstCtx<'ast>,
@@ -262,6 +311,7 @@ pub struct AttributeVisitor<'ast, 'src> {
     applies_to_node: Node<'src>,
     should_skip: bool,
     test_attributes: Vec<String>,
+    link_libraries: Vec<LinkLibrary>,
 }
 
 impl<'ast, 'src> AttributeVisitor<'ast, 'src> {
@@ -284,6 +334,7 @@ impl<'ast, 'src> AttributeVisitor<'ast, 'src> {
             applies_to_node: node,
             should_skip: false,
             test_attributes: Vec::new(),
+            link_libraries: Vec::new(),
         };
 
         if let Some(node) = node.child_by_field(FieldKind::Attributes) {
@@ -295,10 +346,53 @@ impl<'ast, 'src> AttributeVisitor<'ast, 'src> {
         if visitor.should_skip {
             Ok(None)
         } else {
+            // Only libraries for items that are not disabled by `#[cfg]` are linked
+            for library in visitor.link_libraries {
+                visitor.global_ctx.add_link_library(library);
+            }
             Ok(Some(visitor.attributes.alloc_on(ast)))
         }
     }
 
+    /// Warns about attributes that are not known to the compiler and arguments that would
+    /// be ignored.
+    fn validate(&self, name: &str, node: Node<'src>) {
+        if name.starts_with("custom::") {
+            return;
+        }
+
+        let kind = match KNOWN_ATTRIBUTES.iter().find(|(n, _)| *n == name) {
+            None => CodeErrorKind::UnknownAttribute(name.to_string()),
+            Some((_, None)) => return,
+            Some((_, Some(max_args))) => {
+                let mut cursor = node.walk();
+                let args = match node.child_by_field(FieldKind::Arguments) {
+                    Some(args) => args
+                        .children_by_field(FieldKind::Argument, &mut cursor)
+                        .count(),
+                    None => node.child_by_field(FieldKind::Value).into_iter().count(),
+                };
+
+                if args <= *max_args {
+                    return;
+                }
+
+                let expected = if *max_args == 0 {
+                    "does not take any arguments, they are ignored"
+                } else {
+                    "takes a single argument, the rest are ignored"
+                };
+
+                CodeErrorKind::UnexpectedAttributeArguments(name.to_string(), expected.to_string())
+            }
+        };
+
+        self.global_ctx.diag().add_warning(CodeError {
+            kind,
+            backtrace: vec![Marker::Span(Span::from_node(self.scope.file_id(), node))],
+        });
+    }
+
     fn finalize(&mut self, node: tree_sitter::Node<'src>) -> Result<(), AluminaError> {
         if !self.test_attributes.is_empty() {
             self.ast.add_test_metadata(
@@ -351,6 +445,7 @@ impl<'ast, 'src> AluminaVisitor<'src> for AttributeVisitor<'ast, 'src> {
             .node_text(node.child_by_field(FieldKind::Name).unwrap());
 
         let span = Span::from_node(self.scope.file_id(), node);
+        self.validate(name, node);
 
         macro_rules! check_duplicate {
             ($attr:pat) => {
@@ -404,6 +499,14 @@ impl<'ast, 'src> AluminaVisitor<'src> for AttributeVisitor<'ast, 'src> {
                 check_duplicate!(Attribute::Transparent);
                 self.attributes.push(Attribute::Transparent);
             }
+            "sealed" => {
+                check_duplicate!(Attribute::Sealed);
+                self.attributes.push(Attribute::Sealed);
+            }
+            "const_eval" => {
+                check_duplicate!(Attribute::ConstEval);
+                self.attributes.push(Attribute::ConstEval);
+            }
             "packed" => {
                 check_duplicate!(Attribute::Packed);
 
@@ -417,6 +520,67 @@ impl<'ast, 'src> AluminaVisitor<'src> for AttributeVisitor<'ast, 'src> {
 
                 self.attributes.push(Attribute::Packed);
             }
+            "bitfield" => {
+                check_duplicate!(Attribute::Bitfield);
+                self.attributes.push(Attribute::Bitfield);
+            }
+            "bits" => {
+                check_duplicate!(Attribute::Bits(_));
+
+                let bits: usize = node
+                    .child_by_field(FieldKind::Arguments)
+                    .and_then(|n| n.child_by_field(FieldKind::Argument))
+                    .map(|n| self.code.node_text(n))
+                    .and_then(|f| f.parse().ok())
+                    .filter(|bits| *bits > 0)
+                    .ok_or_else(|| {
+                        CodeErrorKind::InvalidAttributeDetail(
+                            "expected `bits(<positive integer>)`".to_string(),
+                        )
+                    })
+                    .with_span_from(&self.scope, node)?;
+
+                self.attributes.push(Attribute::Bits(bits));
+            }
+            "repr" => {
+                check_duplicate!(Attribute::Repr(_) | Attribute::ReprC);
+
+                let repr = node
+                    .child_by_field(FieldKind::Arguments)
+                    .and_then(|n| n.child_by_field(FieldKind::Argument))
+                    .map(|n| self.code.node_text(n))
+                    .ok_or_else(|| {
+                        CodeErrorKind::InvalidAttributeDetail(
+                            "expected `repr(C)` or `repr(<integer type>)`".to_string(),
+                        )
+                    })
+                    .with_span_from(&self.scope, node)?;
+
+                let attribute = match repr {
+                    "C" => Attribute::ReprC,
+                    "u8" => Attribute::Repr(BuiltinType::U8),
+                    "u16" => Attribute::Repr(BuiltinType::U16),
+                    "u32" => Attribute::Repr(BuiltinType::U32),
+                    "u64" => Attribute::Repr(BuiltinType::U64),
+                    "u128" => Attribute::Repr(BuiltinType::U128),
+                    "usize" => Attribute::Repr(BuiltinType::USize),
+                    "i8" => Attribute::Repr(BuiltinType::I8),
+                    "i16" => Attribute::Repr(BuiltinType::I16),
+                    "i32" => Attribute::Repr(BuiltinType::I32),
+                    "i64" => Attribute::Repr(BuiltinType::I64),
+                    "i128" => Attribute::Repr(BuiltinType::I128),
+                    "isize" => Attribute::Repr(BuiltinType::ISize),
+                    _ => {
+                        return Err(CodeErrorKind::InvalidAttributeDetail(format!(
+                            "unsupported representation `{}`",
+                            repr
+                        )))
+                        .with_span_from(&self.scope, node)
+                    }
+                };
+
+                self.attributes.push(attribute);
+            }
             "allow" | "deny" | "warn" => {
                 let lint_name = node
                     .child_by_field(FieldKind::Arguments)
@@ -435,24 +599,38 @@ impl<'ast, 'src> AluminaVisitor<'src> for AttributeVisitor<'ast, 'src> {
                 };
 
                 let enclosing_span = Span::from_node(self.scope.file_id(), self.applies_to_node);
+                let attribute = Some((span, format!("{}({})", name, lint_name)));
 
-                match CodeErrorKind::VARIANTS.iter().find(|v| **v == lint_name) {
-                    Some(lint) => {
+                let lint = CodeErrorKind::VARIANTS.iter().find(|v| **v == lint_name);
+                match (lint, lints::lint_group(lint_name)) {
+                    (Some(lint), _) => {
                         self.global_ctx.diag().add_override(diagnostics::Override {
                             span: Some(enclosing_span),
                             kind: Some(lint),
                             action,
+                            attribute,
                         });
                     }
-                    None if lint_name.starts_with("warnings") => {
+                    (None, Some(group)) => {
+                        for lint in group {
+                            self.global_ctx.diag().add_override(diagnostics::Override {
+                                span: Some(enclosing_span),
+                                kind: Some(lint),
+                                action,
+                                attribute: attribute.clone(),
+                            });
+                        }
+                    }
+                    (None, None) if lint_name.starts_with("warnings") => {
                         // all warnings
                         self.global_ctx.diag().add_override(diagnostics::Override {
                             span: Some(enclosing_span),
                             kind: None,
                             action,
+                            attribute,
                         });
                     }
-                    None => {
+                    (None, None) => {
                         // ironic really
                         self.global_ctx.diag().add_warning(CodeError {
                             kind: CodeErrorKind::ImSoMetaEvenThisAcronym(
@@ -483,6 +661,141 @@ impl<'ast, 'src> AluminaVisitor<'src> for AttributeVisitor<'ast, 'src> {
                     }
                 }
             }
+            "optimize" => {
+                check_duplicate!(Attribute::Optimize(_));
+                match node
+                    .child_by_field(FieldKind::Arguments)
+                    .and_then(|n| n.child_by_field(FieldKind::Argument))
+                    .map(|n| self.code.node_text(n))
+                {
+                    Some("none") => self
+                        .attributes
+                        .push(Attribute::Optimize(OptimizationLevel::None)),
+                    Some("size") => self
+                        .attributes
+                        .push(Attribute::Optimize(OptimizationLevel::Size)),
+                    Some("speed") => self
+                        .attributes
+                        .push(Attribute::Optimize(OptimizationLevel::Speed)),
+                    _ => {
+                        return Err(CodeErrorKind::InvalidAttributeDetail(
+                            "expected `optimize(none)`, `optimize(size)` or `optimize(speed)`"
+                                .to_string(),
+                        ))
+                        .with_span_from(&self.scope, node)
+                    }
+                }
+            }
+            "target_feature" => {
+                check_duplicate!(Attribute::TargetFeature(..) | Attribute::Multiversion(..));
+
+                let bytes = node
+                    .child_by_field(FieldKind::Arguments)
+                    .and_then(|n| n.child_by_field(FieldKind::Argument))
+                    .map(|n| self.code.node_text(n).as_bytes())
+                    .filter(|b| b.len() <= 255 && b.starts_with(b"\""))
+                    .ok_or_else(|| {
+                        CodeErrorKind::InvalidAttributeDetail(
+                            "expected `target_feature(\"<features>\")`".to_string(),
+                        )
+                    })
+                    .with_span_from(&self.scope, node)?;
+
+                let mut val = [0; 255];
+                val.as_mut_slice()[0..bytes.len()].copy_from_slice(bytes);
+
+                self.attributes
+                    .push(Attribute::TargetFeature(bytes.len(), val));
+            }
+            "multiversion" => {
+                check_duplicate!(Attribute::TargetFeature(..) | Attribute::Multiversion(..));
+
+                let usage = "expected `multiversion(\"<features>\", ..., \"default\")`";
+                let mut cursor = node.walk();
+                let args: Vec<_> = node
+                    .child_by_field(FieldKind::Arguments)
+                    .map(|a| {
+                        a.children_by_field(FieldKind::Argument, &mut cursor)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                let mut versions: Vec<Vec<u8>> = Vec::new();
+                for arg in args {
+                    let version = Some(self.code.node_text(arg))
+                        .filter(|v| v.starts_with('"'))
+                        .ok_or_else(|| CodeErrorKind::InvalidAttributeDetail(usage.to_string()))
+                        .and_then(parse_string_literal)
+                        .with_span_from(&self.scope, arg)?;
+
+                    // The versions end up in C string literals and identifiers
+                    if version.is_empty()
+                        || !version
+                            .iter()
+                            .all(|c| c.is_ascii_alphanumeric() || b".,=_-".contains(c))
+                    {
+                        return Err(CodeErrorKind::InvalidAttributeDetail(format!(
+                            "invalid version `{}`",
+                            String::from_utf8_lossy(&version)
+                        )))
+                        .with_span_from(&self.scope, arg);
+                    }
+                    if versions.contains(&version) {
+                        return Err(CodeErrorKind::InvalidAttributeDetail(format!(
+                            "version `{}` is listed more than once",
+                            String::from_utf8_lossy(&version)
+                        )))
+                        .with_span_from(&self.scope, arg);
+                    }
+                    versions.push(version);
+                }
+
+                if !versions.iter().any(|v| v == b"default") {
+                    return Err(CodeErrorKind::InvalidAttributeDetail(
+                        "a `\"default\"` version is required for CPUs without the features"
+                            .to_string(),
+                    ))
+                    .with_span_from(&self.scope, node);
+                }
+
+                let bytes = versions.join(&0);
+                if bytes.len() > 255 {
+                    return Err(CodeErrorKind::InvalidAttributeDetail(
+                        "the versions must be at most 255 bytes long in total".to_string(),
+                    ))
+                    .with_span_from(&self.scope, node);
+                }
+
+                let mut val = [0; 255];
+                val.as_mut_slice()[0..bytes.len()].copy_from_slice(&bytes);
+
+                self.attributes
+                    .push(Attribute::Multiversion(bytes.len(), val));
+            }
+            "c_attribute" => {
+                let usage = "expected `c_attribute(\"<attribute>\")`";
+                let bytes = node
+                    .child_by_field(FieldKind::Arguments)
+                    .and_then(|n| n.child_by_field(FieldKind::Argument))
+                    .map(|n| self.code.node_text(n))
+                    .filter(|v| v.starts_with('"'))
+                    .ok_or_else(|| CodeErrorKind::InvalidAttributeDetail(usage.to_string()))
+                    .and_then(parse_string_literal)
+                    .with_span_from(&self.scope, node)?;
+
+                if bytes.len() > 255 || std::str::from_utf8(&bytes).is_err() {
+                    return Err(CodeErrorKind::InvalidAttributeDetail(
+                        "the attribute must be valid UTF-8 and at most 255 bytes long".to_string(),
+                    ))
+                    .with_span_from(&self.scope, node);
+                }
+
+                let mut val = [0; 255];
+                val.as_mut_slice()[0..bytes.len()].copy_from_slice(&bytes);
+
+                self.attributes
+                    .push(Attribute::CAttribute(bytes.len(), val));
+            }
             "builtin" => {
                 check_duplicate!(Attribute::Builtin);
                 self.attributes.push(Attribute::Builtin);
@@ -490,12 +803,145 @@ impl<'ast, 'src> AluminaVisitor<'src> for AttributeVisitor<'ast, 'src> {
             "export" => {
                 check_duplicate!(Attribute::Export);
                 self.attributes.push(Attribute::Export);
+
+                // #[export(name = "symbol")] exports the function under a different name
+                if let Some(args) = node.child_by_field(FieldKind::Arguments) {
+                    check_duplicate!(Attribute::LinkName(..));
+
+                    let mut cursor = node.walk();
+                    let link_name = args
+                        .children_by_field(FieldKind::Argument, &mut cursor)
+                        .find(|n| {
+                            n.child_by_field(FieldKind::Name)
+                                .map(|n| self.code.node_text(n))
+                                == Some("name")
+                        })
+                        .and_then(|n| n.child_by_field(FieldKind::Value))
+                        .filter(|n| self.code.node_text(*n).starts_with('"'))
+                        .ok_or_else(|| {
+                            CodeErrorKind::InvalidAttributeDetail(
+                                "expected `export(name = \"<symbol>\")`".to_string(),
+                            )
+                        })
+                        .with_span_from(&self.scope, node)?;
+
+                    let bytes = self.code.node_text(link_name).as_bytes();
+                    if bytes.len() > 255 {
+                        return Err(CodeErrorKind::InvalidAttributeDetail(
+                            "symbol name is too long".to_string(),
+                        ))
+                        .with_span_from(&self.scope, node);
+                    }
+
+                    let mut val = [0; 255];
+                    val.as_mut_slice()[0..bytes.len()].copy_from_slice(bytes);
+
+                    self.attributes.push(Attribute::LinkName(bytes.len(), val));
+                }
+            }
+            "link" => {
+                let usage = "expected `link(name = \"<library>\", kind = \"dylib\" | \"static\")`";
+                let mut name = None;
+                let mut kind = LinkKind::Dylib;
+
+                let mut cursor = node.walk();
+                let args: Vec<_> = node
+                    .child_by_field(FieldKind::Arguments)
+                    .map(|a| {
+                        a.children_by_field(FieldKind::Argument, &mut cursor)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                for arg in args {
+                    let key = arg
+                        .child_by_field(FieldKind::Name)
+                        .map(|n| self.code.node_text(n));
+                    let value = arg
+                        .child_by_field(FieldKind::Value)
+                        .map(|n| self.code.node_text(n))
+                        .filter(|v| v.starts_with('"'))
+                        .map(parse_string_literal)
+                        .transpose()
+                        .with_span_from(&self.scope, arg)?
+                        .and_then(|v| String::from_utf8(v).ok());
+
+                    match (key, value) {
+                        (Some("name"), Some(value)) if !value.is_empty() && name.is_none() => {
+                            name = Some(value)
+                        }
+                        (Some("kind"), Some(value)) if value == "dylib" => kind = LinkKind::Dylib,
+                        (Some("kind"), Some(value)) if value == "static" => kind = LinkKind::Static,
+                        _ => {
+                            return Err(CodeErrorKind::InvalidAttributeDetail(usage.to_string()))
+                                .with_span_from(&self.scope, arg)
+                        }
+                    }
+                }
+
+                let name = name
+                    .ok_or_else(|| CodeErrorKind::InvalidAttributeDetail(usage.to_string()))
+                    .with_span_from(&self.scope, node)?;
+
+                self.link_libraries.push(LinkLibrary { name, kind });
+            }
+            "no_recursion" => {
+                check_duplicate!(Attribute::NoRecursion);
+                self.attributes.push(Attribute::NoRecursion);
+            }
+            "no_instrument" => {
+                check_duplicate!(Attribute::NoInstrument);
+                self.attributes.push(Attribute::NoInstrument);
+            }
+            "no_builtins" => {
+                check_duplicate!(Attribute::NoBuiltins);
+                self.attributes.push(Attribute::NoBuiltins);
+            }
+            "allocator" => {
+                check_duplicate!(Attribute::Allocator);
+                self.attributes.push(Attribute::Allocator);
+            }
+            "interrupt" => {
+                check_duplicate!(Attribute::Interrupt);
+                self.attributes.push(Attribute::Interrupt);
+            }
+            "isr" => {
+                check_duplicate!(Attribute::Interrupt);
+
+                let mut cursor = node.walk();
+                let vector = node
+                    .child_by_field(FieldKind::Arguments)
+                    .and_then(|n| {
+                        n.children_by_field(FieldKind::Argument, &mut cursor)
+                            .find(|n| {
+                                n.child_by_field(FieldKind::Name)
+                                    .map(|n| self.code.node_text(n))
+                                    == Some("vector")
+                            })
+                    })
+                    .and_then(|n| n.child_by_field(FieldKind::Value))
+                    .and_then(|n| self.code.node_text(n).parse().ok())
+                    .ok_or_else(|| {
+                        CodeErrorKind::InvalidAttributeDetail(
+                            "expected `isr(vector = <integer>)`".to_string(),
+                        )
+                    })
+                    .with_span_from(&self.scope, node)?;
+
+                self.attributes.push(Attribute::Interrupt);
+                self.attributes.push(Attribute::InterruptVector(vector));
             }
             "thread_local" => {
                 check_duplicate!(Attribute::ThreadLocal);
                 // We can skip thread-local on programs that are compiled with threads
-                // disabled.
-                if self.global_ctx.has_flag("threading") {
+                // disabled, but not on extern statics, since the foreign symbol is
+                // thread-local either way.
+                if self.global_ctx.has_flag("threading")
+                    || self
+                        .applies_to_node
+                        .child_by_field(FieldKind::Extern)
+                        .is_some()
+                {
                     self.attributes.push(Attribute::ThreadLocal)
                 }
             }
@@ -524,6 +970,30 @@ impl<'ast, 'src> AluminaVisitor<'src> for AttributeVisitor<'ast, 'src> {
                         .to_string(),
                 );
             }
+            "feature" => {
+                if self.applies_to_node.kind() != "source_file" {
+                    return Err(CodeErrorKind::FeatureNotAtTopOfFile)
+                        .with_span_from(&self.scope, node);
+                }
+
+                let mut cursor = node.walk();
+                let args: Vec<_> = node
+                    .child_by_field(FieldKind::Arguments)
+                    .map(|a| {
+                        a.children_by_field(FieldKind::Argument, &mut cursor)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                for arg in args {
+                    let name = self.code.node_text(arg);
+                    let feature = Feature::from_name(name)
+                        .ok_or_else(|| CodeErrorKind::UnknownFeature(name.to_string()))
+                        .with_span_from(&self.scope, arg)?;
+                    self.global_ctx
+                        .enable_feature(self.scope.file_id(), feature);
+                }
+            }
             "cfg" => {
                 let mut cfg_visitor = CfgVisitor::new(self.global_ctx.clone(), self.scope.clone());
                 if !cfg_visitor.visit(node)? {
@@ -599,6 +1069,7 @@ pub struct CfgVisitor<'ast, 'src> {
     code: &'src ParseCtx<'src>,
     scope: Scope<'ast, 'src>,
     state: Vec<State>,
+    failed: Option<Node<'src>>,
 }
 
 impl<'ast, 'src> CfgVisitor<'ast, 'src> {
@@ -610,8 +1081,55 @@ impl<'ast, 'src> CfgVisitor<'ast, 'src> {
                 .expect("cannot run on scope without parse context"),
             scope,
             state: vec![],
+            failed: None,
+        }
+    }
+
+    /// The innermost predicate that made the condition false (if it was)
+    pub fn failed_predicate(&self) -> Option<&'src str> {
+        self.failed.map(|n| self.code.node_text(n))
+    }
+}
+
+/// The predicate that does not hold if the file has a top-level `#![cfg(...)]` attribute
+/// that excludes it. Such modules are skipped entirely, before any of their items are
+/// added to the scope.
+pub fn cfg_exclusion<'src>(
+    global_ctx: GlobalCtx,
+    scope: &Scope<'_, 'src>,
+    node: Node<'src>,
+) -> Result<Option<String>, AluminaError> {
+    let Some(attributes) = node.child_by_field(FieldKind::Attributes) else {
+        return Ok(None);
+    };
+
+    let code = scope
+        .code()
+        .expect("cannot run on scope without parse context");
+
+    let mut cursor = attributes.walk();
+    for item in attributes.children(&mut cursor) {
+        let Some(inner) = item.child_by_field(FieldKind::Inner) else {
+            continue;
+        };
+        if inner
+            .child_by_field(FieldKind::Name)
+            .map(|n| code.node_text(n))
+            != Some("cfg")
+        {
+            continue;
+        }
+
+        let mut cfg_visitor = CfgVisitor::new(global_ctx.clone(), scope.clone());
+        if !cfg_visitor.visit(inner)? {
+            let predicate = cfg_visitor
+                .failed_predicate()
+                .unwrap_or_else(|| code.node_text(inner));
+            return Ok(Some(predicate.to_string()));
         }
     }
+
+    Ok(None)
 }
 
 impl<'ast, 'src> AluminaVisitor<'src> for CfgVisitor<'ast, 'src> {
@@ -643,6 +1161,13 @@ impl<'ast, 'src> AluminaVisitor<'src> for CfgVisitor<'ast, 'src> {
                 _ => return Err(CodeErrorKind::InvalidAttribute).with_span_from(&self.scope, node),
             };
             self.state.pop();
+
+            // If all the alternatives fail (or the negated condition holds), the whole
+            // predicate is to blame
+            if !ret && matches!(name, "any" | "not") {
+                self.failed = Some(node);
+            }
+
             Ok(ret)
         } else {
             let expected = node
@@ -661,6 +1186,10 @@ impl<'ast, 'src> AluminaVisitor<'src> for CfgVisitor<'ast, 'src> {
                 (_, None) => false,
             };
 
+            if !matches {
+                self.failed = Some(node);
+            }
+
             Ok(matches)
         }
     }